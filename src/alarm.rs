@@ -0,0 +1,81 @@
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveTime, TimeZone};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// 闹钟：每天在设定的时间开始播放（音量逐渐增大），并可在另一个时间自动停止
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    alarm_time: Option<NaiveTime>,
+    stop_time: Option<NaiveTime>,
+    pub next_alarm: Option<DateTime<Local>>,
+    next_stop: Option<DateTime<Local>>,
+    /// 音量渐强的开始时间和时长
+    ramp: Option<(Instant, Duration)>,
+}
+
+/// time 在 after 之后的下一次出现（今天或明天）
+fn next_occurrence(time: NaiveTime, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = after.date_naive().and_time(time);
+    let next = if today > after.naive_local() { today } else { today + ChronoDuration::days(1) };
+    // 夏令时切换时不存在的时间取最早的合法时间
+    Local.from_local_datetime(&next).earliest()
+}
+
+impl Scheduler {
+    pub fn from_config(config: &Config, now: DateTime<Local>) -> Self {
+        Scheduler {
+            alarm_time: config.alarm_time,
+            stop_time: config.alarm_stop_time,
+            next_alarm: config.alarm_time.and_then(|time| next_occurrence(time, now)),
+            next_stop: config.alarm_stop_time.and_then(|time| next_occurrence(time, now)),
+            ramp: None,
+        }
+    }
+
+    /// 到了闹钟时间时返回 true，并排定明天的同一时间
+    pub fn alarm_due(&mut self, now: DateTime<Local>) -> bool {
+        match (self.next_alarm, self.alarm_time) {
+            (Some(next), Some(time)) if now >= next => {
+                self.next_alarm = next_occurrence(time, now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn stop_due(&mut self, now: DateTime<Local>) -> bool {
+        match (self.next_stop, self.stop_time) {
+            (Some(next), Some(time)) if now >= next => {
+                self.next_stop = next_occurrence(time, now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn start_ramp(&mut self, now: Instant, duration: Duration) {
+        self.ramp = (!duration.is_zero()).then_some((now, duration));
+    }
+
+    pub fn cancel_ramp(&mut self) {
+        self.ramp = None;
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.ramp.is_some()
+    }
+
+    /// 当前音量相对设定音量的比例，渐强结束后为 1
+    pub fn volume_factor(&mut self, now: Instant) -> f32 {
+        let Some((start, duration)) = self.ramp else {
+            return 1.0;
+        };
+        let factor = now.saturating_duration_since(start).as_secs_f32() / duration.as_secs_f32();
+        if factor >= 1.0 {
+            self.ramp = None;
+            return 1.0;
+        }
+        factor
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::{self, Write};
+
+/// 切歌或播放队列播完时的提醒方式，给音量开得很小或者远程查看播放状态的用户
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMode {
+    Off,
+    /// 界面外框短暂变色
+    Flash,
+    /// 终端响铃（BEL），终端可以设置成闪屏或者系统通知
+    Bell,
+    Both,
+}
+
+impl AlertMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "off" | "none" => Some(AlertMode::Off),
+            "flash" => Some(AlertMode::Flash),
+            "bell" => Some(AlertMode::Bell),
+            "both" => Some(AlertMode::Both),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertMode::Off => "off",
+            AlertMode::Flash => "flash",
+            AlertMode::Bell => "bell",
+            AlertMode::Both => "both",
+        }
+    }
+
+    pub fn flashes(self) -> bool {
+        matches!(self, AlertMode::Flash | AlertMode::Both)
+    }
+
+    pub fn rings(self) -> bool {
+        matches!(self, AlertMode::Bell | AlertMode::Both)
+    }
+}
+
+/// 向终端输出 BEL 字符
+pub fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
@@ -1,376 +1,5243 @@
 use anyhow::Result;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
-
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
-use std::collections::VecDeque;
+use crossterm::event::KeyCode;
+use rodio::decoder::Mp4Type;
+use rodio::source::EmptyCallback;
+use rodio::{Decoder, Sink, Source};
+use symphonia::core::io::MediaSource;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::BufReader;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::io::{BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::alarm::Scheduler;
+use crate::alert;
+use crate::audio_output::{self, AudioOutput, Dither, DitherMode};
+use crate::automix::{self, SkipSilence, TempoRamp};
+use crate::beets::{self, BeetsLibrary};
+use crate::bookmarks::{self, Bookmark};
+use crate::bpm;
+use crate::clipboard;
+use crate::clock::Clock;
+use crate::collections::{self, Collection, Pin, PinScope};
+use crate::config::Config;
+use crate::cues::{self, CuePoint, RekordboxLibrary};
+use crate::downmix::{self, Downmix};
+use crate::dsd::{self, Dop, DsdOutput, DsdReader, DsdToPcm};
+use crate::equalizer::{self, EqControl, Equalizer};
+use crate::file_access::{self, Unavailable};
+use crate::fingerprint;
+use crate::genre::{self, Suggestion};
+use crate::http;
+use crate::http_stream::{self, HttpStream};
+use crate::import::{self, ExternalTrack};
+use crate::info::{self, InfoResult};
+use crate::instrumental::{self, Detection};
+use crate::jobs::{Job, JobOutput, JobState};
+use crate::karaoke::{KaraokeControl, VocalReducer};
+use crate::keymap::{self, Action, KeyMatch, Keymap};
+use crate::leveler::{Leveler, LevelerControl};
+use crate::library::{self, ArtistEntry};
+use crate::lighting::{self, Lighting};
+use crate::listenbrainz;
+use crate::lyrics::{self, Lyrics, LyricsResult, Provider};
+use crate::macros::{self, Macros};
+use crate::metadata::{self, Badge, Chapter, DetailSection, ProbeResult, Tags};
+use crate::metadata_cache::{self, MetadataCache};
+use crate::metrics;
+use crate::mqtt::{self, Mqtt};
+use crate::json;
+use crate::playlists::{self, PlaylistChange, WatchedPlaylist};
+use crate::ratings::{self, Ratings};
+use crate::remote::{self, Command};
+use crate::remote_library;
+use crate::replaygain;
+use crate::scan_filter::ScanFilter;
+use crate::search::{self, SearchEntry, SearchGroup, SearchHit};
+use crate::session::{self, Session};
+use crate::settings;
+use crate::snapshots::{self, QueueSnapshot};
+use crate::stats::Stats;
+use crate::status_line;
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+use crate::tag_writer;
+use crate::ui::format_duration;
+use crate::track_settings::{self, AlbumKey, TrackGain, TrackSettings};
+use crate::trim;
+use crate::undo::History;
+use crate::visualizer::{Tap, Visualizer, VisualizerMode};
+use crate::waveform::{self, Waveform};
+use crate::web::WebRemote;
+use crate::webhook::{self, Webhooks};
+use crate::ytdlp;
+
+/// 播放会话自动保存的间隔
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// 脱离终端后等界面进程释放控制端口的时间
+const DETACH_LISTEN_TIMEOUT: Duration = Duration::from_secs(5);
+/// 检查播放列表文件是否被其他程序修改的间隔
+const PLAYLIST_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// 省电模式下至少隔这么久检查一次播放状态（也就是播放中每秒重绘一次）
+const POWER_SAVER_TICK: Duration = Duration::from_secs(1);
+/// 省电模式下按键后重绘的最高帧率
+const POWER_SAVER_FPS: u32 = 10;
+/// 省电模式下保存会话、检查播放列表文件等后台工作的间隔
+const POWER_SAVER_BATCH: Duration = Duration::from_secs(30);
+/// 重新播放当前歌曲后，在这段时间内再按上一首就切到上一首歌
+const PREVIOUS_WINDOW: Duration = Duration::from_secs(2);
+/// 往回跳到上一个标记时，忽略刚经过这么久以内的标记
+const MARKER_BACK_WINDOW: Duration = Duration::from_secs(1);
+/// 组合键的两个键之间最多间隔这么久
+const CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+/// 切歌提醒时外框闪烁的时长
+const FLASH_DURATION: Duration = Duration::from_millis(1500);
+/// 网页遥控最多显示的队列长度
+const WEB_QUEUE_LIMIT: usize = 100;
+/// 灯光节拍的起点和实际播放位置相差超过这么多时重新同步（拖动进度、缓冲之后）
+const LIGHTING_RESYNC: Duration = Duration::from_millis(250);
+/// 扫描曲库目录时最多进入这么多层子目录（也避免符号链接形成的循环）
+const MAX_SCAN_DEPTH: usize = 16;
+/// 要播放的歌曲被儿童模式或收藏集排除时的提示
+const OUT_OF_SCOPE: &str = "这首歌不在当前范围内（儿童模式或收藏集），不会播放";
+
+/// 模拟模式下时长未知的歌曲（直播流、读不出时长的文件）按这个长度算作播放完
+pub const SIMULATED_UNKNOWN_DURATION: Duration = Duration::from_secs(180);
+
+/// 交给播放管线的解码器（symphonia 流式解码或 rodio 解码器）
+pub type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+/// 在后台打开本地文件的结果
+pub enum Opened {
+    /// 文件打不开
+    Unreadable(std::io::Error),
+    /// 从开头解码的音源和声道布局
+    Decoded(Result<(BoxedSource, Option<u32>)>),
+    /// DSD 文件可以打开，按输出设置（DoP 或转换成 PCM）打开要在界面线程中进行
+    Dsd,
+}
+
+/// 打开并解码本地文件，在后台任务中运行
+fn open_file(media: &Path, path: &Path, start: Duration, fallback: bool) -> Opened {
+    let file = match fs::File::open(media) {
+        Ok(file) => file,
+        Err(err) => return Opened::Unreadable(err),
+    };
+    if dsd::is_dsd(path) {
+        return Opened::Dsd;
+    }
+    Opened::Decoded(decode(Box::new(file), path, start, fallback))
+}
 
 #[derive(Debug, Clone)]
 pub struct Song {
     pub path: PathBuf,
     pub name: String,
     pub duration: Option<Duration>,
+    pub tags: Tags,
+    /// 格式标记（编码、位深/采样率或比特率）
+    pub badge: Option<Badge>,
+    /// 文件已经不存在（被删除或移动）
+    pub missing: bool,
+}
+
+impl Song {
+    pub fn from_path(path: PathBuf) -> Self {
+        let probe = metadata::probe(&path);
+        Song::from_probe(path, probe)
+    }
+    
+    /// 用已有的探测结果（例如元数据缓存）创建歌曲
+    pub fn from_probe(path: PathBuf, probe: ProbeResult) -> Self {
+        let name = match http_stream::is_url(&path) {
+            true => http_stream::url_file_name(&path.to_string_lossy()),
+            false => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        };
+        let missing = !http_stream::is_url(&path) && !path.exists();
+        
+        Song {
+            path,
+            name,
+            duration: probe.duration,
+            badge: probe.badge(),
+            tags: probe.tags,
+            missing,
+        }
+    }
+    
+    /// 标题：优先使用标签，否则取文件名中 "艺术家 - 标题" 的标题部分
+    pub fn title(&self) -> String {
+        if let Some(title) = &self.tags.title {
+            return title.clone();
+        }
+        let stem = self.file_stem();
+        match stem.split_once(" - ") {
+            Some((_, title)) => title.trim().to_string(),
+            None => stem,
+        }
+    }
+    
+    /// 艺术家：优先使用标签，否则取文件名中 "艺术家 - 标题" 的艺术家部分
+    pub fn artist(&self) -> Option<String> {
+        if let Some(artist) = &self.tags.artist {
+            return Some(artist.clone());
+        }
+        self.file_stem()
+            .split_once(" - ")
+            .map(|(artist, _)| artist.trim().to_string())
+    }
+    
+    fn file_stem(&self) -> String {
+        self.path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// 一组歌曲的总时长。还不知道时长的歌曲（没播放过的网络音频等）不计入，这时第二项为 true
+pub fn total_duration<'a>(songs: impl IntoIterator<Item = &'a Song>) -> (Duration, bool) {
+    songs.into_iter().fold((Duration::ZERO, false), |(total, unknown), song| match song.duration {
+        Some(duration) => (total + duration, unknown),
+        None => (total, true),
+    })
+}
+
+pub fn is_audio_file(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let ext = extension.to_string_lossy().to_lowercase();
+            matches!(ext.as_str(), "mp3" | "wav" | "flac" | "ogg" | "m4a" | "m4b" | "mp4a" | "aac" | "dsf" | "dff")
+        }
+        None => false,
+    }
+}
+
+/// 扫描目录下支持的音频文件
+pub fn scan_songs(dir: &Path) -> Result<Vec<Song>> {
+    let paths = scan_paths(dir)?;
+    let mut cache = MetadataCache::load();
+    let songs = load_cached(paths, &mut cache, metadata_cache::worker_count(0));
+    let _ = cache.save();
+    Ok(songs)
+}
+
+/// 通过元数据缓存创建歌曲，只有新文件或修改过的文件才会（并行）重新探测
+pub fn load_cached(paths: Vec<PathBuf>, cache: &mut MetadataCache, workers: usize) -> Vec<Song> {
+    let probes = cache.probe_all(&paths, workers, &AtomicBool::new(false), || {});
+    paths
+        .into_iter()
+        .zip(probes)
+        .map(|(path, probe)| Song::from_probe(path, probe))
+        .collect()
+}
+
+/// beets 曲库中有的文件直接使用 beets 的标签和音频信息，其余的通过元数据缓存探测
+pub fn load_with_beets(paths: Vec<PathBuf>, beets: &BeetsLibrary, cache: &mut MetadataCache, workers: usize) -> Vec<Song> {
+    let mut songs: Vec<Option<Song>> = Vec::with_capacity(paths.len());
+    let mut unknown = Vec::new();
+    for path in paths {
+        match beets.get(&path) {
+            Some(item) => songs.push(Some(Song::from_probe(path, item.probe.clone()))),
+            None => {
+                unknown.push((songs.len(), path));
+                songs.push(None);
+            }
+        }
+    }
+    let (positions, unknown): (Vec<usize>, Vec<PathBuf>) = unknown.into_iter().unzip();
+    for (position, song) in positions.into_iter().zip(load_cached(unknown, cache, workers)) {
+        songs[position] = Some(song);
+    }
+    songs.into_iter().flatten().collect()
+}
+
+/// rodio 自带的解码器。rodio 不按扩展名猜格式，m4a 和裸 AAC 要明确指定
+pub fn rodio_decoder<R>(reader: R, path: &Path) -> Result<Decoder<R>, rodio::decoder::DecoderError>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "m4a" | "m4b" | "mp4a" => Decoder::new_mp4(reader, Mp4Type::M4a),
+        "aac" => Decoder::new_aac(reader),
+        _ => Decoder::new(reader),
+    }
+}
+
+/// 默认用 symphonia 流式解码：分块读取文件，跳转时直接定位而不是从头解码；
+/// 用户选择重试的文件（fallback）改用 rodio 自带的解码器。返回音源和声道布局
+fn decode(media: Box<dyn MediaSource>, path: &Path, start: Duration, fallback: bool) -> Result<(BoxedSource, Option<u32>)> {
+    if fallback {
+        let decoder = rodio_decoder(BufReader::new(media), path)?;
+        return Ok((Box::new(decoder.skip_duration(start).convert_samples()), None));
+    }
+    let decoder = StreamingDecoder::new(media, path, start)?;
+    let layout = decoder.channel_layout();
+    Ok((Box::new(decoder), Some(layout)))
+}
+
+/// 目录及其子目录下支持的音频文件路径（不探测标签），按路径排序
+pub fn scan_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    if !dir.exists() {
+        return Ok(paths);
+    }
+    // 目录本身读不了时报错，子目录读不了时跳过
+    fs::read_dir(dir)?;
+    scan_paths_recursive(dir, MAX_SCAN_DEPTH, &mut paths);
+    paths.sort();
+    Ok(paths)
+}
+
+/// 递归查找目录下的音频文件，最多向下 depth 层
+pub fn scan_paths_recursive(dir: &Path, depth: usize, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                scan_paths_recursive(&path, depth - 1, paths);
+            }
+        } else if is_audio_file(&path) {
+            paths.push(path);
+        }
+    }
+}
+
+/// 曲库中的文件：指定了播放列表时为其中的全部条目（包括已经不存在的文件），
+/// 指定了目录时为目录下的音频文件，否则为 assets 目录下的音频文件
+pub fn library_paths(playlist: Option<&Path>) -> Result<Vec<PathBuf>> {
+    match playlist {
+        Some(dir) if dir.is_dir() => scan_paths(dir),
+        Some(path) => playlists::load_m3u(path),
+        None => scan_paths(Path::new("assets")),
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PlaybackState {
     Playing,
     Paused,
     Stopped,
 }
 
+/// 随机播放的粒度：按单曲，或整张专辑/整个文件夹按顺序播放、专辑/文件夹之间随机
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShuffleMode {
+    Off,
+    Track,
+    Album,
+    Folder,
+}
+
+impl ShuffleMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ShuffleMode::Off => "off",
+            ShuffleMode::Track => "track",
+            ShuffleMode::Album => "album",
+            ShuffleMode::Folder => "folder",
+        }
+    }
+    
+    pub fn next(self) -> Self {
+        match self {
+            ShuffleMode::Off => ShuffleMode::Track,
+            ShuffleMode::Track => ShuffleMode::Album,
+            ShuffleMode::Album => ShuffleMode::Folder,
+            ShuffleMode::Folder => ShuffleMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShuffleMode::Off => "随机播放关闭",
+            ShuffleMode::Track => "随机播放: 单曲",
+            ShuffleMode::Album => "随机播放: 专辑",
+            ShuffleMode::Folder => "随机播放: 文件夹",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Playlist,
+    Bookmarks,
+    /// 按专辑艺术家浏览曲库，合辑归入 Various Artists
+    Artists,
+    /// 收藏集列表，选中后只显示其中的歌曲
+    Collections,
+    /// 左边曲库、右边队列的编辑界面，用来快速排好一组歌
+    Editor,
+    /// 列出全部配置项，在界面中修改并写回配置文件
+    Settings,
+    /// 保存的播放队列快照，选中后恢复
+    Snapshots,
+    /// 流派识别的建议，确认后写入标签
+    Genres,
+}
+
+/// 当前获得焦点的面板，方向键等按键优先交给它处理
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Playlist,
+    Queue,
+    Info,
+    Lyrics,
+    Jobs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Name,
+    Bpm,
+}
+
+#[derive(Debug, Clone)]
+pub enum PromptKind {
+    BookmarkName { path: PathBuf, position: Duration },
+    BpmFilter,
+    /// 把光标所在歌曲按 scope 加入收藏集，输入收藏集名称
+    Collection { path: PathBuf, scope: PinScope },
+    RelocateDir,
+    Setting { key: &'static str },
+    /// 保存播放队列快照，输入快照名称
+    SnapshotName,
+    /// 确认前修改流派建议
+    Genre { path: PathBuf },
+    /// 导出 A-B 循环的片段，输入保存的文件路径
+    ExportSegment { path: PathBuf, start: Duration, end: Duration },
+}
+
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+}
+
+/// 设置界面中等待按下新键的操作
+#[derive(Debug, Clone)]
+pub struct KeyCapture {
+    /// 在 `keymap::ACTIONS` 中的位置
+    pub index: usize,
+    /// 和其他操作冲突、等待再按一次确认的键
+    pub pending: Option<KeyCode>,
+}
+
+/// 自动混音切到下一首时的重叠时长和新歌曲的播放速度
+#[derive(Debug, Clone, Copy)]
+struct MixIn {
+    overlap: Duration,
+    ratio: f32,
+}
+
+/// 开始播放一首歌需要的参数。歌曲在后台打开，打开之后再按这些参数开始播放
+struct PlayRequest {
+    path: PathBuf,
+    /// 本地文件超过这个时间还没打开就认为所在的挂载点没有响应，网络音频为 None
+    deadline: Option<Instant>,
+    /// 调用者要求的位置，为零表示从头播放
+    position: Duration,
+    /// 实际开始的位置（从头播放时跳过开头）
+    start: Duration,
+    mix: Option<MixIn>,
+    soft: bool,
+    switch_fade: Duration,
+    /// 打开后停在开始位置，不出声
+    paused: bool,
+}
+
+/// 队列和曲库在一次编辑前的状态，用于撤销。按路径保存，曲库变化后仍能还原队列
+#[derive(Debug, Clone)]
+pub struct EditSnapshot {
+    queue: Vec<PathBuf>,
+    // 只有修改了曲库的编辑才保存整个曲库
+    songs: Option<Vec<Song>>,
+}
+
+/// 歌曲详情窗口：编解码参数、文件信息和全部标签
+#[derive(Debug, Clone)]
+pub struct DetailsPopup {
+    pub name: String,
+    pub sections: Vec<DetailSection>,
+    pub scroll: u16,
+}
+
+/// 全局搜索窗口：每次输入都在后台重新查找，旧的结果在新结果到来前继续显示
+pub struct SearchPane {
+    pub query: String,
+    pub groups: Vec<SearchGroup>,
+    /// 在所有分组的结果中的位置
+    pub selected: usize,
+    pub searching: bool,
+    index: Arc<Vec<SearchEntry>>,
+    rx: Option<Receiver<Vec<SearchGroup>>>,
+}
+
+impl SearchPane {
+    pub fn hits(&self) -> impl Iterator<Item = &SearchHit> {
+        self.groups.iter().flat_map(|group| &group.hits)
+    }
+}
+
+/// A-B 循环：播放到 B 点时跳回 A 点，只对设置它的歌曲生效
+#[derive(Debug, Clone)]
+pub struct AbLoop {
+    pub path: PathBuf,
+    pub start: Duration,
+    /// 只设置了 A 点时为 None
+    pub end: Option<Duration>,
+}
+
+/// 歌曲属性窗口：编辑单首歌曲的播放设置
+#[derive(Debug, Clone)]
+pub struct TrackEditor {
+    pub path: PathBuf,
+    pub name: String,
+    pub settings: TrackSettings,
+    pub field: usize,
+    /// 歌曲所在专辑和整张专辑跳过开头的秒数，没有专辑标签时为 None
+    pub album: Option<(AlbumKey, f32)>,
+}
+
+/// 均衡器模式：按流派自动选择，或手动指定预设
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EqMode {
+    Auto,
+    Manual(usize),
+}
+
+/// 艺术家/专辑信息面板的内容
+#[derive(Debug, Clone)]
+pub struct InfoPane {
+    pub key: String,
+    pub lines: Vec<String>,
+    pub loading: bool,
+}
+
+/// 歌词面板的内容
+#[derive(Debug, Clone)]
+pub struct LyricsPane {
+    pub path: PathBuf,
+    pub lyrics: Option<Lyrics>,
+    /// 歌词来源或没有歌词的原因
+    pub status: String,
+    pub loading: bool,
+    /// 用方向键选中的行，为 None 时跟随播放位置
+    pub cursor: Option<usize>,
+}
+
 pub struct App {
     pub songs: Vec<Song>,
+    // 启动时指定的播放列表，重新扫描曲库时使用
+    library_source: Option<PathBuf>,
     pub current_index: usize,
     pub playback_state: PlaybackState,
     pub current_time: Duration,
     pub volume: f32,
-    pub is_shuffle: bool,
+    pub shuffle: ShuffleMode,
     pub shuffle_history: VecDeque<usize>,
+    // 队列和曲库编辑的撤销/重做
+    edit_history: History<EditSnapshot>,
+    
+    // 播放队列：下一曲时优先播放，元素为 songs 中的下标
+    pub queue: VecDeque<usize>,
+    
+    // ListenBrainz 推荐电台
+    pub radio: bool,
+    radio_rx: Option<Receiver<Result<Vec<ExternalTrack>, String>>>,
     
     // Rodio components
-    pub _stream: OutputStream,
-    pub _stream_handle: OutputStreamHandle,
+    pub output: AudioOutput,
+    // 音频设备无法打开时的错误，这时只能浏览曲库，按键重试
+    pub audio_error: Option<String>,
     pub sink: Option<Sink>,
+    // 每次开始播放加一；播放管线在歌曲放完时发回这个编号，旧编号说明已经切歌，忽略
+    pub playback_id: u64,
+    end_tx: Sender<u64>,
+    end_rx: Receiver<u64>,
+    // 正在播放的网络音频的下载缓冲
+    pub stream: Option<Arc<HttpStream>>,
+    // 网络数据不够，暂停输出等待缓冲（播放状态仍然是播放中）
+    pub buffering: bool,
+    // 正在后台连接、读取开头的网络音频
+    opening: Option<(PlayRequest, Job)>,
     
     // Progress tracking
     pub play_start_time: Option<std::time::Instant>,
+    /// 播放计时和闹钟使用的时钟，模拟模式下由调用方推进
+    pub clock: Clock,
     pub current_play_time: Duration,
+
+    // 界面状态
+    pub config: Config,
+    pub view: View,
+    pub focus: Focus,
+    pub playlist_selected: usize,
+    /// 播放列表第一行在可见歌曲中的位置，绘制时更新，让光标移动时列表不跳动
+    pub playlist_offset: Cell<usize>,
+    pub queue_selected: usize,
+    pub info_scroll: u16,
+    // 拖动模式下进度条上虚拟光标的位置
+    pub scrub: Option<Duration>,
+    // 上一次按“上一首”的时间，用来判断是不是连按
+    last_previous: Option<Instant>,
+    pub prompt: Option<Prompt>,
+    pub track_editor: Option<TrackEditor>,
+    pub details: Option<DetailsPopup>,
+    pub search: Option<SearchPane>,
+    pub message: Option<(String, Instant)>,
+    // 切歌提醒：外框闪烁时显示的文字和开始时间
+    pub flash: Option<(String, Instant)>,
+    // 最近一首从播放队列取出的歌曲，它播完且队列已空时提醒队列播完
+    queued_path: Option<PathBuf>,
+    // 已经提醒过播放队列快播完，队列重新加长到提醒时间以上之前不再提醒
+    queue_warned: bool,
+    // 下一次开始播放是手动切歌，旧歌曲淡出、新歌曲淡入
+    soft_switch: bool,
+    // 下一次开始播放后保持暂停（暂停时跳转）
+    keep_paused: bool,
+    /// 自动混音时下一次播放的重叠时长和速度
+    mix_in: Option<MixIn>,
+    /// 当前歌曲的结尾分析，完成后得到开始重叠的位置
+    automix_rx: Option<Receiver<Option<Duration>>>,
+    mix_at: Option<Duration>,
+    /// 当前歌曲的章节，换歌时重新读取
+    chapters: (PathBuf, Vec<Chapter>),
+    /// 当前歌曲的 DJ 提示点（Serato 标签和 Rekordbox 曲库中的），换歌时重新读取
+    cues: (PathBuf, Vec<CuePoint>),
+    rekordbox: RekordboxLibrary,
+    pub ab_loop: Option<AbLoop>,
+    // 正在用 DoP 输出 DSD 文件
+    pub dop: bool,
+    pub confirm_quit: bool,
+    // 启动时询问是否恢复上次的播放会话
+    pub restore_prompt: Option<Session>,
+    // 作为曲库打开的 M3U 文件，以及它被其他程序修改后等待确认的重新加载
+    playlist_watch: Option<WatchedPlaylist>,
+    playlist_checked: Option<Instant>,
+    pub playlist_reload: Option<PlaylistChange>,
+    // 闹钟
+    pub scheduler: Scheduler,
+    // 其他程序通过 `music_tui ctl` 发来的控制命令
+    remote: Option<remote::Listener>,
+    // 向 MQTT 服务器发布播放状态并接收命令
+    mqtt: Option<Mqtt>,
+    webhooks: Option<Webhooks>,
+    // 上一次通知 webhook 时的播放状态和歌曲，变化时发送事件
+    webhook_state: (PlaybackState, Option<PathBuf>),
+    web: Option<WebRemote>,
+    // WLED/Hue 灯光，以及上次发给灯光的歌曲和节拍（每分钟拍数，歌曲开头对应的时刻）
+    lighting: Option<Lighting>,
+    lighting_song: Option<PathBuf>,
+    lighting_tempo: Option<(f32, Instant)>,
+    // 显示中的网页遥控配对地址（以二维码显示）
+    pub pairing: Option<String>,
+    // 上次写入磁盘的会话，没有变化时不重复写入
+    saved_session: Option<Session>,
+    session_saved_at: Instant,
+    pub minimize_requested: bool,
+    /// 退出界面后在后台接着播放（见 detached.rs）
+    pub detach_requested: bool,
+    /// 脱离终端运行时，新打开的界面要求接管播放
+    pub attach_requested: bool,
+    /// 以单个文件启动时的预览模式：该文件所在的文件夹就是播放列表
+    pub preview: Option<PathBuf>,
+    /// 预览播放结束后退出
+    pub exit_requested: bool,
+    // 配置了 beets_library 时读取的 beets 曲库，以及等待在 beets 中修改标签的歌曲
+    pub beets: Option<BeetsLibrary>,
+    pub beets_edit: Option<PathBuf>,
+    // 界面状态有变化、需要重绘
+    pub dirty: bool,
+    // 终端大小变了，不等下一帧立即按新的大小重绘
+    pub resized: bool,
+
+    // 书签
+    pub bookmarks: Vec<Bookmark>,
+    pub bookmark_selected: usize,
+    
+    // 按专辑艺术家浏览：打开时重新分组，第 0 项是“全部艺术家”
+    pub artists: Vec<ArtistEntry>,
+    pub artist_selected: usize,
+    pub setting_selected: usize,
+    pub keymap: Keymap,
+    pub key_capture: Option<KeyCapture>,
+    /// 组合键已按下的部分和最后一个键的时间
+    key_sequence: Vec<KeyCode>,
+    key_sequence_at: Option<Instant>,
+    /// 键盘宏的寄存器和录制状态
+    pub macros: Macros,
+    pub artist_filter: Option<(String, HashSet<PathBuf>)>,
+    
+    // 收听统计
+    pub stats: Stats,
+    // 歌曲评分
+    pub ratings: Ratings,
+    
+    // 艺术家/专辑信息
+    pub info_visible: bool,
+    pub info: Option<InfoPane>,
+    info_rx: Option<Receiver<InfoResult>>,
+    
+    // 同步歌词
+    pub lyrics_visible: bool,
+    pub lyrics: Option<LyricsPane>,
+    lyrics_rx: Option<Receiver<LyricsResult>>,
+    // 在线查找过但没有找到歌词的歌曲，本次运行不再重复查找
+    lyrics_misses: HashMap<PathBuf, String>,
+    
+    // 均衡器
+    pub eq: Arc<EqControl>,
+    pub eq_mode: EqMode,
+    pub eq_preset: usize,
+    eq_genre_map: Vec<(String, usize)>,
+    
+    // 响度均衡
+    pub leveler: Arc<LevelerControl>,
+    
+    // 示波器和频谱图
+    pub visualizer: Visualizer,
+    
+    // 卡拉 OK：削弱人声并全屏显示歌词
+    pub karaoke: Arc<KaraokeControl>,
+    
+    // BPM 分析结果、排序和速度过滤
+    pub bpms: HashMap<PathBuf, f32>,
+    /// 纯音乐识别的结果
+    pub instrumentals: HashMap<PathBuf, Detection>,
+    pub instrumental_filter: bool,
+    /// 播放列表中显示的波形缩略图
+    pub waveforms: HashMap<PathBuf, Waveform>,
+    // 每首歌的跳过开头、结尾淡出和前置增益
+    pub track_settings: HashMap<PathBuf, TrackSettings>,
+    pub album_skips: HashMap<AlbumKey, f32>,
+    // 响度分析得到的 LUFS 和真峰值
+    pub loudness: HashMap<PathBuf, replaygain::Loudness>,
+    pub sort_order: SortOrder,
+    pub bpm_filter: Option<(f32, f32)>,
+    pub collections: Vec<Collection>,
+    pub collection_selected: usize,
+    /// 只显示这个收藏集中的歌曲
+    pub collection_filter: Option<String>,
+    // 以名称保存的播放队列，最新的在前
+    pub queue_snapshots: Vec<QueueSnapshot>,
+    pub snapshot_selected: usize,
+    // 流派识别的建议，确认后写入标签，拒绝的不再建议
+    pub genre_suggestions: HashMap<PathBuf, Suggestion>,
+    pub genre_selected: usize,
+    
+    // 暂时无法访问的文件，按退避时间在后台重试
+    pub unavailable: HashMap<PathBuf, Unavailable>,
+    // 无法解码的文件和错误信息，播放时跳过
+    pub decode_errors: HashMap<PathBuf, String>,
+    // 改用 rodio 解码器播放的文件
+    fallback_decode: HashSet<PathBuf>,
+    availability_tx: Sender<(PathBuf, Option<String>)>,
+    availability_rx: Receiver<(PathBuf, Option<String>)>,
+    
+    // 后台任务
+    pub jobs: Vec<Job>,
+    pub jobs_visible: bool,
+    pub job_selected: usize,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
-        let (_stream, _stream_handle) = OutputStream::try_default()?;
+    pub fn new(playlist: Option<PathBuf>) -> Result<Self> {
+        Self::with_clock(playlist, Clock::System)
+    }
+    
+    /// 使用模拟时钟时不打开音频设备、不监听控制端口，也不写入会话和收听统计
+    pub fn with_clock(playlist: Option<PathBuf>, clock: Clock) -> Result<Self> {
+        let config = Config::load();
+        // 参数是单个音频文件时进入预览模式，改为加载它所在的文件夹
+        let preview = playlist
+            .as_deref()
+            .filter(|path| path.is_file() && is_audio_file(path))
+            .map(|path| storage::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        let playlist = match &preview {
+            Some(file) => Some(file.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."))),
+            None => playlist,
+        };
+        // 没有声音服务器或设备被占用时不退出，进入只能浏览的状态
+        let mut audio_error = None;
+        let mut output = match clock.is_simulated() {
+            true => AudioOutput::silent(),
+            false => audio_output::open(&config).unwrap_or_else(|err| {
+                audio_error = Some(err.to_string());
+                AudioOutput::unavailable()
+            }),
+        };
+        let output_warning = output.warning.take();
+        let (availability_tx, availability_rx) = mpsc::channel();
+        let (end_tx, end_rx) = mpsc::channel();
         
         let mut app = App {
             songs: Vec::new(),
+            library_source: playlist.clone(),
             current_index: 0,
             playback_state: PlaybackState::Stopped,
             current_time: Duration::ZERO,
             volume: 0.5,
-            is_shuffle: false,
+            shuffle: ShuffleMode::Off,
             shuffle_history: VecDeque::new(),
-            _stream,
-            _stream_handle,
+            edit_history: History::default(),
+            queue: VecDeque::new(),
+            radio: false,
+            radio_rx: None,
+            output,
+            audio_error,
             sink: None,
+            stream: None,
+            buffering: false,
+            opening: None,
             play_start_time: None,
+            clock,
             current_play_time: Duration::ZERO,
+            config,
+            view: View::Playlist,
+            focus: Focus::Playlist,
+            playlist_selected: 0,
+            playlist_offset: Cell::new(0),
+            queue_selected: 0,
+            info_scroll: 0,
+            scrub: None,
+            last_previous: None,
+            prompt: None,
+            track_editor: None,
+            details: None,
+            search: None,
+            message: None,
+            flash: None,
+            queued_path: None,
+            queue_warned: false,
+            soft_switch: false,
+            keep_paused: false,
+            mix_in: None,
+            automix_rx: None,
+            mix_at: None,
+            chapters: (PathBuf::new(), Vec::new()),
+            cues: (PathBuf::new(), Vec::new()),
+            rekordbox: RekordboxLibrary::default(),
+            ab_loop: None,
+            dop: false,
+            confirm_quit: false,
+            restore_prompt: None,
+            playlist_watch: None,
+            playlist_checked: None,
+            playlist_reload: None,
+            scheduler: Scheduler::default(),
+            remote: None,
+            mqtt: None,
+            webhooks: None,
+            webhook_state: (PlaybackState::Stopped, None),
+            web: None,
+            lighting: None,
+            lighting_song: None,
+            lighting_tempo: None,
+            pairing: None,
+            saved_session: None,
+            session_saved_at: Instant::now(),
+            minimize_requested: false,
+            detach_requested: false,
+            attach_requested: false,
+            preview,
+            exit_requested: false,
+            beets: None,
+            beets_edit: None,
+            dirty: true,
+            resized: false,
+            bookmarks: bookmarks::load(),
+            bookmark_selected: 0,
+            artists: Vec::new(),
+            artist_selected: 0,
+            setting_selected: 0,
+            keymap: Keymap::load(),
+            key_capture: None,
+            key_sequence: Vec::new(),
+            key_sequence_at: None,
+            macros: Macros::default(),
+            artist_filter: None,
+            stats: Stats::load(),
+            ratings: Ratings::load(),
+            info_visible: false,
+            info: None,
+            info_rx: None,
+            lyrics_visible: false,
+            lyrics: None,
+            lyrics_rx: None,
+            lyrics_misses: HashMap::new(),
+            eq: Arc::new(EqControl::default()),
+            eq_mode: EqMode::Auto,
+            eq_preset: 0,
+            eq_genre_map: equalizer::load_genre_map(),
+            leveler: Arc::new(LevelerControl::default()),
+            visualizer: Visualizer::default(),
+            karaoke: Arc::new(KaraokeControl::default()),
+            bpms: bpm::load(),
+            instrumentals: instrumental::load(),
+            instrumental_filter: false,
+            waveforms: waveform::load(),
+            track_settings: track_settings::load(),
+            album_skips: track_settings::load_albums(),
+            loudness: replaygain::load(),
+            sort_order: SortOrder::Name,
+            bpm_filter: None,
+            collections: collections::load(),
+            collection_selected: 0,
+            collection_filter: None,
+            queue_snapshots: snapshots::load(),
+            snapshot_selected: 0,
+            genre_suggestions: genre::load(),
+            genre_selected: 0,
+            unavailable: HashMap::new(),
+            decode_errors: HashMap::new(),
+            fallback_decode: HashSet::new(),
+            availability_tx,
+            availability_rx,
+            playback_id: 0,
+            end_tx,
+            end_rx,
+            jobs: Vec::new(),
+            jobs_visible: false,
+            job_selected: 0,
         };
         
-        app.load_songs()?;
-        Ok(app)
-    }
-    
-    fn get_audio_duration(path: &PathBuf) -> Option<Duration> {
-        let src = match std::fs::File::open(path) {
-            Ok(file) => MediaSourceStream::new(Box::new(file), Default::default()),
-            Err(_) => return None,
-        };
-
-        let mut hint = Hint::new();
-        if let Some(extension) = path.extension() {
-            if let Some(extension_str) = extension.to_str() {
-                hint.with_extension(extension_str);
+        app.load_songs(playlist)?;
+        app.import_tag_ratings();
+        if app.library_source.is_none() && !app.config.remote_sources.is_empty() {
+            app.start_remote_scan();
+        }
+        app.scheduler = Scheduler::from_config(&app.config, app.clock.local_now());
+        app.leveler.set_enabled(app.config.leveler);
+        app.leveler.set_target(app.config.leveler_target_lufs);
+        app.apply_visualizer_mode();
+        if let Some(warning) = output_warning {
+            app.set_message(warning);
+        }
+        if app.clock.is_simulated() {
+            return Ok(app);
+        }
+        // 预览时不打开控制端口、不询问恢复会话，以免和正在运行的播放器冲突
+        if let Some(file) = app.preview.clone() {
+            app.start_preview(&file)?;
+            return Ok(app);
+        }
+        if app.config.remote_port != 0 {
+            match remote::listen(app.config.remote_port) {
+                Ok(listener) => app.remote = Some(listener),
+                Err(err) => app.set_message(format!("{}，ctl 控制命令不可用", err)),
             }
         }
-
-        let meta_opts: MetadataOptions = Default::default();
-        let fmt_opts: FormatOptions = Default::default();
-
-        let probed = match symphonia::default::get_probe().format(&hint, src, &fmt_opts, &meta_opts) {
-            Ok(probed) => probed,
-            Err(_) => return None,
-        };
-
-        let format = probed.format;
-        let track = match format.tracks().iter().next() {
-            Some(track) => track,
-            None => return None,
-        };
-
-        let time_base = track.codec_params.time_base;
-        let duration = track.codec_params.n_frames;
-
-        if let (Some(tb), Some(n_frames)) = (time_base, duration) {
-            let time = tb.calc_time(n_frames);
-            // 修复时间计算：使用正确的秒数计算
-            let total_seconds = time.seconds as f64 + (time.frac as f64 / tb.denom as f64);
-            Some(Duration::from_secs_f64(total_seconds))
-        } else {
-            None
+        if !app.config.mqtt_broker.is_empty() {
+            app.mqtt = Some(Mqtt::start(mqtt::Settings {
+                broker: app.config.mqtt_broker.clone(),
+                topic: app.config.mqtt_topic.clone(),
+                username: app.config.mqtt_username.clone(),
+                password: app.config.mqtt_password.clone(),
+            }));
         }
-    }
-
-    fn load_songs(&mut self) -> Result<()> {
-        let assets_dir = PathBuf::from("assets");
-        if !assets_dir.exists() {
-            return Ok(());
+        if !app.config.webhook_urls.is_empty() {
+            app.webhooks = Some(Webhooks::start(app.config.webhook_urls.clone(), app.config.webhook_retries));
         }
-        
-        for entry in fs::read_dir(assets_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                if matches!(ext.as_str(), "mp3" | "wav" | "flac" | "ogg" | "m4a" | "mp4a") {
-                    let name = path.file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string();
-                    
-                    let duration = Self::get_audio_duration(&path);
-                    
-                    self.songs.push(Song {
-                        path,
-                        name,
-                        duration,
-                    });
-                }
+        if !app.config.lighting_targets.is_empty() {
+            let targets = app.config.lighting_targets.iter().filter_map(|target| lighting::Target::parse(target).ok()).collect();
+            app.lighting = Some(Lighting::start(targets));
+        }
+        if !app.config.web_address.is_empty() {
+            match WebRemote::start(&app.config.web_address, &app.config.web_token) {
+                Ok(web) => app.web = Some(web),
+                Err(err) => app.set_message(err.to_string()),
             }
         }
-        
-        Ok(())
+        if !app.config.metrics_address.is_empty() {
+            if let Err(err) = metrics::serve(&app.config.metrics_address) {
+                app.set_message(err.to_string());
+            }
+        }
+        app.start_waveform_job();
+        if let Some(file) = app.library_source.clone().filter(|path| path.is_file()) {
+            let entries = playlists::load_m3u(&file).unwrap_or_default();
+            app.playlist_watch = Some(WatchedPlaylist::new(&file, entries));
+        }
+        app.restore_prompt = session::load().filter(|session| app.can_restore(session));
+        Ok(app)
     }
     
-    pub fn play(&mut self) -> Result<()> {
-        if self.songs.is_empty() {
+    /// 选中并立即播放预览的文件
+    fn start_preview(&mut self, file: &Path) -> Result<()> {
+        let Some(index) = self.songs.iter().position(|song| song.path == file) else {
+            self.set_message(format!("无法打开: {}", file.display()));
             return Ok(());
+        };
+        self.current_index = index;
+        self.playlist_selected = index;
+        self.play()
+    }
+    
+    /// 会话中的歌曲至少有一首还在曲库中才值得询问
+    fn can_restore(&self, session: &Session) -> bool {
+        let current = session.current.iter().map(|(path, _)| path);
+        current
+            .chain(session.queue.iter())
+            .any(|path| self.songs.iter().any(|song| &song.path == path))
+    }
+    
+    fn current_session(&self, clean_exit: bool) -> Session {
+        let current = match self.playback_state {
+            PlaybackState::Stopped => None,
+            _ => self
+                .get_current_song()
+                .map(|song| (song.path.clone(), self.get_current_time())),
+        };
+        Session {
+            current,
+            queue: self.queue.iter().map(|&index| self.songs[index].path.clone()).collect(),
+            clean_exit,
+            paused: self.playback_state == PlaybackState::Paused,
         }
+    }
+    
+    /// 每隔几秒把会话写入磁盘，程序意外退出后下次启动可以恢复
+    fn autosave_session(&mut self) {
+        // 还没有回答是否恢复时不能覆盖上次的会话
+        if self.restore_prompt.is_some()
+            || self.preview.is_some()
+            || self.clock.is_simulated()
+            || self.session_saved_at.elapsed() < self.batch_interval(SESSION_SAVE_INTERVAL)
+        {
+            return;
+        }
+        self.session_saved_at = Instant::now();
         
-        self.stop()?;
-        
-        let song = &self.songs[self.current_index];
-        
-        let file = fs::File::open(&song.path)?;
-        let reader = BufReader::new(file);
-        
-        let sink = Sink::try_new(&self._stream_handle)?;
-        
-        // Try to decode with rodio decoder
-        match Decoder::new(reader) {
-            Ok(decoder) => {
-                sink.append(decoder);
-                sink.set_volume(self.volume);
-                sink.play();
-                
-                self.sink = Some(sink);
-                self.playback_state = PlaybackState::Playing;
-                self.play_start_time = Some(std::time::Instant::now());
-                self.current_play_time = Duration::ZERO;
-            }
-            Err(_) => {
-                // 解码失败，尝试下一个文件
-                if self.songs.len() > 1 {
-                    self.next_without_play()?;
-                    self.play()?;
-                }
+        let session = self.current_session(false);
+        if self.saved_session.as_ref() != Some(&session) {
+            if let Err(err) = session::save(&session) {
+                self.set_message(format!("保存播放会话失败: {}", err));
             }
+            self.saved_session = Some(session);
         }
-        
-        Ok(())
     }
     
-    pub fn pause(&mut self) {
-        if let Some(ref sink) = self.sink {
-            sink.pause();
-            self.playback_state = PlaybackState::Paused;
-            // 保存当前播放时间
-            if let Some(start_time) = self.play_start_time {
-                self.current_play_time = start_time.elapsed();
-            }
+    /// 正常退出时保存会话
+    pub fn save_session_on_exit(&self) -> Result<()> {
+        if self.restore_prompt.is_some() || self.preview.is_some() || self.clock.is_simulated() {
+            return Ok(());
         }
+        session::save(&self.current_session(true))
     }
     
-    pub fn resume(&mut self) {
-        if let Some(ref sink) = self.sink {
-            sink.play();
-            self.playback_state = PlaybackState::Playing;
-            // 重新设置开始时间，考虑已经播放的时间
-            let elapsed = self.current_play_time;
-            self.play_start_time = Some(std::time::Instant::now() - elapsed);
-        }
+    /// 接管另一个播放器进程（界面或脱离终端的播放器）刚保存的会话：
+    /// 不询问直接恢复，暂停中的歌曲保持暂停
+    pub fn take_over_session(&mut self) -> Result<()> {
+        self.keep_paused = self.restore_prompt.as_ref().is_some_and(|session| session.paused);
+        let result = self.restore_session();
+        self.keep_paused = false;
+        result
     }
     
-    pub fn stop(&mut self) -> Result<()> {
-        if let Some(sink) = self.sink.take() {
-            sink.stop();
+    /// 脱离终端：退出界面后由后台的播放器进程接着播放，之后可以通过控制端口接回
+    pub fn request_detach(&mut self) {
+        if self.preview.is_some() {
+            self.set_message("预览模式不能脱离终端");
+        } else if self.config.remote_port == 0 || self.remote.is_none() {
+            self.set_message("控制端口不可用，无法脱离终端后再接回");
+        } else if self.playback_state == PlaybackState::Stopped {
+            self.set_message("没有在播放，不需要脱离终端");
+        } else {
+            // 正在播放说明已经不需要恢复上次的会话，退出时保存当前会话交给后台播放器
+            self.restore_prompt = None;
+            self.detach_requested = true;
         }
-        self.playback_state = PlaybackState::Stopped;
-        self.current_time = Duration::ZERO;
-        self.play_start_time = None;
-        self.current_play_time = Duration::ZERO;
-        Ok(())
     }
     
-    pub fn next(&mut self) -> Result<()> {
-        if self.songs.is_empty() {
-            return Ok(());
+    /// 脱离终端运行时控制端口是接回界面的唯一途径，打不开就不能运行。
+    /// 界面进程刚退出时端口可能还没释放，稍等再试
+    pub fn listen_detached(&mut self) -> Result<()> {
+        let deadline = Instant::now() + DETACH_LISTEN_TIMEOUT;
+        while self.remote.is_none() {
+            match remote::listen(self.config.remote_port) {
+                Ok(listener) => self.remote = Some(listener),
+                Err(err) if Instant::now() >= deadline => return Err(err),
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
         }
-        
-        if self.is_shuffle {
-            self.next_shuffle();
-        } else {
-            self.current_index = (self.current_index + 1) % self.songs.len();
+        if let Some(remote) = &self.remote {
+            remote.set_detached();
         }
-        
-        self.play()?;
         Ok(())
     }
     
-    pub fn next_without_play(&mut self) -> Result<()> {
-        if self.songs.is_empty() {
+    /// 恢复上次的当前歌曲、播放位置和队列
+    pub fn restore_session(&mut self) -> Result<()> {
+        let Some(session) = self.restore_prompt.take() else {
             return Ok(());
-        }
+        };
+        // 儿童模式下不恢复被隐藏的歌曲
+        let positions: HashMap<&Path, usize> = self
+            .songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| self.in_scope(song))
+            .map(|(index, song)| (song.path.as_path(), index))
+            .collect();
+        self.queue = session.queue.iter().filter_map(|path| positions.get(path.as_path()).copied()).collect();
         
-        if self.is_shuffle {
-            self.next_shuffle();
-        } else {
-            self.current_index = (self.current_index + 1) % self.songs.len();
+        let current = session
+            .current
+            .and_then(|(path, position)| Some((*positions.get(path.as_path())?, position)));
+        match current {
+            Some((index, position)) => {
+                self.current_index = index;
+                self.playlist_selected = index;
+                self.play_from(position)?;
+                self.set_message("已恢复上次的播放会话");
+            }
+            None => self.set_message(format!("已恢复播放队列（{} 首）", self.queue.len())),
         }
-        
         Ok(())
     }
     
-    pub fn previous(&mut self) -> Result<()> {
-        if self.songs.is_empty() {
-            return Ok(());
-        }
+    fn load_songs(&mut self, playlist: Option<PathBuf>) -> Result<()> {
+        let filter = self.scan_filter();
+        let paths: Vec<PathBuf> = library_paths(playlist.as_deref())?
+            .into_iter()
+            .filter(|path| filter.allows_path(path))
+            .collect();
         
-        if self.current_index == 0 {
-            self.current_index = self.songs.len() - 1;
-        } else {
-            self.current_index -= 1;
+        let mut cache = MetadataCache::load();
+        let workers = self.probe_workers();
+        self.load_beets();
+        self.load_rekordbox();
+        self.songs = match &self.beets {
+            Some(beets) => load_with_beets(paths, beets, &mut cache, workers),
+            None => load_cached(paths, &mut cache, workers),
+        };
+        self.songs.retain(|song| filter.allows_song(song));
+        if let Err(err) = cache.save() {
+            self.set_message(format!("保存元数据缓存失败: {}", err));
         }
-        
-        self.play()?;
         Ok(())
     }
     
-    pub fn toggle_shuffle(&mut self) {
-        self.is_shuffle = !self.is_shuffle;
-        if self.is_shuffle {
-            self.shuffle_history.clear();
+    fn load_beets(&mut self) {
+        if self.config.beets_library.is_empty() {
+            return;
+        }
+        match BeetsLibrary::load(Path::new(&self.config.beets_library)) {
+            Ok(library) => self.beets = Some(library),
+            Err(err) => self.set_message(format!("读取 beets 曲库失败: {}", err)),
         }
     }
     
-    fn next_shuffle(&mut self) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        
-        if self.shuffle_history.len() >= self.songs.len() {
-            self.shuffle_history.clear();
+    fn load_rekordbox(&mut self) {
+        if self.config.rekordbox_xml.is_empty() {
+            return;
         }
-        
-        let mut next_index;
-        loop {
-            next_index = rng.gen_range(0..self.songs.len());
-            if !self.shuffle_history.contains(&next_index) {
-                break;
-            }
+        match RekordboxLibrary::load(Path::new(&self.config.rekordbox_xml)) {
+            Ok(library) => self.rekordbox = library,
+            Err(err) => self.set_message(format!("读取 Rekordbox 曲库失败: {}", err)),
         }
-        
-        self.shuffle_history.push_back(self.current_index);
-        self.current_index = next_index;
     }
     
-    pub fn set_volume(&mut self, volume: f32) {
-        self.volume = volume.max(0.0).min(1.0);
-        if let Some(ref sink) = self.sink {
-            sink.set_volume(self.volume);
+    /// 在 beets 中修改光标所在歌曲的标签，由主循环交出终端后执行
+    pub fn request_beets_edit(&mut self) {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return;
+        };
+        match &self.beets {
+            Some(beets) if beets.get(&song.path).is_some() => self.beets_edit = Some(song.path.clone()),
+            Some(_) => self.set_message("这首歌不在 beets 曲库中"),
+            None => self.set_message("没有配置 beets 曲库（beets_library）"),
         }
     }
     
-    pub fn get_current_song(&self) -> Option<&Song> {
-        self.songs.get(self.current_index)
+    /// 编辑器退出后从 beets 重新读取这首歌的标签
+    pub fn finish_beets_edit(&mut self, path: &Path, result: Result<()>) {
+        if let Err(err) = result {
+            self.set_message(format!("运行 beets 失败: {}", err));
+            return;
+        }
+        let Some(beets) = self.beets.as_mut() else {
+            return;
+        };
+        let probe = match beets.reload(path) {
+            Ok(Some(item)) => item.probe.clone(),
+            Ok(None) => return self.set_message("这首歌已不在 beets 曲库中"),
+            Err(err) => return self.set_message(format!("读取 beets 曲库失败: {}", err)),
+        };
+        let mut updated = None;
+        for song in self.songs.iter_mut().filter(|song| song.path == path) {
+            let missing = song.missing;
+            *song = Song::from_probe(song.path.clone(), probe.clone());
+            song.missing = missing;
+            updated = Some(song.name.clone());
+        }
+        if let Some(name) = updated {
+            self.set_message(format!("已从 beets 更新标签: {}", name));
+        }
+    }
+    
+    /// 扫描目录时使用配置中的排除规则，显式指定的播放列表则原样加载
+    fn scan_filter(&self) -> ScanFilter {
+        match self.library_source {
+            Some(_) => ScanFilter::default(),
+            None => ScanFilter::from_config(&self.config),
+        }
+    }
+    
+    pub fn play(&mut self) -> Result<()> {
+        self.play_from(Duration::ZERO)
+    }
+    
+    /// 从指定位置开始播放当前歌曲
+    pub fn play_from(&mut self, position: Duration) -> Result<()> {
+        let paused = std::mem::take(&mut self.keep_paused);
+        if self.songs.is_empty() {
+            return Ok(());
+        }
+        if self.audio_error.is_some() {
+            self.set_message(format!("音频设备不可用，按 {} 重新打开", self.keymap.action_label(Action::RetryAudio)));
+            return Ok(());
+        }
+        
+        // 手动切歌：旧歌曲在后台淡出，新歌曲淡入；自动混音时淡出淡入的时间就是重叠的时长
+        let mix = self.mix_in.take();
+        let switch_fade = match &mix {
+            Some(mix) => mix.overlap,
+            None => Duration::from_millis(self.config.switch_fade_ms),
+        };
+        let soft = (std::mem::take(&mut self.soft_switch) || mix.is_some())
+            && !switch_fade.is_zero()
+            && !self.clock.is_simulated();
+        if soft && self.playback_state == PlaybackState::Playing && !self.dop {
+            if let Some(sink) = self.sink.take() {
+                audio_output::fade_out(sink, switch_fade);
+            }
+        }
+        self.automix_rx = None;
+        self.mix_at = None;
+        self.stop()?;
+        self.apply_genre_eq();
+        
+        // 儿童模式和收藏集是硬性限制：队列、书签等途径选到范围之外的歌曲也不播放
+        if !self.in_scope(&self.songs[self.current_index]) {
+            match self.songs.iter().any(|song| self.in_scope(song)) {
+                true => self.set_message(OUT_OF_SCOPE),
+                false => self.set_message("当前范围内没有可以播放的歌曲（儿童模式或收藏集），已停止播放"),
+            }
+            return Ok(());
+        }
+        let path = self.songs[self.current_index].path.clone();
+        self.dop = false;
+        let start = self.start_position(position);
+        // 网络音频要等服务器回应、读取开头的数据，在后台打开，期间界面显示缓冲中
+        if http_stream::is_url(&path) && self.cached_copy(&path).is_none() {
+            self.open_stream(PlayRequest { path, deadline: None, position, start, mix, soft, switch_fade, paused });
+            return Ok(());
+        }
+        let media = self.media_path(&path);
+        let fallback = self.fallback_decode.contains(&path);
+        // 模拟模式不调用 poll_background，直接在当前线程打开
+        if self.clock.is_simulated() {
+            let opened = open_file(&media, &path, start, fallback);
+            return self.finish_open(PlayRequest { path, deadline: None, position, start, mix, soft, switch_fade, paused }, opened);
+        }
+        // 网络共享上的文件可能很久才打开，在后台打开，超时由 update_opening 处理
+        let deadline = Some(Instant::now() + Duration::from_millis(self.config.open_timeout_ms));
+        let request = PlayRequest { path: path.clone(), deadline, position, start, mix, soft, switch_fade, paused };
+        let name = self.songs[self.current_index].name.clone();
+        let job = Job::spawn(format!("打开 {}", name), move |_| Ok(JobOutput::Opened(open_file(&media, &path, start, fallback))));
+        self.begin_opening(request, job);
+        Ok(())
+    }
+    
+    /// 从头播放时按歌曲或专辑的设置跳过开头
+    fn start_position(&self, position: Duration) -> Duration {
+        if !position.is_zero() {
+            return position;
+        }
+        let skip = self.intro_skip(&self.songs[self.current_index]);
+        self.get_total_duration().map_or(skip, |total| skip.min(total))
+    }
+    
+    /// 在后台连接网络音频并解码开头，完成后由 update_opening 开始播放
+    fn open_stream(&mut self, request: PlayRequest) {
+        let url = request.path.to_string_lossy().to_string();
+        let path = request.path.clone();
+        let start = request.start;
+        let fallback = self.fallback_decode.contains(&path);
+        let name = self.songs[self.current_index].name.clone();
+        let job = Job::spawn(format!("打开 {}", name), move |progress| {
+            progress.set_status("正在连接…");
+            let stream = HttpStream::open(&url)?;
+            progress.set_status("正在缓冲…");
+            let decoded = decode(Box::new(stream.reader()), &path, start, fallback);
+            // 等待期间已经切歌的话结果不会再被使用，停止下载
+            match decoded.and_then(|decoded| progress.check_cancelled().map(|()| decoded)) {
+                Ok((source, layout)) => Ok(JobOutput::Stream(stream, source, layout)),
+                Err(err) => {
+                    stream.close();
+                    Err(err)
+                }
+            }
+        });
+        self.begin_opening(request, job);
+    }
+    
+    /// 等待后台打开歌曲，期间界面显示缓冲中
+    fn begin_opening(&mut self, request: PlayRequest, job: Job) {
+        self.current_play_time = request.start;
+        self.opening = Some((request, job));
+        self.playback_state = PlaybackState::Playing;
+        self.buffering = true;
+        self.dirty = true;
+    }
+    
+    /// 后台打开的歌曲准备好后开始播放，打开失败时和解码失败一样处理；
+    /// 本地文件超时没有打开时把所在的挂载点标记为暂不可用
+    fn update_opening(&mut self) {
+        let Some((request, job)) = self.opening.as_mut() else {
+            return;
+        };
+        let output = job.poll();
+        let timed_out = request.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if job.is_running() && !timed_out {
+            return;
+        }
+        let Some((request, job)) = self.opening.take() else {
+            return;
+        };
+        self.buffering = false;
+        self.dirty = true;
+        if job.is_running() {
+            // 打开文件的线程卡在系统调用里，返回后结果被丢弃
+            job.cancel();
+            self.open_timed_out(&request.path);
+            return;
+        }
+        let result = match (output, &job.state) {
+            (Some(JobOutput::Opened(opened)), _) => self.finish_open(request, opened),
+            (Some(JobOutput::Stream(stream, source, layout)), _) => {
+                self.stream = Some(stream);
+                self.start_decoded(request, Ok((source, layout)))
+            }
+            (_, JobState::Failed(err)) => self.start_decoded(request, Err(anyhow::anyhow!("{}", err))),
+            _ => self.start_decoded(request, Err(anyhow::anyhow!("{}", job.status()))),
+        };
+        if let Err(err) = result {
+            self.set_message(err.to_string());
+        }
+    }
+    
+    /// 本地文件打开后开始播放。文件不存在或打不开时跳到下一首，
+    /// 网络共享断开时把整个挂载点标记为暂不可用并停止播放
+    fn finish_open(&mut self, request: PlayRequest, opened: Opened) -> Result<()> {
+        let path = request.path.clone();
+        let decoded = match opened {
+            Opened::Unreadable(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(song) = self.songs.iter_mut().find(|song| song.path == path) {
+                    song.missing = true;
+                }
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                self.set_message(format!("文件不存在，已跳过: {}（F: 查找移动的文件  X: 移除缺失条目）", name));
+                return self.skip_unplayable();
+            }
+            Opened::Unreadable(err) if matches!(err.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::NotConnected) => {
+                self.open_timed_out(&path);
+                return Ok(());
+            }
+            Opened::Unreadable(err) => {
+                // 单个文件暂时无法访问：标记后跳过，稍后在后台重试
+                self.mark_unavailable(path, err.to_string());
+                return self.skip_unplayable();
+            }
+            Opened::Dsd => self.open_dsd(&path, request.start).map(|source| (source, None)),
+            Opened::Decoded(decoded) => decoded,
+        };
+        // 能打开说明文件和所在的挂载点都已恢复
+        self.unavailable.retain(|key, _| !path.starts_with(key));
+        self.start_decoded(request, decoded)
+    }
+    
+    /// 挂载点没有响应：其中的歌曲都标记为暂不可用，停止播放而不是逐首尝试，
+    /// 之后按退避时间在后台重试整个挂载点
+    fn open_timed_out(&mut self, path: &Path) {
+        let mount = file_access::mount_point(path);
+        let reason = format!("打开文件超过 {} 秒没有响应", self.config.open_timeout_ms as f32 / 1000.0);
+        self.unavailable
+            .entry(mount.clone())
+            .and_modify(|entry| entry.failed_again(reason.clone()))
+            .or_insert_with(|| Unavailable::new(reason));
+        self.playback_state = PlaybackState::Stopped;
+        self.set_message(format!("{} 没有响应，已停止播放，恢复访问后自动去掉标记", mount.display()));
+    }
+    
+    /// 用打开好的音源开始播放，解码失败时提示并跳到下一首
+    fn start_decoded(&mut self, request: PlayRequest, decoded: Result<(BoxedSource, Option<u32>)>) -> Result<()> {
+        let PlayRequest { path, position, start, mix, soft, switch_fade, paused, .. } = request;
+        let settings = self.track_settings.get(&path).copied().unwrap_or_default();
+        match decoded {
+            Ok((mut source, layout)) => {
+                self.decode_errors.remove(&path);
+                // 5.1 等多声道文件按标准系数混成立体声，DoP 数据不能混音。
+                // 允许多声道输出时，PipeWire 输出先换成歌曲的声道数
+                let channels = source.channels();
+                if !self.dop && !self.clock.is_simulated() {
+                    let wanted = if self.config.surround_passthrough { channels.max(2) } else { 2 };
+                    self.output.match_channels(wanted, &self.config);
+                }
+                if !self.dop && downmix::needed(channels, self.output.channels, self.config.surround_passthrough) {
+                    source = Box::new(Downmix::new(source, layout));
+                }
+                // 网络音频在打开之前不知道时长
+                let song = &mut self.songs[self.current_index];
+                if song.duration.is_none() {
+                    song.duration = source.total_duration();
+                }
+                let total = self.get_total_duration();
+                // 跟随歌曲采样率：先把设备切换到歌曲的采样率，切换不了时才用配置的重采样器转换
+                if self.config.match_sample_rate
+                    && !self.output.match_sample_rate(source.sample_rate(), &self.config)
+                {
+                    self.set_message(format!("输出设备不支持 {} Hz，已重采样", source.sample_rate()));
+                }
+                let sink = self.output.new_sink()?;
+                if self.dop {
+                    // DoP 数据必须原样交给 DAC：不经过重采样、增益、淡入淡出和抖动
+                    sink.append(source);
+                } else {
+                    // 自动混音：新歌曲从第一个声音开始，按旧歌曲的速度播放，重叠之后慢慢回到原速
+                    if let Some(mix) = &mix {
+                        source = Box::new(SkipSilence::new(source));
+                        if mix.ratio != 1.0 {
+                            source = Box::new(TempoRamp::new(source, mix.ratio, mix.overlap));
+                        }
+                    }
+                    let source = audio_output::resample(source, self.output.sample_rate, self.config.resampler);
+                    let mut source = TrackGain::new(source, settings, start, total.unwrap_or_default());
+                    if soft {
+                        source = source.with_fade_in(switch_fade);
+                    }
+                    let tap = self.visualizer.tap.clone();
+                    let dither = self.dither_bits(&self.songs[self.current_index]);
+                    if self.config.match_sample_rate {
+                        // 位精确输出：不经过响度均衡和均衡器
+                        sink.append(Dither::new(Tap::new(source, tap), dither));
+                    } else {
+                        let track_gain = self.songs[self.current_index].tags.replaygain_db;
+                        let source = VocalReducer::new(source, self.karaoke.clone());
+                        let source = Leveler::new(source, self.leveler.clone(), track_gain);
+                        sink.append(Dither::new(Tap::new(Equalizer::new(source, self.eq.clone()), tap), dither));
+                    }
+                }
+                
+                // 歌曲的所有样本都交给声卡之后才会执行这个回调
+                self.playback_id += 1;
+                let id = self.playback_id;
+                let end_tx = self.end_tx.clone();
+                sink.append(EmptyCallback::<f32>::new(Box::new(move || {
+                    let _ = end_tx.send(id);
+                })));
+                sink.set_volume(self.output_volume());
+                match paused {
+                    true => sink.pause(),
+                    false => sink.play(),
+                }
+                
+                self.sink = Some(sink);
+                self.playback_state = if paused { PlaybackState::Paused } else { PlaybackState::Playing };
+                self.play_start_time = Some(self.clock.now() - start);
+                self.current_play_time = start;
+                
+                // 从头播放才算一次收听，书签跳转等不重复计数
+                if position.is_zero() && !self.clock.is_simulated() {
+                    let path = self.songs[self.current_index].path.clone();
+                    self.stats.record_play(&path);
+                    metrics::record_track();
+                    if let Err(err) = self.stats.save() {
+                        self.set_message(format!("保存收听统计失败: {}", err));
+                    }
+                }
+                if position.is_zero() {
+                    self.warn_clipping();
+                }
+                self.plan_automix();
+                self.load_chapters();
+            }
+            Err(err) => {
+                // 解码失败：记下错误并提示，然后尝试下一个文件
+                let name = self.songs[self.current_index].name.clone();
+                self.set_message(format!("无法解码 {}: {}（r: 换用另一个解码器重试）", name, err));
+                self.decode_errors.insert(path, err.to_string());
+                metrics::record_decode_error();
+                self.skip_unplayable()?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// 自动混音能否用于当前的播放：DoP 和跟随歌曲采样率的输出不能叠加两路声音，网络流切歌时会被关闭
+    fn automix_active(&self) -> bool {
+        self.config.automix
+            && !self.dop
+            && !self.config.match_sample_rate
+            && self.stream.is_none()
+            && !self.clock.is_simulated()
+            && self.songs.len() > 1
+    }
+    
+    /// 当前歌曲按自己的 BPM 计算的重叠时长
+    fn automix_overlap(&self) -> Duration {
+        let bpm = self.get_current_song().and_then(|song| self.bpms.get(&song.path).copied());
+        automix::overlap(bpm, self.config.automix_beats)
+    }
+    
+    /// 开始播放后在后台找出结尾开始重叠的位置
+    fn plan_automix(&mut self) {
+        if !self.automix_active() {
+            return;
+        }
+        let (Some(song), Some(total)) = (self.get_current_song(), self.get_total_duration()) else {
+            return;
+        };
+        let bpm = self.bpms.get(&song.path).copied();
+        let path = song.path.clone();
+        self.automix_rx = Some(automix::spawn_analysis(path, total, bpm, self.automix_overlap()));
+    }
+    
+    /// 播放到重叠位置时开始播放下一首，旧歌曲在后台淡出。
+    /// 结尾还没分析完时从总时长往前推算；歌曲短于两倍重叠时长时不混音
+    fn update_automix(&mut self) -> Result<()> {
+        if let Some(rx) = &self.automix_rx {
+            match rx.try_recv() {
+                Ok(mix_at) => {
+                    self.mix_at = mix_at;
+                    self.automix_rx = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.automix_rx = None,
+            }
+        }
+        if !self.automix_active() || self.playback_state != PlaybackState::Playing || self.sink.is_none() {
+            return Ok(());
+        }
+        let Some(total) = self.get_total_duration() else {
+            return Ok(());
+        };
+        let overlap = self.automix_overlap();
+        if total < overlap * 2 {
+            return Ok(());
+        }
+        let mix_at = self.mix_at.unwrap_or(total - overlap).max(overlap);
+        if self.get_current_time() < mix_at {
+            return Ok(());
+        }
+        
+        let from = self.get_current_song().and_then(|song| self.bpms.get(&song.path).copied());
+        let follow = self.cursor_on_current();
+        self.next_without_play()?;
+        let to = self.get_current_song().and_then(|song| self.bpms.get(&song.path).copied());
+        let ratio = automix::tempo_ratio(from, to, self.config.automix_tolerance as f32).unwrap_or(1.0);
+        self.mix_in = Some(MixIn { overlap, ratio });
+        self.play()?;
+        self.follow_playback(follow);
+        if let Some(song) = self.get_current_song() {
+            let text = format!("正在播放: {}", song.name);
+            self.alert(text);
+        }
+        Ok(())
+    }
+    
+    /// 打开 DSD 文件。DoP 输出要求开启 match_sample_rate、24 位以上的整数输出格式，并且设备支持 DSD 采样率的 1/16，
+    /// 条件不满足时转换成 PCM 播放
+    fn open_dsd(&mut self, path: &Path, start: Duration) -> Result<BoxedSource> {
+        let reader = DsdReader::open(path)?;
+        if self.config.dsd_output == DsdOutput::Dop {
+            let rate = reader.info.dop_rate();
+            let reason = if !self.config.match_sample_rate {
+                Some("需要开启 match_sample_rate".to_string())
+            } else if self.output.bits.filter(|&bits| bits >= 24).is_none() {
+                Some("输出格式不是 24 或 32 位整数".to_string())
+            } else if !self.output.match_sample_rate(rate, &self.config) {
+                Some(format!("输出设备不支持 {} Hz", rate))
+            } else {
+                None
+            };
+            match reason {
+                None => {
+                    self.dop = true;
+                    self.set_message(format!("{} DoP 输出，音量由 DAC 控制", reader.info.name()));
+                    return Ok(Box::new(Dop::new(reader, start)?));
+                }
+                Some(reason) => self.set_message(format!("无法用 DoP 输出（{}），已转换成 PCM", reason)),
+            }
+        }
+        Ok(Box::new(DsdToPcm::new(reader, start, self.config.dsd_filter)?))
+    }
+    
+    /// 播放这首歌时抖动到的位深，不需要抖动时为 None
+    pub fn dither_bits(&self, song: &Song) -> Option<u32> {
+        if self.config.dither == DitherMode::Off {
+            return None;
+        }
+        let bits = self.output.bits.filter(|&bits| bits <= 16)?;
+        // 跟随歌曲采样率时没有增益处理，位深不超过输出的文件原样输出；有损格式解码后没有固定位深
+        let source = song.badge.as_ref().and_then(|badge| badge.bits_per_sample);
+        if self.config.match_sample_rate && source.is_some_and(|source| source <= bits) {
+            return None;
+        }
+        Some(bits)
+    }
+    
+    /// 远程曲库中最近播放过的歌曲在本地的缓存
+    fn cached_copy(&self, path: &Path) -> Option<PathBuf> {
+        remote_library::source_of(path, &self.config.remote_sources)
+            .map(|_| remote_library::cached_copy(path))
+            .filter(|copy| copy.is_file())
+    }
+    
+    /// 要打开的文件：本地文件本身，或者远程歌曲的本地缓存
+    fn media_path(&mut self, path: &Path) -> PathBuf {
+        if let Some(copy) = self.cached_copy(path) {
+            remote_library::touch(&copy);
+            // 标签在第一次缓存之后才能读到
+            let song = &mut self.songs[self.current_index];
+            if song.badge.is_none() {
+                let probe = metadata::probe(&copy);
+                song.duration = probe.duration.or(song.duration);
+                song.badge = probe.badge();
+                song.tags = probe.tags;
+            }
+            return copy;
+        }
+        path.to_path_buf()
+    }
+    
+    /// 当前歌曲无法播放时跳到下一首，所有歌曲都无法播放时停止
+    fn skip_unplayable(&mut self) -> Result<()> {
+        let current = self.current_index;
+        if (0..self.songs.len()).any(|index| index != current && self.is_playable(index)) {
+            self.next_without_play()?;
+            self.play()?;
+        } else {
+            self.set_message("没有可以播放的歌曲");
+        }
+        Ok(())
+    }
+    
+    fn mark_unavailable(&mut self, path: PathBuf, reason: String) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        self.unavailable
+            .entry(path)
+            .and_modify(|entry| entry.failed_again(reason.clone()))
+            .or_insert_with(|| Unavailable::new(reason.clone()));
+        self.set_message(format!("暂时无法访问 {}，稍后自动重试: {}", name, reason));
+    }
+    
+    /// 歌曲当前是否暂时无法访问
+    pub fn is_unavailable(&self, index: usize) -> bool {
+        self.songs
+            .get(index)
+            .is_some_and(|song| self.unavailable_entry(&song.path).is_some())
+    }
+    
+    /// 文件本身或所在的挂载点暂时无法访问时的记录
+    pub fn unavailable_entry(&self, path: &Path) -> Option<&Unavailable> {
+        self.unavailable
+            .iter()
+            .find(|(key, _)| path.starts_with(key))
+            .map(|(_, entry)| entry)
+    }
+    
+    /// 自动切歌时可以选择的歌曲：通过过滤、文件存在且当前可以访问
+    fn is_playable(&self, index: usize) -> bool {
+        self.is_visible(index)
+            && !self.is_unavailable(index)
+            && self
+                .songs
+                .get(index)
+                .is_some_and(|song| !song.missing && !self.decode_errors.contains_key(&song.path))
+    }
+    
+    /// 换用另一个解码器重新播放光标所在的歌曲（symphonia 流式解码 ↔ rodio 自带解码器）
+    pub fn retry_selected_decode(&mut self) -> Result<()> {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return Ok(());
+        };
+        let path = song.path.clone();
+        if self.decode_errors.remove(&path).is_none() {
+            self.set_message("这首歌没有解码错误");
+            return Ok(());
+        }
+        
+        let pipeline = if self.fallback_decode.remove(&path) {
+            "symphonia 流式解码"
+        } else {
+            self.fallback_decode.insert(path);
+            "rodio 解码器"
+        };
+        self.set_message(format!("改用 {} 重试: {}", pipeline, song.name));
+        self.current_index = self.playlist_selected;
+        self.play()
+    }
+    
+    /// 到了重试时间的文件在后台重新尝试打开，恢复后即可再次播放
+    fn update_availability(&mut self) {
+        let now = Instant::now();
+        for (path, entry) in self.unavailable.iter_mut() {
+            if !entry.checking && entry.retry_at <= now {
+                entry.checking = true;
+                self.dirty = true;
+                file_access::spawn_check(path.clone(), self.availability_tx.clone());
+            }
+        }
+        
+        while let Ok((path, error)) = self.availability_rx.try_recv() {
+            self.dirty = true;
+            match error {
+                None => {
+                    if self.unavailable.remove(&path).is_some() {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                        self.set_message(format!("已恢复访问: {}", name));
+                    }
+                }
+                Some(reason) => {
+                    if let Some(entry) = self.unavailable.get_mut(&path) {
+                        entry.failed_again(reason);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// 跳转到当前歌曲的指定位置，暂停状态下跳转后保持暂停
+    pub fn seek_to(&mut self, position: Duration) -> Result<()> {
+        // 歌曲在后台打开，打开之后才能暂停，不能在 play_from 返回后马上调用 pause
+        self.keep_paused = self.playback_state == PlaybackState::Paused;
+        let position = self.clamp_to_total(position);
+        self.play_from(position)
+    }
+    
+    /// 换歌后读取新歌曲的章节，网络歌曲没有
+    fn load_chapters(&mut self) {
+        let Some(path) = self.get_current_song().map(|song| song.path.clone()) else {
+            return;
+        };
+        if self.chapters.0 != path {
+            let chapters = if path.is_file() { metadata::chapters(&path) } else { Vec::new() };
+            self.chapters = (path.clone(), chapters);
+        }
+        if self.cues.0 != path {
+            let mut cues = if path.is_file() { cues::read(&path) } else { Vec::new() };
+            cues::merge(&mut cues, self.rekordbox.get(&path));
+            self.cues = (path, cues);
+        }
+    }
+    
+    pub fn current_cues(&self) -> &[CuePoint] {
+        match self.get_current_song() {
+            Some(song) if song.path == self.cues.0 => &self.cues.1,
+            _ => &[],
+        }
+    }
+    
+    /// 跳到当前位置之后（或之前）最近的章节、DJ 提示点或书签。
+    /// 往回跳时忽略刚经过的一秒内的标记，连续按可以一个个往前跳
+    pub fn jump_marker(&mut self, forward: bool) -> Result<()> {
+        let Some(path) = self.get_current_song().map(|song| song.path.clone()) else {
+            return Ok(());
+        };
+        if self.playback_state == PlaybackState::Stopped {
+            self.set_message("播放时才能跳到标记");
+            return Ok(());
+        }
+        let mut markers: Vec<(Duration, String)> = self
+            .current_chapters()
+            .iter()
+            .map(|(start, name)| (*start, name.clone()))
+            .collect();
+        markers.extend(self.current_cues().iter().map(|cue| (cue.position, cue.describe())));
+        markers.extend(
+            self.bookmarks
+                .iter()
+                .filter(|bookmark| bookmark.path == path)
+                .map(|bookmark| (bookmark.position, format!("书签「{}」", bookmark.name))),
+        );
+        markers.sort_by_key(|(position, _)| *position);
+        let position = self.get_current_time();
+        let target = match forward {
+            true => markers.into_iter().find(|(marker, _)| *marker > position),
+            false => markers.into_iter().rev().find(|(marker, _)| *marker + MARKER_BACK_WINDOW < position),
+        };
+        let Some((marker, name)) = target else {
+            self.set_message(if forward { "后面没有标记了" } else { "前面没有标记了" });
+            return Ok(());
+        };
+        self.seek_to(marker)?;
+        self.set_message(format!("{} ({})", name, format_duration(marker)));
+        Ok(())
+    }
+    
+    pub fn current_chapters(&self) -> &[Chapter] {
+        match self.get_current_song() {
+            Some(song) if song.path == self.chapters.0 => &self.chapters.1,
+            _ => &[],
+        }
+    }
+    
+    /// 当前播放位置所在的章节
+    pub fn current_chapter(&self) -> Option<&Chapter> {
+        let position = self.get_current_time();
+        self.current_chapters().iter().rev().find(|(start, _)| *start <= position)
+    }
+    
+    /// 当前歌曲的 A-B 循环
+    pub fn current_loop(&self) -> Option<&AbLoop> {
+        let song = self.get_current_song()?;
+        self.ab_loop.as_ref().filter(|ab_loop| ab_loop.path == song.path)
+    }
+    
+    /// 第一次按设置 A 点，第二次设置 B 点并开始循环，第三次取消
+    pub fn cycle_ab_loop(&mut self) {
+        let Some(path) = self.get_current_song().map(|song| song.path.clone()) else {
+            return;
+        };
+        if self.playback_state == PlaybackState::Stopped {
+            self.set_message("播放时才能设置 A-B 循环");
+            return;
+        }
+        let position = self.get_current_time();
+        match self.current_loop().map(|ab_loop| (ab_loop.start, ab_loop.end)) {
+            Some((start, None)) if position > start => {
+                self.ab_loop = Some(AbLoop { path, start, end: Some(position) });
+                let range = format!("{} - {}", format_duration(start), format_duration(position));
+                self.set_message(format!("A-B 循环 {}", range));
+            }
+            Some((_, None)) => self.set_message("B 点要在 A 点之后"),
+            Some((_, Some(_))) => {
+                self.ab_loop = None;
+                self.set_message("已取消 A-B 循环");
+            }
+            None => {
+                self.ab_loop = Some(AbLoop { path, start: position, end: None });
+                self.set_message(format!("A 点 {}，再按一次设置 B 点", format_duration(position)));
+            }
+        }
+    }
+    
+    /// 导出当前歌曲 A-B 循环的片段（做铃声、截取采样），先输入保存的文件路径
+    pub fn start_segment_export(&mut self) {
+        let Some((path, start, Some(end))) = self.current_loop().map(|ab_loop| (ab_loop.path.clone(), ab_loop.start, ab_loop.end)) else {
+            self.set_message("先按 l 设置 A 点和 B 点");
+            return;
+        };
+        if !path.is_file() {
+            self.set_message("只能导出本地文件的片段");
+            return;
+        }
+        let input = trim::default_output(&path, start, end).to_string_lossy().to_string();
+        self.prompt = Some(Prompt {
+            kind: PromptKind::ExportSegment { path, start, end },
+            input,
+        });
+    }
+    
+    fn export_segment(&mut self, source: PathBuf, start: Duration, end: Duration, dest: PathBuf) {
+        let job = Job::spawn("导出片段", move |progress| {
+            progress.set_total(1);
+            progress.set_status(dest.file_name().unwrap_or_default().to_string_lossy());
+            let (written, method) = trim::export(&source, start, end, &dest, progress.cancel_flag())?;
+            progress.advance();
+            Ok(JobOutput::Message(format!("已导出片段（{}）: {}", method.label(), written.display())))
+        });
+        self.start_job(job);
+    }
+    
+    /// 播放到 B 点时跳回 A 点
+    fn update_ab_loop(&mut self) -> Result<()> {
+        if self.playback_state != PlaybackState::Playing {
+            return Ok(());
+        }
+        let Some((start, Some(end))) = self.current_loop().map(|ab_loop| (ab_loop.start, ab_loop.end)) else {
+            return Ok(());
+        };
+        if self.get_current_time() >= end {
+            self.seek_to(start)?;
+        }
+        Ok(())
+    }
+    
+    /// 进入拖动模式，虚拟光标从当前播放位置开始
+    pub fn start_scrub(&mut self) {
+        if self.playback_state == PlaybackState::Stopped {
+            self.set_message("没有正在播放的歌曲");
+            return;
+        }
+        self.scrub = Some(self.get_current_time());
+    }
+    
+    /// 移动虚拟光标，不影响正在进行的播放
+    pub fn move_scrub(&mut self, seconds: i64) {
+        if let Some(position) = self.scrub {
+            let target = position.as_secs() as i64 + seconds;
+            self.scrub = Some(self.clamp_to_total(Duration::from_secs(target.max(0) as u64)));
+        }
+    }
+    
+    pub fn set_scrub(&mut self, position: Duration) {
+        if self.scrub.is_some() {
+            self.scrub = Some(self.clamp_to_total(position));
+        }
+    }
+    
+    /// 时长已知时不超过总时长，时长未知时原样返回
+    fn clamp_to_total(&self, position: Duration) -> Duration {
+        self.get_total_duration().map_or(position, |total| position.min(total))
+    }
+    
+    pub fn commit_scrub(&mut self) -> Result<()> {
+        if let Some(position) = self.scrub.take() {
+            self.seek_to(position)?;
+        }
+        Ok(())
+    }
+    
+    pub fn pause(&mut self) {
+        if let Some(ref sink) = self.sink {
+            sink.pause();
+            self.playback_state = PlaybackState::Paused;
+            // 保存当前播放时间（缓冲时已经保存过）
+            if let Some(start_time) = self.play_start_time.filter(|_| !self.buffering) {
+                self.current_play_time = self.clock.since(start_time);
+            }
+            self.buffering = false;
+        } else if let Some((_, job)) = self.opening.take() {
+            // 歌曲还没打开：放弃打开，再按播放时重新打开
+            job.cancel();
+            self.buffering = false;
+            self.playback_state = PlaybackState::Stopped;
+        }
+    }
+    
+    pub fn resume(&mut self) {
+        if let Some(ref sink) = self.sink {
+            sink.play();
+            self.playback_state = PlaybackState::Playing;
+            // 重新设置开始时间，考虑已经播放的时间
+            let elapsed = self.current_play_time;
+            self.play_start_time = Some(self.clock.now() - elapsed);
+        }
+    }
+    
+    pub fn stop(&mut self) -> Result<()> {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+        self.visualizer.tap.clear();
+        if let Some((_, job)) = self.opening.take() {
+            job.cancel();
+        }
+        if let Some(stream) = self.stream.take() {
+            stream.close();
+            // 远程曲库中完整下载过的歌曲保存到本地缓存，下次播放不再下载
+            if let Some(song) = self.songs.get(self.current_index) {
+                if remote_library::source_of(&song.path, &self.config.remote_sources).is_some() {
+                    remote_library::store(&song.path, stream, self.config.remote_cache_mb);
+                }
+            }
+        }
+        self.buffering = false;
+        self.playback_state = PlaybackState::Stopped;
+        self.current_time = Duration::ZERO;
+        self.play_start_time = None;
+        self.current_play_time = Duration::ZERO;
+        Ok(())
+    }
+    
+    pub fn next(&mut self) -> Result<()> {
+        if self.songs.is_empty() {
+            return Ok(());
+        }
+        
+        let follow = self.cursor_on_current();
+        self.next_without_play()?;
+        self.play()?;
+        self.follow_playback(follow);
+        Ok(())
+    }
+    
+    /// 用户手动切到下一首或上一首，切换时淡出淡入而不是突然断开
+    pub fn switch_track(&mut self, forward: bool) -> Result<()> {
+        self.soft_switch = true;
+        let result = match forward {
+            true => self.next(),
+            false => self.previous(),
+        };
+        self.soft_switch = false;
+        result
+    }
+    
+    /// 光标停在当前歌曲上时切歌才跟随；用户把光标移开浏览曲库时不把它拉回来
+    fn cursor_on_current(&self) -> bool {
+        self.config.follow_playback && self.playlist_selected == self.current_index
+    }
+    
+    fn follow_playback(&mut self, follow: bool) {
+        if follow && self.is_visible(self.current_index) {
+            self.playlist_selected = self.current_index;
+        }
+    }
+    
+    /// 开启/关闭光标跟随播放，开启时光标立即移到当前歌曲
+    pub fn toggle_follow_playback(&mut self) {
+        self.config.follow_playback = !self.config.follow_playback;
+        self.save_config();
+        match self.config.follow_playback {
+            true => {
+                self.follow_playback(true);
+                self.set_message("光标跟随播放: 开（把光标移开后不再跟随，移回当前歌曲恢复）");
+            }
+            false => self.set_message("光标跟随播放: 关"),
+        }
+    }
+    
+    pub fn next_without_play(&mut self) -> Result<()> {
+        if self.songs.is_empty() {
+            return Ok(());
+        }
+        
+        // 播放队列中的歌曲优先于正常顺序，范围之外的歌曲直接丢弃
+        while let Some(index) = self.queue.pop_front() {
+            if !self.songs.get(index).is_some_and(|song| self.in_scope(song)) {
+                continue;
+            }
+            self.current_index = index;
+            self.queued_path = self.songs.get(index).map(|song| song.path.clone());
+            if self.queue.is_empty() && self.radio {
+                self.radio = false;
+                self.set_message("推荐电台的歌曲已全部播放");
+            }
+            return Ok(());
+        }
+        
+        match self.shuffle {
+            ShuffleMode::Off => self.current_index = self.step_visible(1),
+            ShuffleMode::Track => self.next_shuffle(),
+            ShuffleMode::Album | ShuffleMode::Folder => self.next_shuffle_group(),
+        }
+        
+        Ok(())
+    }
+    
+    /// 上一首：当前歌曲已经播放超过 `previous_restart_secs` 秒时先从头重新播放，
+    /// 在开头附近或者重新播放后很快再按一次才切到上一首歌
+    pub fn previous(&mut self) -> Result<()> {
+        if self.songs.is_empty() {
+            return Ok(());
+        }
+        
+        let pressed_again = self.last_previous.is_some_and(|last| self.clock.since(last) < PREVIOUS_WINDOW);
+        self.last_previous = Some(self.clock.now());
+        // 从头播放时会跳过开头，按跳过后的位置计算
+        let start = self.get_current_song().map_or(Duration::ZERO, |song| self.intro_skip(song));
+        let threshold = Duration::from_secs(self.config.previous_restart_secs);
+        let position = self.get_current_time().saturating_sub(start);
+        if self.playback_state != PlaybackState::Stopped
+            && !threshold.is_zero()
+            && position > threshold
+            && !pressed_again
+        {
+            return self.seek_to(Duration::ZERO);
+        }
+        
+        let follow = self.cursor_on_current();
+        self.current_index = self.step_visible(-1);
+        self.play()?;
+        self.follow_playback(follow);
+        Ok(())
+    }
+    
+    /// 在 关闭 → 单曲 → 专辑 → 文件夹 之间循环切换随机播放模式
+    pub fn cycle_shuffle(&mut self) {
+        self.shuffle = self.shuffle.next();
+        self.shuffle_history.clear();
+        self.set_message(self.shuffle.label());
+    }
+    
+    /// 随机播放的候选歌曲：只在当前可见（未被过滤掉）的歌曲中随机
+    fn shuffle_pool(&self) -> Vec<usize> {
+        let visible: Vec<usize> = (0..self.songs.len()).filter(|&i| self.is_playable(i)).collect();
+        if !visible.is_empty() {
+            return visible;
+        }
+        // 都被过滤或暂时无法访问时仍然只在范围之内选择
+        (0..self.songs.len()).filter(|&i| self.in_scope(&self.songs[i])).collect()
+    }
+    
+    fn next_shuffle(&mut self) {
+        use rand::seq::SliceRandom;
+        let mut rng = rand::thread_rng();
+        
+        let pool = self.shuffle_pool();
+        
+        let mut candidates: Vec<usize> = pool
+            .iter()
+            .copied()
+            .filter(|i| *i != self.current_index && !self.shuffle_history.contains(i))
+            .collect();
+        if candidates.is_empty() {
+            self.shuffle_history.clear();
+            candidates = pool.iter().copied().filter(|i| *i != self.current_index).collect();
+        }
+        
+        let next_index = candidates.choose(&mut rng).copied().unwrap_or(self.current_index);
+        self.shuffle_history.push_back(self.current_index);
+        self.current_index = next_index;
+    }
+    
+    /// 专辑/文件夹随机时歌曲所属的组。没有专辑标签的歌曲自成一组
+    fn shuffle_group(&self, index: usize) -> String {
+        let song = &self.songs[index];
+        match self.shuffle {
+            ShuffleMode::Album => match &song.tags.album {
+                Some(album) => {
+                    let artist = match song.tags.compilation {
+                        true => Some(library::VARIOUS_ARTISTS),
+                        false => song.tags.album_artist.as_deref().or(song.tags.artist.as_deref()),
+                    };
+                    format!("{}\u{0}{}", artist.unwrap_or_default(), album)
+                }
+                None => song.path.to_string_lossy().to_string(),
+            },
+            _ => song.path.parent().unwrap_or(&song.path).to_string_lossy().to_string(),
+        }
+    }
+    
+    /// 专辑/文件夹随机：先按音轨号顺序播完当前组，再随机选择一个还没播放过的组
+    fn next_shuffle_group(&mut self) {
+        use rand::seq::IteratorRandom;
+        let mut rng = rand::thread_rng();
+        
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for index in self.shuffle_pool() {
+            groups.entry(self.shuffle_group(index)).or_default().push(index);
+        }
+        for members in groups.values_mut() {
+            members.sort_by_key(|&index| (self.songs[index].tags.track_number.unwrap_or(u32::MAX), index));
+        }
+        
+        let current_group = self.shuffle_group(self.current_index);
+        if let Some(members) = groups.get(&current_group) {
+            let next = members
+                .iter()
+                .position(|&index| index == self.current_index)
+                .and_then(|position| members.get(position + 1));
+            if let Some(&next) = next {
+                self.shuffle_history.push_back(self.current_index);
+                self.current_index = next;
+                return;
+            }
+        }
+        
+        let unplayed = |key: &String, members: &Vec<usize>| {
+            *key != current_group && !members.iter().any(|index| self.shuffle_history.contains(index))
+        };
+        let mut choice = groups.iter().filter(|(key, members)| unplayed(key, members)).choose(&mut rng);
+        if choice.is_none() {
+            // 所有组都播放过了，重新开始一轮
+            self.shuffle_history.clear();
+            choice = groups
+                .iter()
+                .filter(|(key, _)| **key != current_group)
+                .choose(&mut rng)
+                .or_else(|| groups.iter().next());
+        }
+        
+        let Some(&first) = choice.and_then(|(_, members)| members.first()) else {
+            return;
+        };
+        self.shuffle_history.push_back(self.current_index);
+        self.current_index = first;
+    }
+    
+    /// 歌曲是否在浏览范围内：选了收藏集时在其中，儿童模式下没有不当内容标记
+    fn in_scope(&self, song: &Song) -> bool {
+        !(self.config.clean_mode && song.tags.explicit)
+            && self.active_collection().is_none_or(|collection| collection.contains(song))
+    }
+    
+    /// 歌曲是否通过当前的收藏集、儿童模式、纯音乐、BPM 和艺术家过滤条件
+    pub fn is_visible(&self, index: usize) -> bool {
+        let Some(song) = self.songs.get(index) else {
+            return false;
+        };
+        if !self.in_scope(song) {
+            return false;
+        }
+        if self.instrumental_filter && !self.is_instrumental(song) {
+            return false;
+        }
+        if let Some((_, paths)) = &self.artist_filter {
+            if !paths.contains(&song.path) {
+                return false;
+            }
+        }
+        let Some((min, max)) = self.bpm_filter else {
+            return true;
+        };
+        self.bpms.get(&song.path).is_some_and(|bpm| (min..=max).contains(bpm))
+    }
+    
+    /// 按方向找到下一首可以播放的歌曲，全部被过滤时退回到范围之内相邻的歌曲；
+    /// 范围之内没有歌曲时不动，由 play_from 停止播放
+    fn step_visible(&self, direction: isize) -> usize {
+        let len = self.songs.len() as isize;
+        let step = |accept: &dyn Fn(usize) -> bool| {
+            (1..=len)
+                .map(|step| (self.current_index as isize + step * direction).rem_euclid(len) as usize)
+                .find(|&index| accept(index))
+        };
+        step(&|index| self.is_playable(index))
+            .or_else(|| step(&|index| self.in_scope(&self.songs[index])))
+            .unwrap_or(self.current_index)
+    }
+    
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        // 手动调节音量时结束闹钟的渐强
+        self.scheduler.cancel_ramp();
+        let volume = self.output_volume();
+        if let Some(ref sink) = self.sink {
+            sink.set_volume(volume);
+        }
+    }
+    
+    /// 实际输出的音量：闹钟渐强期间低于设定音量；DoP 输出时改变音量会破坏数据，固定为 100%
+    fn output_volume(&mut self) -> f32 {
+        if self.dop {
+            return 1.0;
+        }
+        self.volume * self.scheduler.volume_factor(self.clock.now())
+    }
+    
+    /// 检查闹钟的开始和停止时间，并在渐强期间逐步调高音量
+    pub fn update_schedule(&mut self) -> Result<()> {
+        let now = self.clock.local_now();
+        if self.scheduler.alarm_due(now) {
+            self.start_alarm()?;
+        }
+        if self.scheduler.stop_due(now) && self.playback_state == PlaybackState::Playing {
+            self.scheduler.cancel_ramp();
+            self.pause();
+            self.set_message("已到设定的停止时间，播放已暂停");
+        }
+        
+        if self.scheduler.is_ramping() {
+            let volume = self.output_volume();
+            if let Some(ref sink) = self.sink {
+                sink.set_volume(volume);
+            }
+        }
+        Ok(())
+    }
+    
+    /// 执行其他程序发来的控制命令
+    pub fn poll_remote(&mut self) -> Result<()> {
+        let mut commands: Vec<Command> = self.remote.iter().flat_map(|remote| remote.commands.try_iter()).collect();
+        commands.extend(self.web.iter().flat_map(|web| web.commands.try_iter()));
+        let events: Vec<mqtt::Event> = self.mqtt.iter().flat_map(|mqtt| mqtt.events.try_iter()).collect();
+        for event in events {
+            match event {
+                mqtt::Event::Command(command) => commands.push(command),
+                mqtt::Event::Connected => self.set_message(format!("已连接 MQTT 服务器 {}", self.config.mqtt_broker)),
+                mqtt::Event::Disconnected(err) => self.set_message(format!("MQTT: {}，稍后重连", err)),
+            }
+        }
+        let webhook_errors: Vec<String> = self.webhooks.iter().flat_map(|webhooks| webhooks.errors.try_iter()).collect();
+        for err in webhook_errors {
+            self.set_message(format!("Webhook 发送失败 {}", err));
+        }
+        let lighting_errors: Vec<String> = self.lighting.iter().flat_map(|lighting| lighting.errors.try_iter()).collect();
+        for err in lighting_errors {
+            self.set_message(format!("灯光控制失败 {}", err));
+        }
+        for command in commands {
+            match command {
+                Command::PlayPause => match self.playback_state {
+                    PlaybackState::Playing => self.pause(),
+                    PlaybackState::Paused => self.resume(),
+                    PlaybackState::Stopped => self.play()?,
+                },
+                Command::Next => self.switch_track(true)?,
+                Command::Previous => self.switch_track(false)?,
+                Command::Stop => self.stop()?,
+                Command::VolumeUp => self.set_volume(self.volume + 0.1),
+                Command::VolumeDown => self.set_volume(self.volume - 0.1),
+                Command::Shuffle => self.cycle_shuffle(),
+                Command::Seek(seek) => {
+                    if self.playback_state != PlaybackState::Stopped {
+                        self.seek_to(seek.target(self.get_current_time()))?;
+                    }
+                }
+                Command::Quit => self.exit_requested = true,
+                Command::Attach => self.attach_requested = true,
+            }
+            self.dirty = true;
+        }
+        
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.publish_state(self.state_json());
+        }
+        self.notify_webhooks();
+        self.update_lighting();
+        if let Some(remote) = &self.remote {
+            remote.set_status(status_line::format(self.config.minimized_format(), self), self.state_json());
+        }
+        if let Some(web) = &self.web {
+            let queue: Vec<String> = self
+                .queue
+                .iter()
+                .take(WEB_QUEUE_LIMIT)
+                .filter_map(|&index| self.songs.get(index))
+                .map(|song| json::string(&song.name))
+                .collect();
+            web.set_state(format!("{{\"now\":{},\"queue\":[{}]}}", self.state_json(), queue.join(",")));
+        }
+        let playing = self.playback_state == PlaybackState::Playing;
+        metrics::set_state(playing, self.volume, self.queue.len(), self.songs.len());
+        Ok(())
+    }
+    
+    /// 播放状态或歌曲变化时通知 webhook：换了歌曲是开始播放，同一首从暂停恢复是继续播放
+    fn notify_webhooks(&mut self) {
+        if self.webhooks.is_none() {
+            return;
+        }
+        let path = self
+            .get_current_song()
+            .filter(|_| self.playback_state != PlaybackState::Stopped)
+            .map(|song| song.path.clone());
+        let current = (self.playback_state, path);
+        let previous = std::mem::replace(&mut self.webhook_state, current.clone());
+        if previous == current {
+            return;
+        }
+        let event = match current.0 {
+            PlaybackState::Stopped => webhook::Event::Stop,
+            PlaybackState::Paused => webhook::Event::Pause,
+            PlaybackState::Playing if previous.0 == PlaybackState::Paused && previous.1 == current.1 => {
+                webhook::Event::Resume
+            }
+            PlaybackState::Playing => webhook::Event::Start,
+        };
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.send(webhook::payload(&self.config.webhook_template, event, &self.state_json(), self));
+        }
+    }
+    
+    /// 换歌时让灯光换成封面的颜色；播放中把歌曲的速度和起点发给灯光线程，由它按拍闪烁
+    fn update_lighting(&mut self) {
+        let Some(lighting) = &self.lighting else {
+            return;
+        };
+        let song = self.get_current_song().filter(|_| self.playback_state != PlaybackState::Stopped);
+        let path = song.map(|song| song.path.clone());
+        if path != self.lighting_song {
+            if let Some(song) = song {
+                let fallback = song.tags.album.clone().unwrap_or_else(|| song.title());
+                lighting.track(&song.path, &fallback);
+            }
+        }
+        let tempo = song
+            .filter(|_| self.config.lighting_beats && self.playback_state == PlaybackState::Playing && !self.buffering)
+            .and_then(|song| self.bpms.get(&song.path).copied())
+            .and_then(|bpm| Some((bpm, Instant::now().checked_sub(self.get_current_time())?)));
+        let changed = match (tempo, self.lighting_tempo) {
+            (Some((bpm, start)), Some((old_bpm, old_start))) => {
+                bpm != old_bpm || start.max(old_start) - start.min(old_start) > LIGHTING_RESYNC
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            lighting.tempo(tempo);
+            self.lighting_tempo = tempo;
+        }
+        self.lighting_song = path;
+    }
+    
+    /// 发布到 MQTT 的播放状态，位置精确到秒，播放中每秒变化一次
+    fn state_json(&self) -> String {
+        let song = self.get_current_song().filter(|_| self.playback_state != PlaybackState::Stopped);
+        let state = match self.playback_state {
+            PlaybackState::Playing => "playing",
+            PlaybackState::Paused => "paused",
+            PlaybackState::Stopped => "stopped",
+        };
+        let number = |value: Option<u64>| value.map_or_else(|| "null".to_string(), |value| value.to_string());
+        let fields = [
+            ("state", json::string(state)),
+            ("title", json::opt_string(song.map(|song| song.title()).as_deref())),
+            ("artist", json::opt_string(song.and_then(|song| song.artist()).as_deref())),
+            ("album", json::opt_string(song.and_then(|song| song.tags.album.as_deref()))),
+            ("file", json::opt_string(song.map(|song| song.path.to_string_lossy()).as_deref())),
+            ("position", number(song.map(|_| self.get_current_time().as_secs()))),
+            ("duration", number(song.and_then(|_| self.get_total_duration()).map(|total| total.as_secs()))),
+            ("volume", format!("{:.0}", self.volume * 100.0)),
+            ("shuffle", json::string(self.shuffle.name())),
+            ("queue", self.queue.len().to_string()),
+        ];
+        let body: Vec<String> = fields.iter().map(|(key, value)| format!("\"{}\":{}", key, value)).collect();
+        format!("{{{}}}", body.join(","))
+    }
+    
+    fn start_alarm(&mut self) -> Result<()> {
+        let ramp = Duration::from_secs(self.config.alarm_ramp_secs);
+        self.scheduler.start_ramp(self.clock.now(), ramp);
+        
+        let playlist = self.config.alarm_playlist.trim().to_string();
+        if playlist.is_empty() {
+            self.play()?;
+        } else {
+            match playlists::load_m3u(Path::new(&playlist)) {
+                Ok(paths) if !paths.is_empty() => {
+                    self.queue.clear();
+                    for path in paths {
+                        self.enqueue_path(path);
+                    }
+                    if self.queue.is_empty() {
+                        self.set_message(format!("闹钟播放列表中没有可以播放的歌曲: {}", playlist));
+                        return Ok(());
+                    }
+                    self.next()?;
+                }
+                // 播放列表有问题时保留错误提示，不用“开始播放”覆盖
+                Ok(_) => {
+                    self.set_message(format!("闹钟播放列表为空: {}", playlist));
+                    return Ok(());
+                }
+                Err(err) => {
+                    self.play()?;
+                    self.set_message(format!("无法读取闹钟播放列表 {}: {}", playlist, err));
+                    return Ok(());
+                }
+            }
+        }
+        // 没有可以播放的歌曲时保留 play_from 的提示
+        if self.playback_state == PlaybackState::Playing {
+            self.set_message("闹钟时间到，开始播放");
+        }
+        Ok(())
+    }
+    
+    pub fn get_current_song(&self) -> Option<&Song> {
+        self.songs.get(self.current_index)
+    }
+    
+    pub fn check_and_auto_next(&mut self) -> Result<()> {
+        self.update_automix()?;
+        self.update_ab_loop()?;
+        // 只处理当前这次播放的结束事件，停止或切歌前发出的事件已经过时
+        let mut finished = false;
+        while let Ok(id) = self.end_rx.try_recv() {
+            finished |= id == self.playback_id;
+        }
+        // 模拟模式没有真正播放，按模拟时钟判断是否播放完
+        if self.clock.is_simulated() {
+            let total = self.get_total_duration().unwrap_or(SIMULATED_UNKNOWN_DURATION);
+            finished = self.get_current_time() >= total;
+        }
+        
+        if finished && self.sink.is_some() && self.playback_state == PlaybackState::Playing {
+            let finished_path = self.get_current_song().map(|song| song.path.clone());
+            let queue_ended = self.queue.is_empty() && finished_path.is_some() && self.queued_path == finished_path;
+            if self.preview.is_some() && self.config.preview_exit {
+                self.exit_requested = true;
+                return Ok(());
+            }
+            // 播放结束，自动播放下一曲
+            if self.songs.len() > 1 {
+                self.next()?;
+            } else {
+                // 只有一首歌，重新播放
+                self.play()?;
+            }
+            
+            if queue_ended {
+                self.queued_path = None;
+                self.alert("播放队列已播完".to_string());
+            } else if let Some(song) = self.get_current_song() {
+                let text = format!("正在播放: {}", song.name);
+                self.alert(text);
+            }
+        }
+        self.check_queue_ending();
+        Ok(())
+    }
+    
+    /// 播放队列剩余的时间少于 `queue_warning_mins` 分钟时提醒一次，让用户在队列播完前继续添加
+    fn check_queue_ending(&mut self) {
+        if self.config.queue_warning_mins == 0 {
+            return;
+        }
+        let Some(remaining) = self.queue_remaining() else {
+            self.queue_warned = false;
+            return;
+        };
+        if remaining >= Duration::from_secs(self.config.queue_warning_mins * 60) {
+            self.queue_warned = false;
+        } else if !self.queue_warned && self.playback_state == PlaybackState::Playing {
+            self.queue_warned = true;
+            let text = format!("播放队列还剩不到 {} 分钟", remaining.as_secs() / 60 + 1);
+            self.set_message(format!("{}，按 a 继续添加歌曲", text));
+            self.alert(text);
+        }
+    }
+    
+    /// 播放队列中还没播的时间：当前从队列取出的歌曲的剩余时间加上队列中的歌曲，
+    /// 时长未知的歌曲不计。没有在播放队列时返回 None
+    fn queue_remaining(&self) -> Option<Duration> {
+        let current = self.get_current_song().map(|song| &song.path);
+        let playing_queued = self.queued_path.is_some() && current == self.queued_path.as_ref();
+        if self.queue.is_empty() && !playing_queued {
+            return None;
+        }
+        let mut remaining: Duration = self.queue.iter().filter_map(|&index| self.songs.get(index)?.duration).sum();
+        if playing_queued {
+            let total = self.get_total_duration().unwrap_or_default();
+            remaining += total.saturating_sub(self.get_current_time());
+        }
+        Some(remaining)
+    }
+    
+    /// 按配置闪烁外框和/或让终端响铃
+    fn alert(&mut self, text: String) {
+        let mode = self.config.track_alert;
+        if mode.flashes() {
+            self.flash = Some((text, Instant::now()));
+            self.dirty = true;
+        }
+        // 模拟模式没有界面，不响铃
+        if mode.rings() && !self.clock.is_simulated() {
+            alert::ring_bell();
+        }
+    }
+    
+    /// 正在闪烁的切歌提醒
+    pub fn active_flash(&self) -> Option<&str> {
+        match &self.flash {
+            Some((text, started)) if started.elapsed() < FLASH_DURATION => Some(text),
+            _ => None,
+        }
+    }
+    
+    pub fn update_play_time(&mut self) {
+        match self.playback_state {
+            PlaybackState::Playing if self.buffering => {}
+            PlaybackState::Playing => {
+                if let Some(start_time) = self.play_start_time {
+                    // 确保播放时间不超过总时长
+                    self.current_play_time = self.clamp_to_total(self.clock.since(start_time));
+                }
+            }
+            PlaybackState::Paused => {
+                // 暂停时不更新播放时间，保持当前值
+            }
+            PlaybackState::Stopped => {
+                // 停止时重置播放时间
+                self.current_play_time = Duration::ZERO;
+            }
+        }
+    }
+    
+    pub fn get_current_time(&self) -> Duration {
+        match self.playback_state {
+            PlaybackState::Playing if self.buffering => self.current_play_time,
+            PlaybackState::Playing => {
+                if let Some(start_time) = self.play_start_time {
+                    // 确保播放时间不超过总时长
+                    return self.clamp_to_total(self.clock.since(start_time));
+                }
+                self.current_play_time
+            }
+            PlaybackState::Paused => {
+                // 暂停时返回保存的播放时间
+                self.current_play_time
+            }
+            PlaybackState::Stopped => {
+                Duration::ZERO
+            }
+        }
+    }
+    
+    /// 当前歌曲的总时长，网络电台和无法探测时长的文件返回 None
+    pub fn get_total_duration(&self) -> Option<Duration> {
+        self.get_current_song().and_then(|song| song.duration)
+    }
+    
+    /// 播放进度 (0..1)，总时长未知时返回 None
+    pub fn get_progress(&self) -> Option<f32> {
+        let total_duration = self.get_total_duration().filter(|total| !total.is_zero())?;
+        let current_time = self.get_current_time();
+        Some((current_time.as_secs_f64() / total_duration.as_secs_f64()).clamp(0.0, 1.0) as f32)
+    }
+    
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+        self.dirty = true;
+    }
+    
+    /// 当前需要显示的提示消息，超过几秒后自动消失
+    pub fn active_message(&self) -> Option<&str> {
+        match &self.message {
+            Some((message, shown_at)) if shown_at.elapsed() < Duration::from_secs(3) => Some(message),
+            _ => None,
+        }
+    }
+    
+    /// 进入/退出队列编辑界面，进入时焦点放在曲库一侧
+    pub fn toggle_editor_view(&mut self) {
+        if self.view == View::Editor {
+            self.view = View::Playlist;
+        } else {
+            self.view = View::Editor;
+            self.focus = Focus::Playlist;
+        }
+    }
+    
+    pub fn toggle_bookmarks_view(&mut self) {
+        self.view = match self.view {
+            View::Bookmarks => View::Playlist,
+            _ => View::Bookmarks,
+        };
+    }
+    
+    /// 打开艺术家列表，每次打开都按当前的播放列表重新分组
+    pub fn toggle_artists_view(&mut self) {
+        if self.view == View::Artists {
+            self.view = View::Playlist;
+            return;
+        }
+        // 选了收藏集或开启儿童模式时只列出范围内的艺术家
+        let songs: Vec<Song> = self.songs.iter().filter(|song| self.in_scope(song)).cloned().collect();
+        self.artists = library::artists(&songs);
+        self.artist_selected = match &self.artist_filter {
+            Some((name, _)) => self.artists.iter().position(|entry| entry.name == *name).map_or(0, |i| i + 1),
+            None => 0,
+        };
+        self.view = View::Artists;
+    }
+    
+    pub fn select_artist(&mut self, offset: isize) {
+        // 第 0 项是“全部艺术家”
+        let last = self.artists.len() as isize;
+        self.artist_selected = (self.artist_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 只显示选中艺术家的歌曲，选中“全部艺术家”时清除过滤
+    pub fn apply_selected_artist(&mut self) {
+        self.view = View::Playlist;
+        let Some(entry) = self.artist_selected.checked_sub(1).and_then(|i| self.artists.get(i)) else {
+            if self.artist_filter.take().is_some() {
+                self.set_message("已清除艺术家过滤");
+            }
+            return;
+        };
+        let message = format!("艺术家 {}: {} 张专辑 · {} 首", entry.name, entry.albums, entry.songs.len());
+        self.artist_filter = Some((entry.name.clone(), entry.songs.iter().cloned().collect()));
+        if let Some(index) = (0..self.songs.len()).find(|&i| self.is_visible(i)) {
+            self.playlist_selected = index;
+        }
+        self.set_message(message);
+    }
+    
+    pub fn active_collection(&self) -> Option<&Collection> {
+        let name = self.collection_filter.as_ref()?;
+        self.collections.iter().find(|collection| collection.name == *name)
+    }
+    
+    pub fn toggle_collections_view(&mut self) {
+        if self.view == View::Collections {
+            self.view = View::Playlist;
+            return;
+        }
+        self.collection_selected = match self.active_collection() {
+            Some(active) => self.collections.iter().position(|c| c.name == active.name).map_or(0, |i| i + 1),
+            None => 0,
+        };
+        self.view = View::Collections;
+    }
+    
+    pub fn select_collection(&mut self, offset: isize) {
+        // 第 0 项是“全部曲库”
+        let last = self.collections.len() as isize;
+        self.collection_selected = (self.collection_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 只显示选中收藏集中的歌曲，选中“全部曲库”时清除过滤
+    pub fn apply_selected_collection(&mut self) {
+        self.view = View::Playlist;
+        let Some(collection) = self.collection_selected.checked_sub(1).and_then(|i| self.collections.get(i)) else {
+            if self.collection_filter.take().is_some() {
+                self.set_message("已显示全部曲库");
+            }
+            return;
+        };
+        let name = collection.name.clone();
+        self.collection_filter = Some(name.clone());
+        let count = (0..self.songs.len()).filter(|&i| self.is_visible(i)).count();
+        if let Some(index) = (0..self.songs.len()).find(|&i| self.is_visible(i)) {
+            self.playlist_selected = index;
+        }
+        self.set_message(format!("收藏集 {}: {} 首", name, count));
+    }
+    
+    pub fn remove_selected_collection(&mut self) {
+        let Some(index) = self.collection_selected.checked_sub(1) else {
+            return;
+        };
+        if index >= self.collections.len() {
+            return;
+        }
+        let removed = self.collections.remove(index);
+        if self.collection_filter.as_ref() == Some(&removed.name) {
+            self.collection_filter = None;
+        }
+        self.collection_selected = self.collection_selected.min(self.collections.len());
+        self.set_message(format!("已删除收藏集: {}", removed.name));
+        self.save_collections();
+    }
+    
+    /// 打开收藏集名称输入框，默认填入正在查看的收藏集
+    pub fn start_collection_prompt(&mut self) {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return;
+        };
+        let kind = PromptKind::Collection {
+            path: song.path.clone(),
+            scope: PinScope::Track,
+        };
+        self.prompt = Some(Prompt {
+            kind,
+            input: self.collection_filter.clone().unwrap_or_default(),
+        });
+    }
+    
+    /// 在收藏集输入框中切换单曲、专辑和文件夹
+    pub fn cycle_prompt_scope(&mut self) {
+        if let Some(Prompt {
+            kind: PromptKind::Collection { scope, .. },
+            ..
+        }) = self.prompt.as_mut()
+        {
+            *scope = scope.next();
+        }
+    }
+    
+    /// 加入收藏集，已经在其中的同一项则移出
+    fn toggle_pin(&mut self, name: &str, path: &Path, scope: PinScope) {
+        if name.is_empty() {
+            self.set_message("请输入收藏集名称");
+            return;
+        }
+        let Some(pin) = self
+            .songs
+            .iter()
+            .find(|song| song.path == path)
+            .and_then(|song| Pin::for_song(song, scope))
+        else {
+            self.set_message("这首歌没有专辑标签，无法按专辑收藏");
+            return;
+        };
+        let description = pin.describe();
+        let index = match self.collections.iter().position(|collection| collection.name == name) {
+            Some(index) => index,
+            None => {
+                self.collections.push(Collection {
+                    name: name.to_string(),
+                    pins: Vec::new(),
+                });
+                self.collections.len() - 1
+            }
+        };
+        let pins = &mut self.collections[index].pins;
+        let message = match pins.iter().position(|existing| *existing == pin) {
+            Some(position) => {
+                pins.remove(position);
+                format!("已从收藏集 {} 移出: {}", name, description)
+            }
+            None => {
+                pins.push(pin);
+                format!("已加入收藏集 {}: {}", name, description)
+            }
+        };
+        if self.collections[index].pins.is_empty() {
+            self.collections.remove(index);
+            if self.collection_filter.as_deref() == Some(name) {
+                self.collection_filter = None;
+            }
+        }
+        self.set_message(message);
+        self.save_collections();
+    }
+    
+    fn save_collections(&mut self) {
+        if let Err(err) = collections::save(&self.collections) {
+            self.set_message(format!("保存收藏集失败: {}", err));
+        }
+    }
+    
+    /// 打开快照命名输入框，默认以当前时间命名，和已有的快照同名时覆盖
+    pub fn start_snapshot_prompt(&mut self) {
+        if self.queue.is_empty() {
+            self.set_message("播放队列是空的");
+            return;
+        }
+        self.prompt = Some(Prompt {
+            kind: PromptKind::SnapshotName,
+            input: format!("队列 {}", self.clock.local_now().format("%m-%d %H:%M")),
+        });
+    }
+    
+    /// 把当前队列以名称保存为快照
+    fn save_queue_snapshot(&mut self, name: String) {
+        let paths: Vec<PathBuf> = self.queue.iter().map(|&index| self.songs[index].path.clone()).collect();
+        let count = paths.len();
+        self.queue_snapshots.retain(|snapshot| snapshot.name != name);
+        self.queue_snapshots.insert(
+            0,
+            QueueSnapshot {
+                name: name.clone(),
+                saved_at: self.clock.local_now().timestamp(),
+                paths,
+            },
+        );
+        self.save_snapshots();
+        if !name.starts_with(snapshots::AUTO_PREFIX) {
+            self.set_message(format!("已保存队列快照「{}」（{} 首）", name, count));
+        }
+    }
+    
+    /// 打乱队列、开启推荐电台之前自动保存一份，同一种操作只保留最近一次
+    fn auto_snapshot(&mut self, label: &str) {
+        if self.queue.len() > 1 && !self.clock.is_simulated() {
+            self.save_queue_snapshot(format!("{}{}", snapshots::AUTO_PREFIX, label));
+        }
+    }
+    
+    fn save_snapshots(&mut self) {
+        if let Err(err) = snapshots::save(&self.queue_snapshots) {
+            self.set_message(format!("保存队列快照失败: {}", err));
+        }
+    }
+    
+    pub fn toggle_snapshots_view(&mut self) {
+        if self.view == View::Snapshots {
+            self.view = View::Playlist;
+            return;
+        }
+        if self.queue_snapshots.is_empty() {
+            self.set_message("还没有保存过队列快照");
+            return;
+        }
+        self.snapshot_selected = 0;
+        self.view = View::Snapshots;
+    }
+    
+    pub fn select_snapshot(&mut self, offset: isize) {
+        if self.queue_snapshots.is_empty() {
+            return;
+        }
+        let last = self.queue_snapshots.len() as isize - 1;
+        self.snapshot_selected = (self.snapshot_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 用选中的快照替换播放队列（可以撤销），不在曲库中的文件先加入曲库，已经不存在的跳过
+    pub fn restore_selected_snapshot(&mut self) {
+        let Some(snapshot) = self.queue_snapshots.get(self.snapshot_selected).cloned() else {
+            return;
+        };
+        self.record_edit(format!("恢复快照「{}」", snapshot.name), true);
+        self.queue.clear();
+        let positions: HashMap<PathBuf, usize> =
+            self.songs.iter().enumerate().map(|(index, song)| (song.path.clone(), index)).collect();
+        let mut skipped = 0;
+        let mut hidden = 0;
+        for path in snapshot.paths {
+            match positions.get(&path) {
+                Some(&index) if self.in_scope(&self.songs[index]) => self.queue.push_back(index),
+                Some(_) => hidden += 1,
+                None if http_stream::is_url(&path) || path.exists() => {
+                    if !self.enqueue_path(path) {
+                        hidden += 1;
+                    }
+                }
+                None => skipped += 1,
+            }
+        }
+        self.queue_selected = 0;
+        self.view = View::Playlist;
+        let mut message = format!("已恢复队列快照「{}」（{} 首", snapshot.name, self.queue.len());
+        if skipped > 0 {
+            message.push_str(&format!("，{} 首已不存在", skipped));
+        }
+        if hidden > 0 {
+            message.push_str(&format!("，{} 首不在当前范围内", hidden));
+        }
+        message.push_str("，u: 撤销）");
+        self.set_message(message);
+    }
+    
+    pub fn remove_selected_snapshot(&mut self) {
+        if self.snapshot_selected >= self.queue_snapshots.len() {
+            return;
+        }
+        let removed = self.queue_snapshots.remove(self.snapshot_selected);
+        self.snapshot_selected = self.snapshot_selected.min(self.queue_snapshots.len().saturating_sub(1));
+        if self.queue_snapshots.is_empty() {
+            self.view = View::Playlist;
+        }
+        self.set_message(format!("已删除队列快照: {}", removed.name));
+        self.save_snapshots();
+    }
+    
+    /// 打开书签命名输入框，位置取按键时的播放位置
+    pub fn start_bookmark_prompt(&mut self) {
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        if self.playback_state == PlaybackState::Stopped {
+            self.set_message("请先开始播放再添加书签");
+            return;
+        }
+        
+        let kind = PromptKind::BookmarkName {
+            path: song.path.clone(),
+            position: self.get_current_time(),
+        };
+        self.prompt = Some(Prompt {
+            kind,
+            input: String::new(),
+        });
+    }
+    
+    pub fn submit_prompt(&mut self) -> Result<()> {
+        let Some(prompt) = self.prompt.take() else {
+            return Ok(());
+        };
+        
+        match prompt.kind {
+            PromptKind::BookmarkName { path, position } => {
+                let name = match prompt.input.trim() {
+                    "" => format!("书签 {}", self.bookmarks.len() + 1),
+                    name => name.to_string(),
+                };
+                self.add_bookmark(name, path, position);
+            }
+            PromptKind::BpmFilter => self.set_bpm_filter(&prompt.input),
+            PromptKind::SnapshotName => {
+                let name = match prompt.input.trim() {
+                    "" => format!("队列 {}", self.clock.local_now().format("%m-%d %H:%M")),
+                    name => name.to_string(),
+                };
+                self.save_queue_snapshot(name);
+            }
+            PromptKind::Collection { path, scope } => self.toggle_pin(prompt.input.trim(), &path, scope),
+            PromptKind::Genre { path } => match prompt.input.trim() {
+                "" => self.set_message("流派不能为空"),
+                genre => self.accept_genre(&path, genre.to_string()),
+            },
+            PromptKind::RelocateDir => {
+                let dir = PathBuf::from(prompt.input.trim());
+                if dir.is_dir() {
+                    self.start_relocation(dir);
+                } else {
+                    self.set_message(format!("目录不存在: {}", dir.display()));
+                }
+            }
+            PromptKind::Setting { key } => self.apply_setting(key, prompt.input.trim()),
+            PromptKind::ExportSegment { path, start, end } => match prompt.input.trim() {
+                "" => self.set_message("没有输入保存的文件路径"),
+                dest => self.export_segment(path, start, end, PathBuf::from(dest)),
+            },
+        }
+        Ok(())
+    }
+    
+    pub fn add_bookmark(&mut self, name: String, path: PathBuf, position: Duration) {
+        self.set_message(format!("已添加书签: {}", name));
+        self.bookmarks.push(Bookmark { name, path, position });
+        self.save_bookmarks();
+    }
+    
+    pub fn remove_selected_bookmark(&mut self) {
+        if self.bookmark_selected >= self.bookmarks.len() {
+            return;
+        }
+        
+        let removed = self.bookmarks.remove(self.bookmark_selected);
+        if self.bookmark_selected >= self.bookmarks.len() && self.bookmark_selected > 0 {
+            self.bookmark_selected -= 1;
+        }
+        self.set_message(format!("已删除书签: {}", removed.name));
+        self.save_bookmarks();
+    }
+    
+    fn save_bookmarks(&mut self) {
+        if let Err(err) = bookmarks::save(&self.bookmarks) {
+            self.set_message(format!("保存书签失败: {}", err));
+        }
+    }
+    
+    pub fn select_bookmark(&mut self, offset: isize) {
+        if self.bookmarks.is_empty() {
+            return;
+        }
+        let last = self.bookmarks.len() as isize - 1;
+        self.bookmark_selected = (self.bookmark_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 跳转到选中的书签：切换到对应歌曲并从书签位置开始播放
+    pub fn jump_to_selected_bookmark(&mut self) -> Result<()> {
+        let Some(bookmark) = self.bookmarks.get(self.bookmark_selected).cloned() else {
+            return Ok(());
+        };
+        
+        match self.songs.iter().position(|song| song.path == bookmark.path) {
+            Some(index) if !self.in_scope(&self.songs[index]) => self.set_message(OUT_OF_SCOPE),
+            Some(index) => {
+                self.current_index = index;
+                self.play_from(bookmark.position)?;
+                self.view = View::Playlist;
+            }
+            None => {
+                self.set_message(format!("找不到书签对应的歌曲: {}", bookmark.path.display()));
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn toggle_info(&mut self) {
+        self.info_visible = !self.info_visible;
+        if !self.info_visible && self.focus == Focus::Info {
+            self.focus = Focus::Playlist;
+        }
+        self.update_info();
+    }
+    
+    /// 信息面板打开时，确保显示的是当前歌曲的信息：先查缓存，没有再后台获取
+    pub fn update_info(&mut self) {
+        if !self.info_visible {
+            return;
+        }
+        
+        if let Some(rx) = &self.info_rx {
+            match rx.try_recv() {
+                Ok((key, result)) => {
+                    self.info_rx = None;
+                    self.dirty = true;
+                    if let Some(pane) = self.info.as_mut().filter(|pane| pane.key == key) {
+                        pane.loading = false;
+                        pane.lines = match result {
+                            Ok(lines) => lines,
+                            Err(err) => vec![format!("获取信息失败: {}", err)],
+                        };
+                    }
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.info_rx = None,
+            }
+        }
+        
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        let Some(artist) = song.artist() else {
+            self.dirty = self.dirty || self.info.as_ref().is_none_or(|pane| !pane.key.is_empty());
+            self.info = Some(InfoPane {
+                key: String::new(),
+                lines: vec!["当前歌曲没有艺术家信息".to_string()],
+                loading: false,
+            });
+            return;
+        };
+        let album = song.tags.album.clone();
+        let key = info::cache_key(&artist, album.as_deref());
+        
+        if self.info.as_ref().is_some_and(|pane| pane.key == key) {
+            return;
+        }
+        
+        self.info_scroll = 0;
+        self.dirty = true;
+        if let Some(lines) = info::load_cached(&key) {
+            self.info = Some(InfoPane { key, lines, loading: false });
+            return;
+        }
+        
+        self.info = Some(InfoPane {
+            key: key.clone(),
+            lines: Vec::new(),
+            loading: true,
+        });
+        self.info_rx = Some(info::spawn_fetch(key, artist, album));
+    }
+    
+    pub fn toggle_lyrics(&mut self) {
+        self.lyrics_visible = !self.lyrics_visible;
+        if !self.lyrics_visible && self.focus == Focus::Lyrics {
+            self.focus = Focus::Playlist;
+        }
+        self.update_lyrics();
+    }
+    
+    /// 在歌词面板中移动光标，第一次移动从正在唱的那一行开始
+    pub fn move_lyrics_cursor(&mut self, offset: isize) {
+        let position = self.get_current_time();
+        let Some(pane) = self.lyrics.as_mut() else {
+            return;
+        };
+        let Some(lyrics) = &pane.lyrics else {
+            return;
+        };
+        let last = lyrics.lines.len() as isize - 1;
+        let start = pane.cursor.or_else(|| lyrics.current_line(position)).unwrap_or(0) as isize;
+        pane.cursor = Some((start + offset).clamp(0, last.max(0)) as usize);
+    }
+    
+    /// 歌词面板的光标回到跟随播放位置
+    pub fn reset_lyrics_cursor(&mut self) {
+        if let Some(pane) = self.lyrics.as_mut() {
+            pane.cursor = None;
+        }
+    }
+    
+    /// 跳到光标所在歌词行的时间，只有带时间标签的歌词可以跳转
+    pub fn seek_to_lyric(&mut self) -> Result<()> {
+        let Some(pane) = self.lyrics.as_mut() else {
+            return Ok(());
+        };
+        let Some(lyrics) = &pane.lyrics else {
+            return Ok(());
+        };
+        if !lyrics.synced {
+            self.set_message("这首歌的歌词没有时间标签，不能跳转");
+            return Ok(());
+        }
+        let Some((time, _)) = pane.cursor.and_then(|cursor| lyrics.lines.get(cursor)) else {
+            return Ok(());
+        };
+        let time = *time;
+        pane.cursor = None;
+        if self.playback_state == PlaybackState::Stopped {
+            self.set_message("没有正在播放的歌曲");
+            return Ok(());
+        }
+        self.seek_to(time)
+    }
+    
+    /// 歌词面板打开时，确保显示的是当前歌曲的歌词：先找本地 .lrc 和缓存，
+    /// 没有时按配置的来源在后台在线查找
+    pub fn update_lyrics(&mut self) {
+        if !self.lyrics_visible && !self.karaoke.is_enabled() {
+            return;
+        }
+        
+        if let Some(rx) = &self.lyrics_rx {
+            match rx.try_recv() {
+                Ok((path, result)) => {
+                    self.lyrics_rx = None;
+                    self.dirty = true;
+                    if let Err(err) = &result {
+                        self.lyrics_misses.insert(path.clone(), err.clone());
+                    }
+                    if let Some(pane) = self.lyrics.as_mut().filter(|pane| pane.path == path) {
+                        pane.loading = false;
+                        match result {
+                            Ok((lyrics, source)) => {
+                                pane.lyrics = Some(lyrics);
+                                pane.status = format!("来自 {}", source);
+                            }
+                            Err(err) => pane.status = err,
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.lyrics_rx = None,
+            }
+        }
+        
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        if self.lyrics.as_ref().is_some_and(|pane| pane.path == song.path) {
+            return;
+        }
+        
+        let path = song.path.clone();
+        let artist = song.artist();
+        let title = song.title();
+        let album = song.tags.album.clone();
+        let duration = song.duration;
+        self.dirty = true;
+        if let Some(lyrics) = lyrics::load_local(&path, artist.as_deref(), &title) {
+            self.lyrics = Some(LyricsPane {
+                path,
+                lyrics: Some(lyrics),
+                status: String::new(),
+                loading: false,
+                cursor: None,
+            });
+            return;
+        }
+        
+        let providers: Vec<Provider> = self.config.lyrics_providers.iter().filter_map(|name| Provider::parse(name)).collect();
+        let status = match (&artist, self.lyrics_misses.get(&path)) {
+            (_, Some(err)) => Some(err.clone()),
+            _ if providers.is_empty() => Some("没有歌词（可以在配置 lyrics_providers 中开启在线查找）".to_string()),
+            (None, _) => Some("没有歌词，缺少艺术家信息，无法在线查找".to_string()),
+            _ => None,
+        };
+        if let Some(status) = status {
+            self.lyrics = Some(LyricsPane { path, lyrics: None, status, loading: false, cursor: None });
+            return;
+        }
+        
+        let query = lyrics::Query {
+            path: path.clone(),
+            artist: artist.unwrap_or_default(),
+            title,
+            album,
+            duration,
+        };
+        self.lyrics = Some(LyricsPane { path, lyrics: None, status: String::new(), loading: true, cursor: None });
+        self.lyrics_rx = Some(lyrics::spawn_fetch(query, providers, self.config.lyrics_next_to_file));
+    }
+    
+    /// 处理后台任务的结果，每个循环调用一次
+    pub fn poll_background(&mut self) {
+        self.update_info();
+        self.update_lyrics();
+        self.update_search();
+        self.update_radio();
+        self.update_jobs();
+        self.update_availability();
+        self.update_opening();
+        self.update_buffering();
+        self.autosave_session();
+        self.check_playlist_file();
+        self.leveler.set_metering(self.shows_loudness_meter());
+        if let Some(err) = self.output.take_error() {
+            self.set_message(err);
+        }
+        
+        // 提示消息和切歌提醒过期后重绘一次把它去掉
+        if self.message.is_some() && self.active_message().is_none() {
+            self.message = None;
+            self.dirty = true;
+        }
+        if self.flash.is_some() && self.active_flash().is_none() {
+            self.flash = None;
+            self.dirty = true;
+        }
+    }
+    
+    /// 每隔几秒检查作为曲库打开的 M3U 文件，被其他程序修改后询问是否重新加载
+    fn check_playlist_file(&mut self) {
+        if !self.config.watch_playlist {
+            return;
+        }
+        let interval = self.batch_interval(PLAYLIST_CHECK_INTERVAL);
+        if self.playlist_checked.is_some_and(|checked| checked.elapsed() < interval) {
+            return;
+        }
+        self.playlist_checked = Some(Instant::now());
+        let Some(watch) = self.playlist_watch.as_mut() else {
+            return;
+        };
+        let Some(entries) = watch.check() else {
+            return;
+        };
+        let local: Vec<PathBuf> = self.songs.iter().map(|song| song.path.clone()).collect();
+        let change = watch.change(entries, &local);
+        // 文件改成了和本地一样的内容（如另一个实例保存了同样的修改），直接以它为基础
+        if change.added == 0 && change.removed == 0 && change.unsaved == 0 {
+            watch.sync(change.entries);
+            return;
+        }
+        self.playlist_reload = Some(change);
+        self.dirty = true;
+    }
+    
+    /// 重新加载被修改的播放列表文件，保留本地还没有写入文件的修改。可以撤销
+    pub fn reload_playlist(&mut self) {
+        let (Some(change), Some(watch)) = (self.playlist_reload.take(), self.playlist_watch.as_ref()) else {
+            return;
+        };
+        let local: Vec<PathBuf> = self.songs.iter().map(|song| song.path.clone()).collect();
+        let merged = watch.merge(&change.entries, &local);
+        self.record_edit("重新加载播放列表", true);
+        let mut existing: HashMap<PathBuf, Song> = self
+            .songs
+            .iter()
+            .map(|song| (storage::canonicalize(&song.path).unwrap_or_else(|_| song.path.clone()), song.clone()))
+            .collect();
+        let songs: Vec<Song> = merged
+            .into_iter()
+            .map(|path| {
+                let key = storage::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                existing.remove(&key).unwrap_or_else(|| Song::from_path(path))
+            })
+            .collect();
+        self.replace_songs(songs);
+        if let Some(watch) = self.playlist_watch.as_mut() {
+            watch.sync(change.entries);
+        }
+        self.start_waveform_job();
+        let mut message = format!("已重新加载播放列表（新增 {} 首，移除 {} 首", change.added, change.removed);
+        if change.unsaved > 0 {
+            message.push_str(&format!("，保留本地加入的 {} 首", change.unsaved));
+        }
+        self.set_message(format!("{}，u: 撤销）", message));
+    }
+    
+    pub fn playlist_file_name(&self) -> Option<String> {
+        let watch = self.playlist_watch.as_ref()?;
+        Some(watch.path.file_name()?.to_string_lossy().to_string())
+    }
+    
+    /// 显示或关闭网页遥控的配对二维码，手机扫码即可打开带令牌的遥控页面
+    pub fn toggle_pairing(&mut self) {
+        if self.pairing.take().is_some() {
+            return;
+        }
+        match &self.web {
+            Some(web) => self.pairing = Some(web.pairing_url()),
+            None => self.set_message("网页遥控没有启动（需要设置 web_address 和 web_token）"),
+        }
+    }
+    
+    /// 不重新加载，下次保存播放列表时本地的内容会覆盖文件
+    pub fn dismiss_playlist_reload(&mut self) {
+        if self.playlist_reload.take().is_some() {
+            self.set_message("保留当前的播放列表，下次保存时会覆盖文件中的修改");
+        }
+    }
+    
+    /// 网络音频的数据跟不上播放时暂停输出，缓冲足够后继续，而不是让解码器读不到数据跳到下一首
+    fn update_buffering(&mut self) {
+        let (Some(stream), Some(sink)) = (&self.stream, &self.sink) else {
+            return;
+        };
+        if self.playback_state != PlaybackState::Playing {
+            return;
+        }
+        if !self.buffering && stream.is_starved() {
+            sink.pause();
+            self.buffering = true;
+            if let Some(start_time) = self.play_start_time {
+                self.current_play_time = self.clock.since(start_time);
+            }
+            self.dirty = true;
+        } else if self.buffering && stream.is_ready() {
+            sink.play();
+            self.buffering = false;
+            self.play_start_time = Some(self.clock.now() - self.current_play_time);
+            self.dirty = true;
+        }
+    }
+    
+    /// 界面上有随时间变化的内容（播放进度、任务进度），需要按固定间隔重绘
+    pub fn is_animating(&self) -> bool {
+        self.playback_state == PlaybackState::Playing || self.jobs.iter().any(Job::is_running)
+    }
+    
+    /// 开启/关闭 ListenBrainz 推荐电台
+    pub fn toggle_radio(&mut self) {
+        if self.radio || self.radio_rx.is_some() {
+            self.radio = false;
+            self.radio_rx = None;
+            self.set_message("推荐电台已关闭");
+            return;
+        }
+        
+        match listenbrainz::load_credentials() {
+            Some(credentials) => {
+                self.auto_snapshot("推荐电台前");
+                self.radio_rx = Some(listenbrainz::spawn_recommendations(credentials));
+                self.set_message("正在获取 ListenBrainz 推荐…");
+            }
+            None => self.set_message("请先运行 music_tui listenbrainz login <令牌>"),
+        }
+    }
+    
+    fn update_radio(&mut self) {
+        let Some(rx) = &self.radio_rx else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("后台任务意外结束".to_string()),
+        };
+        self.radio_rx = None;
+        
+        let tracks = match result {
+            Ok(tracks) => tracks,
+            Err(err) => {
+                self.set_message(format!("获取推荐失败: {}", err));
+                return;
+            }
+        };
+        
+        // 只把曲库中能匹配到、又在收藏集和儿童模式范围内的推荐加入队列
+        let mut added = 0;
+        for track in &tracks {
+            let Some(song) = import::find_match(track, &self.songs) else {
+                continue;
+            };
+            if !self.in_scope(song) {
+                continue;
+            }
+            let Some(index) = self.songs.iter().position(|s| s.path == song.path) else {
+                continue;
+            };
+            if index != self.current_index && !self.queue.contains(&index) {
+                self.queue.push_back(index);
+                added += 1;
+            }
+        }
+        
+        if added == 0 {
+            self.set_message(format!("{} 首推荐中没有曲库里的歌曲", tracks.len()));
+            return;
+        }
+        
+        self.radio = true;
+        self.set_message(format!("推荐电台: 已将 {} 首歌曲加入队列", added));
+        if self.playback_state == PlaybackState::Stopped {
+            if let Err(err) = self.next() {
+                self.set_message(format!("播放失败: {}", err));
+            }
+        }
+    }
+    
+    /// 自动模式下按当前歌曲的流派标签选择均衡器预设
+    fn apply_genre_eq(&mut self) {
+        let preset = match self.eq_mode {
+            EqMode::Manual(preset) => preset,
+            EqMode::Auto => self
+                .get_current_song()
+                .and_then(|song| song.tags.genre.as_deref())
+                .and_then(|genre| equalizer::preset_for_genre(&self.eq_genre_map, genre))
+                .unwrap_or(0),
+        };
+        self.eq_preset = preset;
+        self.eq.set_gains(equalizer::PRESETS[preset].gains);
+    }
+    
+    /// 循环切换均衡器：自动 → 各个预设 → 自动
+    pub fn cycle_eq(&mut self) {
+        self.eq_mode = match self.eq_mode {
+            EqMode::Auto => EqMode::Manual(0),
+            EqMode::Manual(preset) if preset + 1 < equalizer::PRESETS.len() => EqMode::Manual(preset + 1),
+            EqMode::Manual(_) => EqMode::Auto,
+        };
+        self.apply_genre_eq();
+        
+        let name = equalizer::PRESETS[self.eq_preset].name;
+        match self.eq_mode {
+            EqMode::Auto => self.set_message(format!("均衡器: 按流派自动 ({})", name)),
+            EqMode::Manual(_) => self.set_message(format!("均衡器: {}", name)),
+        }
+    }
+    
+    /// 开启/关闭响度均衡，立即作用于正在播放的歌曲
+    pub fn toggle_leveler(&mut self) {
+        self.config.leveler = !self.config.leveler;
+        self.leveler.set_enabled(self.config.leveler);
+        self.save_config();
+        match self.config.leveler {
+            true => self.set_message(format!("响度均衡已开启（目标 {} LUFS）", self.config.leveler_target_lufs)),
+            false => self.set_message("响度均衡已关闭"),
+        }
+    }
+    
+    /// 开启/关闭卡拉 OK 模式：削弱人声，界面换成全屏歌词
+    pub fn toggle_karaoke(&mut self) {
+        let enabled = !self.karaoke.is_enabled();
+        self.karaoke.set_enabled(enabled);
+        self.update_lyrics();
+        match enabled {
+            true if self.config.match_sample_rate => self.set_message("卡拉 OK 已开启（跟随歌曲采样率时不削弱人声）"),
+            true => self.set_message("卡拉 OK 已开启，Z 退出"),
+            false => self.set_message("卡拉 OK 已关闭"),
+        }
+    }
+    
+    /// 依次切换可视化模式：关闭、示波器、频谱图
+    pub fn cycle_visualizer(&mut self) {
+        self.config.visualizer = self.config.visualizer.next();
+        self.apply_visualizer_mode();
+        self.save_config();
+        let suffix = if self.config.power_saver { "（省电模式下不显示）" } else { "" };
+        self.set_message(format!("可视化: {}{}", self.config.visualizer.label(), suffix));
+    }
+    
+    /// 省电模式下不采集也不显示可视化
+    fn apply_visualizer_mode(&mut self) {
+        let mode = match self.config.power_saver {
+            true => VisualizerMode::Off,
+            false => self.config.visualizer,
+        };
+        self.visualizer.set_mode(mode);
+    }
+    
+    /// 开启/关闭省电模式
+    pub fn toggle_power_saver(&mut self) {
+        self.config.power_saver = !self.config.power_saver;
+        self.apply_power_saver();
+        self.save_config();
+        match self.config.power_saver {
+            true => self.set_message("省电模式：每秒刷新一次，关闭可视化和波形"),
+            false => self.set_message("已关闭省电模式"),
+        }
+    }
+    
+    fn apply_power_saver(&mut self) {
+        self.apply_visualizer_mode();
+        match self.config.power_saver {
+            true => {
+                for job in self.jobs.iter().filter(|job| job.is_running() && job.name == "波形缩略图") {
+                    job.cancel();
+                }
+            }
+            false => self.start_waveform_job(),
+        }
+    }
+    
+    /// 没有输入时检查播放状态的间隔
+    pub fn tick_rate(&self) -> Duration {
+        let tick = Duration::from_millis(self.config.tick_ms);
+        match self.config.power_saver {
+            true => tick.max(POWER_SAVER_TICK),
+            false => tick,
+        }
+    }
+    
+    /// 两帧之间的最短间隔
+    pub fn frame_interval(&self) -> Duration {
+        let fps = match self.config.power_saver {
+            true => self.config.max_fps.min(POWER_SAVER_FPS),
+            false => self.config.max_fps,
+        };
+        Duration::from_secs(1) / fps
+    }
+    
+    /// 定期的后台工作在省电模式下合并成较长的间隔
+    fn batch_interval(&self, interval: Duration) -> Duration {
+        match self.config.power_saver {
+            true => interval.max(POWER_SAVER_BATCH),
+            false => interval,
+        }
+    }
+    
+    /// 探测标签的线程数，省电模式下只用一个线程，不同时唤醒所有 CPU 核心
+    fn probe_workers(&self) -> usize {
+        match self.config.power_saver {
+            true => 1,
+            false => metadata_cache::worker_count(self.config.probe_workers),
+        }
+    }
+    
+    pub fn show_waveforms(&self) -> bool {
+        self.config.playlist_waveforms && !self.config.power_saver
+    }
+    
+    /// 可视化开启并且正在播放时每帧都要重绘
+    pub fn update_visualizer(&mut self) {
+        if self.visualizer.mode != VisualizerMode::Off && self.playback_state == PlaybackState::Playing {
+            self.visualizer.update();
+            self.dirty = true;
+        }
+    }
+    
+    /// 按新的顺序重排歌曲，同时更新当前歌曲、队列和随机历史中的下标
+    fn reorder_songs(&mut self, order: Vec<usize>) {
+        let mut new_index = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+        
+        let mut old_songs: Vec<Option<Song>> = std::mem::take(&mut self.songs).into_iter().map(Some).collect();
+        self.songs = order.iter().filter_map(|&old| old_songs[old].take()).collect();
+        
+        self.current_index = new_index.get(self.current_index).copied().unwrap_or(0);
+        self.playlist_selected = new_index.get(self.playlist_selected).copied().unwrap_or(0);
+        for index in self.queue.iter_mut().chain(self.shuffle_history.iter_mut()) {
+            *index = new_index[*index];
+        }
+    }
+    
+    /// 在按文件名排序和按 BPM 排序之间切换
+    pub fn toggle_sort_order(&mut self) {
+        self.sort_order = match self.sort_order {
+            SortOrder::Name => SortOrder::Bpm,
+            SortOrder::Bpm => SortOrder::Name,
+        };
+        self.apply_sort_order();
+        
+        match self.sort_order {
+            SortOrder::Name => self.set_message("按文件名排序"),
+            SortOrder::Bpm => self.set_message("按 BPM 排序"),
+        }
+    }
+    
+    fn apply_sort_order(&mut self) {
+        let mut order: Vec<usize> = (0..self.songs.len()).collect();
+        match self.sort_order {
+            SortOrder::Name => order.sort_by(|&a, &b| self.songs[a].path.cmp(&self.songs[b].path)),
+            SortOrder::Bpm => order.sort_by(|&a, &b| {
+                // 没有 BPM 的歌曲排在最后
+                let bpm_a = self.bpms.get(&self.songs[a].path).copied().unwrap_or(f32::MAX);
+                let bpm_b = self.bpms.get(&self.songs[b].path).copied().unwrap_or(f32::MAX);
+                bpm_a.total_cmp(&bpm_b)
+            }),
+        }
+        self.reorder_songs(order);
+    }
+    
+    /// 识别为纯音乐的歌曲；还没识别过的按标签判断
+    pub fn is_instrumental(&self, song: &Song) -> bool {
+        match self.instrumentals.get(&song.path).and_then(|detection| detection.instrumental) {
+            Some(instrumental) => instrumental,
+            None => instrumental::tagged(song),
+        }
+    }
+    
+    pub fn toggle_instrumental_filter(&mut self) {
+        self.instrumental_filter = !self.instrumental_filter;
+        if !self.instrumental_filter {
+            self.set_message("已显示全部歌曲");
+            return;
+        }
+        let count = (0..self.songs.len()).filter(|&i| self.is_visible(i)).count();
+        let pending = self.songs.iter().filter(|song| !self.instrumentals.contains_key(&song.path)).count();
+        match pending {
+            0 => self.set_message(format!("只显示纯音乐: {} 首歌曲", count)),
+            _ => self.set_message(format!("只显示纯音乐: {} 首歌曲（{} 首还没识别，只看了标签）", count, pending)),
+        }
+    }
+    
+    /// 在后台识别还没识别过的歌曲是不是纯音乐，网络歌曲只看标签和歌词
+    pub fn start_instrumental_detection(&mut self) {
+        if self.jobs.iter().any(|job| job.is_running() && job.name == "纯音乐识别") {
+            self.set_message("纯音乐识别已在进行中");
+            return;
+        }
+        let songs: Vec<Song> = self
+            .songs
+            .iter()
+            .filter(|song| !song.missing && !self.instrumentals.contains_key(&song.path))
+            .cloned()
+            .collect();
+        if songs.is_empty() {
+            self.set_message("所有歌曲都已识别过");
+            return;
+        }
+        let job = Job::spawn("纯音乐识别", move |progress| {
+            progress.set_total(songs.len());
+            let mut results = Vec::new();
+            for song in &songs {
+                progress.check_cancelled()?;
+                progress.set_status(&song.name);
+                let detection = instrumental::detect(song, progress.cancel_flag());
+                progress.check_cancelled()?;
+                results.push((song.path.clone(), detection));
+                progress.advance();
+            }
+            let found = results.iter().filter(|(_, detection)| detection.instrumental == Some(true)).count();
+            Ok(JobOutput::Instrumentals(results, format!("找到 {} 首纯音乐", found)))
+        });
+        self.start_job(job);
+    }
+    
+    fn apply_instrumentals(&mut self, results: Vec<(PathBuf, Detection)>) -> Option<String> {
+        self.instrumentals.extend(results);
+        instrumental::save(&self.instrumentals)
+            .err()
+            .map(|err| format!("保存纯音乐识别结果失败: {}", err))
+    }
+    
+    /// 开启波形缩略图时在后台为还没有缩略图、或者文件已经变化的歌曲生成，网络歌曲不生成
+    fn start_waveform_job(&mut self) {
+        if !self.show_waveforms() || self.jobs.iter().any(|job| job.is_running() && job.name == "波形缩略图") {
+            return;
+        }
+        let songs: Vec<Song> = self
+            .songs
+            .iter()
+            .filter(|song| !song.missing && song.duration.is_some() && !http_stream::is_url(&song.path))
+            .filter(|song| self.waveforms.get(&song.path).is_none_or(|waveform| !waveform.is_current(&song.path)))
+            .cloned()
+            .collect();
+        if songs.is_empty() {
+            return;
+        }
+        let job = Job::spawn("波形缩略图", move |progress| {
+            progress.set_total(songs.len());
+            let mut results = Vec::new();
+            for song in &songs {
+                progress.check_cancelled()?;
+                progress.set_status(&song.name);
+                let waveform = waveform::compute(&song.path, song.duration, progress.cancel_flag());
+                progress.check_cancelled()?;
+                results.extend(waveform.map(|waveform| (song.path.clone(), waveform)));
+                progress.advance();
+            }
+            let message = format!("生成 {} 首歌曲的波形", results.len());
+            Ok(JobOutput::Waveforms(results, message))
+        });
+        self.start_job(job);
+    }
+    
+    fn apply_waveforms(&mut self, results: Vec<(PathBuf, Waveform)>) -> Option<String> {
+        self.waveforms.extend(results);
+        waveform::save(&self.waveforms)
+            .err()
+            .map(|err| format!("保存波形缩略图失败: {}", err))
+    }
+    
+    /// 在后台为没有流派标签的歌曲给出流派建议，确认之前不修改文件。
+    /// 还没有分析过 BPM 的歌曲顺便分析，结果只用于识别
+    pub fn start_genre_classification(&mut self) {
+        if self.jobs.iter().any(|job| job.is_running() && job.name == "流派识别") {
+            self.set_message("流派识别已在进行中");
+            return;
+        }
+        let songs: Vec<(Song, Option<f32>)> = self
+            .songs
+            .iter()
+            .filter(|song| !song.missing && song.tags.genre.is_none() && !http_stream::is_url(&song.path))
+            .filter(|song| !self.genre_suggestions.contains_key(&song.path))
+            .map(|song| (song.clone(), self.bpms.get(&song.path).copied()))
+            .collect();
+        if songs.is_empty() {
+            self.set_message("没有需要识别流派的歌曲");
+            return;
+        }
+        let command = self.config.genre_command.clone();
+        let job = Job::spawn("流派识别", move |progress| {
+            progress.set_total(songs.len());
+            let mut results = Vec::new();
+            let mut failed = 0;
+            for (song, bpm) in &songs {
+                progress.check_cancelled()?;
+                progress.set_status(&song.name);
+                let bpm = match (bpm, command.is_empty()) {
+                    (None, true) => bpm::detect(&song.path),
+                    _ => *bpm,
+                };
+                match genre::classify(&song.path, song.duration, bpm, &command, progress.cancel_flag()) {
+                    Ok(suggestion) => results.extend(suggestion.map(|suggestion| (song.path.clone(), suggestion))),
+                    Err(_) => failed += 1,
+                }
+                progress.check_cancelled()?;
+                progress.advance();
+            }
+            let message = match failed {
+                0 => format!("{} 首歌曲有流派建议，按 gC 确认", results.len()),
+                failed => format!("{} 首歌曲有流派建议，{} 首识别失败", results.len(), failed),
+            };
+            Ok(JobOutput::Genres(results, message))
+        });
+        self.start_job(job);
+    }
+    
+    fn apply_genres(&mut self, results: Vec<(PathBuf, Suggestion)>) -> Option<String> {
+        self.genre_suggestions.extend(results);
+        self.save_genre_suggestions()
+    }
+    
+    fn save_genre_suggestions(&mut self) -> Option<String> {
+        genre::save(&self.genre_suggestions)
+            .err()
+            .map(|err| format!("保存流派建议失败: {}", err))
+    }
+    
+    /// 等待确认的流派建议，按曲库中的顺序
+    pub fn pending_genres(&self) -> Vec<(usize, &Suggestion)> {
+        self.songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| song.tags.genre.is_none())
+            .filter_map(|(index, song)| {
+                let suggestion = self.genre_suggestions.get(&song.path)?;
+                (!suggestion.rejected).then_some((index, suggestion))
+            })
+            .collect()
+    }
+    
+    pub fn toggle_genres_view(&mut self) {
+        if self.view == View::Genres {
+            self.view = View::Playlist;
+            return;
+        }
+        if self.pending_genres().is_empty() {
+            self.set_message("没有等待确认的流派建议，按 gc 开始识别");
+            return;
+        }
+        self.genre_selected = 0;
+        self.view = View::Genres;
+    }
+    
+    pub fn select_genre(&mut self, offset: isize) {
+        let count = self.pending_genres().len();
+        if count == 0 {
+            return;
+        }
+        self.genre_selected = (self.genre_selected as isize + offset).clamp(0, count as isize - 1) as usize;
+    }
+    
+    fn selected_genre(&self) -> Option<(PathBuf, String)> {
+        let (index, suggestion) = self.pending_genres().into_iter().nth(self.genre_selected)?;
+        Some((self.songs[index].path.clone(), suggestion.genre.clone()))
+    }
+    
+    pub fn accept_selected_genre(&mut self) {
+        if let Some((path, genre)) = self.selected_genre() {
+            self.accept_genre(&path, genre);
+        }
+    }
+    
+    /// 修改选中的建议后再确认
+    pub fn start_genre_prompt(&mut self) {
+        if let Some((path, genre)) = self.selected_genre() {
+            self.prompt = Some(Prompt {
+                kind: PromptKind::Genre { path },
+                input: genre,
+            });
+        }
+    }
+    
+    /// 确认流派：在后台写入文件标签，曲库中的歌曲马上显示新流派
+    fn accept_genre(&mut self, path: &Path, genre: String) {
+        self.genre_suggestions.remove(path);
+        for song in self.songs.iter_mut().filter(|song| song.path == path) {
+            song.tags.genre = Some(genre.clone());
+        }
+        self.finish_genre_review(format!("流派: {}", genre));
+        if let Some(err) = self.save_genre_suggestions() {
+            self.set_message(err);
+        }
+        let path = path.to_path_buf();
+        let job = Job::spawn("写入流派", move |progress| {
+            progress.set_total(1);
+            progress.set_status(path.file_name().unwrap_or_default().to_string_lossy());
+            tag_writer::write_genre(&path, &genre)?;
+            progress.advance();
+            Ok(JobOutput::Message(format!("已写入流派 {}", genre)))
+        });
+        self.start_job(job);
+    }
+    
+    /// 拒绝选中的建议，之后识别时跳过这首歌
+    pub fn reject_selected_genre(&mut self) {
+        let Some((path, genre)) = self.selected_genre() else {
+            return;
+        };
+        if let Some(suggestion) = self.genre_suggestions.get_mut(&path) {
+            suggestion.rejected = true;
+        }
+        self.finish_genre_review(format!("已拒绝流派建议: {}", genre));
+        if let Some(err) = self.save_genre_suggestions() {
+            self.set_message(err);
+        }
+    }
+    
+    /// 处理完一条建议后保持光标位置，全部处理完时返回播放列表
+    fn finish_genre_review(&mut self, message: String) {
+        let remaining = self.pending_genres().len();
+        self.genre_selected = self.genre_selected.min(remaining.saturating_sub(1));
+        if remaining == 0 {
+            self.view = View::Playlist;
+        }
+        self.set_message(message);
+    }
+    
+    pub fn start_bpm_filter_prompt(&mut self) {
+        let input = match self.bpm_filter {
+            Some((min, max)) => format!("{}-{}", min, max),
+            None => String::new(),
+        };
+        self.prompt = Some(Prompt {
+            kind: PromptKind::BpmFilter,
+            input,
+        });
+    }
+    
+    /// 解析 "120-130" 形式的 BPM 范围，输入为空时清除过滤
+    fn set_bpm_filter(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() {
+            self.bpm_filter = None;
+            self.set_message("已清除 BPM 过滤");
+            return;
+        }
+        
+        let range = input
+            .split_once('-')
+            .and_then(|(min, max)| Some((min.trim().parse::<f32>().ok()?, max.trim().parse::<f32>().ok()?)));
+        match range {
+            Some((min, max)) if min <= max => {
+                self.bpm_filter = Some((min, max));
+                let count = (0..self.songs.len()).filter(|&i| self.is_visible(i)).count();
+                self.set_message(format!("BPM {}-{}: {} 首歌曲", min, max, count));
+            }
+            _ => self.set_message("BPM 范围格式应为 120-130"),
+        }
+    }
+    
+    /// 调整播放列表区域的高度：变大时先收起控制说明，再压缩播放状态面板；变小时反过来
+    pub fn resize_playlist(&mut self, grow: bool) -> bool {
+        let config = &mut self.config;
+        if grow {
+            if config.controls_height > 3 {
+                config.controls_height -= 1;
+            } else if config.controls_height > 0 {
+                config.controls_height = 0;
+            } else if config.now_playing_height > 3 {
+                config.now_playing_height -= 1;
+            }
+        } else if config.controls_height < 3 {
+            config.controls_height = 3;
+        } else if config.controls_height < 8 {
+            config.controls_height += 1;
+        } else if config.now_playing_height < 6 {
+            config.now_playing_height += 1;
+        }
+        self.save_config();
+        true
+    }
+    
+    /// 调整信息面板的宽度，信息面板未打开时不处理
+    pub fn resize_info(&mut self, grow: bool) -> bool {
+        if !self.info_visible {
+            return false;
+        }
+        let width = if grow {
+            self.config.info_width + 5
+        } else {
+            self.config.info_width.saturating_sub(5)
+        };
+        self.config.info_width = width.clamp(20, 80);
+        self.save_config();
+        true
+    }
+    
+    fn save_config(&mut self) {
+        if let Err(err) = self.config.save() {
+            self.set_message(format!("保存配置失败: {}", err));
+        }
+    }
+    
+    pub fn toggle_settings_view(&mut self) {
+        self.view = match self.view {
+            View::Settings => View::Playlist,
+            _ => View::Settings,
+        };
+    }
+    
+    /// 设置界面先列出配置项，后面是按键绑定
+    pub fn select_setting(&mut self, offset: isize) {
+        let last = (self.config.entries().len() + keymap::ACTIONS.len()) as isize - 1;
+        self.setting_selected = (self.setting_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 光标在响度均衡的配置项上时显示均衡前后的响度表，播放线程只在这时测量
+    pub fn shows_loudness_meter(&self) -> bool {
+        self.view == View::Settings
+            && self
+                .config
+                .entries()
+                .into_iter()
+                .nth(self.setting_selected)
+                .is_some_and(|(key, _)| key == "leveler" || key == "leveler_target_lufs")
+    }
+    
+    /// 光标所在的按键绑定在 `keymap::ACTIONS` 中的位置，光标在配置项上时为 None
+    fn selected_binding(&self) -> Option<usize> {
+        self.setting_selected.checked_sub(self.config.entries().len())
+    }
+    
+    /// 修改光标所在的配置项：开关直接切换，其他的打开输入框；按键绑定等待按下新键
+    pub fn edit_selected_setting(&mut self) {
+        if let Some(index) = self.selected_binding() {
+            self.key_capture = Some(KeyCapture { index, pending: None });
+            return;
+        }
+        let Some((key, value)) = self.config.entries().into_iter().nth(self.setting_selected) else {
+            return;
+        };
+        if settings::is_locked(key) {
+            self.set_message(format!("{} 只能在配置文件中修改", key));
+            return;
+        }
+        match value.as_str() {
+            "true" => self.apply_setting(key, "false"),
+            "false" => self.apply_setting(key, "true"),
+            _ => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Setting { key },
+                    input: settings::edit_value(&value),
+                });
+            }
+        }
+    }
+    
+    /// 按键绑定界面中按下的键。和其他操作冲突时先提示，再按一次同一个键才改绑
+    pub fn capture_key(&mut self, key: KeyCode) {
+        let Some(capture) = self.key_capture.as_mut() else {
+            return;
+        };
+        let (_, _, label, _) = keymap::ACTIONS[capture.index];
+        let name = keymap::key_name(key);
+        if key == KeyCode::Esc {
+            self.key_capture = None;
+            return;
+        }
+        if keymap::is_reserved(key) {
+            self.set_message(format!("{} 不能绑定（留给焦点切换、面板导航和数字音量键）", name));
+            return;
+        }
+        let conflicts = self.keymap.conflicts(capture.index, &[key]);
+        let others: Vec<&str> = conflicts.iter().map(|&other| keymap::ACTIONS[other].2).collect();
+        if !conflicts.is_empty() && capture.pending != Some(key) {
+            capture.pending = Some(key);
+            self.set_message(format!("{} 已绑定到「{}」，再按一次 {} 改绑，Esc 取消", name, others.join("、"), name));
+            return;
+        }
+        let index = capture.index;
+        self.key_capture = None;
+        self.keymap.bind(index, key);
+        self.save_keymap();
+        match others.is_empty() {
+            true => self.set_message(format!("「{}」改为 {}", label, name)),
+            false => self.set_message(format!("「{}」改为 {}，已从「{}」移除", label, name, others.join("、"))),
+        }
+    }
+    
+    /// 光标所在的按键绑定恢复默认
+    pub fn reset_selected_binding(&mut self) {
+        let Some(index) = self.selected_binding() else {
+            return;
+        };
+        self.keymap.reset(index);
+        self.save_keymap();
+        let (_, _, label, _) = keymap::ACTIONS[index];
+        match self.keymap.has_conflict(index) {
+            true => self.set_message(format!("「{}」已恢复默认按键，但和其他操作冲突", label)),
+            false => self.set_message(format!("「{}」已恢复默认按键 {}", label, self.keymap.label(index))),
+        }
+    }
+    
+    /// 按下一个全局按键。组合键（如 gg）还没按完时返回 None，等待下一个键
+    pub fn press_key(&mut self, key: KeyCode) -> Option<Action> {
+        if self.key_sequence_at.is_some_and(|at| self.clock.since(at) > CHORD_TIMEOUT) {
+            self.key_sequence.clear();
+        }
+        self.key_sequence.push(key);
+        match self.keymap.lookup(&self.key_sequence) {
+            KeyMatch::Action(action) => {
+                self.key_sequence.clear();
+                Some(action)
+            }
+            KeyMatch::Prefix => {
+                self.key_sequence_at = Some(self.clock.now());
+                None
+            }
+            KeyMatch::None => {
+                // 组合键按到一半换了别的键：放弃前面的键，这个键重新开始
+                let restart = self.key_sequence.len() > 1;
+                self.key_sequence.clear();
+                if restart {
+                    self.press_key(key)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// 是否正在等待组合键的下一个键
+    pub fn awaiting_chord(&self) -> bool {
+        !self.key_sequence.is_empty() && self.key_sequence_at.is_some_and(|at| self.clock.since(at) <= CHORD_TIMEOUT)
+    }
+
+    /// 开始录制宏（接着按下寄存器的名字），正在录制时停止录制
+    pub fn toggle_macro_recording(&mut self) {
+        match self.macros.stop_recording() {
+            Some((register, count)) => self.set_message(format!("已录制宏 @{}，共 {} 个按键", register, count)),
+            None => {
+                self.macros.pending = Some(macros::Pending::Record);
+                self.set_message("按下字母或数字选择录制到哪个寄存器，Esc 取消");
+            }
+        }
+    }
+    
+    /// 执行宏：接着按下寄存器的名字，`@` 表示上一次执行的宏
+    pub fn start_macro_replay(&mut self) {
+        self.macros.pending = Some(macros::Pending::Play);
+        match self.macros.last {
+            Some(register) => self.set_message(format!("按下寄存器执行宏，@ 再执行一次 @{}", register)),
+            None => self.set_message("按下寄存器执行宏，Esc 取消"),
+        }
+    }
+    
+    /// 已按下的组合键，显示在标题栏中
+    pub fn pending_keys(&self) -> Option<String> {
+        self.awaiting_chord().then(|| self.keymap.sequence_label(&self.key_sequence))
+    }
+
+    fn save_keymap(&mut self) {
+        if let Err(err) = self.keymap.save() {
+            self.set_message(format!("保存按键绑定失败: {}", err));
+        }
+    }
+    
+    /// 修改一项配置并写回配置文件，能立即生效的马上应用
+    pub fn apply_setting(&mut self, key: &str, value: &str) {
+        if let Err(err) = self.config.set(key, value) {
+            self.set_message(err);
+            return;
+        }
+        match key {
+            "leveler" => self.leveler.set_enabled(self.config.leveler),
+            "leveler_target_lufs" => self.leveler.set_target(self.config.leveler_target_lufs),
+            "visualizer" => self.apply_visualizer_mode(),
+            "power_saver" => self.apply_power_saver(),
+            "playlist_waveforms" => self.start_waveform_job(),
+            "alarm_time" | "alarm_stop_time" => {
+                self.scheduler = Scheduler::from_config(&self.config, self.clock.local_now());
+            }
+            "beets_library" => {
+                self.beets = None;
+                self.load_beets();
+            }
+            "rekordbox_xml" => {
+                self.rekordbox = RekordboxLibrary::default();
+                self.load_rekordbox();
+                // 重新读取当前歌曲的提示点
+                self.cues = (PathBuf::new(), Vec::new());
+                self.load_chapters();
+            }
+            _ => {}
+        }
+        self.save_config();
+        let value = self.config.entries().into_iter().find(|(name, _)| *name == key).map(|(_, value)| value);
+        let value = settings::display_value(key, &value.unwrap_or_default());
+        match settings::needs_restart(key) {
+            true => self.set_message(format!("已保存 {} = {}，重启后生效", key, value)),
+            false => self.set_message(format!("已保存 {} = {}", key, value)),
+        }
+    }
+    
+    /// 在可见的面板之间切换焦点
+    pub fn cycle_focus(&mut self, forward: bool) {
+        let mut panes = vec![Focus::Playlist, Focus::Queue];
+        // 编辑界面只在曲库和队列之间切换
+        if self.info_visible && self.view != View::Editor {
+            panes.push(Focus::Info);
+        }
+        if self.lyrics_visible && self.view != View::Editor {
+            panes.push(Focus::Lyrics);
+        }
+        if self.jobs_visible && self.view != View::Editor {
+            panes.push(Focus::Jobs);
+        }
+        
+        let current = panes.iter().position(|pane| *pane == self.focus).unwrap_or(0);
+        let next = if forward {
+            (current + 1) % panes.len()
+        } else {
+            (current + panes.len() - 1) % panes.len()
+        };
+        self.focus = panes[next];
+    }
+    
+    /// 在播放列表中移动光标，跳过被过滤掉的歌曲
+    pub fn move_playlist_cursor(&mut self, offset: isize) {
+        let visible: Vec<usize> = (0..self.songs.len()).filter(|&i| self.is_visible(i)).collect();
+        if visible.is_empty() {
+            return;
+        }
+        
+        let position = visible
+            .iter()
+            .position(|&index| index >= self.playlist_selected)
+            .unwrap_or(visible.len() - 1) as isize;
+        let target = (position + offset).clamp(0, visible.len() as isize - 1) as usize;
+        self.playlist_selected = visible[target];
+    }
+    
+    /// 获得焦点的列表的光标跳到开头或末尾
+    pub fn jump_cursor(&mut self, end: bool) {
+        let offset = if end { isize::MAX / 2 } else { isize::MIN / 2 };
+        match (self.focus, self.view) {
+            (Focus::Queue, _) => self.move_queue_cursor(offset),
+            (Focus::Jobs, _) => self.move_job_cursor(offset),
+            (Focus::Lyrics, _) => self.move_lyrics_cursor(offset),
+            (Focus::Info, _) if !end => self.info_scroll = 0,
+            (Focus::Info, _) => {}
+            (Focus::Playlist, View::Bookmarks) => self.select_bookmark(offset),
+            (Focus::Playlist, View::Artists) => self.select_artist(offset),
+            (Focus::Playlist, View::Collections) => self.select_collection(offset),
+            (Focus::Playlist, View::Snapshots) => self.select_snapshot(offset),
+            (Focus::Playlist, View::Genres) => self.select_genre(offset),
+            (Focus::Playlist, View::Settings) => self.select_setting(offset),
+            (Focus::Playlist, View::Playlist | View::Editor) => self.move_playlist_cursor(offset),
+        }
+    }
+    
+    /// 播放光标所在的歌曲
+    pub fn play_selected(&mut self) -> Result<()> {
+        if self.playlist_selected < self.songs.len() {
+            self.current_index = self.playlist_selected;
+            self.play()?;
+        }
+        Ok(())
+    }
+    
+    /// 把播放列表光标所在的歌曲加入队列末尾
+    pub fn enqueue_selected(&mut self) {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return;
+        };
+        let name = song.name.clone();
+        self.queue.push_back(self.playlist_selected);
+        self.set_message(format!("已加入队列末尾（第 {} 首）: {}", self.queue.len(), name));
+    }
+    
+    /// 编辑界面：把曲库光标所在的歌曲加入队列，光标移到下一首，连续按就能依次加入
+    pub fn add_selected_and_advance(&mut self) {
+        if self.playlist_selected >= self.songs.len() {
+            return;
+        }
+        self.queue.push_back(self.playlist_selected);
+        self.queue_selected = self.queue.len() - 1;
+        self.move_playlist_cursor(1);
+    }
+    
+    /// 编辑界面：把队列光标所在的歌曲移回曲库（移出队列），曲库光标定位到这首歌
+    pub fn return_queue_selected(&mut self) {
+        let Some(&index) = self.queue.get(self.queue_selected) else {
+            return;
+        };
+        self.remove_queue_selected();
+        self.playlist_selected = index;
+    }
+    
+    /// 把播放列表光标所在的歌曲插到队列最前面，当前歌曲结束后立即播放
+    pub fn play_next_selected(&mut self) {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return;
+        };
+        let name = song.name.clone();
+        self.queue.push_front(self.playlist_selected);
+        self.queue_selected = 0;
+        self.set_message(format!("下一首播放: {}", name));
+    }
+    
+    /// 显示歌曲的详细信息，selected 为 true 时是播放列表光标所在的歌曲，否则是当前歌曲
+    pub fn open_details(&mut self, selected: bool) {
+        let index = if selected { self.playlist_selected } else { self.current_index };
+        let Some(song) = self.songs.get(index) else {
+            return;
+        };
+        match metadata::details(&song.path) {
+            Ok(mut sections) => {
+                if let Some(err) = self.decode_errors.get(&song.path) {
+                    sections.insert(0, ("解码错误", vec![("错误".to_string(), err.clone())]));
+                }
+                if let Some(item) = self.beets.as_ref().and_then(|beets| beets.get(&song.path)) {
+                    sections.push(("beets", beets::details(item)));
+                }
+                if let Some(detection) = self.instrumentals.get(&song.path) {
+                    let verdict = match detection.instrumental {
+                        Some(true) => "纯音乐",
+                        Some(false) => "有人声",
+                        None => "无法判断",
+                    };
+                    let value = format!("{}（依据: {}）", verdict, detection.evidence.label());
+                    sections.push(("分析", vec![("纯音乐识别".to_string(), value)]));
+                }
+                self.details = Some(DetailsPopup {
+                    name: song.name.clone(),
+                    sections,
+                    scroll: 0,
+                });
+            }
+            Err(err) => self.set_message(format!("读取歌曲信息失败: {}", err)),
+        }
+    }
+    
+    pub fn scroll_details(&mut self, offset: i32) {
+        if let Some(details) = self.details.as_mut() {
+            details.scroll = (details.scroll as i32 + offset).max(0) as u16;
+        }
+    }
+    
+    /// 打开全局搜索窗口，在整个曲库（包括远程曲库）和保存的播放列表中查找，不受当前过滤条件限制
+    pub fn open_search(&mut self) {
+        let clean_mode = self.config.clean_mode;
+        let index = search::build_index(&self.songs, &self.config.remote_sources, |song| {
+            !(clean_mode && song.tags.explicit)
+        });
+        self.search = Some(SearchPane {
+            query: String::new(),
+            groups: Vec::new(),
+            selected: 0,
+            searching: false,
+            index,
+            rx: None,
+        });
+    }
+    
+    pub fn search_push(&mut self, text: &str) {
+        if let Some(pane) = self.search.as_mut() {
+            pane.query.push_str(text);
+        }
+        self.run_search();
+    }
+    
+    pub fn search_pop(&mut self) {
+        if let Some(pane) = self.search.as_mut() {
+            pane.query.pop();
+        }
+        self.run_search();
+    }
+    
+    /// 按新的输入重新查找，还没返回的旧查找直接丢弃
+    fn run_search(&mut self) {
+        let Some(pane) = self.search.as_mut() else {
+            return;
+        };
+        pane.searching = !pane.query.trim().is_empty();
+        pane.rx = pane
+            .searching
+            .then(|| search::spawn(pane.query.clone(), Arc::clone(&pane.index)));
+        if !pane.searching {
+            pane.groups.clear();
+            pane.selected = 0;
+        }
     }
     
-    pub fn check_and_auto_next(&mut self) -> Result<()> {
-        if let Some(ref sink) = self.sink {
-            // 检查 sink 是否为空且不在暂停状态（播放结束）
-            if sink.len() == 0 && !sink.is_paused() && self.playback_state == PlaybackState::Playing {
-                // 播放结束，自动播放下一曲
-                if self.songs.len() > 1 {
-                    self.next()?;
-                } else {
-                    // 只有一首歌，重新播放
-                    self.play()?;
-                }
+    fn update_search(&mut self) {
+        let Some(pane) = self.search.as_mut() else {
+            return;
+        };
+        let Some(rx) = &pane.rx else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(groups) => {
+                pane.groups = groups;
+                pane.selected = 0;
+                pane.searching = false;
+                pane.rx = None;
+                self.dirty = true;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                pane.searching = false;
+                pane.rx = None;
+            }
+        }
+    }
+    
+    pub fn move_search_cursor(&mut self, offset: isize) {
+        if let Some(pane) = self.search.as_mut() {
+            let last = pane.hits().count() as isize - 1;
+            pane.selected = (pane.selected as isize + offset).clamp(0, last.max(0)) as usize;
+        }
+    }
+    
+    /// 光标所在结果对应的歌曲在曲库中的位置，按路径查找；
+    /// 曲库在搜索期间重新扫描过时去掉已经不存在的
+    fn selected_search_songs(&self) -> Vec<usize> {
+        let Some(hit) = self.search.as_ref().and_then(|pane| pane.hits().nth(pane.selected)) else {
+            return Vec::new();
+        };
+        let paths = match hit {
+            SearchHit::Song { path, .. } => std::slice::from_ref(path),
+            SearchHit::Playlist { songs, .. } => songs.as_slice(),
+        };
+        let positions: HashMap<&Path, usize> =
+            self.songs.iter().enumerate().map(|(index, song)| (song.path.as_path(), index)).collect();
+        paths.iter().filter_map(|path| positions.get(path.as_path()).copied()).collect()
+    }
+    
+    /// 播放光标所在的结果并关闭搜索窗口；播放列表从第一首开始，其余的排进队列
+    pub fn play_search_hit(&mut self) -> Result<()> {
+        let songs = self.selected_search_songs();
+        let Some((&first, rest)) = songs.split_first() else {
+            return Ok(());
+        };
+        self.search = None;
+        self.queue.extend(rest.iter().copied());
+        self.playlist_selected = first;
+        self.current_index = first;
+        self.play()
+    }
+    
+    /// 把光标所在的结果加入队列末尾，搜索窗口保持打开，可以接着加
+    pub fn enqueue_search_hit(&mut self) {
+        let songs = self.selected_search_songs();
+        if songs.is_empty() {
+            return;
+        }
+        self.queue.extend(songs.iter().copied());
+        match songs.as_slice() {
+            [index] => {
+                let name = self.songs[*index].name.clone();
+                self.set_message(format!("已加入队列末尾（第 {} 首）: {}", self.queue.len(), name));
+            }
+            _ => self.set_message(format!("已把 {} 首歌加入队列", songs.len())),
+        }
+    }
+    
+    /// 给播放列表光标所在的歌曲加一星，五星之后清除评分。
+    /// 配置了 write_rating_tags 时在后台同时写入文件标签
+    pub fn cycle_selected_rating(&mut self) {
+        let Some(path) = self.songs.get(self.playlist_selected).map(|song| song.path.clone()) else {
+            return;
+        };
+        let stars = (self.ratings.get(&path).unwrap_or(0) + 1) % (ratings::MAX_STARS + 1);
+        self.ratings.set(&path, stars);
+        if let Err(err) = self.ratings.save() {
+            self.set_message(format!("保存评分失败: {}", err));
+            return;
+        }
+        let glyphs = self.config.glyphs.glyphs();
+        let label = match stars {
+            0 => "已清除评分".to_string(),
+            stars => format!("评分: {}", ratings::stars_label(stars, glyphs.star, glyphs.star_empty)),
+        };
+        self.set_message(label);
+
+        if self.config.write_rating_tags && !http_stream::is_url(&path) {
+            // 标签和数据库保持一致，下次扫描时不会再导入旧的评分
+            self.songs[self.playlist_selected].tags.rating = (stars > 0).then_some(stars);
+            let job = Job::spawn("写入评分", move |progress| {
+                progress.set_total(1);
+                progress.set_status(path.file_name().unwrap_or_default().to_string_lossy());
+                tag_writer::write_rating(&path, stars)?;
+                progress.advance();
+                Ok(JobOutput::Message("已写入文件标签".to_string()))
+            });
+            self.start_job(job);
+        }
+    }
+    
+    /// 把文件标签中的评分导入还没有评分的歌曲，让评分在数据丢失后也能恢复
+    fn import_tag_ratings(&mut self) {
+        let mut imported = false;
+        for song in &self.songs {
+            if let Some(stars) = song.tags.rating {
+                imported |= self.ratings.import(&song.path, stars);
+            }
+        }
+        if imported {
+            if let Err(err) = self.ratings.save() {
+                self.set_message(format!("保存评分失败: {}", err));
+            }
+        }
+    }
+    
+    /// 打开播放列表光标所在歌曲的属性窗口
+    /// 从头播放时跳过的开头：单曲的设置优先，其次是整张专辑的设置
+    pub fn intro_skip(&self, song: &Song) -> Duration {
+        let settings = self.track_settings.get(&song.path).copied().unwrap_or_default();
+        let album_skip = track_settings::album_key(song).and_then(|key| self.album_skips.get(&key).copied());
+        settings.effective_intro_skip(album_skip)
+    }
+    
+    pub fn open_track_editor(&mut self) {
+        let Some(song) = self.songs.get(self.playlist_selected) else {
+            return;
+        };
+        let album = track_settings::album_key(song).map(|key| {
+            let skip = self.album_skips.get(&key).copied().unwrap_or_default();
+            (key, skip)
+        });
+        self.track_editor = Some(TrackEditor {
+            path: song.path.clone(),
+            name: song.name.clone(),
+            settings: self.track_settings.get(&song.path).copied().unwrap_or_default(),
+            field: 0,
+            album,
+        });
+    }
+    
+    pub fn move_track_field(&mut self, offset: isize) {
+        if let Some(editor) = self.track_editor.as_mut() {
+            let count = track_settings::FIELDS.len() as isize;
+            editor.field = (editor.field as isize + offset).rem_euclid(count) as usize;
+        }
+    }
+    
+    pub fn adjust_track_field(&mut self, steps: i32) {
+        let Some(editor) = self.track_editor.as_mut() else {
+            return;
+        };
+        match (editor.field, editor.album.as_mut()) {
+            (track_settings::ALBUM_FIELD, Some((_, skip))) => *skip = (*skip + steps as f32 * 0.5).clamp(0.0, 600.0),
+            (track_settings::ALBUM_FIELD, None) => self.set_message("这首歌没有专辑标签"),
+            (field, _) => editor.settings.adjust(field, steps),
+        }
+    }
+    
+    pub fn reset_track_field(&mut self) {
+        if let Some(editor) = self.track_editor.as_mut() {
+            match (editor.field, editor.album.as_mut()) {
+                (track_settings::ALBUM_FIELD, Some((_, skip))) => *skip = 0.0,
+                (field, _) => editor.settings.reset(field),
+            }
+        }
+    }
+    
+    /// 保存属性窗口中的设置；正在播放这首歌时从当前位置重新开始，立即生效
+    pub fn save_track_editor(&mut self) -> Result<()> {
+        let Some(editor) = self.track_editor.take() else {
+            return Ok(());
+        };
+        if editor.settings.is_default() {
+            self.track_settings.remove(&editor.path);
+        } else {
+            self.track_settings.insert(editor.path.clone(), editor.settings);
+        }
+        if let Some((key, skip)) = editor.album.clone() {
+            match skip > 0.0 {
+                true => self.album_skips.insert(key, skip),
+                false => self.album_skips.remove(&key),
+            };
+        }
+        let clipping = self.songs.iter().find(|song| song.path == editor.path).and_then(|song| self.clipping_db(song));
+        let saved = track_settings::save(&self.track_settings).and_then(|_| track_settings::save_albums(&self.album_skips));
+        match (saved, clipping) {
+            (Ok(()), Some(over)) => {
+                self.set_message(format!("已保存歌曲设置: {}，按当前增益播放会削波 {:+.1} dB", editor.name, over))
             }
+            (Ok(()), None) => self.set_message(format!("已保存歌曲设置: {}", editor.name)),
+            (Err(err), _) => self.set_message(format!("保存歌曲设置失败: {}", err)),
+        }
+        
+        let playing = self.get_current_song().is_some_and(|song| song.path == editor.path);
+        if playing && self.playback_state != PlaybackState::Stopped && !self.current_play_time.is_zero() {
+            self.seek_to(self.current_play_time)?;
         }
         Ok(())
     }
     
-    pub fn update_play_time(&mut self) {
-        match self.playback_state {
-            PlaybackState::Playing => {
-                if let Some(start_time) = self.play_start_time {
-                    let elapsed = start_time.elapsed();
-                    // 确保播放时间不超过总时长
-                    let total_duration = self.get_total_duration();
-                    if elapsed > total_duration {
-                        self.current_play_time = total_duration;
-                    } else {
-                        self.current_play_time = elapsed;
+    pub fn move_queue_cursor(&mut self, offset: isize) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let last = self.queue.len() as isize - 1;
+        self.queue_selected = (self.queue_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    /// 立即播放队列中光标所在的歌曲，并把它移出队列
+    pub fn play_queue_selected(&mut self) -> Result<()> {
+        if let Some(index) = self.queue.remove(self.queue_selected) {
+            self.clamp_queue_cursor();
+            self.current_index = index;
+            self.play()?;
+        }
+        Ok(())
+    }
+    
+    pub fn remove_queue_selected(&mut self) {
+        let Some(&index) = self.queue.get(self.queue_selected) else {
+            return;
+        };
+        let name = self.songs[index].name.clone();
+        self.record_edit(format!("移出队列: {}", name), false);
+        self.queue.remove(self.queue_selected);
+        self.clamp_queue_cursor();
+        self.set_message(format!("已移出队列: {}（u: 撤销）", name));
+    }
+    
+    /// 把队列光标所在的项向前或向后移动一位
+    pub fn move_queue_item(&mut self, offset: isize) {
+        let target = self.queue_selected as isize + offset;
+        if target < 0 || target as usize >= self.queue.len() {
+            return;
+        }
+        self.record_edit("调整队列顺序", false);
+        self.queue.swap(self.queue_selected, target as usize);
+        self.queue_selected = target as usize;
+    }
+    
+    /// 把播放队列打乱一次，之后的播放顺序就是队列中看到的顺序，可以继续调整或撤销
+    pub fn shuffle_queue(&mut self) {
+        use rand::seq::SliceRandom;
+        if self.queue.len() < 2 {
+            self.set_message("队列中至少要有两首歌才能打乱");
+            return;
+        }
+        self.auto_snapshot("打乱队列前");
+        self.record_edit("打乱队列", false);
+        self.queue.make_contiguous().shuffle(&mut rand::thread_rng());
+        self.queue_selected = 0;
+        self.set_message(format!("已打乱队列（{} 首，u: 撤销）", self.queue.len()));
+    }
+    
+    pub fn clear_queue(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        self.record_edit("清空队列", false);
+        let count = self.queue.len();
+        self.queue.clear();
+        self.queue_selected = 0;
+        self.set_message(format!("已清空队列（{} 首，u: 撤销）", count));
+    }
+    
+    fn snapshot(&self, with_songs: bool) -> EditSnapshot {
+        EditSnapshot {
+            queue: self.queue.iter().map(|&index| self.songs[index].path.clone()).collect(),
+            songs: with_songs.then(|| self.songs.clone()),
+        }
+    }
+    
+    /// 在修改队列或曲库之前调用，记录可以撤销的状态
+    fn record_edit(&mut self, label: impl Into<String>, with_songs: bool) {
+        let snapshot = self.snapshot(with_songs);
+        self.edit_history.record(label, snapshot);
+    }
+    
+    fn restore(&mut self, snapshot: EditSnapshot) {
+        if let Some(songs) = snapshot.songs {
+            self.replace_songs(songs);
+            self.save_library_playlist(&[]);
+        }
+        let positions: HashMap<&Path, usize> = self
+            .songs
+            .iter()
+            .enumerate()
+            .map(|(index, song)| (song.path.as_path(), index))
+            .collect();
+        self.queue = snapshot.queue.iter().filter_map(|path| positions.get(path.as_path()).copied()).collect();
+        self.clamp_queue_cursor();
+    }
+    
+    pub fn undo(&mut self) {
+        // 先取出历史栈，快照当前状态时不必同时借用
+        let mut history = std::mem::take(&mut self.edit_history);
+        let entry = history.undo(|before| self.snapshot(before.songs.is_some()));
+        self.edit_history = history;
+        let Some((label, before)) = entry else {
+            self.set_message("没有可以撤销的操作");
+            return;
+        };
+        self.restore(before);
+        self.set_message(format!("已撤销: {}（Ctrl+R: 重做）", label));
+    }
+    
+    pub fn redo(&mut self) {
+        let mut history = std::mem::take(&mut self.edit_history);
+        let entry = history.redo(|after| self.snapshot(after.songs.is_some()));
+        self.edit_history = history;
+        let Some((label, after)) = entry else {
+            self.set_message("没有可以重做的操作");
+            return;
+        };
+        self.restore(after);
+        self.set_message(format!("已重做: {}", label));
+    }
+    
+    fn clamp_queue_cursor(&mut self) {
+        self.queue_selected = self.queue_selected.min(self.queue.len().saturating_sub(1));
+    }
+    
+    pub fn scroll_info(&mut self, offset: i32) {
+        self.info_scroll = (self.info_scroll as i32 + offset).max(0) as u16;
+    }
+    
+    fn start_job(&mut self, job: Job) {
+        self.set_message(format!("已开始后台任务: {}", job.name));
+        self.jobs.push(job);
+        self.jobs_visible = true;
+    }
+    
+    /// 重新打开音频设备（例如启动声音服务器或释放被占用的设备之后），成功后恢复播放功能
+    pub fn retry_audio(&mut self) {
+        if self.audio_error.is_none() {
+            self.set_message("音频设备已经打开");
+            return;
+        }
+        match audio_output::open(&self.config) {
+            Ok(mut output) => {
+                let warning = output.warning.take();
+                self.output = output;
+                self.audio_error = None;
+                self.set_message(warning.unwrap_or_else(|| "音频设备已打开".to_string()));
+            }
+            Err(err) => {
+                self.audio_error = Some(err.to_string());
+                self.set_message("仍然无法打开音频设备");
+            }
+        }
+    }
+    
+    /// 在后台重新扫描曲库并探测标签
+    pub fn start_library_scan(&mut self) {
+        if self.jobs.iter().any(|job| job.is_running() && job.name == "扫描曲库") {
+            self.set_message("曲库扫描已在进行中");
+            return;
+        }
+        
+        let source = self.library_source.clone();
+        let filter = self.scan_filter();
+        let workers = self.probe_workers();
+        let remote_sources = match source {
+            Some(_) => Vec::new(),
+            None => self.config.remote_sources.clone(),
+        };
+        let job = Job::spawn("扫描曲库", move |progress| {
+            let paths: Vec<PathBuf> = library_paths(source.as_deref())?
+                .into_iter()
+                .filter(|path| filter.allows_path(path))
+                .collect();
+            progress.set_total(paths.len());
+            
+            progress.set_status(format!("{} 个线程并行探测", workers));
+            let started = Instant::now();
+            let mut cache = MetadataCache::load();
+            let probes = cache.probe_all(&paths, workers, progress.cancel_flag(), || progress.advance());
+            progress.check_cancelled()?;
+            cache.save()?;
+            
+            progress.set_status("检查移动过的文件");
+            let mut fingerprints = fingerprint::load();
+            let moves = fingerprint::reconcile(&mut fingerprints, &paths, progress.cancel_flag());
+            progress.check_cancelled()?;
+            fingerprint::save(&fingerprints)?;
+            
+            let mut songs: Vec<Song> = paths
+                .into_iter()
+                .zip(probes)
+                .map(|(path, probe)| Song::from_probe(path, probe))
+                .filter(|song| filter.allows_song(song))
+                .collect();
+            
+            let mut status = format!("用时 {:.1} 秒", started.elapsed().as_secs_f32());
+            if !remote_sources.is_empty() {
+                progress.set_status("列出远程曲库");
+                let (paths, errors) = remote_library::list_all(&remote_sources);
+                let paths: Vec<PathBuf> = paths.into_iter().filter(|path| filter.allows_path(path)).collect();
+                songs.extend(remote_library::songs(paths, &mut cache, workers));
+                cache.save()?;
+                status = format!("用时 {:.1} 秒", started.elapsed().as_secs_f32());
+                if !errors.is_empty() {
+                    status.push_str(&format!("，远程曲库出错: {}", errors.join("; ")));
+                }
+            }
+            progress.set_status(status);
+            Ok(JobOutput::Library(songs, moves))
+        });
+        self.start_job(job);
+    }
+    
+    /// 启动时在后台列出远程曲库，完成后追加到曲库末尾
+    fn start_remote_scan(&mut self) {
+        let sources = self.config.remote_sources.clone();
+        let filter = self.scan_filter();
+        let workers = self.probe_workers();
+        let job = Job::spawn("扫描远程曲库", move |progress| {
+            progress.set_total(1);
+            progress.set_status(sources.join(", "));
+            let (paths, errors) = remote_library::list_all(&sources);
+            progress.check_cancelled()?;
+            let paths: Vec<PathBuf> = paths.into_iter().filter(|path| filter.allows_path(path)).collect();
+            
+            let mut cache = MetadataCache::load();
+            let songs = remote_library::songs(paths, &mut cache, workers);
+            cache.save()?;
+            progress.advance();
+            if !errors.is_empty() {
+                progress.set_status(format!("出错: {}", errors.join("; ")));
+            }
+            Ok(JobOutput::AddSongs(songs))
+        });
+        self.start_job(job);
+    }
+    
+    /// 在后台计算整个播放列表的 ReplayGain 并写回标签
+    pub fn start_loudness_analysis(&mut self) {
+        let paths: Vec<PathBuf> = self.songs.iter().map(|song| song.path.clone()).collect();
+        let job = Job::spawn("ReplayGain 分析", move |progress| {
+            progress.set_total(paths.len());
+            
+            let mut written = 0;
+            let mut failed = 0;
+            let mut results = Vec::new();
+            for path in &paths {
+                progress.check_cancelled()?;
+                progress.set_status(path.file_name().unwrap_or_default().to_string_lossy());
+                match replaygain::analyze(path, progress.cancel_flag()) {
+                    Ok(analysis) => {
+                        results.extend(analysis.summary().map(|loudness| (path.clone(), loudness)));
+                        if let Some(tags) = replaygain::track_tags(&analysis) {
+                            match tag_writer::write_text_tags(path, &tags) {
+                                Ok(()) => written += 1,
+                                Err(_) => failed += 1,
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        progress.check_cancelled()?;
+                        failed += 1;
                     }
                 }
+                progress.advance();
             }
-            PlaybackState::Paused => {
-                // 暂停时不更新播放时间，保持当前值
+            Ok(JobOutput::Loudness(results, format!("写入 {} 首，{} 首失败", written, failed)))
+        });
+        self.start_job(job);
+    }
+    
+    /// 按当前的前置增益和响度均衡设置，歌曲播放时的固定增益 (dB)
+    pub fn playback_gain_db(&self, song: &Song, preamp_db: f32) -> f64 {
+        let mut gain = preamp_db as f64;
+        if self.leveler.is_enabled() && !self.config.match_sample_rate {
+            if let Some(track_gain) = song.tags.replaygain_db {
+                gain += track_gain as f64 + self.config.leveler_target_lufs as f64 - replaygain::REFERENCE_LUFS;
             }
-            PlaybackState::Stopped => {
-                // 停止时重置播放时间
-                self.current_play_time = Duration::ZERO;
+        }
+        gain
+    }
+    
+    /// 按当前增益播放时超出 0 dBTP 的分贝数，没有分析过响度或不会削波时为 None
+    pub fn clipping_db(&self, song: &Song) -> Option<f64> {
+        let loudness = self.loudness.get(&song.path)?;
+        let preamp = self.track_settings.get(&song.path).map(|settings| settings.preamp_db).unwrap_or_default();
+        loudness.clip_db(self.playback_gain_db(song, preamp))
+    }
+    
+    /// 保存分析结果，并把新的增益用到内存中的歌曲上，不用重新扫描
+    fn apply_loudness(&mut self, results: Vec<(PathBuf, replaygain::Loudness)>) -> Option<String> {
+        for (path, loudness) in results {
+            for song in self.songs.iter_mut().filter(|song| song.path == path) {
+                song.tags.replaygain_db = Some(replaygain::gain_for(loudness.lufs) as f32);
             }
+            self.loudness.insert(path, loudness);
         }
+        if let Err(err) = replaygain::save(&self.loudness) {
+            return Some(format!("保存响度分析结果失败: {}", err));
+        }
+        let clipping = self.songs.iter().filter(|song| self.clipping_db(song).is_some()).count();
+        (clipping > 0).then(|| format!("{} 首歌曲按当前增益播放会削波，可在歌曲属性中降低前置增益", clipping))
     }
     
-    pub fn get_current_time(&self) -> Duration {
-        match self.playback_state {
-            PlaybackState::Playing => {
-                if let Some(start_time) = self.play_start_time {
-                    let elapsed = start_time.elapsed();
-                    // 确保播放时间不超过总时长
-                    let total_duration = self.get_total_duration();
-                    if elapsed > total_duration {
-                        return total_duration;
+    /// 开始播放的歌曲会削波时提醒
+    fn warn_clipping(&mut self) {
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        if let Some(over) = self.clipping_db(song) {
+            let message = format!("{} 按当前增益播放会削波 {:+.1} dB", song.name, over);
+            self.set_message(message);
+        }
+    }
+    
+    /// 处理结束的后台任务，并用提示消息通知结果
+    fn update_jobs(&mut self) {
+        let mut outputs = Vec::new();
+        let mut toasts = Vec::new();
+        for job in self.jobs.iter_mut() {
+            if !job.is_running() {
+                continue;
+            }
+            let output = job.poll();
+            if !job.is_running() {
+                toasts.push(format!("{}: {}", job.name, job.status()));
+            }
+            outputs.extend(output);
+        }
+        
+        for output in outputs {
+            match output {
+                JobOutput::Message(_) => {}
+                JobOutput::Library(songs, moves) => {
+                    self.replace_songs(songs);
+                    // 重新扫描时读过了播放列表文件
+                    if let Some(watch) = self.playlist_watch.as_mut() {
+                        watch.sync(self.songs.iter().map(|song| song.path.clone()).collect());
                     }
-                    return elapsed;
+                    self.apply_relocations(moves);
+                    self.start_waveform_job();
                 }
-                self.current_play_time
+                JobOutput::Relocated(moves) => self.apply_relocations(moves),
+                JobOutput::Enqueue(paths) => {
+                    for path in paths {
+                        self.enqueue_path(path);
+                    }
+                }
+                JobOutput::AddSongs(songs) => {
+                    for song in songs {
+                        if !self.songs.iter().any(|existing| existing.path == song.path) {
+                            self.songs.push(song);
+                        }
+                    }
+                    self.import_tag_ratings();
+                }
+                JobOutput::EnqueueSongs(songs) => {
+                    for song in songs {
+                        self.songs.push(song);
+                        self.queue.push_back(self.songs.len() - 1);
+                    }
+                }
+                // 放在任务完成消息之后显示
+                JobOutput::Loudness(results, _) => toasts.extend(self.apply_loudness(results)),
+                JobOutput::Instrumentals(results, _) => toasts.extend(self.apply_instrumentals(results)),
+                JobOutput::Waveforms(results, _) => toasts.extend(self.apply_waveforms(results)),
+                JobOutput::Genres(results, _) => toasts.extend(self.apply_genres(results)),
+                // 打开歌曲的任务不在任务列表中，由 update_opening 处理
+                JobOutput::Stream(stream, ..) => stream.close(),
+                JobOutput::Opened(_) => {}
             }
-            PlaybackState::Paused => {
-                // 暂停时返回保存的播放时间
-                self.current_play_time
+        }
+        for toast in toasts {
+            self.set_message(toast);
+        }
+    }
+    
+    /// 用重新扫描的结果替换曲库，按路径保留当前歌曲、光标和队列
+    fn replace_songs(&mut self, songs: Vec<Song>) {
+        let positions: HashMap<PathBuf, usize> = songs
+            .iter()
+            .enumerate()
+            .map(|(index, song)| (song.path.clone(), index))
+            .collect();
+        let old = std::mem::replace(&mut self.songs, songs);
+        let remap = |index: usize| old.get(index).and_then(|song| positions.get(&song.path).copied());
+        
+        self.current_index = remap(self.current_index).unwrap_or(0);
+        self.playlist_selected = remap(self.playlist_selected).unwrap_or(0);
+        self.queue = self.queue.iter().filter_map(|&index| remap(index)).collect();
+        self.shuffle_history = self.shuffle_history.iter().filter_map(|&index| remap(index)).collect();
+        self.queue_selected = self.queue_selected.min(self.queue.len().saturating_sub(1));
+        
+        self.import_tag_ratings();
+        if self.sort_order != SortOrder::Name {
+            self.apply_sort_order();
+        }
+    }
+    
+    pub fn toggle_jobs(&mut self) {
+        self.jobs_visible = !self.jobs_visible;
+        if !self.jobs_visible && self.focus == Focus::Jobs {
+            self.focus = Focus::Playlist;
+        }
+    }
+    
+    pub fn move_job_cursor(&mut self, offset: isize) {
+        if self.jobs.is_empty() {
+            return;
+        }
+        let last = self.jobs.len() as isize - 1;
+        self.job_selected = (self.job_selected as isize + offset).clamp(0, last) as usize;
+    }
+    
+    pub fn cancel_selected_job(&mut self) {
+        if let Some(job) = self.jobs.get(self.job_selected) {
+            job.cancel();
+        }
+    }
+    
+    /// 清除已结束的任务
+    pub fn clear_finished_jobs(&mut self) {
+        self.jobs.retain(|job| job.is_running());
+        self.job_selected = self.job_selected.min(self.jobs.len().saturating_sub(1));
+    }
+    
+    /// 从曲库中移除所有已经不存在的文件，从播放列表启动时同时写回播放列表文件
+    pub fn remove_missing(&mut self) {
+        for song in self.songs.iter_mut() {
+            song.missing = !http_stream::is_url(&song.path) && !song.path.exists();
+        }
+        let kept: Vec<Song> = self.songs.iter().filter(|song| !song.missing).cloned().collect();
+        let removed = self.songs.len() - kept.len();
+        if removed == 0 {
+            self.set_message("没有缺失的文件");
+            return;
+        }
+        
+        self.record_edit(format!("移除 {} 个缺失的条目", removed), true);
+        self.replace_songs(kept);
+        self.save_library_playlist(&[]);
+        self.set_message(format!("已移除 {} 个缺失的条目（u: 撤销）", removed));
+    }
+    
+    /// 从播放列表启动时，把曲库中删除和移动（moves）的条目写回播放列表文件
+    fn save_library_playlist(&mut self, moves: &[(PathBuf, PathBuf)]) {
+        let paths: Vec<PathBuf> = self.songs.iter().map(|song| song.path.clone()).collect();
+        let Some(watch) = self.playlist_watch.as_mut() else {
+            return;
+        };
+        if let Err(err) = watch.save(&paths, moves) {
+            self.set_message(format!("保存播放列表失败: {}", err));
+        }
+    }
+    
+    /// 询问在哪个目录中查找被移动的文件
+    pub fn start_relocate_prompt(&mut self) {
+        if !self.songs.iter().any(|song| song.missing) {
+            self.set_message("没有缺失的文件");
+            return;
+        }
+        self.prompt = Some(Prompt {
+            kind: PromptKind::RelocateDir,
+            input: "assets".to_string(),
+        });
+    }
+    
+    /// 在后台递归搜索目录，按文件名或标签（艺术家 + 标题）找回缺失的文件
+    fn start_relocation(&mut self, dir: PathBuf) {
+        let missing: Vec<(PathBuf, ExternalTrack)> = self
+            .songs
+            .iter()
+            .filter(|song| song.missing)
+            .map(|song| {
+                let track = ExternalTrack {
+                    artist: song.artist().unwrap_or_default(),
+                    title: song.title(),
+                };
+                (song.path.clone(), track)
+            })
+            .collect();
+        
+        let job = Job::spawn("查找移动的文件", move |progress| {
+            progress.set_status("搜索目录");
+            let mut paths = Vec::new();
+            scan_paths_recursive(&dir, 8, &mut paths);
+            
+            // 标签只在文件名匹配不到时才需要，读取一次后复用
+            let mut candidates: Option<Vec<Song>> = None;
+            let mut moves = Vec::new();
+            progress.set_total(missing.len());
+            for (old, track) in &missing {
+                progress.check_cancelled()?;
+                progress.set_status(old.file_name().unwrap_or_default().to_string_lossy());
+                
+                let by_name = paths.iter().find(|path| path.file_name() == old.file_name());
+                let found = match by_name {
+                    Some(path) => Some(path.clone()),
+                    None => {
+                        let library = candidates.get_or_insert_with(|| {
+                            let mut cache = MetadataCache::load();
+                            let songs = load_cached(paths.clone(), &mut cache, metadata_cache::worker_count(0));
+                            let _ = cache.save();
+                            songs
+                        });
+                        import::find_match(track, library).map(|song| song.path.clone())
+                    }
+                };
+                if let Some(new) = found {
+                    moves.push((old.clone(), new));
+                }
+                progress.advance();
             }
-            PlaybackState::Stopped => {
-                Duration::ZERO
+            Ok(JobOutput::Relocated(moves))
+        });
+        self.start_job(job);
+    }
+    
+    /// 把缺失的歌曲指向找到的新位置，书签、收藏集、BPM、收听统计和保存的播放列表一并迁移
+    fn apply_relocations(&mut self, moves: Vec<(PathBuf, PathBuf)>) {
+        if moves.is_empty() {
+            return;
+        }
+        
+        for (old, new) in &moves {
+            for song in self.songs.iter_mut().filter(|song| &song.path == old) {
+                *song = Song::from_path(new.clone());
+            }
+            for bookmark in self.bookmarks.iter_mut().filter(|bookmark| &bookmark.path == old) {
+                bookmark.path = new.clone();
+            }
+            for pin in self.collections.iter_mut().flat_map(|collection| collection.pins.iter_mut()) {
+                if *pin == Pin::Track(old.clone()) {
+                    *pin = Pin::Track(new.clone());
+                }
+            }
+            if let Some(bpm) = self.bpms.remove(old) {
+                self.bpms.insert(new.clone(), bpm);
+            }
+            if let Some(settings) = self.track_settings.remove(old) {
+                self.track_settings.insert(new.clone(), settings);
+            }
+            if let Some(loudness) = self.loudness.remove(old) {
+                self.loudness.insert(new.clone(), loudness);
+            }
+            if let Some(detection) = self.instrumentals.remove(old) {
+                self.instrumentals.insert(new.clone(), detection);
+            }
+            if let Some(waveform) = self.waveforms.remove(old) {
+                self.waveforms.insert(new.clone(), waveform);
+            }
+            if let Some(suggestion) = self.genre_suggestions.remove(old) {
+                self.genre_suggestions.insert(new.clone(), suggestion);
             }
+            self.stats.rename(old, new);
+            self.ratings.rename(old, new);
+            self.unavailable.remove(old);
         }
+        
+        self.save_bookmarks();
+        self.save_collections();
+        let saved = bpm::save(&self.bpms)
+            .and_then(|_| track_settings::save(&self.track_settings))
+            .and_then(|_| replaygain::save(&self.loudness))
+            .and_then(|_| instrumental::save(&self.instrumentals))
+            .and_then(|_| waveform::save(&self.waveforms))
+            .and_then(|_| genre::save(&self.genre_suggestions))
+            .and_then(|_| self.stats.save())
+            .and_then(|_| self.ratings.save());
+        if let Err(err) = saved.and_then(|_| playlists::rename_entries(&moves)) {
+            self.set_message(format!("保存数据失败: {}", err));
+        }
+        self.save_library_playlist(&moves);
     }
     
-    pub fn get_total_duration(&self) -> Duration {
-        if let Some(song) = self.get_current_song() {
-            song.duration.unwrap_or(Duration::from_secs(180)) // 默认3分钟
-        } else {
-            Duration::from_secs(180)
+    /// 处理粘贴的文本：每行一个文件路径、目录、file:// 或 http(s) 地址，加入播放队列
+    pub fn paste(&mut self, text: &str) {
+        let mut added = 0;
+        for line in text.lines() {
+            let entry = line.trim().trim_matches(|c| c == '"' || c == '\'');
+            if entry.is_empty() {
+                continue;
+            }
+            
+            if http_stream::is_url(Path::new(entry)) {
+                // 不是直接指向音频文件的地址（视频网站、播放列表页面）交给 yt-dlp 解析；
+                // 默认边下载边播放，配置了 download_urls 时先完整下载到缓存目录
+                let direct = is_audio_file(Path::new(&http_stream::url_file_name(entry)));
+                if !direct && !self.config.ytdlp.is_empty() {
+                    self.start_ytdlp(entry.to_string());
+                } else if self.config.download_urls {
+                    self.start_download(entry.to_string());
+                } else {
+                    self.enqueue_path(PathBuf::from(entry));
+                    added += 1;
+                }
+                continue;
+            }
+            
+            let path = match entry.strip_prefix("file://") {
+                Some(rest) => PathBuf::from(http::decode(rest)),
+                None => PathBuf::from(entry),
+            };
+            if path.is_dir() {
+                for file in scan_paths(&path).unwrap_or_default() {
+                    self.enqueue_path(file);
+                    added += 1;
+                }
+            } else if path.is_file() && is_audio_file(&path) {
+                self.enqueue_path(path);
+                added += 1;
+            } else {
+                self.set_message(format!("无法识别粘贴的内容: {}", entry));
+            }
+        }
+        
+        if added > 0 {
+            self.set_message(format!("已从粘贴内容加入队列 {} 首", added));
         }
     }
     
-    pub fn get_progress(&self) -> f32 {
-        let current_time = self.get_current_time();
-        let total_duration = self.get_total_duration();
+    /// 把文件加入播放队列，不在曲库中的文件先追加到曲库末尾
+    /// 按路径加入队列，不在曲库中的先加入曲库。范围之外的歌曲不加入队列，返回 false
+    fn enqueue_path(&mut self, path: PathBuf) -> bool {
+        let index = match self.songs.iter().position(|song| song.path == path) {
+            Some(index) => index,
+            None => {
+                self.songs.push(Song::from_path(path));
+                self.songs.len() - 1
+            }
+        };
+        if !self.in_scope(&self.songs[index]) {
+            return false;
+        }
+        self.queue.push_back(index);
+        true
+    }
+    
+    /// 在后台用 yt-dlp 解析视频或播放列表地址，完成后加入队列
+    fn start_ytdlp(&mut self, url: String) {
+        let program = self.config.ytdlp.clone();
+        let job = Job::spawn(format!("解析 {}", url), move |progress| {
+            ytdlp::resolve(&program, &url, progress).map(JobOutput::EnqueueSongs)
+        });
+        self.start_job(job);
+    }
+    
+    /// 在后台把网络上的音频文件下载到缓存目录，完成后加入队列
+    fn start_download(&mut self, url: String) {
+        let file_name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .map(http::decode)
+            .map(|name| storage::sanitize_file_name(&name))
+            .unwrap_or_default();
+        if !is_audio_file(Path::new(&file_name)) {
+            self.set_message(format!("只支持直接指向音频文件的地址: {}", url));
+            return;
+        }
+        
+        let dest = storage::cache_dir().join("downloads").join(&file_name);
+        let job = Job::spawn(format!("下载 {}", file_name), move |progress| {
+            progress.set_total(1);
+            progress.set_status(url.clone());
+            if let Some(dir) = dest.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            http::download(&url, &dest)?;
+            progress.advance();
+            Ok(JobOutput::Enqueue(vec![dest]))
+        });
+        self.start_job(job);
+    }
+    
+    /// 复制当前歌曲的路径，或者 "艺术家 - 标题"
+    pub fn copy_current(&mut self, path: bool) {
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        let text = if path {
+            song.path.to_string_lossy().to_string()
+        } else {
+            match song.artist() {
+                Some(artist) => format!("{} - {}", artist, song.title()),
+                None => song.title(),
+            }
+        };
         
-        if total_duration.as_secs() > 0 {
-            let progress = (current_time.as_secs_f64() / total_duration.as_secs_f64()).min(1.0).max(0.0) as f32;
-            return progress;
+        match clipboard::copy(&text) {
+            Ok(method) => self.set_message(format!("已复制 ({}): {}", method, text)),
+            Err(err) => self.set_message(format!("复制失败: {}", err)),
         }
-        0.0
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,878 @@
+use anyhow::{anyhow, bail, Result};
+use rodio::cpal::traits::{HostTrait, StreamTrait};
+use rodio::cpal::{self, BufferSize, FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig, SupportedBufferSize};
+use rodio::dynamic_mixer::{self, DynamicMixer, DynamicMixerController};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rodio::{DeviceTrait, Sink, Source, SupportedStreamConfig};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// 重采样质量。rodio 自带的是线性插值，在树莓派上开销最小；
+/// 其他两档在交给 rodio 之前先转换到输出采样率
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResamplerQuality {
+    /// rodio 的线性插值
+    Fast,
+    /// 四点三次插值
+    Medium,
+    /// Lanczos 窗函数的 sinc 插值
+    High,
+}
+
+impl ResamplerQuality {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "fast" | "linear" => Some(ResamplerQuality::Fast),
+            "medium" | "cubic" => Some(ResamplerQuality::Medium),
+            "high" | "sinc" => Some(ResamplerQuality::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResamplerQuality::Fast => "fast",
+            ResamplerQuality::Medium => "medium",
+            ResamplerQuality::High => "high",
+        }
+    }
+}
+
+/// 输出到 16 位及以下的整数格式时是否加抖动
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMode {
+    Off,
+    /// ±1 LSB 的三角分布噪声
+    Tpdf,
+}
+
+impl DitherMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "off" | "none" => Some(DitherMode::Off),
+            "tpdf" | "on" => Some(DitherMode::Tpdf),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DitherMode::Off => "off",
+            DitherMode::Tpdf => "tpdf",
+        }
+    }
+}
+
+/// 音频输出方式。PipeWire 和 JACK 通过它们的命令行客户端（pw-cat、jack-stdin）输出，
+/// 播放器作为独立的节点/客户端出现在音频路由图中
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputBackend {
+    /// rodio 默认设备（ALSA、CoreAudio、WASAPI）
+    Device,
+    PipeWire,
+    Jack,
+}
+
+impl OutputBackend {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "default" | "device" => Some(OutputBackend::Device),
+            "pipewire" => Some(OutputBackend::PipeWire),
+            "jack" => Some(OutputBackend::Jack),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputBackend::Device => "default",
+            OutputBackend::PipeWire => "pipewire",
+            OutputBackend::Jack => "jack",
+        }
+    }
+}
+
+/// pw-cat 在播放第一首歌之前使用的声道数
+const PROCESS_CHANNELS: u16 = 2;
+/// 每次写给外部输出程序的帧数
+const PROCESS_WRITE_FRAMES: usize = 256;
+
+/// 配置文件中的样本格式名称，如 f32、i16
+pub fn parse_sample_format(value: &str) -> Option<SampleFormat> {
+    let format = match value.to_lowercase().as_str() {
+        "i8" => SampleFormat::I8,
+        "i16" => SampleFormat::I16,
+        "i32" => SampleFormat::I32,
+        "i64" => SampleFormat::I64,
+        "u8" => SampleFormat::U8,
+        "u16" => SampleFormat::U16,
+        "u32" => SampleFormat::U32,
+        "u64" => SampleFormat::U64,
+        "f32" => SampleFormat::F32,
+        "f64" => SampleFormat::F64,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// 整数样本格式的位深，浮点格式返回 None
+fn integer_bits(format: SampleFormat) -> Option<u32> {
+    match format {
+        SampleFormat::F32 | SampleFormat::F64 => None,
+        format => Some(format.sample_size() as u32 * 8),
+    }
+}
+
+enum Backend {
+    /// 直接用 cpal 打开的输出流，各首歌的播放控制接到同一个混音器上
+    Device {
+        _stream: cpal::Stream,
+        mixer: Arc<DynamicMixerController<f32>>,
+    },
+    /// 混音结果写入外部程序的标准输入
+    Process {
+        kind: OutputBackend,
+        mixer: Arc<DynamicMixerController<f32>>,
+        child: Child,
+    },
+    /// 跟随歌曲切换采样率时，旧设备已经关闭、新设备还没打开
+    Closed,
+    /// 模拟模式：创建的播放控制不连接任何输出，歌曲不会被解码和播放
+    Silent,
+}
+
+/// 打开的音频输出
+pub struct AudioOutput {
+    backend: Backend,
+    /// 输出采样率，使用 rodio 默认设备选择时为 None
+    pub sample_rate: Option<u32>,
+    /// 整数输出格式的位深，浮点输出时为 None
+    pub bits: Option<u32>,
+    /// 输出设备的声道数，不知道时为 None
+    pub channels: Option<u16>,
+    /// 配置的输出格式无法使用、改用默认设置时的提示
+    pub warning: Option<String>,
+    /// 外部输出程序意外退出或输出流出错时的错误
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl AudioOutput {
+    /// 为一首歌创建播放控制
+    pub fn new_sink(&self) -> Result<Sink> {
+        match &self.backend {
+            Backend::Device { mixer, .. } | Backend::Process { mixer, .. } => {
+                let (sink, output) = Sink::new_idle();
+                mixer.add(output);
+                Ok(sink)
+            }
+            Backend::Closed => Err(anyhow!("音频设备无法打开")),
+            Backend::Silent => Ok(Sink::new_idle().0),
+        }
+    }
+
+    /// 不输出声音的音频输出，用于模拟模式
+    pub fn silent() -> Self {
+        AudioOutput {
+            backend: Backend::Silent,
+            sample_rate: None,
+            bits: None,
+            channels: None,
+            warning: None,
+            error: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 音频设备无法打开时的占位输出：仍然可以浏览曲库，重新打开设备之前不能播放
+    pub fn unavailable() -> Self {
+        AudioOutput {
+            backend: Backend::Closed,
+            sample_rate: None,
+            bits: None,
+            channels: None,
+            warning: None,
+            error: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 跟随歌曲采样率：重新打开输出设备，让采样率与歌曲一致，避免系统混音器或 rodio 重采样。
+    /// 设备不支持这个采样率时保持原样，返回 false
+    pub fn match_sample_rate(&mut self, rate: u32, config: &Config) -> bool {
+        if self.sample_rate == Some(rate) {
+            return true;
+        }
+        if !matches!(self.backend, Backend::Device { .. }) {
+            return false;
+        }
+        let Some(device) = find_device(&config.output_device) else {
+            return false;
+        };
+        let Ok(supported) = choose_config(&device, config, Some(rate)) else {
+            return false;
+        };
+        // 先关闭旧的输出，很多硬件设备同时只能打开一次
+        self.backend = Backend::Closed;
+        let bits = integer_bits(supported.sample_format());
+        match open_stream(&device, &supported, config.output_latency_ms, &self.error) {
+            Ok((_stream, mixer)) => {
+                self.backend = Backend::Device { _stream, mixer };
+                self.sample_rate = Some(rate);
+                self.bits = bits;
+                true
+            }
+            Err(_) => {
+                // 换回原来的采样率
+                if let Ok(supported) = choose_config(&device, config, self.sample_rate) {
+                    if let Ok((_stream, mixer)) = open_stream(&device, &supported, config.output_latency_ms, &self.error) {
+                        self.backend = Backend::Device { _stream, mixer };
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// PipeWire 输出：按歌曲的声道数重新启动 pw-cat，多声道文件不用混成立体声。
+    /// 其他输出方式的声道数不变（JACK 由 jack_ports 的端口数决定）。返回输出的声道数是否已经一致
+    pub fn match_channels(&mut self, channels: u16, config: &Config) -> bool {
+        if self.channels == Some(channels) {
+            return true;
+        }
+        if !matches!(self.backend, Backend::Process { kind: OutputBackend::PipeWire, .. }) {
+            return false;
+        }
+        match open_process(OutputBackend::PipeWire, config, channels) {
+            Ok(output) => {
+                *self = output;
+                true
+            }
+            Err(err) => {
+                if let Ok(mut error) = self.error.lock() {
+                    *error = Some(format!("无法按 {} 声道重新启动 pw-cat: {}", channels, err));
+                }
+                false
+            }
+        }
+    }
+
+    /// 外部输出程序退出的错误，只返回一次
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().ok()?.take()
+    }
+}
+
+impl Drop for AudioOutput {
+    fn drop(&mut self) {
+        if let Backend::Process { child, .. } = &mut self.backend {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// 按配置打开音频输出。PipeWire/JACK 客户端无法启动时改用默认设备
+pub fn open(config: &Config) -> Result<AudioOutput> {
+    let warning = match config.output_backend {
+        OutputBackend::Device => None,
+        backend => match open_process(backend, config, PROCESS_CHANNELS) {
+            Ok(output) => return Ok(output),
+            Err(err) => Some(format!("{}，已使用默认音频设备", err)),
+        },
+    };
+    let mut output = open_device(config)?;
+    if output.warning.is_none() {
+        output.warning = warning;
+    }
+    Ok(output)
+}
+
+/// 按配置的采样率、样本格式和延迟打开默认输出设备
+fn open_device(config: &Config) -> Result<AudioOutput> {
+    let error = Arc::default();
+    let custom = config.output_sample_rate != 0 || !config.output_sample_format.is_empty();
+
+    let mut warning = None;
+    let device = find_device(&config.output_device).or_else(|| {
+        warning = Some(format!("找不到音频设备 {}，已使用默认设备", config.output_device));
+        cpal::default_host().default_output_device()
+    });
+    if let Some(device) = device {
+        match choose_config(&device, config, None) {
+            Ok(supported) => {
+                let sample_rate = supported.sample_rate().0;
+                let bits = integer_bits(supported.sample_format());
+                let channels = supported.channels();
+                match open_stream(&device, &supported, config.output_latency_ms, &error) {
+                    Ok((_stream, mixer)) => {
+                        return Ok(AudioOutput {
+                            backend: Backend::Device { _stream, mixer },
+                            sample_rate: Some(sample_rate),
+                            bits,
+                            channels: Some(channels),
+                            warning,
+                            error,
+                        });
+                    }
+                    Err(err) if custom => warning = Some(format!("无法按配置打开音频输出: {}，已使用默认设置", err)),
+                    Err(_) => {}
+                }
+            }
+            Err(err) if custom => warning = Some(format!("{}，已使用默认设置", err)),
+            Err(_) => {}
+        }
+    }
+
+    // 按默认设备的默认格式打开
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| anyhow!("找不到音频输出设备"))?;
+    let supported = device.default_output_config()?;
+    let (_stream, mixer) = open_stream(&device, &supported, config.output_latency_ms, &error)?;
+    Ok(AudioOutput {
+        backend: Backend::Device { _stream, mixer },
+        sample_rate: None,
+        bits: integer_bits(supported.sample_format()),
+        channels: Some(supported.channels()),
+        warning,
+        error,
+    })
+}
+
+/// 启动 pw-cat 或 jack-stdin，在后台线程中把混音结果写入它的标准输入。
+/// pw-cat 按 channels 个声道输出；jack-stdin 每个声道对应 jack_ports 中的一个端口
+fn open_process(backend: OutputBackend, config: &Config, channels: u16) -> Result<AudioOutput> {
+    let mut warning = None;
+    let (mut command, sample_rate, channels, encoding) = match backend {
+        OutputBackend::Jack => {
+            // JACK 服务器以固定采样率运行，jack-stdin 不做转换
+            let rate = match config.output_sample_rate {
+                0 => jack_sample_rate().unwrap_or_else(|| {
+                    warning = Some("无法获取 JACK 采样率，按 48000 Hz 输出".to_string());
+                    48000
+                }),
+                rate => rate,
+            };
+            if config.jack_ports.is_empty() {
+                bail!("jack_ports 中没有输出端口");
+            }
+            // jack-stdin 没有设置客户端名的选项，JACK 中总是显示为 jack-stdin
+            if config.output_client_name != Config::default().output_client_name {
+                warning = Some("jack-stdin 不支持自定义客户端名，output_client_name 只用于 PipeWire".to_string());
+            }
+            let mut command = Command::new("jack-stdin");
+            command.args(["-b", "24", "-L"]).args(&config.jack_ports);
+            (command, rate, config.jack_ports.len() as u16, Encoding::S24)
+        }
+        _ => {
+            let rate = match config.output_sample_rate {
+                0 => 48000,
+                rate => rate,
+            };
+            let mut command = Command::new("pw-cat");
+            command
+                .args(["--playback", "--raw", "--format", "f32", "--media-role", "Music"])
+                .args(["--rate", &rate.to_string(), "--channels", &channels.to_string()])
+                .arg("--properties")
+                .arg(format!(
+                    "{{ node.name = \"{0}\" node.description = \"{0}\" application.name = \"{0}\" }}",
+                    config.output_client_name
+                ));
+            if config.output_latency_ms > 0 {
+                command.arg("--latency").arg(format!("{}ms", config.output_latency_ms));
+            }
+            command.arg("-");
+            (command, rate, channels, Encoding::F32)
+        }
+    };
+
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| anyhow!("无法启动 {}: {}", program, err))?;
+    let stdin = child.stdin.take().ok_or_else(|| anyhow!("无法写入 {}", program))?;
+
+    let (mixer, output) = dynamic_mixer::mixer::<f32>(channels, sample_rate);
+    let error = Arc::new(Mutex::new(None));
+    let thread_error = error.clone();
+    thread::spawn(move || {
+        if let Err(err) = write_samples(output, stdin, channels, encoding) {
+            if let Ok(mut error) = thread_error.lock() {
+                *error = Some(format!("{} 已退出: {}", program, err));
+            }
+        }
+    });
+
+    let bits = match encoding {
+        Encoding::F32 => None,
+        Encoding::S24 => Some(24),
+    };
+    Ok(AudioOutput {
+        backend: Backend::Process { kind: backend, mixer, child },
+        sample_rate: Some(sample_rate),
+        bits,
+        channels: Some(channels),
+        warning,
+        error,
+    })
+}
+
+/// 写给外部程序的样本编码
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    /// 32 位浮点，小端
+    F32,
+    /// 24 位有符号整数（3 字节），小端
+    S24,
+}
+
+/// 不断把混音结果写给外部程序，由管道的阻塞控制速度；没有歌曲在播放时写入静音
+fn write_samples(mut mixer: DynamicMixer<f32>, mut stdin: ChildStdin, channels: u16, encoding: Encoding) -> std::io::Result<()> {
+    let samples = PROCESS_WRITE_FRAMES * channels as usize;
+    let mut bytes = Vec::with_capacity(samples * 4);
+    loop {
+        bytes.clear();
+        for _ in 0..samples {
+            let sample = mixer.next().unwrap_or(0.0).clamp(-1.0, 1.0);
+            match encoding {
+                Encoding::F32 => bytes.extend(sample.to_le_bytes()),
+                Encoding::S24 => {
+                    let value = (sample * 8_388_607.0) as i32;
+                    bytes.extend(&value.to_le_bytes()[..3]);
+                }
+            }
+        }
+        stdin.write_all(&bytes)?;
+    }
+}
+
+/// 用 jack_samplerate 查询 JACK 服务器的采样率
+fn jack_sample_rate() -> Option<u32> {
+    let output = Command::new("jack_samplerate").stderr(Stdio::null()).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// 打开设备的输出流。latency_ms 不为 0 时按它设置缓冲区的帧数（超出设备支持的范围时取最近的值），
+/// 设备不接受固定大小的缓冲区时改用默认大小
+fn open_stream(
+    device: &cpal::Device,
+    supported: &SupportedStreamConfig,
+    latency_ms: u32,
+    error: &Arc<Mutex<Option<String>>>,
+) -> Result<(cpal::Stream, Arc<DynamicMixerController<f32>>)> {
+    let mut config = supported.config();
+    if latency_ms > 0 {
+        let frames = (supported.sample_rate().0 as u64 * latency_ms as u64 / 1000).max(1) as u32;
+        let frames = match supported.buffer_size() {
+            SupportedBufferSize::Range { min, max } => frames.clamp(*min, (*max).max(*min)),
+            SupportedBufferSize::Unknown => frames,
+        };
+        config.buffer_size = BufferSize::Fixed(frames);
+        if let Ok(stream) = build_stream(device, &config, supported.sample_format(), error) {
+            return Ok(stream);
+        }
+        config.buffer_size = BufferSize::Default;
+    }
+    build_stream(device, &config, supported.sample_format(), error)
+}
+
+/// 按样本格式建立输出流，回调里从混音器取样本；出错时记下错误，由界面显示
+fn build_stream(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    format: SampleFormat,
+    error: &Arc<Mutex<Option<String>>>,
+) -> Result<(cpal::Stream, Arc<DynamicMixerController<f32>>)> {
+    fn build<T: SizedSample + FromSample<f32>>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        mut mixer: DynamicMixer<f32>,
+        error: Arc<Mutex<Option<String>>>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError> {
+        device.build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                for sample in data.iter_mut() {
+                    *sample = T::from_sample(mixer.next().unwrap_or(0.0));
+                }
+            },
+            move |err| {
+                if let Ok(mut error) = error.lock() {
+                    *error = Some(format!("音频输出出错: {}", err));
+                }
+            },
+            None,
+        )
+    }
+
+    let (controller, mixer) = dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate.0);
+    let error = error.clone();
+    let stream = match format {
+        SampleFormat::I8 => build::<i8>(device, config, mixer, error),
+        SampleFormat::I16 => build::<i16>(device, config, mixer, error),
+        SampleFormat::I32 => build::<i32>(device, config, mixer, error),
+        SampleFormat::I64 => build::<i64>(device, config, mixer, error),
+        SampleFormat::U8 => build::<u8>(device, config, mixer, error),
+        SampleFormat::U16 => build::<u16>(device, config, mixer, error),
+        SampleFormat::U32 => build::<u32>(device, config, mixer, error),
+        SampleFormat::U64 => build::<u64>(device, config, mixer, error),
+        SampleFormat::F32 => build::<f32>(device, config, mixer, error),
+        SampleFormat::F64 => build::<f64>(device, config, mixer, error),
+        format => bail!("不支持的样本格式 {}", format),
+    }?;
+    stream.play()?;
+    Ok((stream, controller))
+}
+
+/// `music_tui devices`：列出输出设备和支持的采样率、样本格式，名称可以填到 output_device
+pub fn run_devices() -> Result<()> {
+    let host = cpal::default_host();
+    let default = host.default_output_device().and_then(|device| device.name().ok());
+    for device in host.output_devices()? {
+        let name = device.name().unwrap_or_else(|_| "未知设备".to_string());
+        let mark = if default.as_ref() == Some(&name) { " (默认)" } else { "" };
+        println!("{}{}", name, mark);
+        let Ok(configs) = device.supported_output_configs() else {
+            continue;
+        };
+        for config in configs {
+            println!(
+                "    {} 声道  {}-{} Hz  {}",
+                config.channels(),
+                config.min_sample_rate().0,
+                config.max_sample_rate().0,
+                config.sample_format()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 按名称查找输出设备，名称为空时使用默认设备
+fn find_device(name: &str) -> Option<cpal::Device> {
+    let host = cpal::default_host();
+    if name.is_empty() {
+        return host.default_output_device();
+    }
+    host.output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+/// 按配置选择输出格式，rate 不为 None 时使用这个采样率（match_sample_rate 开启时跟随歌曲）
+fn choose_config(device: &cpal::Device, config: &Config, rate: Option<u32>) -> Result<SupportedStreamConfig> {
+    let default = device.default_output_config()?;
+    let format = parse_sample_format(&config.output_sample_format);
+    let rate = rate.or((config.output_sample_rate != 0).then_some(config.output_sample_rate));
+    if rate.is_none() && format.is_none() {
+        return Ok(default);
+    }
+
+    let rate = rate.unwrap_or(default.sample_rate().0);
+    let format = format.unwrap_or(default.sample_format());
+    device
+        .supported_output_configs()?
+        .filter(|range| range.channels() == default.channels() && range.sample_format() == format)
+        .find_map(|range| range.try_with_sample_rate(SampleRate(rate)))
+        .ok_or_else(|| anyhow!("音频设备不支持 {} Hz / {}", rate, format))
+}
+
+/// 在后台线程中把音量逐渐降到 0 后停止，手动切歌时不会突然断掉。新歌曲同时开始播放。
+/// 至少分 10 步，较长的淡出（自动混音）每 20 毫秒调整一次，听不出台阶
+pub fn fade_out(sink: Sink, length: Duration) {
+    let steps = (length.as_millis() / 20).max(10) as u32;
+    thread::spawn(move || {
+        let volume = sink.volume();
+        for step in 1..=steps {
+            thread::sleep(length / steps);
+            sink.set_volume(volume * (steps - step) as f32 / steps as f32);
+        }
+        sink.stop();
+    });
+}
+
+/// 按配置的质量把音频转换到输出采样率；快速模式或采样率相同时原样返回，交给 rodio 处理
+pub fn resample(
+    source: Box<dyn Source<Item = f32> + Send>,
+    output_rate: Option<u32>,
+    quality: ResamplerQuality,
+) -> Box<dyn Source<Item = f32> + Send> {
+    match output_rate {
+        Some(rate) if quality != ResamplerQuality::Fast && rate != source.sample_rate() => {
+            Box::new(Resampler::new(source, rate, quality))
+        }
+        _ => source,
+    }
+}
+
+/// 在转换成整数样本之前加上 ±1 LSB 的 TPDF 抖动，把截断误差变成与信号无关的均匀底噪，
+/// 淡出和很安静的段落不会出现量化失真。bits 为 None 时原样输出
+pub struct Dither<S> {
+    input: S,
+    /// 输出格式的最低有效位对应的幅度
+    lsb: f32,
+    rng: StdRng,
+}
+
+impl<S> Dither<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, bits: Option<u32>) -> Self {
+        let lsb = bits.map(|bits| 1.0 / (1u64 << (bits.clamp(2, 32) - 1)) as f32).unwrap_or(0.0);
+        Dither {
+            input,
+            lsb,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl<S> Iterator for Dither<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.lsb == 0.0 {
+            return Some(sample);
+        }
+        // 两个均匀分布之差是 -1 到 1 之间的三角分布
+        let noise = self.rng.gen::<f32>() - self.rng.gen::<f32>();
+        Some(sample + noise * self.lsb)
+    }
+}
+
+impl<S> Source for Dither<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Lanczos 窗的半径（输入帧数）
+const LANCZOS_RADIUS: f64 = 8.0;
+
+/// 把音频转换到指定采样率的 Source 适配器
+pub struct Resampler<S> {
+    input: S,
+    quality: ResamplerQuality,
+    channels: usize,
+    output_rate: u32,
+    /// 每个输出帧前进的输入帧数
+    step: f64,
+    /// 下采样时降低截止频率，避免混叠
+    cutoff: f64,
+    /// 插值时在当前位置前后各需要的输入帧数
+    radius: usize,
+    /// 缓存的输入帧（交错存放），第一帧的序号是 base
+    frames: VecDeque<f32>,
+    base: u64,
+    input_ended: bool,
+    /// 下一个输出帧的序号
+    output_frame: u64,
+    current: Vec<f32>,
+    channel: usize,
+    weights: Vec<f64>,
+}
+
+impl<S> Resampler<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, output_rate: u32, quality: ResamplerQuality) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let step = input.sample_rate().max(1) as f64 / output_rate.max(1) as f64;
+        let cutoff = (1.0 / step).min(1.0);
+        let radius = match quality {
+            ResamplerQuality::High => (LANCZOS_RADIUS / cutoff).ceil() as usize,
+            _ => 2,
+        };
+        Resampler {
+            input,
+            quality,
+            channels,
+            output_rate,
+            step,
+            cutoff,
+            radius,
+            frames: VecDeque::new(),
+            base: 0,
+            input_ended: false,
+            output_frame: 0,
+            current: Vec::new(),
+            channel: 0,
+            weights: Vec::new(),
+        }
+    }
+
+    fn buffered_frames(&self) -> u64 {
+        (self.frames.len() / self.channels) as u64
+    }
+
+    /// 读取输入直到缓存中有序号 end 之前的所有帧
+    fn fill_until(&mut self, end: u64) {
+        while !self.input_ended && self.base + self.buffered_frames() < end {
+            for _ in 0..self.channels {
+                match self.input.next() {
+                    Some(sample) => self.frames.push_back(sample),
+                    None => {
+                        // 不完整的最后一帧丢弃
+                        let partial = self.frames.len() % self.channels;
+                        self.frames.truncate(self.frames.len() - partial);
+                        self.input_ended = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn sample(&self, frame: i64, channel: usize) -> f32 {
+        if frame < self.base as i64 {
+            return 0.0;
+        }
+        let index = (frame as u64 - self.base) as usize * self.channels + channel;
+        self.frames.get(index).copied().unwrap_or(0.0)
+    }
+
+    /// 计算下一个输出帧，输入已经全部用完时返回 false
+    fn next_frame(&mut self) -> bool {
+        let position = self.output_frame as f64 * self.step;
+        let center = position.floor() as i64;
+        self.fill_until((center + self.radius as i64 + 1).max(0) as u64);
+        if self.input_ended && center >= (self.base + self.buffered_frames()) as i64 {
+            return false;
+        }
+
+        // 丢弃不再需要的旧帧
+        let keep_from = (center - self.radius as i64).max(0) as u64;
+        while self.base < keep_from && !self.frames.is_empty() {
+            self.frames.drain(..self.channels);
+            self.base += 1;
+        }
+
+        let first = center - self.radius as i64 + 1;
+        self.weights.clear();
+        for tap in 0..self.radius * 2 {
+            let x = (first + tap as i64) as f64 - position;
+            self.weights.push(self.kernel(x));
+        }
+        if self.quality == ResamplerQuality::High {
+            // 归一化，避免直流分量随插值位置波动
+            let sum: f64 = self.weights.iter().sum();
+            if sum.abs() > 1e-9 {
+                self.weights.iter_mut().for_each(|weight| *weight /= sum);
+            }
+        }
+        self.current.clear();
+        for channel in 0..self.channels {
+            let value: f64 = self
+                .weights
+                .iter()
+                .enumerate()
+                .map(|(tap, weight)| self.sample(first + tap as i64, channel) as f64 * weight)
+                .sum();
+            self.current.push(value as f32);
+        }
+        self.output_frame += 1;
+        true
+    }
+
+    /// 距离插值位置 x 个输入帧的样本的权重
+    fn kernel(&self, x: f64) -> f64 {
+        match self.quality {
+            ResamplerQuality::High => {
+                let scaled = x * self.cutoff;
+                if scaled.abs() >= LANCZOS_RADIUS {
+                    return 0.0;
+                }
+                self.cutoff * sinc(scaled) * sinc(scaled / LANCZOS_RADIUS)
+            }
+            // Catmull-Rom 三次插值
+            _ => {
+                let x = x.abs();
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl<S> Iterator for Resampler<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == self.current.len() {
+            if !self.next_frame() {
+                return None;
+            }
+            self.channel = 0;
+        }
+        let sample = self.current[self.channel];
+        self.channel += 1;
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+impl<S> Source for Resampler<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -0,0 +1,276 @@
+use rodio::Source;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::streaming::StreamingDecoder;
+
+/// 分析结尾时比重叠部分多看的秒数，用来找到节拍的相位
+const TAIL_SECONDS: u64 = 20;
+const HOP_SIZE: usize = 512;
+/// 低于 -45 dBFS 的段落算作静音
+const SILENCE_RMS: f32 = 0.0056;
+/// 新歌曲开头跳过的静音最多这么多秒，避免把很安静的前奏整段跳过
+const MAX_LEADING_SILENCE: f32 = 10.0;
+/// 新歌曲开头低于 -50 dBFS 的样本算作静音
+const SILENCE_SAMPLE: f32 = 0.003;
+/// 不知道 BPM 时按 120 BPM 计算重叠时长
+const DEFAULT_BPM: f32 = 120.0;
+
+/// 重叠 beats 拍的时长，按旧歌曲的速度计算
+pub fn overlap(bpm: Option<f32>, beats: u32) -> Duration {
+    Duration::from_secs_f32(beats as f32 * 60.0 / bpm.unwrap_or(DEFAULT_BPM))
+}
+
+/// 新歌曲要对上旧歌曲的速度需要的播放速度，差别超过 tolerance（百分比）时不调整
+pub fn tempo_ratio(from: Option<f32>, to: Option<f32>, tolerance: f32) -> Option<f32> {
+    let ratio = from? / to?;
+    ((ratio - 1.0).abs() * 100.0 <= tolerance).then_some(ratio)
+}
+
+/// 在后台分析结尾，返回开始重叠的位置
+pub fn spawn_analysis(path: PathBuf, total: Duration, bpm: Option<f32>, overlap: Duration) -> Receiver<Option<Duration>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(mix_point(&path, total, bpm, overlap));
+    });
+    rx
+}
+
+/// 找到结尾的静音之前、落在节拍上的重叠起点，这样新歌曲的第一拍和旧歌曲的节拍对齐，
+/// 曲间也不会留下静音。不知道 BPM 时只避开结尾的静音
+fn mix_point(path: &Path, total: Duration, bpm: Option<f32>, overlap: Duration) -> Option<Duration> {
+    let from = total.saturating_sub(overlap + Duration::from_secs(TAIL_SECONDS));
+    let file = fs::File::open(path).ok()?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, from).ok()?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate().max(1) as f32;
+    let samples: Vec<f32> = decoder.collect();
+
+    let energies: Vec<f32> = samples
+        .chunks(HOP_SIZE * channels)
+        .map(|chunk| chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32)
+        .collect();
+    let last = energies.iter().rposition(|energy| *energy > SILENCE_RMS * SILENCE_RMS)?;
+    let hop_seconds = HOP_SIZE as f32 / sample_rate;
+    let audio_end = from + Duration::from_secs_f32((last + 1) as f32 * hop_seconds);
+    let fallback = audio_end.checked_sub(overlap);
+    let Some(bpm) = bpm else {
+        return fallback;
+    };
+
+    // 起音包络在哪个相位上和节拍周期最吻合，onsets[i] 是进入第 i 个 hop 时的能量上升
+    let onsets: Vec<f32> = std::iter::once(0.0)
+        .chain(energies[..=last].windows(2).map(|pair| (pair[1].sqrt() - pair[0].sqrt()).max(0.0)))
+        .collect();
+    let period = 60.0 / bpm / hop_seconds;
+    let score = |phase: f32| -> f32 {
+        (0..)
+            .map(|beat| (phase + beat as f32 * period).round() as usize)
+            .take_while(|&index| index < onsets.len())
+            .map(|index| onsets[index])
+            .sum()
+    };
+    let phase = (0..period.ceil() as usize)
+        .map(|phase| phase as f32)
+        .max_by(|a, b| score(*a).total_cmp(&score(*b)))?;
+
+    // 最后一个重叠完仍在静音之前的节拍
+    let latest = audio_end.checked_sub(overlap)?.saturating_sub(from).as_secs_f32() / hop_seconds;
+    if latest < phase {
+        return fallback;
+    }
+    let beat = ((latest - phase) / period).floor();
+    Some(from + Duration::from_secs_f32((phase + beat * period) * hop_seconds))
+}
+
+/// 跳过开头静音的 Source 适配器，让新歌曲的第一个声音立即出现
+pub struct SkipSilence<S> {
+    input: S,
+    skipped: bool,
+    /// 第一帧有声音的样本，倒序存放
+    pending: Vec<f32>,
+}
+
+impl<S> SkipSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S) -> Self {
+        SkipSilence {
+            input,
+            skipped: false,
+            pending: Vec::new(),
+        }
+    }
+
+    fn skip(&mut self) {
+        self.skipped = true;
+        let channels = self.input.channels().max(1) as usize;
+        let frames = (self.input.sample_rate() as f32 * MAX_LEADING_SILENCE) as usize;
+        // 按整帧跳过，声道不会错位
+        for _ in 0..frames {
+            let frame: Vec<f32> = self.input.by_ref().take(channels).collect();
+            if frame.len() < channels {
+                return;
+            }
+            if frame.iter().any(|sample| sample.abs() > SILENCE_SAMPLE) {
+                self.pending = frame.into_iter().rev().collect();
+                return;
+            }
+        }
+    }
+}
+
+impl<S> Iterator for SkipSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.skipped {
+            self.skip();
+        }
+        self.pending.pop().or_else(|| self.input.next())
+    }
+}
+
+impl<S> Source for SkipSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// 改变播放速度的 Source 适配器（和调整唱机转速一样，音高随之变化）：
+/// 重叠期间保持 ratio 的速度，之后在同样长的时间里慢慢回到原速
+pub struct TempoRamp<S> {
+    input: S,
+    channels: usize,
+    start_ratio: f64,
+    /// 保持初始速度的输出帧数
+    hold: u64,
+    /// 回到原速用的输出帧数
+    glide: u64,
+    frame: u64,
+    previous: Vec<f32>,
+    current: Vec<f32>,
+    /// 输出位置在 previous 和 current 之间的比例
+    position: f64,
+    output: Vec<f32>,
+    output_index: usize,
+}
+
+impl<S> TempoRamp<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, ratio: f32, hold: Duration) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let frames = (hold.as_secs_f64() * input.sample_rate() as f64) as u64;
+        TempoRamp {
+            input,
+            channels,
+            start_ratio: ratio as f64,
+            hold: frames,
+            glide: frames,
+            frame: 0,
+            previous: Vec::new(),
+            current: Vec::new(),
+            // 第一次输出前先读入两帧
+            position: 2.0,
+            output: Vec::new(),
+            output_index: 0,
+        }
+    }
+
+    fn ratio(&self) -> f64 {
+        if self.frame < self.hold {
+            return self.start_ratio;
+        }
+        let progress = (self.frame - self.hold) as f64 / self.glide.max(1) as f64;
+        self.start_ratio + (1.0 - self.start_ratio) * progress.min(1.0)
+    }
+
+    fn read_frame(&mut self) -> bool {
+        let frame: Vec<f32> = self.input.by_ref().take(self.channels).collect();
+        if frame.len() < self.channels {
+            return false;
+        }
+        self.previous = std::mem::replace(&mut self.current, frame);
+        true
+    }
+}
+
+impl<S> Iterator for TempoRamp<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.output_index < self.output.len() {
+            self.output_index += 1;
+            return Some(self.output[self.output_index - 1]);
+        }
+        // 速度回到原速后不再插值，按原样输出
+        if self.frame >= self.hold + self.glide && self.position.fract() != 0.0 {
+            self.position = self.position.ceil();
+        }
+        while self.position >= 1.0 {
+            if !self.read_frame() {
+                return None;
+            }
+            self.position -= 1.0;
+        }
+        let fraction = self.position as f32;
+        self.output = self
+            .previous
+            .iter()
+            .zip(&self.current)
+            .map(|(previous, current)| previous + (current - previous) * fraction)
+            .collect();
+        self.position += self.ratio();
+        self.frame += 1;
+        self.output_index = 1;
+        self.output.first().copied()
+    }
+}
+
+impl<S> Source for TempoRamp<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
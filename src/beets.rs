@@ -0,0 +1,136 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::json::Value;
+use crate::metadata::{self, ProbeResult, Tags};
+use crate::sqlite::{self, number, text};
+use crate::storage;
+
+const ITEM_QUERY: &str = "SELECT i.id, CAST(i.path AS TEXT) AS path, i.title, i.artist, i.album, i.albumartist, \
+                          i.genre, i.track, i.comp, i.length, i.samplerate, i.bitrate, i.bitdepth, i.format, \
+                          i.rg_track_gain, i.mb_trackid, CAST(a.artpath AS TEXT) AS artpath, \
+                          (SELECT CAST(f.value AS TEXT) FROM item_attributes f WHERE f.entity_id = i.id \
+                           AND f.key IN ('itunesadvisory', 'explicit') LIMIT 1) AS advisory \
+                          FROM items i LEFT JOIN albums a ON a.id = i.album_id";
+
+/// beets 曲库中的一首歌
+#[derive(Debug, Clone)]
+pub struct BeetsItem {
+    pub id: i64,
+    /// 由 beets 的标签和音频信息组成，代替探测文件的结果
+    pub probe: ProbeResult,
+    /// 专辑封面（fetchart 插件下载的图片）
+    pub art: Option<PathBuf>,
+    pub mb_trackid: Option<String>,
+}
+
+/// 配置 `beets_library` 后读取的 beets 曲库，按文件的绝对路径查找
+#[derive(Debug)]
+pub struct BeetsLibrary {
+    db: PathBuf,
+    items: HashMap<PathBuf, BeetsItem>,
+}
+
+impl BeetsLibrary {
+    pub fn load(db: &Path) -> Result<Self> {
+        let items = sqlite::query(db, ITEM_QUERY)?
+            .iter()
+            .map(|row| (PathBuf::from(text(row, "path")), parse_item(row)))
+            .collect();
+        Ok(BeetsLibrary {
+            db: db.to_path_buf(),
+            items,
+        })
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&BeetsItem> {
+        let path = storage::canonicalize(path).ok()?;
+        self.items.get(&path)
+    }
+
+    /// 在 beets 中修改标签后重新读取这一首
+    pub fn reload(&mut self, path: &Path) -> Result<Option<&BeetsItem>> {
+        let path = storage::canonicalize(path)?;
+        let sql = format!(
+            "{} WHERE CAST(i.path AS TEXT) = {}",
+            ITEM_QUERY,
+            sqlite::quote(&path.to_string_lossy())
+        );
+        match sqlite::query(&self.db, &sql)?.first() {
+            Some(row) => {
+                self.items.insert(path.clone(), parse_item(row));
+                Ok(self.items.get(&path))
+            }
+            None => {
+                self.items.remove(&path);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_item(row: &Value) -> BeetsItem {
+    let optional = |key: &str| Some(text(row, key)).filter(|value| !value.trim().is_empty());
+    // beets 对没有的数值字段存 0
+    let positive = |key: &str| number(row, key).filter(|value| *value > 0.0);
+
+    let tags = Tags {
+        title: optional("title"),
+        artist: optional("artist"),
+        album: optional("album"),
+        album_artist: optional("albumartist"),
+        genre: optional("genre"),
+        track_number: positive("track").map(|track| track as u32),
+        rating: None,
+        replaygain_db: number(row, "rg_track_gain").filter(|gain| *gain != 0.0).map(|gain| gain as f32),
+        compilation: number(row, "comp").is_some_and(|comp| comp != 0.0),
+        // beets 没有内置的分级字段，读取 `beet modify explicit=1` 这样的自定义字段
+        explicit: metadata::is_explicit_value(&text(row, "advisory")),
+    };
+    let probe = ProbeResult {
+        duration: positive("length").map(Duration::from_secs_f64),
+        sample_rate: positive("samplerate").map(|rate| rate as u32),
+        codec: optional("format").map(|format| codec_name(&format)),
+        bits_per_sample: positive("bitdepth").map(|bits| bits as u32),
+        bitrate_kbps: positive("bitrate").map(|bps| (bps / 1000.0).round() as u32),
+        tags,
+    };
+    BeetsItem {
+        id: number(row, "id").unwrap_or_default() as i64,
+        probe,
+        art: optional("artpath").map(PathBuf::from),
+        mb_trackid: optional("mb_trackid"),
+    }
+}
+
+/// beets 的格式名换成和探测结果一致的编码名
+fn codec_name(format: &str) -> String {
+    match format.to_uppercase().as_str() {
+        "OGG" => "VORBIS".to_string(),
+        "WAV" | "AIFF" => "PCM".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// 详情弹窗中显示的 beets 信息
+pub fn details(item: &BeetsItem) -> Vec<(String, String)> {
+    let mut lines = vec![("ID".to_string(), item.id.to_string())];
+    if let Some(art) = &item.art {
+        lines.push(("封面".to_string(), art.display().to_string()));
+    }
+    if let Some(id) = &item.mb_trackid {
+        lines.push(("MusicBrainz".to_string(), id.clone()));
+    }
+    lines
+}
+
+/// 用 beets 的 edit 插件修改这首歌的标签（在 $EDITOR 中编辑 YAML）
+pub fn edit_command(program: &str, path: &Path) -> Command {
+    let path = storage::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut command = Command::new(program);
+    command.arg("edit").arg(format!("path:{}", path.display()));
+    command
+}
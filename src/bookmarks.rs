@@ -0,0 +1,45 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::storage;
+
+const BOOKMARKS_FILE: &str = "bookmarks.tsv";
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub path: PathBuf,
+    pub position: Duration,
+}
+
+pub fn load() -> Vec<Bookmark> {
+    let records = storage::read_records(BOOKMARKS_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 名称 \t 毫秒位置 \t 文件路径
+            let [name, position, path] = <[String; 3]>::try_from(record).ok()?;
+            let position = position.parse::<u64>().ok()?;
+            Some(Bookmark {
+                name,
+                path: storage::path_from_field(&path),
+                position: Duration::from_millis(position),
+            })
+        })
+        .collect()
+}
+
+pub fn save(bookmarks: &[Bookmark]) -> Result<()> {
+    let records: Vec<Vec<String>> = bookmarks
+        .iter()
+        .map(|bookmark| {
+            vec![
+                bookmark.name.clone(),
+                bookmark.position.as_millis().to_string(),
+                storage::path_to_field(&bookmark.path),
+            ]
+        })
+        .collect();
+    storage::write_records(BOOKMARKS_FILE, &records)
+}
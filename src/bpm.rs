@@ -0,0 +1,132 @@
+use anyhow::Result;
+use rodio::Source;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app::scan_songs;
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+
+const BPM_FILE: &str = "bpm.tsv";
+
+/// 只分析开头的这段时间，足够得到稳定的速度
+const ANALYSIS_SECONDS: u32 = 90;
+const HOP_SIZE: usize = 512;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+
+pub fn load() -> HashMap<PathBuf, f32> {
+    let records = storage::read_records(BPM_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: BPM \t 文件路径
+            let [bpm, path] = <[String; 2]>::try_from(record).ok()?;
+            Some((storage::path_from_field(&path), bpm.parse().ok()?))
+        })
+        .collect()
+}
+
+pub fn save(bpms: &HashMap<PathBuf, f32>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = bpms
+        .iter()
+        .map(|(path, bpm)| vec![format!("{:.1}", bpm), storage::path_to_field(path)])
+        .collect();
+    records.sort_by(|a, b| a[1].cmp(&b[1]));
+    storage::write_records(BPM_FILE, &records)
+}
+
+/// `music_tui bpm [--force]`：分析曲库中还没有 BPM 的歌曲
+pub fn run(args: &[OsString]) -> Result<()> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let songs = scan_songs(Path::new("assets"))?;
+    let mut bpms = load();
+
+    let pending: Vec<_> = songs
+        .iter()
+        .filter(|song| force || !bpms.contains_key(&song.path))
+        .collect();
+    println!("需要分析 {} 首歌曲", pending.len());
+
+    for (i, song) in pending.iter().enumerate() {
+        match detect(&song.path) {
+            Some(bpm) => {
+                println!("[{}/{}] {:6.1} BPM  {}", i + 1, pending.len(), bpm, song.name);
+                bpms.insert(song.path.clone(), bpm);
+                // 每首分析完就保存，中途中断也不会丢失结果
+                save(&bpms)?;
+            }
+            None => println!("[{}/{}]    ??? BPM  {}", i + 1, pending.len(), song.name),
+        }
+    }
+    Ok(())
+}
+
+/// 估算歌曲的 BPM：计算能量起伏（onset）包络，再对包络做自相关找出最强的节拍周期
+pub fn detect(path: &Path) -> Option<f32> {
+    let file = fs::File::open(path).ok()?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, Duration::ZERO).ok()?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate();
+
+    let max_samples = (sample_rate * ANALYSIS_SECONDS) as usize * channels;
+    let samples: Vec<f32> = decoder.take(max_samples).collect();
+
+    // 混成单声道后按 hop 计算能量
+    let energies: Vec<f32> = samples
+        .chunks(HOP_SIZE * channels)
+        .map(|chunk| chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32)
+        .collect();
+    if energies.len() < 64 {
+        return None;
+    }
+
+    // 只保留能量上升的部分作为起音包络
+    let mut envelope: Vec<f32> = energies
+        .windows(2)
+        .map(|pair| (pair[1].sqrt() - pair[0].sqrt()).max(0.0))
+        .collect();
+    let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+    for value in envelope.iter_mut() {
+        *value -= mean;
+    }
+
+    let frames_per_second = sample_rate as f32 / HOP_SIZE as f32;
+    let autocorrelation = |lag: f32| -> f32 {
+        let whole = lag.floor() as usize;
+        let frac = lag - whole as f32;
+        if whole + 1 >= envelope.len() {
+            return 0.0;
+        }
+        (0..envelope.len() - whole - 1)
+            .map(|n| envelope[n] * (envelope[n + whole] * (1.0 - frac) + envelope[n + whole + 1] * frac))
+            .sum()
+    };
+
+    let mut best: Option<(f32, f32)> = None;
+    let mut bpm = MIN_BPM;
+    while bpm <= MAX_BPM {
+        let lag = 60.0 * frames_per_second / bpm;
+        let score = autocorrelation(lag);
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((bpm, score));
+        }
+        bpm += 0.5;
+    }
+
+    let (mut bpm, score) = best?;
+    if score <= 0.0 {
+        return None;
+    }
+    // 折叠到常见的速度范围，避免半速/倍速误判
+    while bpm < 80.0 {
+        bpm *= 2.0;
+    }
+    while bpm > 180.0 {
+        bpm /= 2.0;
+    }
+    Some(bpm)
+}
@@ -0,0 +1,59 @@
+use anyhow::{bail, Result};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// 复制文本到系统剪贴板。优先使用系统自带的剪贴板工具，
+/// 都不可用时（例如通过 SSH 连接）改用终端的 OSC 52 转义序列。返回使用的方式。
+pub fn copy(text: &str) -> Result<&'static str> {
+    let tools: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("clip.exe", &[]),
+    ];
+    for (tool, args) in tools {
+        if pipe_to(tool, args, text).is_ok() {
+            return Ok(tool);
+        }
+    }
+
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok("OSC 52")
+}
+
+fn pipe_to(program: &str, args: &[&str], text: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    drop(child.stdin.take());
+    if !child.wait()?.success() {
+        bail!("{} 执行失败", program);
+    }
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[(value >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Local};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 播放计时和闹钟使用的时钟。平时就是系统时钟；模拟模式下时间只在调用 advance 时前进，
+/// 可以不出声地快进播放，每次运行的结果都相同
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    #[default]
+    System,
+    Simulated(Arc<SimulatedTime>),
+}
+
+#[derive(Debug)]
+pub struct SimulatedTime {
+    start: Instant,
+    start_local: DateTime<Local>,
+    elapsed: Mutex<Duration>,
+}
+
+impl Clock {
+    /// 从 start_local 这个时刻开始的模拟时钟
+    pub fn simulated(start_local: DateTime<Local>) -> Self {
+        Clock::Simulated(Arc::new(SimulatedTime {
+            start: Instant::now(),
+            start_local,
+            elapsed: Mutex::new(Duration::ZERO),
+        }))
+    }
+
+    pub fn is_simulated(&self) -> bool {
+        matches!(self, Clock::Simulated(_))
+    }
+
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Simulated(time) => time.start + time.elapsed(),
+        }
+    }
+
+    /// 当地时间，闹钟按这个时间判断
+    pub fn local_now(&self) -> DateTime<Local> {
+        match self {
+            Clock::System => Local::now(),
+            Clock::Simulated(time) => {
+                time.start_local + chrono::Duration::from_std(time.elapsed()).unwrap_or_default()
+            }
+        }
+    }
+
+    /// 从 earlier 到现在经过的时间
+    pub fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+    /// 模拟时钟开始以来经过的时间，系统时钟返回 0
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            Clock::System => Duration::ZERO,
+            Clock::Simulated(time) => time.elapsed(),
+        }
+    }
+
+    /// 让模拟时钟前进，系统时钟不受影响
+    pub fn advance(&self, by: Duration) {
+        if let Clock::Simulated(time) = self {
+            if let Ok(mut elapsed) = time.elapsed.lock() {
+                *elapsed += by;
+            }
+        }
+    }
+}
+
+impl SimulatedTime {
+    fn elapsed(&self) -> Duration {
+        self.elapsed.lock().map(|elapsed| *elapsed).unwrap_or_default()
+    }
+}
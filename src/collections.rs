@@ -0,0 +1,133 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::app::Song;
+use crate::storage;
+
+const COLLECTIONS_FILE: &str = "collections.tsv";
+
+/// 加入收藏集的范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinScope {
+    Track,
+    Album,
+    Folder,
+}
+
+impl PinScope {
+    pub fn next(self) -> Self {
+        match self {
+            PinScope::Track => PinScope::Album,
+            PinScope::Album => PinScope::Folder,
+            PinScope::Folder => PinScope::Track,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PinScope::Track => "单曲",
+            PinScope::Album => "整张专辑",
+            PinScope::Folder => "整个文件夹",
+        }
+    }
+}
+
+/// 收藏集中的一项。专辑和文件夹按条件匹配，之后加入曲库的歌曲也算在内
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pin {
+    Track(PathBuf),
+    /// (专辑艺术家或艺术家, 专辑名)
+    Album(String, String),
+    Folder(PathBuf),
+}
+
+impl Pin {
+    /// 光标所在歌曲对应范围的收藏项，没有专辑标签时无法按专辑收藏
+    pub fn for_song(song: &Song, scope: PinScope) -> Option<Pin> {
+        match scope {
+            PinScope::Track => Some(Pin::Track(song.path.clone())),
+            PinScope::Album => {
+                let album = song.tags.album.clone()?;
+                let artist = song.tags.album_artist.clone().or_else(|| song.tags.artist.clone());
+                Some(Pin::Album(artist.unwrap_or_default(), album))
+            }
+            PinScope::Folder => song.path.parent().map(|dir| Pin::Folder(dir.to_path_buf())),
+        }
+    }
+
+    pub fn matches(&self, song: &Song) -> bool {
+        match self {
+            Pin::Track(path) => song.path == *path,
+            Pin::Album(artist, album) => {
+                let song_artist = song.tags.album_artist.as_ref().or(song.tags.artist.as_ref());
+                song.tags.album.as_ref().is_some_and(|name| name.eq_ignore_ascii_case(album))
+                    && song_artist.map_or(artist.is_empty(), |name| name.eq_ignore_ascii_case(artist))
+            }
+            Pin::Folder(dir) => song.path.starts_with(dir),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Pin::Track(path) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            Pin::Album(artist, album) if artist.is_empty() => format!("专辑 {}", album),
+            Pin::Album(artist, album) => format!("专辑 {} - {}", artist, album),
+            Pin::Folder(dir) => format!("文件夹 {}", dir.display()),
+        }
+    }
+}
+
+/// 从曲库中挑出的一组歌曲（如“黑胶转录”“儿歌”），和播放列表不同，只用来缩小浏览和播放的范围
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub name: String,
+    pub pins: Vec<Pin>,
+}
+
+impl Collection {
+    pub fn contains(&self, song: &Song) -> bool {
+        self.pins.iter().any(|pin| pin.matches(song))
+    }
+}
+
+pub fn load() -> Vec<Collection> {
+    let records = storage::read_records(COLLECTIONS_FILE).unwrap_or_default();
+    let mut collections: Vec<Collection> = Vec::new();
+    for record in records {
+        // 格式: 名称 \t 类型 \t 路径或专辑艺术家 [\t 专辑名]
+        let pin = match record.get(1).map(String::as_str) {
+            Some("track") if record.len() >= 3 => Pin::Track(storage::path_from_field(&record[2])),
+            Some("folder") if record.len() >= 3 => Pin::Folder(storage::path_from_field(&record[2])),
+            Some("album") if record.len() >= 4 => Pin::Album(record[2].clone(), record[3].clone()),
+            _ => continue,
+        };
+        match collections.iter_mut().find(|collection| collection.name == record[0]) {
+            Some(collection) => collection.pins.push(pin),
+            None => collections.push(Collection {
+                name: record[0].clone(),
+                pins: vec![pin],
+            }),
+        }
+    }
+    collections
+}
+
+pub fn save(collections: &[Collection]) -> Result<()> {
+    let records: Vec<Vec<String>> = collections
+        .iter()
+        .flat_map(|collection| {
+            collection.pins.iter().map(|pin| {
+                let mut record = vec![collection.name.clone()];
+                match pin {
+                    Pin::Track(path) => record.extend(["track".to_string(), storage::path_to_field(path)]),
+                    Pin::Folder(dir) => record.extend(["folder".to_string(), storage::path_to_field(dir)]),
+                    Pin::Album(artist, album) => {
+                        record.extend(["album".to_string(), artist.clone(), album.clone()])
+                    }
+                }
+                record
+            })
+        })
+        .collect();
+    storage::write_records(COLLECTIONS_FILE, &records)
+}
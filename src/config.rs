@@ -0,0 +1,594 @@
+use anyhow::Result;
+use chrono::NaiveTime;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::alert::AlertMode;
+use crate::audio_output::{self, DitherMode, OutputBackend, ResamplerQuality};
+use crate::dsd::{DsdFilter, DsdOutput};
+use crate::glyphs::GlyphStyle;
+use crate::lighting;
+use crate::lyrics::Provider;
+use crate::storage;
+use crate::visualizer::VisualizerMode;
+
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_STATUS_FORMAT: &str = "%state% %artist% - %title% [%elapsed%/%total%] 音量:%vol%% %shuffle%";
+const DEFAULT_NOW_PLAYING_FORMAT: &str = "{yellow}当前播放: {white}%file%\\n{yellow}状态: {green}%status%%buffer%";
+
+/// 用户配置，保存在配置目录的 config.toml 中（每行一个 `键 = 值`）
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 控制说明面板的高度（含边框），0 表示隐藏
+    pub controls_height: u16,
+    /// 播放状态面板的高度（含边框）
+    pub now_playing_height: u16,
+    /// 信息面板占主区域宽度的百分比
+    pub info_width: u16,
+    /// 打开音频文件的超时时间（毫秒），网络共享响应过慢时把歌曲标记为暂不可用
+    pub open_timeout_ms: u64,
+    /// 扫描曲库时忽略的 glob 模式，如 `**/ringtones/**`、`*.spam.mp3`
+    pub scan_ignore: Vec<String>,
+    /// 扫描曲库时跳过短于该时长（秒）的文件，0 表示不限制
+    pub min_duration_secs: u64,
+    /// 儿童模式：隐藏标记为不当内容的歌曲，只能在配置文件中修改
+    pub clean_mode: bool,
+    /// 扫描曲库时并行探测标签的线程数（同时打开的文件数），0 表示按 CPU 核数自动选择
+    pub probe_workers: usize,
+    /// 界面符号风格: emoji、nerdfont 或 ascii
+    pub glyphs: GlyphStyle,
+    /// 没有输入时检查播放状态和后台任务的间隔（毫秒），播放中也按这个间隔刷新进度
+    pub tick_ms: u64,
+    /// 每秒最多重绘的次数
+    pub max_fps: u32,
+    /// 省电模式：降低刷新频率，关闭可视化和波形，后台检查合并成较长的间隔
+    pub power_saver: bool,
+    /// 闹钟：每天在这个时间开始播放，None 表示关闭
+    pub alarm_time: Option<NaiveTime>,
+    /// 每天在这个时间自动暂停，None 表示关闭
+    pub alarm_stop_time: Option<NaiveTime>,
+    /// 闹钟响起时播放的 M3U 播放列表，为空时从当前歌曲开始播放
+    pub alarm_playlist: String,
+    /// 闹钟响起时音量从 0 增大到设定音量所用的秒数
+    pub alarm_ramp_secs: u64,
+    /// 接收 `music_tui ctl` 控制命令的本机端口，0 表示关闭
+    pub remote_port: u16,
+    /// 粘贴 http(s) 地址时先完整下载到缓存目录，而不是边下载边播放
+    pub download_urls: bool,
+    /// 用来解析视频网站地址的 yt-dlp 程序，为空表示不使用
+    pub ytdlp: String,
+    /// 远程曲库（`sftp://` 或 WebDAV 的 `https://` 目录），和 assets 目录一起加入曲库
+    pub remote_sources: Vec<String>,
+    /// 远程歌曲本地缓存的上限（MB）
+    pub remote_cache_mb: u64,
+    /// 底部状态栏的模板，`%title%`、`%elapsed%` 等变量每次重绘时替换，为空表示不显示状态栏
+    pub status_format: String,
+    /// 播放状态面板的模板，`\n` 分隔各行，可以使用颜色和条件段落
+    pub now_playing_format: String,
+    /// 评分时同时写入文件标签（MP3 的 POPM、FLAC 的 RATING），其他播放器也能看到
+    pub write_rating_tags: bool,
+    /// 在播放列表中显示每首歌的波形缩略图，后台生成后保存在缓存目录中
+    pub playlist_waveforms: bool,
+    /// 作为曲库打开的 M3U 文件被其他程序修改后提示重新加载
+    pub watch_playlist: bool,
+    /// 响度均衡：有 ReplayGain 标签的歌曲按标签调整，没有标签的（如网络电台）自动调整音量
+    pub leveler: bool,
+    /// 响度均衡的目标响度 (LUFS)
+    pub leveler_target_lufs: i32,
+    /// 输出延迟（毫秒），换算成输出流的缓冲区帧数，0 表示系统默认
+    pub output_latency_ms: u32,
+    /// 输出采样率，0 表示使用设备默认值
+    pub output_sample_rate: u32,
+    /// 输出样本格式（f32、i16、i32 等），为空表示使用设备默认值
+    pub output_sample_format: String,
+    /// 重采样质量: fast、medium 或 high
+    pub resampler: ResamplerQuality,
+    /// 输出为 16 位及以下整数格式时的抖动: off 或 tpdf
+    pub dither: DitherMode,
+    /// 音频输出方式: default（系统默认设备）、pipewire 或 jack
+    pub output_backend: OutputBackend,
+    /// 在 PipeWire 路由图中显示的节点名称
+    pub output_client_name: String,
+    /// JACK 输出时连接到的端口，按声道顺序
+    pub jack_ports: Vec<String>,
+    /// 输出设备名称（`music_tui devices` 列出），为空表示系统默认设备
+    pub output_device: String,
+    /// 位精确输出：设备采样率跟随歌曲，不经过响度均衡和均衡器
+    pub match_sample_rate: bool,
+    /// 输出设备的声道数足够时，多声道文件不混成立体声
+    pub surround_passthrough: bool,
+    /// DSD 文件的输出方式: pcm（转换成 PCM）或 dop
+    pub dsd_output: DsdOutput,
+    /// DSD 转 PCM 的滤波器: fast 或 sharp
+    pub dsd_filter: DsdFilter,
+    /// 可视化模式: off、oscilloscope 或 spectrogram
+    pub visualizer: VisualizerMode,
+    /// 可视化面板的高度（含边框）
+    pub visualizer_height: u16,
+    /// 没有本地 .lrc 时按顺序在线查找歌词的来源（lrclib、netease），为空表示不在线查找
+    pub lyrics_providers: Vec<String>,
+    /// 下载的歌词保存在音频文件旁边，而不是缓存目录
+    pub lyrics_next_to_file: bool,
+    /// 当前歌曲播放超过这么多秒时，“上一首”先从头重新播放，0 表示总是切到上一首歌
+    pub previous_restart_secs: u64,
+    /// 切歌和播放队列播完时的提醒: off、flash（外框闪烁）、bell（终端响铃）或 both
+    pub track_alert: AlertMode,
+    /// 播放队列剩余不到这么多分钟时提醒添加歌曲（提示消息，并按 `track_alert` 闪烁或响铃），0 表示不提醒
+    pub queue_warning_mins: u64,
+    /// 切歌时播放列表的光标跟着移到新歌曲（光标被移开浏览时不跟随）
+    pub follow_playback: bool,
+    /// 手动切歌时旧歌曲淡出、新歌曲淡入的时间（毫秒），0 表示直接切换
+    pub switch_fade_ms: u64,
+    /// 实验性的自动混音：歌曲结尾和下一首重叠，速度相近时对齐节拍
+    pub automix: bool,
+    /// 自动混音时重叠的拍数
+    pub automix_beats: u32,
+    /// 两首歌的速度相差不超过这个百分比时调整新歌曲的速度
+    pub automix_tolerance: u32,
+    /// 以单个文件启动（从文件管理器打开）时，播放结束后退出
+    pub preview_exit: bool,
+    /// beets 曲库（library.db）的路径：其中的歌曲直接使用 beets 的标签，不再探测文件，为空表示不使用
+    pub beets_library: String,
+    /// beets 的命令行程序
+    pub beets_command: String,
+    /// 识别流派的外部程序，以文件路径为参数，把流派打印在第一行；为空表示使用内置模型
+    pub genre_command: String,
+    /// Rekordbox 导出的 XML 曲库，读取其中的提示点显示在进度条上；为空表示不读取
+    pub rekordbox_xml: String,
+    /// MQTT 服务器（`主机` 或 `主机:端口`），为空表示不发布播放状态
+    pub mqtt_broker: String,
+    /// MQTT 主题前缀，状态发布到 `前缀/state`，命令从 `前缀/command` 接收
+    pub mqtt_topic: String,
+    pub mqtt_username: String,
+    pub mqtt_password: String,
+    /// 提供 Prometheus 统计（`/metrics`）的地址，如 `127.0.0.1:9464`，为空表示不提供
+    pub metrics_address: String,
+    /// 网页遥控的监听地址，如 `0.0.0.0:8080`，为空表示关闭
+    pub web_address: String,
+    /// 网页遥控的访问令牌，没有设置时不启动网页遥控
+    pub web_token: String,
+    /// 开始、暂停、继续和停止播放时 POST JSON 的地址
+    pub webhook_urls: Vec<String>,
+    /// webhook 的请求内容模板，为空时发送播放状态
+    pub webhook_template: String,
+    /// webhook 发送失败后的重试次数
+    pub webhook_retries: u32,
+    /// 随播放变换颜色的灯光：`wled://地址` 或 `hue://桥接器地址/用户名[/灯组]`
+    pub lighting_targets: Vec<String>,
+    /// 灯光随歌曲的节拍闪烁（需要已分析过 BPM）
+    pub lighting_beats: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            controls_height: 3,
+            now_playing_height: 3,
+            info_width: 45,
+            open_timeout_ms: 3000,
+            scan_ignore: Vec::new(),
+            min_duration_secs: 0,
+            clean_mode: false,
+            probe_workers: 0,
+            glyphs: GlyphStyle::Emoji,
+            tick_ms: 200,
+            max_fps: 30,
+            power_saver: false,
+            alarm_time: None,
+            alarm_stop_time: None,
+            alarm_playlist: String::new(),
+            alarm_ramp_secs: 60,
+            remote_port: 47800,
+            download_urls: false,
+            ytdlp: "yt-dlp".to_string(),
+            remote_sources: Vec::new(),
+            remote_cache_mb: 1024,
+            status_format: DEFAULT_STATUS_FORMAT.to_string(),
+            now_playing_format: DEFAULT_NOW_PLAYING_FORMAT.to_string(),
+            write_rating_tags: false,
+            playlist_waveforms: false,
+            watch_playlist: true,
+            leveler: false,
+            leveler_target_lufs: -18,
+            output_latency_ms: 0,
+            output_sample_rate: 0,
+            output_sample_format: String::new(),
+            resampler: ResamplerQuality::Fast,
+            dither: DitherMode::Off,
+            output_backend: OutputBackend::Device,
+            output_client_name: "CuperMusic".to_string(),
+            jack_ports: vec!["system:playback_1".to_string(), "system:playback_2".to_string()],
+            output_device: String::new(),
+            match_sample_rate: false,
+            surround_passthrough: false,
+            dsd_output: DsdOutput::Pcm,
+            dsd_filter: DsdFilter::Fast,
+            visualizer: VisualizerMode::Off,
+            visualizer_height: 10,
+            lyrics_providers: Vec::new(),
+            lyrics_next_to_file: false,
+            previous_restart_secs: 3,
+            track_alert: AlertMode::Off,
+            queue_warning_mins: 0,
+            follow_playback: true,
+            switch_fade_ms: 150,
+            automix: false,
+            automix_beats: 16,
+            automix_tolerance: 6,
+            preview_exit: false,
+            beets_library: String::new(),
+            beets_command: "beet".to_string(),
+            genre_command: String::new(),
+            rekordbox_xml: String::new(),
+            mqtt_broker: String::new(),
+            mqtt_topic: "cupermusic".to_string(),
+            mqtt_username: String::new(),
+            mqtt_password: String::new(),
+            metrics_address: String::new(),
+            web_address: String::new(),
+            web_token: String::new(),
+            webhook_urls: Vec::new(),
+            webhook_template: String::new(),
+            webhook_retries: 3,
+            lighting_targets: Vec::new(),
+            lighting_beats: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        storage::config_dir().join(CONFIG_FILE)
+    }
+
+    /// 读取配置，文件不存在或某项无法解析时使用默认值
+    pub fn load() -> Self {
+        let mut config = Config::default();
+        let Ok(content) = fs::read_to_string(Self::path()) else {
+            return config;
+        };
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"');
+                let _ = config.set(key.trim(), value);
+            }
+        }
+        config
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = storage::config_dir();
+        fs::create_dir_all(&dir)?;
+
+        let mut content = String::from("# CuperMusicPlayer 配置\n");
+        for (key, value) in self.entries() {
+            content.push_str(&format!("{} = {}\n", key, value));
+        }
+        fs::write(Self::path(), content)?;
+        Ok(())
+    }
+
+    /// 收起界面时显示的状态行模板，关闭了状态栏时使用默认模板
+    pub fn minimized_format(&self) -> &str {
+        match self.status_format.as_str() {
+            "" => DEFAULT_STATUS_FORMAT,
+            format => format,
+        }
+    }
+
+    /// 播放状态面板的模板，为空时使用默认模板
+    pub fn now_playing_format(&self) -> &str {
+        match self.now_playing_format.as_str() {
+            "" => DEFAULT_NOW_PLAYING_FORMAT,
+            format => format,
+        }
+    }
+
+    /// 按键名设置一项配置
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parse_u16 = |value: &str| value.parse::<u16>().map_err(|_| format!("{} 需要整数", key));
+        match key {
+            "controls_height" => self.controls_height = parse_u16(value)?.min(8),
+            "now_playing_height" => self.now_playing_height = parse_u16(value)?.clamp(3, 6),
+            "info_width" => self.info_width = parse_u16(value)?.clamp(20, 80),
+            "open_timeout_ms" => {
+                let timeout = value.parse::<u64>().map_err(|_| format!("{} 需要整数", key))?;
+                self.open_timeout_ms = timeout.clamp(100, 60_000);
+            }
+            "scan_ignore" => self.scan_ignore = parse_list(value),
+            "min_duration_secs" => {
+                self.min_duration_secs = value.parse().map_err(|_| format!("{} 需要整数", key))?;
+            }
+            "clean_mode" => {
+                self.clean_mode = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "probe_workers" => {
+                let workers = value.parse::<usize>().map_err(|_| format!("{} 需要整数", key))?;
+                self.probe_workers = workers.min(64);
+            }
+            "glyphs" => {
+                self.glyphs = GlyphStyle::parse(value)
+                    .ok_or_else(|| format!("{} 只能是 emoji、nerdfont 或 ascii", key))?;
+            }
+            "tick_ms" => {
+                let tick = value.parse::<u64>().map_err(|_| format!("{} 需要整数", key))?;
+                self.tick_ms = tick.clamp(20, 1000);
+            }
+            "max_fps" => {
+                let fps = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.max_fps = fps.clamp(1, 120);
+            }
+            "power_saver" => self.power_saver = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?,
+            "alarm_time" => self.alarm_time = parse_time(key, value)?,
+            "alarm_stop_time" => self.alarm_stop_time = parse_time(key, value)?,
+            "alarm_playlist" => self.alarm_playlist = value.to_string(),
+            "alarm_ramp_secs" => {
+                let secs = value.parse::<u64>().map_err(|_| format!("{} 需要整数", key))?;
+                self.alarm_ramp_secs = secs.min(3600);
+            }
+            "remote_port" => self.remote_port = parse_u16(value)?,
+            "ytdlp" => self.ytdlp = value.to_string(),
+            "beets_library" => self.beets_library = value.to_string(),
+            "beets_command" => self.beets_command = value.to_string(),
+            "genre_command" => self.genre_command = value.to_string(),
+            "rekordbox_xml" => self.rekordbox_xml = value.to_string(),
+            "mqtt_broker" => self.mqtt_broker = value.to_string(),
+            "mqtt_topic" => {
+                let topic = value.trim_matches('/');
+                if topic.is_empty() || topic.contains(['+', '#']) {
+                    return Err(format!("{} 不能为空，也不能包含通配符 + 或 #", key));
+                }
+                self.mqtt_topic = topic.to_string();
+            }
+            "mqtt_username" => self.mqtt_username = value.to_string(),
+            "mqtt_password" => self.mqtt_password = value.to_string(),
+            "metrics_address" => self.metrics_address = value.to_string(),
+            "web_address" => self.web_address = value.to_string(),
+            "web_token" => self.web_token = value.to_string(),
+            "webhook_urls" => self.webhook_urls = parse_list(value),
+            "webhook_template" => self.webhook_template = value.to_string(),
+            "webhook_retries" => {
+                let retries = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.webhook_retries = retries.min(10);
+            }
+            "lighting_targets" => {
+                let targets = parse_list(value);
+                for target in &targets {
+                    lighting::Target::parse(target).map_err(|err| err.to_string())?;
+                }
+                self.lighting_targets = targets;
+            }
+            "lighting_beats" => self.lighting_beats = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?,
+            "status_format" => self.status_format = value.to_string(),
+            "now_playing_format" => self.now_playing_format = value.to_string(),
+            "remote_sources" => self.remote_sources = parse_list(value),
+            "remote_cache_mb" => {
+                self.remote_cache_mb = value.parse().map_err(|_| format!("{} 需要整数", key))?;
+            }
+            "download_urls" => {
+                self.download_urls = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "playlist_waveforms" => {
+                self.playlist_waveforms = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "watch_playlist" => {
+                self.watch_playlist = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "leveler" => {
+                self.leveler = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "leveler_target_lufs" => {
+                let target = value.parse::<i32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.leveler_target_lufs = target.clamp(-30, -8);
+            }
+            "output_latency_ms" => {
+                let latency = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.output_latency_ms = latency.min(2000);
+            }
+            "output_sample_rate" => {
+                let rate = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                if rate != 0 && !(8000..=384_000).contains(&rate) {
+                    return Err(format!("{} 需要在 8000 到 384000 之间（0 表示默认）", key));
+                }
+                self.output_sample_rate = rate;
+            }
+            "output_sample_format" => {
+                if !value.is_empty() && audio_output::parse_sample_format(value).is_none() {
+                    return Err(format!("{} 只能是 f32、f64、i16、i32 等样本格式", key));
+                }
+                self.output_sample_format = value.to_lowercase();
+            }
+            "resampler" => {
+                self.resampler = ResamplerQuality::parse(value)
+                    .ok_or_else(|| format!("{} 只能是 fast、medium 或 high", key))?;
+            }
+            "dither" => {
+                self.dither = DitherMode::parse(value).ok_or_else(|| format!("{} 只能是 off 或 tpdf", key))?;
+            }
+            "output_backend" => {
+                self.output_backend = OutputBackend::parse(value)
+                    .ok_or_else(|| format!("{} 只能是 default、pipewire 或 jack", key))?;
+            }
+            "output_client_name" => self.output_client_name = value.to_string(),
+            "jack_ports" => self.jack_ports = parse_list(value),
+            "output_device" => self.output_device = value.to_string(),
+            // exclusive_output 是这一项以前的名字，旧的配置文件照样生效
+            "match_sample_rate" | "exclusive_output" => {
+                self.match_sample_rate = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "surround_passthrough" => {
+                self.surround_passthrough = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "dsd_output" => {
+                self.dsd_output = DsdOutput::parse(value).ok_or_else(|| format!("{} 只能是 pcm 或 dop", key))?;
+            }
+            "dsd_filter" => {
+                self.dsd_filter = DsdFilter::parse(value).ok_or_else(|| format!("{} 只能是 fast 或 sharp", key))?;
+            }
+            "visualizer" => {
+                self.visualizer = VisualizerMode::parse(value)
+                    .ok_or_else(|| format!("{} 只能是 off、oscilloscope 或 spectrogram", key))?;
+            }
+            "visualizer_height" => self.visualizer_height = parse_u16(value)?.clamp(3, 30),
+            "lyrics_providers" => {
+                let providers = parse_list(value);
+                if let Some(unknown) = providers.iter().find(|name| Provider::parse(name).is_none()) {
+                    return Err(format!("{} 中的 {} 不是支持的歌词来源（lrclib、netease）", key, unknown));
+                }
+                self.lyrics_providers = providers;
+            }
+            "lyrics_next_to_file" => {
+                self.lyrics_next_to_file = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "previous_restart_secs" => {
+                let secs = value.parse::<u64>().map_err(|_| format!("{} 需要整数", key))?;
+                self.previous_restart_secs = secs.min(60);
+            }
+            "track_alert" => {
+                self.track_alert = AlertMode::parse(value)
+                    .ok_or_else(|| format!("{} 只能是 off、flash、bell 或 both", key))?;
+            }
+            "queue_warning_mins" => {
+                self.queue_warning_mins = value.parse().map_err(|_| format!("{} 需要整数", key))?;
+            }
+            "follow_playback" => {
+                self.follow_playback = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "automix" => {
+                self.automix = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "automix_beats" => {
+                let beats = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.automix_beats = beats.clamp(1, 64);
+            }
+            "automix_tolerance" => {
+                let tolerance = value.parse::<u32>().map_err(|_| format!("{} 需要整数", key))?;
+                self.automix_tolerance = tolerance.min(20);
+            }
+            "preview_exit" => {
+                self.preview_exit = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            "switch_fade_ms" => {
+                let fade = value.parse::<u64>().map_err(|_| format!("{} 需要整数", key))?;
+                self.switch_fade_ms = fade.min(1000);
+            }
+            "write_rating_tags" => {
+                self.write_rating_tags = value.parse().map_err(|_| format!("{} 只能是 true 或 false", key))?;
+            }
+            _ => return Err(format!("未知配置项: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// 所有配置项及其值（按写入文件的格式）
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("controls_height", self.controls_height.to_string()),
+            ("now_playing_height", self.now_playing_height.to_string()),
+            ("info_width", self.info_width.to_string()),
+            ("open_timeout_ms", self.open_timeout_ms.to_string()),
+            ("scan_ignore", format_list(&self.scan_ignore)),
+            ("min_duration_secs", self.min_duration_secs.to_string()),
+            ("clean_mode", self.clean_mode.to_string()),
+            ("probe_workers", self.probe_workers.to_string()),
+            ("glyphs", self.glyphs.name().to_string()),
+            ("tick_ms", self.tick_ms.to_string()),
+            ("max_fps", self.max_fps.to_string()),
+            ("power_saver", self.power_saver.to_string()),
+            ("alarm_time", format_time(self.alarm_time)),
+            ("alarm_stop_time", format_time(self.alarm_stop_time)),
+            ("alarm_playlist", format!("\"{}\"", self.alarm_playlist)),
+            ("alarm_ramp_secs", self.alarm_ramp_secs.to_string()),
+            ("remote_port", self.remote_port.to_string()),
+            ("download_urls", self.download_urls.to_string()),
+            ("ytdlp", format!("\"{}\"", self.ytdlp)),
+            ("remote_sources", format_list(&self.remote_sources)),
+            ("remote_cache_mb", self.remote_cache_mb.to_string()),
+            ("status_format", format!("\"{}\"", self.status_format)),
+            ("now_playing_format", format!("\"{}\"", self.now_playing_format)),
+            ("write_rating_tags", self.write_rating_tags.to_string()),
+            ("playlist_waveforms", self.playlist_waveforms.to_string()),
+            ("watch_playlist", self.watch_playlist.to_string()),
+            ("leveler", self.leveler.to_string()),
+            ("leveler_target_lufs", self.leveler_target_lufs.to_string()),
+            ("output_latency_ms", self.output_latency_ms.to_string()),
+            ("output_sample_rate", self.output_sample_rate.to_string()),
+            ("output_sample_format", format!("\"{}\"", self.output_sample_format)),
+            ("resampler", self.resampler.name().to_string()),
+            ("dither", self.dither.name().to_string()),
+            ("output_backend", self.output_backend.name().to_string()),
+            ("output_client_name", format!("\"{}\"", self.output_client_name)),
+            ("jack_ports", format_list(&self.jack_ports)),
+            ("output_device", format!("\"{}\"", self.output_device)),
+            ("match_sample_rate", self.match_sample_rate.to_string()),
+            ("surround_passthrough", self.surround_passthrough.to_string()),
+            ("dsd_output", self.dsd_output.name().to_string()),
+            ("dsd_filter", self.dsd_filter.name().to_string()),
+            ("visualizer", self.visualizer.name().to_string()),
+            ("visualizer_height", self.visualizer_height.to_string()),
+            ("lyrics_providers", format_list(&self.lyrics_providers)),
+            ("lyrics_next_to_file", self.lyrics_next_to_file.to_string()),
+            ("previous_restart_secs", self.previous_restart_secs.to_string()),
+            ("track_alert", self.track_alert.name().to_string()),
+            ("queue_warning_mins", self.queue_warning_mins.to_string()),
+            ("follow_playback", self.follow_playback.to_string()),
+            ("switch_fade_ms", self.switch_fade_ms.to_string()),
+            ("automix", self.automix.to_string()),
+            ("automix_beats", self.automix_beats.to_string()),
+            ("automix_tolerance", self.automix_tolerance.to_string()),
+            ("preview_exit", self.preview_exit.to_string()),
+            ("beets_library", format!("\"{}\"", self.beets_library)),
+            ("beets_command", format!("\"{}\"", self.beets_command)),
+            ("genre_command", format!("\"{}\"", self.genre_command)),
+            ("rekordbox_xml", format!("\"{}\"", self.rekordbox_xml)),
+            ("mqtt_broker", format!("\"{}\"", self.mqtt_broker)),
+            ("mqtt_topic", format!("\"{}\"", self.mqtt_topic)),
+            ("mqtt_username", format!("\"{}\"", self.mqtt_username)),
+            ("mqtt_password", format!("\"{}\"", self.mqtt_password)),
+            ("metrics_address", format!("\"{}\"", self.metrics_address)),
+            ("web_address", format!("\"{}\"", self.web_address)),
+            ("web_token", format!("\"{}\"", self.web_token)),
+            ("webhook_urls", format_list(&self.webhook_urls)),
+            ("webhook_template", format!("\"{}\"", self.webhook_template)),
+            ("webhook_retries", self.webhook_retries.to_string()),
+            ("lighting_targets", format_list(&self.lighting_targets)),
+            ("lighting_beats", self.lighting_beats.to_string()),
+        ]
+    }
+}
+
+/// 解析 `07:30` 形式的时间，空字符串表示关闭
+fn parse_time(key: &str, value: &str) -> Result<Option<NaiveTime>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map(Some)
+        .map_err(|_| format!("{} 需要 HH:MM 格式的时间", key))
+}
+
+fn format_time(time: Option<NaiveTime>) -> String {
+    format!("\"{}\"", time.map(|time| time.format("%H:%M").to_string()).unwrap_or_default())
+}
+
+/// 解析 `["a", "b"]` 形式的字符串列表（也接受不带括号的逗号分隔）
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| item.trim().trim_matches('"').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn format_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|item| format!("\"{}\"", item)).collect();
+    format!("[{}]", quoted.join(", "))
+}
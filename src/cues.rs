@@ -0,0 +1,327 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::http;
+use crate::import;
+use crate::metadata;
+use crate::tag_writer;
+
+/// Serato 保存标记的位置：MP3 中是这个描述的 GEOB 帧，FLAC/OGG 中是这个 Vorbis comment
+const SERATO_GEOB: &str = "Serato Markers2";
+const SERATO_COMMENT: &str = "SERATO_MARKERS_V2";
+/// 位置相差不到这么多的同一个提示点（例如 Serato 标签和 Rekordbox 曲库中都有）只保留一个
+const SAME_POSITION: Duration = Duration::from_millis(20);
+
+/// DJ 软件中设置的提示点
+#[derive(Debug, Clone, PartialEq)]
+pub struct CuePoint {
+    pub position: Duration,
+    /// 热提示点的编号（从 0 开始），记忆提示点为 None
+    pub hot_cue: Option<u8>,
+    pub name: String,
+    pub color: Option<(u8, u8, u8)>,
+}
+
+impl CuePoint {
+    /// 热提示点在进度条上显示为编号 1-9
+    pub fn number(&self) -> Option<char> {
+        self.hot_cue.and_then(|index| char::from_digit(index as u32 + 1, 10))
+    }
+
+    pub fn describe(&self) -> String {
+        let kind = match self.hot_cue {
+            Some(index) => format!("热提示点 {}", index + 1),
+            None => "记忆提示点".to_string(),
+        };
+        match self.name.is_empty() {
+            true => kind,
+            false => format!("{}「{}」", kind, self.name),
+        }
+    }
+}
+
+/// 读取文件标签中 Serato 写入的提示点，按位置排序。没有或读不出来时为空
+pub fn read(path: &Path) -> Vec<CuePoint> {
+    let is_mp3 = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"));
+    let markers = match is_mp3 {
+        true => read_geob(path).unwrap_or_default(),
+        // Vorbis comment 中是 base64 编码的 GEOB 内容：MIME 类型、文件名、描述，然后是数据
+        false => metadata::raw_tag(path, SERATO_COMMENT)
+            .and_then(|value| base64_decode(&value))
+            .and_then(|object| {
+                let marker = format!("{}\0", SERATO_GEOB);
+                let start = find(&object, marker.as_bytes())? + marker.len();
+                Some(object[start..].to_vec())
+            })
+            .unwrap_or_default(),
+    };
+    let mut cues = parse_markers2(&markers);
+    cues.sort_by_key(|cue| cue.position);
+    cues
+}
+
+/// 把另一个来源的提示点合并进来，同一位置的只保留先有的
+pub fn merge(cues: &mut Vec<CuePoint>, other: &[CuePoint]) {
+    for cue in other {
+        let duplicate = cues.iter().any(|existing| {
+            existing.hot_cue == cue.hot_cue && existing.position.abs_diff(cue.position) < SAME_POSITION
+        });
+        if !duplicate {
+            cues.push(cue.clone());
+        }
+    }
+    cues.sort_by_key(|cue| cue.position);
+}
+
+/// 只读取文件开头的 ID3v2 标签，找到 Serato Markers2 的 GEOB 帧
+fn read_geob(path: &Path) -> Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header)?;
+    let Some(length) = tag_writer::id3_tag_length(&header) else {
+        return Ok(Vec::new());
+    };
+    let mut tag = header.to_vec();
+    file.take((length - header.len()) as u64).read_to_end(&mut tag)?;
+    for (id, _, body) in tag_writer::read_id3_frames(&tag)? {
+        if &id != b"GEOB" {
+            continue;
+        }
+        if let Some((description, data)) = split_geob(&body) {
+            if description == SERATO_GEOB {
+                return Ok(data.to_vec());
+            }
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// GEOB 帧：编码、MIME 类型、文件名、描述，最后是数据。返回 (描述, 数据)
+fn split_geob(body: &[u8]) -> Option<(String, &[u8])> {
+    let (encoding, rest) = body.split_first()?;
+    let mime_end = rest.iter().position(|byte| *byte == 0)?;
+    let rest = &rest[mime_end + 1..];
+    // UTF-16 的字符串以两个 0 字节结束
+    let wide = matches!(encoding, 1 | 2);
+    let split = |text: &[u8]| -> Option<(usize, usize)> {
+        match wide {
+            true => text.chunks_exact(2).position(|pair| pair == [0, 0]).map(|i| (i * 2, i * 2 + 2)),
+            false => text.iter().position(|byte| *byte == 0).map(|i| (i, i + 1)),
+        }
+    };
+    let (_, file_end) = split(rest)?;
+    let rest = &rest[file_end..];
+    let (description_end, data_start) = split(rest)?;
+    let description = match wide {
+        true => {
+            let units: Vec<u16> = rest[..description_end]
+                .chunks_exact(2)
+                .map(|pair| match encoding {
+                    2 => u16::from_be_bytes([pair[0], pair[1]]),
+                    _ => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .filter(|unit| *unit != 0xFEFF)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        false => String::from_utf8_lossy(&rest[..description_end]).to_string(),
+    };
+    Some((description, &rest[data_start..]))
+}
+
+/// Serato Markers2 的数据：版本 `01 01`，之后是 base64 编码的条目列表。
+/// 每个条目是以 0 结尾的类型名、4 字节长度和内容，这里只读取 CUE 条目
+fn parse_markers2(data: &[u8]) -> Vec<CuePoint> {
+    let Some(encoded) = data.strip_prefix(&[1, 1]) else {
+        return Vec::new();
+    };
+    let end = encoded.iter().position(|byte| *byte == 0).unwrap_or(encoded.len());
+    let Some(decoded) = base64_decode(&String::from_utf8_lossy(&encoded[..end])) else {
+        return Vec::new();
+    };
+    let Some(mut rest) = decoded.strip_prefix(&[1, 1]) else {
+        return Vec::new();
+    };
+    let mut cues = Vec::new();
+    while let Some(name_end) = rest.iter().position(|byte| *byte == 0).filter(|end| *end > 0) {
+        let name = &rest[..name_end];
+        let Some(length) = rest.get(name_end + 1..name_end + 5) else {
+            break;
+        };
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        let Some(entry) = rest.get(name_end + 5..name_end + 5 + length) else {
+            break;
+        };
+        if name == b"CUE" {
+            cues.extend(parse_cue(entry));
+        }
+        rest = &rest[name_end + 5 + length..];
+    }
+    cues
+}
+
+/// CUE 条目：0、编号、4 字节的毫秒位置、0、RGB 颜色、两个 0，然后是以 0 结尾的名称
+fn parse_cue(entry: &[u8]) -> Option<CuePoint> {
+    let header = entry.get(..12)?;
+    let position = u32::from_be_bytes([header[2], header[3], header[4], header[5]]);
+    let name = &entry[12..];
+    let name_end = name.iter().position(|byte| *byte == 0).unwrap_or(name.len());
+    Some(CuePoint {
+        position: Duration::from_millis(position as u64),
+        hot_cue: Some(header[1]),
+        name: String::from_utf8_lossy(&name[..name_end]).trim().to_string(),
+        color: Some((header[7], header[8], header[9])),
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// 解码 base64，忽略换行和填充。Serato 写入的数据有时在末尾多出一个字符，多余的位直接丢弃
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Rekordbox 不把提示点写进文件，需要在 Rekordbox 中用“文件 → 导出曲库为 xml 格式”导出。
+/// 按文件路径查找；在另一台电脑上导出时路径对不上，再按文件名和文件大小查找
+#[derive(Debug, Default)]
+pub struct RekordboxLibrary {
+    by_path: HashMap<PathBuf, Vec<CuePoint>>,
+    by_name: HashMap<(String, u64), Vec<CuePoint>>,
+}
+
+impl RekordboxLibrary {
+    /// 读取导出的 XML：`<TRACK Location=… Size=…>` 中的 `<POSITION_MARK Start=… Num=…/>`
+    pub fn load(file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(file)?;
+        let mut library = RekordboxLibrary::default();
+        let mut track: Option<(PathBuf, u64, Vec<CuePoint>)> = None;
+        let mut rest = content.as_str();
+        while let Some(start) = rest.find('<') {
+            let Some(end) = rest[start..].find('>') else {
+                break;
+            };
+            let tag = &rest[start + 1..start + end];
+            rest = &rest[start + end + 1..];
+
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            match name {
+                "TRACK" => {
+                    let attributes = attributes(tag);
+                    // 播放列表部分的 TRACK 只有 Key，没有位置
+                    let Some(location) = attributes.get("Location") else {
+                        continue;
+                    };
+                    let size = attributes.get("Size").and_then(|size| size.parse().ok()).unwrap_or(0);
+                    track = Some((location_to_path(location), size, Vec::new()));
+                    if tag.ends_with('/') {
+                        library.insert(track.take());
+                    }
+                }
+                "/TRACK" => library.insert(track.take()),
+                "POSITION_MARK" => {
+                    if let Some((_, _, cues)) = track.as_mut() {
+                        cues.extend(position_mark(&attributes(tag)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(library)
+    }
+
+    fn insert(&mut self, track: Option<(PathBuf, u64, Vec<CuePoint>)>) {
+        let Some((path, size, mut cues)) = track.filter(|(_, _, cues)| !cues.is_empty()) else {
+            return;
+        };
+        cues.sort_by_key(|cue| cue.position);
+        if let Some(name) = path.file_name() {
+            self.by_name.insert((name.to_string_lossy().to_lowercase(), size), cues.clone());
+        }
+        self.by_path.insert(path, cues);
+    }
+
+    pub fn get(&self, path: &Path) -> &[CuePoint] {
+        if let Some(cues) = self.by_path.get(path) {
+            return cues;
+        }
+        let size = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        self.by_name.get(&(name, size)).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// `file://localhost/Users/…/a.mp3` 或 `file://localhost/C:/Music/a.mp3`
+fn location_to_path(location: &str) -> PathBuf {
+    let path = location
+        .strip_prefix("file://localhost")
+        .or_else(|| location.strip_prefix("file://"))
+        .unwrap_or(location);
+    let path = http::decode(path);
+    let is_drive = path.as_bytes().get(1).is_some_and(u8::is_ascii_alphabetic) && path.get(2..3) == Some(":");
+    match is_drive && cfg!(windows) {
+        true => PathBuf::from(&path[1..]),
+        false => PathBuf::from(path),
+    }
+}
+
+/// Num 为 -1 的是记忆提示点，0-7 是热提示点 A-H
+fn position_mark(attributes: &HashMap<String, String>) -> Option<CuePoint> {
+    let start: f64 = attributes.get("Start")?.parse().ok()?;
+    if !start.is_finite() || start < 0.0 {
+        return None;
+    }
+    let number: i32 = attributes.get("Num").and_then(|num| num.parse().ok()).unwrap_or(-1);
+    let channel = |key: &str| attributes.get(key).and_then(|value| value.parse::<u8>().ok());
+    let color = match (channel("Red"), channel("Green"), channel("Blue")) {
+        (Some(red), Some(green), Some(blue)) => Some((red, green, blue)),
+        _ => None,
+    };
+    Some(CuePoint {
+        position: Duration::from_secs_f64(start),
+        hot_cue: u8::try_from(number).ok(),
+        name: attributes.get("Name").cloned().unwrap_or_default(),
+        color,
+    })
+}
+
+/// 标签中的 `名字="值"` 属性
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = tag;
+    while let Some(equals) = rest.find("=\"") {
+        let name = rest[..equals].rsplit(char::is_whitespace).next().unwrap_or_default();
+        let value_start = equals + 2;
+        let Some(value_end) = rest[value_start..].find('"') else {
+            break;
+        };
+        let value = &rest[value_start..value_start + value_end];
+        attributes.insert(name.to_string(), import::decode_entities(value));
+        rest = &rest[value_start + value_end + 1..];
+    }
+    attributes
+}
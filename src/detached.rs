@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::App;
+
+/// 后台播放器检查播放状态和控制命令的间隔
+const TICK: Duration = Duration::from_millis(50);
+
+/// 界面退出后启动后台播放器，接着播放界面刚保存的会话
+pub fn spawn(playlist: Option<&Path>) -> Result<()> {
+    let mut command = Command::new(env::current_exe()?);
+    command.arg("--detached");
+    if let Some(playlist) = playlist {
+        command.arg(playlist);
+    }
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    // 放进单独的进程组，关闭终端时不会随前台进程一起收到挂断信号
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command.spawn().context("无法启动后台播放器")?;
+    Ok(())
+}
+
+/// `music_tui --detached [播放列表]`：没有界面的播放器，只接受控制端口的命令。
+/// `music_tui ctl quit` 停止，重新打开界面时收到 `attach`，保存会话后退出由界面接管
+pub fn run(args: &[OsString]) -> Result<()> {
+    let playlist = args.first().map(PathBuf::from);
+    let mut app = App::new(playlist)?;
+    app.listen_detached()?;
+    app.take_over_session()?;
+
+    while !app.exit_requested && !app.attach_requested {
+        app.check_and_auto_next()?;
+        app.update_schedule()?;
+        app.poll_remote()?;
+        app.update_play_time();
+        app.poll_background();
+        thread::sleep(TICK);
+    }
+
+    app.save_session_on_exit()
+}
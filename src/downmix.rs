@@ -0,0 +1,131 @@
+use rodio::Source;
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::time::Duration;
+
+/// 每个声道位置混入 (左, 右) 的系数，按 WAV/symphonia 声道掩码的位顺序排列：
+/// 前左、前右、前中、LFE、后左、后右、前左中、前右中、后中、侧左、侧右、顶中、顶前左、顶前中、顶前右、
+/// 顶后左、顶后中、顶后右。中置和环绕按 ITU-R BS.775 降低 3 dB，LFE 不混入
+const COEFFICIENTS: [(f32, f32); 18] = [
+    (1.0, 0.0),
+    (0.0, 1.0),
+    (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (0.0, 0.0),
+    (FRAC_1_SQRT_2, 0.0),
+    (0.0, FRAC_1_SQRT_2),
+    (1.0, 0.0),
+    (0.0, 1.0),
+    (0.5, 0.5),
+    (FRAC_1_SQRT_2, 0.0),
+    (0.0, FRAC_1_SQRT_2),
+    (0.5, 0.5),
+    (FRAC_1_SQRT_2, 0.0),
+    (0.5, 0.5),
+    (0.0, FRAC_1_SQRT_2),
+    (0.5, 0.0),
+    (0.35, 0.35),
+    (0.0, 0.5),
+];
+
+/// 文件没有写明声道布局时按声道数采用的常见布局（和 WAV、FLAC 的默认顺序一致）
+fn default_layout(channels: usize) -> u32 {
+    match channels {
+        3 => 0x7,   // 前左 前右 前中
+        4 => 0x33,  // 前左 前右 后左 后右
+        5 => 0x37,  // 5.0
+        6 => 0x3F,  // 5.1
+        7 => 0x70F, // 6.1：前三个 + LFE + 后中 + 侧左右
+        8 => 0x63F, // 7.1
+        n => (1u32 << n.min(31)) - 1,
+    }
+}
+
+/// 把多声道（5.1、7.1 等）音源按标准系数混成立体声的 Source 适配器。
+/// 不处理时 rodio 只会保留前两个声道，中置的人声和环绕声道都会丢掉
+pub struct Downmix<S> {
+    input: S,
+    /// 每个输入声道的 (左, 右) 系数
+    gains: Vec<(f32, f32)>,
+    pending_right: Option<f32>,
+}
+
+impl<S> Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    /// layout 是 symphonia 的声道掩码，位数和声道数不一致时按声道数推测
+    pub fn new(input: S, layout: Option<u32>) -> Self {
+        let channels = input.channels() as usize;
+        let layout = layout
+            .filter(|mask| mask.count_ones() as usize == channels)
+            .unwrap_or_else(|| default_layout(channels));
+        let mut gains: Vec<(f32, f32)> = (0..32)
+            .filter(|bit| layout & (1 << bit) != 0)
+            .map(|bit| COEFFICIENTS.get(bit).copied().unwrap_or_default())
+            .collect();
+
+        // 所有声道同时满幅时也不会削波
+        let left: f32 = gains.iter().map(|(left, _)| left).sum();
+        let right: f32 = gains.iter().map(|(_, right)| right).sum();
+        let scale = left.max(right).max(1.0);
+        for (left, right) in &mut gains {
+            *left /= scale;
+            *right /= scale;
+        }
+        Downmix {
+            input,
+            gains,
+            pending_right: None,
+        }
+    }
+}
+
+/// 是否需要混成立体声：passthrough 时输出设备的声道数够用就原样输出
+pub fn needed(channels: u16, output_channels: Option<u16>, passthrough: bool) -> bool {
+    channels > 2 && !(passthrough && output_channels.is_some_and(|output| output >= channels))
+}
+
+impl<S> Iterator for Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+        let (mut left, mut right) = (0.0, 0.0);
+        for (left_gain, right_gain) in &self.gains {
+            // 不完整的最后一帧丢弃
+            let sample = self.input.next()?;
+            left += sample * left_gain;
+            right += sample * right_gain;
+        }
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
+
+impl<S> Source for Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let pending = usize::from(self.pending_right.is_some());
+        self.input
+            .current_frame_len()
+            .map(|len| len / self.gains.len().max(1) * 2 + pending)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -0,0 +1,563 @@
+use anyhow::{anyhow, bail, Result};
+use rodio::Source;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::metadata::ProbeResult;
+
+/// DSD64 的采样率，DSD128、DSD256 是它的整数倍
+const DSD64_RATE: u32 = 2_822_400;
+/// DSD64 每 32 个 1 位样本（4 个字节）输出一个 PCM 样本，得到 88.2 kHz
+const DSD64_DECIMATION_BYTES: usize = 4;
+
+/// DSD 转 PCM 的低通滤波器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsdFilter {
+    /// 512 阶（DSD64），开销小，20 kHz 附近略有衰减
+    Fast,
+    /// 1536 阶（DSD64），过渡带更窄，滤掉更多超声波噪声
+    Sharp,
+}
+
+impl DsdFilter {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "fast" => Some(DsdFilter::Fast),
+            "sharp" => Some(DsdFilter::Sharp),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DsdFilter::Fast => "fast",
+            DsdFilter::Sharp => "sharp",
+        }
+    }
+
+    /// (DSD64 下的滤波器长度（字节）, 截止频率 Hz, 是否用 Blackman 窗)
+    fn design(self) -> (usize, f64, bool) {
+        match self {
+            DsdFilter::Fast => (64, 30_000.0, false),
+            DsdFilter::Sharp => (192, 26_000.0, true),
+        }
+    }
+}
+
+/// DSD 文件的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsdOutput {
+    /// 解码时转换成 88.2 kHz（DSD128 为 176.4 kHz，依此类推）的 PCM
+    Pcm,
+    /// 按 DoP 标准把 DSD 数据打包进 24 位 PCM 帧，交给支持 DSD 的 DAC 还原
+    Dop,
+}
+
+impl DsdOutput {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "pcm" => Some(DsdOutput::Pcm),
+            "dop" => Some(DsdOutput::Dop),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DsdOutput::Pcm => "pcm",
+            DsdOutput::Dop => "dop",
+        }
+    }
+}
+
+pub fn is_dsd(path: &Path) -> bool {
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    matches!(extension.as_str(), "dsf" | "dff")
+}
+
+/// 文件头中的格式信息
+#[derive(Debug, Clone, Copy)]
+pub struct DsdInfo {
+    /// 1 位样本的采样率，如 2822400
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 每个声道的样本数
+    pub samples: u64,
+}
+
+impl DsdInfo {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    /// DSD64、DSD128 等名称
+    pub fn name(&self) -> String {
+        format!("DSD{}", self.sample_rate / 44_100)
+    }
+
+    /// DoP 输出的 PCM 采样率：每帧携带 16 个 1 位样本
+    pub fn dop_rate(&self) -> u32 {
+        self.sample_rate / 16
+    }
+
+    fn frames(&self) -> u64 {
+        self.samples / 8
+    }
+}
+
+/// 读取文件头，给播放列表显示时长和格式。DSF 末尾的 ID3 标签暂不读取
+pub fn probe(path: &Path) -> ProbeResult {
+    let Ok(reader) = DsdReader::open(path) else {
+        return ProbeResult::default();
+    };
+    let info = reader.info;
+    ProbeResult {
+        duration: Some(info.duration()),
+        sample_rate: Some(info.sample_rate),
+        codec: Some(info.name()),
+        bits_per_sample: None,
+        bitrate_kbps: Some(info.sample_rate / 1000 * info.channels as u32),
+        tags: Default::default(),
+    }
+}
+
+/// 数据在文件中的排列方式
+enum Layout {
+    /// DSF：每个声道 block_size 字节的块轮流存放；lsb_first 时字节内时间顺序从低位开始
+    Dsf { block_size: usize, lsb_first: bool },
+    /// DFF：每帧每个声道一个字节，字节内高位在前
+    Dff,
+}
+
+/// 按时间顺序逐帧读出 DSD 数据，每帧每个声道一个字节，字节内高位是较早的样本
+pub struct DsdReader {
+    reader: BufReader<File>,
+    pub info: DsdInfo,
+    layout: Layout,
+    data_start: u64,
+    /// 下一帧的序号
+    frame: u64,
+    /// DSF 当前读入的一组块（所有声道）
+    block: Vec<u8>,
+    current: Vec<u8>,
+}
+
+impl DsdReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let (info, layout, data_start) = match &magic {
+            b"DSD " => read_dsf_header(&mut reader)?,
+            b"FRM8" => read_dff_header(&mut reader)?,
+            _ => bail!("不是 DSF 或 DFF 文件"),
+        };
+        if info.channels == 0 || info.sample_rate < DSD64_RATE {
+            bail!("不支持的 DSD 格式: {} 声道 {} Hz", info.channels, info.sample_rate);
+        }
+
+        let mut dsd = DsdReader {
+            reader,
+            info,
+            layout,
+            data_start,
+            frame: 0,
+            block: Vec::new(),
+            current: vec![0; info.channels as usize],
+        };
+        dsd.seek_frame(0)?;
+        Ok(dsd)
+    }
+
+    /// 定位到第 frame 帧（每声道第 frame 个字节）
+    fn seek_frame(&mut self, frame: u64) -> Result<()> {
+        let frame = frame.min(self.info.frames());
+        let channels = self.info.channels as u64;
+        match self.layout {
+            Layout::Dsf { block_size, .. } => {
+                let group = frame / block_size as u64;
+                self.reader.seek(SeekFrom::Start(self.data_start + group * block_size as u64 * channels))?;
+                self.block.clear();
+                self.frame = group * block_size as u64;
+                // 定位到块的中间时先读入整组块
+                if frame > self.frame {
+                    self.read_block()?;
+                }
+            }
+            Layout::Dff => {
+                self.reader.seek(SeekFrom::Start(self.data_start + frame * channels))?;
+            }
+        }
+        self.frame = frame;
+        Ok(())
+    }
+
+    fn read_block(&mut self) -> Result<()> {
+        let Layout::Dsf { block_size, .. } = self.layout else {
+            return Ok(());
+        };
+        self.block.resize(block_size * self.info.channels as usize, 0);
+        self.reader.read_exact(&mut self.block)?;
+        Ok(())
+    }
+
+    /// 读出下一帧，文件结束时返回 None
+    pub fn next_frame(&mut self) -> Option<&[u8]> {
+        if self.frame >= self.info.frames() {
+            return None;
+        }
+        match self.layout {
+            Layout::Dsf { block_size, lsb_first } => {
+                let offset = (self.frame % block_size as u64) as usize;
+                if offset == 0 {
+                    self.read_block().ok()?;
+                }
+                for (channel, byte) in self.current.iter_mut().enumerate() {
+                    let value = self.block[channel * block_size + offset];
+                    *byte = if lsb_first { value.reverse_bits() } else { value };
+                }
+            }
+            Layout::Dff => self.reader.read_exact(&mut self.current).ok()?,
+        }
+        self.frame += 1;
+        Some(&self.current)
+    }
+}
+
+fn read_u32_le(reader: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64_le(reader: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u64_be(reader: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// DSF：`DSD ` 块（28 字节）之后是 `fmt ` 块和 `data` 块，数值都是小端
+fn read_dsf_header(reader: &mut BufReader<File>) -> Result<(DsdInfo, Layout, u64)> {
+    let header_size = read_u64_le(reader)?;
+    reader.seek(SeekFrom::Start(header_size))?;
+
+    let mut id = [0; 4];
+    reader.read_exact(&mut id)?;
+    if &id != b"fmt " {
+        bail!("DSF 文件缺少 fmt 块");
+    }
+    let fmt_size = read_u64_le(reader)?;
+    let _version = read_u32_le(reader)?;
+    let format_id = read_u32_le(reader)?;
+    if format_id != 0 {
+        bail!("不支持的 DSF 编码 {}", format_id);
+    }
+    let _channel_type = read_u32_le(reader)?;
+    let channels = read_u32_le(reader)?;
+    let sample_rate = read_u32_le(reader)?;
+    let bits_per_sample = read_u32_le(reader)?;
+    let samples = read_u64_le(reader)?;
+    let block_size = read_u32_le(reader)? as usize;
+    if block_size == 0 {
+        bail!("DSF 块大小为 0");
+    }
+
+    reader.seek(SeekFrom::Start(header_size + fmt_size))?;
+    reader.read_exact(&mut id)?;
+    if &id != b"data" {
+        bail!("DSF 文件缺少 data 块");
+    }
+    let _data_size = read_u64_le(reader)?;
+    let data_start = reader.stream_position()?;
+
+    let info = DsdInfo {
+        sample_rate,
+        channels: channels as u16,
+        samples,
+    };
+    let layout = Layout::Dsf {
+        block_size,
+        lsb_first: bits_per_sample == 1,
+    };
+    Ok((info, layout, data_start))
+}
+
+/// DFF (DSDIFF)：`FRM8` 容器中的 `PROP` 块记录采样率和声道数，`DSD ` 块是交错的数据，数值都是大端
+fn read_dff_header(reader: &mut BufReader<File>) -> Result<(DsdInfo, Layout, u64)> {
+    let _form_size = read_u64_be(reader)?;
+    let mut form = [0; 4];
+    reader.read_exact(&mut form)?;
+    if &form != b"DSD " {
+        bail!("不是 DSDIFF 文件");
+    }
+
+    let mut sample_rate = None;
+    let mut channels = None;
+    loop {
+        let mut id = [0; 4];
+        reader.read_exact(&mut id).map_err(|_| anyhow!("DFF 文件缺少 DSD 数据块"))?;
+        let size = read_u64_be(reader)?;
+        let start = reader.stream_position()?;
+        match &id {
+            b"PROP" => {
+                let mut kind = [0; 4];
+                reader.read_exact(&mut kind)?;
+                while reader.stream_position()? < start + size {
+                    let mut sub = [0; 4];
+                    reader.read_exact(&mut sub)?;
+                    let sub_size = read_u64_be(reader)?;
+                    let sub_start = reader.stream_position()?;
+                    let mut bytes = [0; 4];
+                    match &sub {
+                        b"FS  " => {
+                            reader.read_exact(&mut bytes)?;
+                            sample_rate = Some(u32::from_be_bytes(bytes));
+                        }
+                        b"CHNL" => {
+                            reader.read_exact(&mut bytes[..2])?;
+                            channels = Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+                        }
+                        b"CMPR" => {
+                            reader.read_exact(&mut bytes)?;
+                            if &bytes != b"DSD " {
+                                bail!("不支持压缩的 DST 格式");
+                            }
+                        }
+                        _ => {}
+                    }
+                    reader.seek(SeekFrom::Start(sub_start + sub_size + sub_size % 2))?;
+                }
+            }
+            b"DSD " => {
+                let (Some(sample_rate), Some(channels)) = (sample_rate, channels) else {
+                    bail!("DFF 文件缺少采样率或声道数");
+                };
+                let samples = size / channels.max(1) as u64 * 8;
+                let info = DsdInfo {
+                    sample_rate,
+                    channels,
+                    samples,
+                };
+                return Ok((info, Layout::Dff, start));
+            }
+            b"DST " => bail!("不支持压缩的 DST 格式"),
+            _ => {}
+        }
+        // 块的长度是奇数时后面有一个填充字节
+        reader.seek(SeekFrom::Start(start + size + size % 2))?;
+    }
+}
+
+/// 把 DSD 转换成 PCM 的 Source：FIR 低通滤波后抽取。每 8 个系数按一个字节的 256 种取值预先求和，
+/// 计算一个输出样本只需要查表累加
+pub struct DsdToPcm {
+    reader: DsdReader,
+    /// tables[k][byte]：从最早的字节数起第 k 个字节对输出的贡献
+    tables: Vec<[f32; 256]>,
+    /// 每个声道最近的 tables.len() 个字节
+    history: Vec<VecDeque<u8>>,
+    decimation: usize,
+    sample_rate: u32,
+    frame: Vec<f32>,
+    channel: usize,
+    ended: bool,
+}
+
+impl DsdToPcm {
+    pub fn new(mut reader: DsdReader, start: Duration, filter: DsdFilter) -> Result<Self> {
+        let info = reader.info;
+        let multiple = (info.sample_rate / DSD64_RATE).max(1) as usize;
+        let (length, cutoff, blackman) = filter.design();
+        let tables = filter_tables(length * multiple, cutoff / info.sample_rate as f64, blackman);
+        let decimation = DSD64_DECIMATION_BYTES * multiple;
+
+        reader.seek_frame((start.as_secs_f64() * info.sample_rate as f64 / 8.0) as u64)?;
+        Ok(DsdToPcm {
+            reader,
+            history: vec![VecDeque::from(vec![0x69; tables.len()]); info.channels as usize],
+            tables,
+            decimation,
+            sample_rate: info.sample_rate / (decimation as u32 * 8),
+            frame: Vec::new(),
+            channel: 0,
+            ended: false,
+        })
+    }
+
+    fn next_frame(&mut self) -> bool {
+        for _ in 0..self.decimation {
+            let Some(bytes) = self.reader.next_frame() else {
+                return false;
+            };
+            for (history, byte) in self.history.iter_mut().zip(bytes) {
+                history.pop_front();
+                history.push_back(*byte);
+            }
+        }
+        self.frame.clear();
+        for history in &self.history {
+            let sample: f32 = history.iter().zip(&self.tables).map(|(byte, table)| table[*byte as usize]).sum();
+            self.frame.push(sample);
+        }
+        self.channel = 0;
+        true
+    }
+}
+
+/// 按字节分组的滤波器系数表。cutoff 是相对 DSD 采样率的截止频率
+fn filter_tables(bytes: usize, cutoff: f64, blackman: bool) -> Vec<[f32; 256]> {
+    let taps = bytes * 8;
+    let center = (taps - 1) as f64 / 2.0;
+    let mut coefficients: Vec<f64> = (0..taps)
+        .map(|n| {
+            let x = n as f64 - center;
+            let sinc = if x == 0.0 { 1.0 } else { (2.0 * PI * cutoff * x).sin() / (PI * x) / (2.0 * cutoff) };
+            let phase = 2.0 * PI * n as f64 / (taps - 1) as f64;
+            let window = match blackman {
+                true => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                false => 0.5 - 0.5 * phase.cos(),
+            };
+            sinc * window
+        })
+        .collect();
+    // 直流增益为 1：全 1 的 DSD 数据对应满幅。SACD 的 0 dB 是 50% 调制，转换后约为 -6 dBFS，留出余量
+    let sum: f64 = coefficients.iter().sum();
+    for coefficient in &mut coefficients {
+        *coefficient /= sum;
+    }
+
+    coefficients
+        .chunks(8)
+        .map(|chunk| {
+            let mut table = [0.0; 256];
+            for (byte, value) in table.iter_mut().enumerate() {
+                let total: f64 = chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(bit, c)| if byte & (0x80 >> bit) != 0 { *c } else { -*c })
+                    .sum();
+                *value = total as f32;
+            }
+            table
+        })
+        .collect()
+}
+
+impl Iterator for DsdToPcm {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel >= self.frame.len() && (self.ended || !self.next_frame()) {
+            self.ended = true;
+            return None;
+        }
+        let sample = self.frame[self.channel];
+        self.channel += 1;
+        Some(sample)
+    }
+}
+
+impl Source for DsdToPcm {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.reader.info.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.reader.info.duration())
+    }
+}
+
+/// DoP (DSD over PCM) 标记字节，每帧在两个值之间交替
+const DOP_MARKERS: [u8; 2] = [0x05, 0xFA];
+
+/// 把 DSD 数据打包成 DoP 帧：每个 24 位样本的最高字节是标记，后两个字节是 16 个 1 位样本。
+/// 样本按浮点交给 rodio，24 位整数除以 2^23 没有精度损失，输出设备必须是 24 或 32 位整数格式
+pub struct Dop {
+    reader: DsdReader,
+    frame: Vec<f32>,
+    channel: usize,
+    marker: usize,
+}
+
+impl Dop {
+    pub fn new(mut reader: DsdReader, start: Duration) -> Result<Self> {
+        let info = reader.info;
+        // 从偶数帧开始，保证每个 DoP 帧的两个字节来自同一段
+        let frame = (start.as_secs_f64() * info.sample_rate as f64 / 8.0) as u64 & !1;
+        reader.seek_frame(frame)?;
+        Ok(Dop {
+            reader,
+            frame: Vec::new(),
+            channel: 0,
+            marker: 0,
+        })
+    }
+
+    fn next_frame(&mut self) -> bool {
+        let Some(first) = self.reader.next_frame().map(<[u8]>::to_vec) else {
+            return false;
+        };
+        let Some(second) = self.reader.next_frame() else {
+            return false;
+        };
+        let marker = DOP_MARKERS[self.marker];
+        self.marker ^= 1;
+        self.frame.clear();
+        for (high, low) in first.iter().zip(second) {
+            // 24 位有符号数，标记 0xFA 时为负
+            let value = i32::from_be_bytes([0, marker, *high, *low]) << 8 >> 8;
+            self.frame.push(value as f32 / 8_388_608.0);
+        }
+        self.channel = 0;
+        true
+    }
+}
+
+impl Iterator for Dop {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel >= self.frame.len() && !self.next_frame() {
+            return None;
+        }
+        let sample = self.frame[self.channel];
+        self.channel += 1;
+        Some(sample)
+    }
+}
+
+impl Source for Dop {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.reader.info.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.info.dop_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.reader.info.duration())
+    }
+}
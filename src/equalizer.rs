@@ -0,0 +1,244 @@
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::storage;
+
+/// 各频段的中心频率 (Hz)
+pub const BANDS: [f32; 5] = [60.0, 230.0, 910.0, 3600.0, 14000.0];
+
+const GENRE_MAP_FILE: &str = "eq_genres.tsv";
+
+#[derive(Debug, Clone, Copy)]
+pub struct EqPreset {
+    pub name: &'static str,
+    /// 每个频段的增益 (dB)
+    pub gains: [f32; 5],
+}
+
+pub const PRESETS: [EqPreset; 8] = [
+    EqPreset { name: "Flat", gains: [0.0, 0.0, 0.0, 0.0, 0.0] },
+    EqPreset { name: "Rock", gains: [4.0, 2.0, -1.0, 2.0, 4.0] },
+    EqPreset { name: "Pop", gains: [-1.0, 2.0, 4.0, 2.0, -1.0] },
+    EqPreset { name: "Jazz", gains: [3.0, 1.0, -1.0, 1.0, 3.0] },
+    EqPreset { name: "Classical", gains: [3.0, 0.0, 0.0, 1.0, 3.0] },
+    EqPreset { name: "Electronic", gains: [5.0, 3.0, 0.0, 1.0, 4.0] },
+    EqPreset { name: "HipHop", gains: [5.0, 3.0, 0.0, -1.0, 2.0] },
+    EqPreset { name: "Vocal", gains: [-2.0, -1.0, 3.0, 3.0, 0.0] },
+];
+
+pub fn preset_index(name: &str) -> Option<usize> {
+    PRESETS.iter().position(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+/// 流派 → 预设的映射，可在数据目录的 eq_genres.tsv 中配置（每行: 流派关键字 \t 预设名）
+pub fn load_genre_map() -> Vec<(String, usize)> {
+    let records = storage::read_records(GENRE_MAP_FILE).unwrap_or_default();
+    let configured: Vec<(String, usize)> = records
+        .into_iter()
+        .filter_map(|record| {
+            let [genre, preset] = <[String; 2]>::try_from(record).ok()?;
+            Some((genre.to_lowercase(), preset_index(&preset)?))
+        })
+        .collect();
+    if !configured.is_empty() {
+        return configured;
+    }
+
+    [
+        ("rock", "Rock"),
+        ("metal", "Rock"),
+        ("pop", "Pop"),
+        ("jazz", "Jazz"),
+        ("blues", "Jazz"),
+        ("classical", "Classical"),
+        ("electronic", "Electronic"),
+        ("dance", "Electronic"),
+        ("house", "Electronic"),
+        ("hip hop", "HipHop"),
+        ("hip-hop", "HipHop"),
+        ("rap", "HipHop"),
+        ("vocal", "Vocal"),
+        ("流行", "Pop"),
+        ("摇滚", "Rock"),
+        ("爵士", "Jazz"),
+        ("古典", "Classical"),
+        ("电子", "Electronic"),
+        ("说唱", "HipHop"),
+    ]
+    .into_iter()
+    .filter_map(|(genre, preset)| Some((genre.to_string(), preset_index(preset)?)))
+    .collect()
+}
+
+/// 按流派标签查找预设，找不到时返回 None
+pub fn preset_for_genre(genre_map: &[(String, usize)], genre: &str) -> Option<usize> {
+    let genre = genre.to_lowercase();
+    genre_map
+        .iter()
+        .find(|(keyword, _)| genre.contains(keyword.as_str()))
+        .map(|(_, preset)| *preset)
+}
+
+/// 播放线程和界面线程共享的均衡器设置
+#[derive(Debug, Default)]
+pub struct EqControl {
+    gains: Mutex<[f32; 5]>,
+    version: AtomicU32,
+}
+
+impl EqControl {
+    pub fn set_gains(&self, gains: [f32; 5]) {
+        if let Ok(mut current) = self.gains.lock() {
+            *current = gains;
+        }
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    fn gains(&self) -> [f32; 5] {
+        self.gains.lock().map(|gains| *gains).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    /// RBJ Audio EQ Cookbook 中的峰值滤波器
+    fn peaking(frequency: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let frequency = frequency.min(sample_rate * 0.45);
+        let q = 1.0;
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos_w0 = w0.cos();
+
+        let a0 = 1.0 + alpha / a;
+        Biquad {
+            b0: (1.0 + alpha * a) / a0,
+            b1: (-2.0 * cos_w0) / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: (-2.0 * cos_w0) / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FilterState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+/// 对音频流应用多段均衡的 Source 适配器
+pub struct Equalizer<S> {
+    input: S,
+    control: Arc<EqControl>,
+    version: u32,
+    filters: Vec<Biquad>,
+    states: Vec<Vec<FilterState>>,
+    channel: usize,
+}
+
+impl<S> Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, control: Arc<EqControl>) -> Self {
+        let mut equalizer = Equalizer {
+            input,
+            control,
+            version: 0,
+            filters: Vec::new(),
+            states: Vec::new(),
+            channel: 0,
+        };
+        equalizer.update_filters();
+        equalizer
+    }
+
+    fn update_filters(&mut self) {
+        self.version = self.control.version.load(Ordering::Acquire);
+        let gains = self.control.gains();
+        let sample_rate = self.input.sample_rate() as f32;
+
+        // 全部为 0 dB 时不做处理
+        self.filters = BANDS
+            .iter()
+            .zip(gains)
+            .filter(|(_, gain)| gain.abs() > 0.01)
+            .map(|(frequency, gain)| Biquad::peaking(*frequency, gain, sample_rate))
+            .collect();
+        let channels = self.input.channels().max(1) as usize;
+        self.states = vec![vec![FilterState::default(); self.filters.len()]; channels];
+    }
+}
+
+impl<S> Iterator for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+
+        if self.control.version.load(Ordering::Relaxed) != self.version {
+            self.update_filters();
+        }
+        if self.filters.is_empty() {
+            return Some(sample);
+        }
+
+        let channel = self.channel % self.states.len();
+        self.channel = (channel + 1) % self.states.len();
+
+        let mut value = sample;
+        for (filter, state) in self.filters.iter().zip(self.states[channel].iter_mut()) {
+            let output = filter.b0 * value + filter.b1 * state.x1 + filter.b2 * state.x2
+                - filter.a1 * state.y1
+                - filter.a2 * state.y2;
+            state.x2 = state.x1;
+            state.x1 = value;
+            state.y2 = state.y1;
+            state.y1 = output;
+            value = output;
+        }
+        Some(value.clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
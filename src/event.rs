@@ -1,12 +1,25 @@
 use anyhow::Result;
-use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent};
+use crossterm::event::{
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent,
+    KeyModifiers,
+};
+use crossterm::cursor::MoveToColumn;
 use crossterm::execute;
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
 use std::io;
+use std::process::Command;
 
 use std::time::{Duration, Instant};
 
-use crate::app::App;
+use crate::app::{App, Focus, PlaybackState, View};
+use crate::keymap::Action;
+use crate::macros::{self, Macros};
+use crate::status_line;
+
+pub type Tui = ratatui::Terminal<ratatui::backend::CrosstermBackend<io::Stdout>>;
 
 pub struct EventHandler {
     pub tick_rate: Duration,
@@ -22,129 +35,683 @@ impl EventHandler {
     }
 
     pub fn next(&mut self) -> Result<Option<Event>> {
-        let timeout = self.tick_rate.checked_sub(self.last_tick.elapsed()).unwrap_or_else(|| Duration::from_secs(0));
+        self.next_before(None)
+    }
+
+    /// 等待输入事件，最多等到下一次 tick 或 deadline（先到者为准）。
+    /// 输入事件到达时立即返回，不会推迟 tick。
+    pub fn next_before(&mut self, deadline: Option<Instant>) -> Result<Option<Event>> {
+        let mut timeout = self.tick_rate.saturating_sub(self.last_tick.elapsed());
+        if let Some(deadline) = deadline {
+            timeout = timeout.min(deadline.saturating_duration_since(Instant::now()));
+        }
 
         if crossterm::event::poll(timeout)? {
-            let event = event::read()?;
-            self.last_tick = Instant::now();
-            Ok(Some(event))
+            Ok(Some(event::read()?))
         } else {
             Ok(None)
         }
     }
+
+    /// 距离上一次 tick 已经超过 tick_rate 时返回 true 并开始下一个周期
+    pub fn tick(&mut self) -> bool {
+        if self.last_tick.elapsed() >= self.tick_rate {
+            self.last_tick = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
 }
 
-pub fn setup_terminal() -> Result<ratatui::Terminal<ratatui::backend::CrosstermBackend<io::Stdout>>> {
+pub fn setup_terminal() -> Result<Tui> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = ratatui::backend::CrosstermBackend::new(stdout);
     let terminal = ratatui::Terminal::new(backend)?;
     Ok(terminal)
 }
 
-pub fn restore_terminal(
-    terminal: &mut ratatui::Terminal<ratatui::backend::CrosstermBackend<io::Stdout>>,
-) -> Result<()> {
+pub fn restore_terminal(terminal: &mut Tui) -> Result<()> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
     Ok(())
 }
 
+/// 暂时退出全屏界面运行外部程序（如在编辑器中修改标签），结束后恢复界面
+pub fn run_external(terminal: &mut Tui, mut command: Command) -> Result<()> {
+    restore_terminal(terminal)?;
+    let status = command.status();
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(anyhow::anyhow!("{}", status)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// 收起界面：关闭全屏界面，终端中只保留一行状态，播放继续进行。程序仍在这个终端中运行，
+/// 关闭终端会结束播放，需要关闭终端时用脱离终端（见 detached.rs）。
+/// 返回 true 表示恢复界面，false 表示退出程序。
+pub fn run_minimized(terminal: &mut Tui, app: &mut App, event_handler: &mut EventHandler) -> Result<bool> {
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    let mut stdout = io::stdout();
+
+    loop {
+        app.check_and_auto_next()?;
+        app.update_schedule()?;
+        app.poll_remote()?;
+        app.update_play_time();
+        if app.exit_requested {
+            execute!(stdout, Print("\r\n"))?;
+            return Ok(false);
+        }
+
+        // 和界面底部的状态栏使用同一个模板
+        let status = format!(
+            "{}  Enter: 返回界面  空格: 播放/暂停  N: 下一曲  Q: 退出",
+            status_line::format(app.config.minimized_format(), app),
+        );
+        execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine), Print(status))?;
+
+        event_handler.tick();
+        if let Some(Event::Key(key_event)) = event_handler.next()? {
+            match key_event.code {
+                KeyCode::Enter => break,
+                KeyCode::Char('q') | KeyCode::Char('Q') => {
+                    execute!(stdout, Print("\r\n"))?;
+                    return Ok(false);
+                }
+                KeyCode::Char(' ') => match app.playback_state {
+                    PlaybackState::Playing => app.pause(),
+                    PlaybackState::Paused => app.resume(),
+                    PlaybackState::Stopped => app.play()?,
+                },
+                KeyCode::Char('n') | KeyCode::Char('N') => app.switch_track(true)?,
+                _ => {}
+            }
+        }
+    }
+
+    execute!(
+        stdout,
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.clear()?;
+    Ok(true)
+}
+
 pub fn handle_events(app: &mut App, event: Event) -> Result<bool> {
     match event {
         Event::Key(key_event) => handle_key_event(app, key_event)?,
         Event::Mouse(_) => {}
-        Event::Resize(_, _) => {}
-        Event::FocusGained | Event::FocusLost | Event::Paste(_) => {}
+        Event::Resize(_, _) => app.resized = true,
+        Event::Paste(text) => {
+            // 输入框打开时粘贴到输入框中
+            let line = text.lines().next().unwrap_or_default();
+            match app.prompt.as_mut() {
+                Some(prompt) => prompt.input.push_str(line),
+                None if app.search.is_some() => app.search_push(line),
+                None => app.paste(&text),
+            }
+        }
+        Event::FocusGained | Event::FocusLost => {}
     }
     Ok(true)
 }
 
 fn handle_key_event(app: &mut App, key_event: KeyEvent) -> Result<()> {
-    match key_event.code {
-        KeyCode::Char('q') | KeyCode::Char('Q') => {
-            return Err(anyhow::anyhow!("Quit"));
-        }
-        KeyCode::Char(' ') => {
-            match app.playback_state {
-                crate::app::PlaybackState::Playing => app.pause(),
-                crate::app::PlaybackState::Paused => app.resume(),
-                crate::app::PlaybackState::Stopped => {
-                    app.play()?;
-                }
+    if let Some(pending) = app.macros.pending.take() {
+        return handle_macro_register_key(app, pending, key_event);
+    }
+    let in_chord = app.awaiting_chord();
+    app.macros.record(key_event, in_chord);
+    
+    if app.restore_prompt.is_some() {
+        return handle_restore_key(app, key_event);
+    }
+    
+    if app.confirm_quit {
+        return handle_quit_confirm_key(app, key_event);
+    }
+    
+    if app.playlist_reload.is_some() {
+        return handle_playlist_reload_key(app, key_event);
+    }
+    
+    // 配对二维码按任意键关闭
+    if app.pairing.is_some() {
+        app.pairing = None;
+        return Ok(());
+    }
+    
+    if app.prompt.is_some() {
+        return handle_prompt_key(app, key_event);
+    }
+    
+    if app.key_capture.is_some() {
+        app.capture_key(key_event.code);
+        return Ok(());
+    }
+    
+    if app.track_editor.is_some() {
+        return handle_track_editor_key(app, key_event);
+    }
+    
+    if app.details.is_some() {
+        handle_details_key(app, key_event);
+        return Ok(());
+    }
+    
+    if app.scrub.is_some() {
+        return handle_scrub_key(app, key_event);
+    }
+    
+    if app.search.is_some() {
+        return handle_search_key(app, key_event);
+    }
+    
+    // Ctrl+方向键调整面板大小
+    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+        let handled = match key_event.code {
+            KeyCode::Up => app.resize_playlist(true),
+            KeyCode::Down => app.resize_playlist(false),
+            KeyCode::Left => app.resize_info(true),
+            KeyCode::Right => app.resize_info(false),
+            KeyCode::Char('r') => {
+                app.redo();
+                true
             }
+            KeyCode::Char('f') => {
+                app.open_search();
+                true
+            }
+            _ => false,
+        };
+        if handled {
+            return Ok(());
         }
-        KeyCode::Char('n') | KeyCode::Char('N') => {
-            app.next()?;
-        }
-        KeyCode::Char('p') | KeyCode::Char('P') => {
-            app.previous()?;
-        }
-        KeyCode::Char('s') | KeyCode::Char('S') => {
-            app.toggle_shuffle();
-        }
-        KeyCode::Char('+') | KeyCode::Char('=') => {
-            let new_volume = (app.volume + 0.1).min(1.0);
-            app.set_volume(new_volume);
-        }
-        KeyCode::Char('-') => {
-            let new_volume = (app.volume - 0.1).max(0.0);
-            app.set_volume(new_volume);
-        }
-        KeyCode::Char('0') => {
-            app.set_volume(0.0);
+    }
+    
+    // 组合键按到一半时，下一个键直接交给按键绑定，不让面板拿走
+    if app.awaiting_chord() {
+        if let Some(action) = app.press_key(key_event.code) {
+            run_action(app, action)?;
         }
-        KeyCode::Char('1') => {
-            app.set_volume(0.1);
+        return Ok(());
+    }
+    
+    // 获得焦点的面板优先处理按键，未处理的再作为全局按键
+    let handled = match app.focus {
+        Focus::Playlist if app.view == View::Bookmarks => handle_bookmarks_key(app, key_event)?,
+        Focus::Playlist if app.view == View::Artists => handle_artists_key(app, key_event),
+        Focus::Playlist if app.view == View::Collections => handle_collections_key(app, key_event),
+        Focus::Playlist if app.view == View::Snapshots => handle_snapshots_key(app, key_event),
+        Focus::Playlist if app.view == View::Genres => handle_genres_key(app, key_event),
+        Focus::Playlist if app.view == View::Settings => handle_settings_key(app, key_event),
+        Focus::Playlist if app.view == View::Editor => handle_editor_library_key(app, key_event)?,
+        Focus::Queue if app.view == View::Editor => handle_editor_queue_key(app, key_event)?,
+        Focus::Playlist => handle_playlist_key(app, key_event)?,
+        Focus::Queue => handle_queue_key(app, key_event)?,
+        Focus::Info => handle_info_key(app, key_event),
+        Focus::Lyrics => handle_lyrics_key(app, key_event)?,
+        Focus::Jobs => handle_jobs_key(app, key_event),
+    };
+    if handled {
+        return Ok(());
+    }
+    
+    match key_event.code {
+        KeyCode::Tab => {
+            app.cycle_focus(true);
+            return Ok(());
         }
-        KeyCode::Char('2') => {
-            app.set_volume(0.2);
+        KeyCode::BackTab => {
+            app.cycle_focus(false);
+            return Ok(());
         }
-        KeyCode::Char('3') => {
-            app.set_volume(0.3);
+        // 数字键快速设置音量 (0-90%)
+        KeyCode::Char(c @ '0'..='9') => {
+            app.set_volume(c.to_digit(10).unwrap_or_default() as f32 / 10.0);
+            return Ok(());
         }
-        KeyCode::Char('4') => {
-            app.set_volume(0.4);
+        _ => {}
+    }
+    
+    // 其他全局按键可以在设置界面中重新绑定
+    match app.press_key(key_event.code) {
+        Some(action) => run_action(app, action),
+        None => Ok(()),
+    }
+}
+
+fn run_action(app: &mut App, action: Action) -> Result<()> {
+    match action {
+        Action::Quit => {
+            // 正在播放时先确认，避免误按打断音乐
+            if app.playback_state == PlaybackState::Playing {
+                app.confirm_quit = true;
+            } else {
+                return Err(anyhow::anyhow!("Quit"));
+            }
         }
-        KeyCode::Char('5') => {
-            app.set_volume(0.5);
+        Action::PlayPause => match app.playback_state {
+            PlaybackState::Playing => app.pause(),
+            PlaybackState::Paused => app.resume(),
+            PlaybackState::Stopped => app.play()?,
+        },
+        Action::Next => app.switch_track(true)?,
+        Action::Previous => app.switch_track(false)?,
+        Action::Scrub => app.start_scrub(),
+        Action::AbLoop => app.cycle_ab_loop(),
+        Action::ExportSegment => app.start_segment_export(),
+        Action::NextMarker => app.jump_marker(true)?,
+        Action::PreviousMarker => app.jump_marker(false)?,
+        Action::Undo => app.undo(),
+        Action::Details => app.open_details(false),
+        Action::Shuffle => app.cycle_shuffle(),
+        Action::ShuffleQueue => app.shuffle_queue(),
+        Action::FollowPlayback => app.toggle_follow_playback(),
+        Action::Bookmark => app.start_bookmark_prompt(),
+        Action::Bookmarks => app.toggle_bookmarks_view(),
+        Action::Editor => app.toggle_editor_view(),
+        Action::Info => app.toggle_info(),
+        Action::Lyrics => app.toggle_lyrics(),
+        Action::Karaoke => app.toggle_karaoke(),
+        Action::Radio => app.toggle_radio(),
+        Action::Equalizer => app.cycle_eq(),
+        Action::Leveler => app.toggle_leveler(),
+        Action::PowerSaver => app.toggle_power_saver(),
+        Action::Visualizer => app.cycle_visualizer(),
+        Action::SortOrder => app.toggle_sort_order(),
+        Action::BpmFilter => app.start_bpm_filter_prompt(),
+        Action::Artists => app.toggle_artists_view(),
+        Action::Collections => app.toggle_collections_view(),
+        Action::Settings => app.toggle_settings_view(),
+        Action::CopyPath => app.copy_current(true),
+        Action::CopyTitle => app.copy_current(false),
+        Action::RemoveMissing => app.remove_missing(),
+        Action::Relocate => app.start_relocate_prompt(),
+        Action::Jobs => app.toggle_jobs(),
+        Action::Rescan => app.start_library_scan(),
+        Action::Loudness => app.start_loudness_analysis(),
+        Action::VolumeUp => app.set_volume((app.volume + 0.1).min(1.0)),
+        Action::VolumeDown => app.set_volume((app.volume - 0.1).max(0.0)),
+        Action::VolumeMax => app.set_volume(1.0),
+        Action::Top => app.jump_cursor(false),
+        Action::Bottom => app.jump_cursor(true),
+        Action::InstrumentalFilter => app.toggle_instrumental_filter(),
+        Action::DetectInstrumentals => app.start_instrumental_detection(),
+        Action::RetryAudio => app.retry_audio(),
+        Action::SaveSnapshot => app.start_snapshot_prompt(),
+        Action::Snapshots => app.toggle_snapshots_view(),
+        Action::RecordMacro => app.toggle_macro_recording(),
+        Action::PlayMacro => app.start_macro_replay(),
+        Action::ClassifyGenres => app.start_genre_classification(),
+        Action::Genres => app.toggle_genres_view(),
+        Action::WebPairing => app.toggle_pairing(),
+    }
+    Ok(())
+}
+
+/// 录制或执行宏时按下的寄存器名字
+fn handle_macro_register_key(app: &mut App, pending: macros::Pending, key_event: KeyEvent) -> Result<()> {
+    let KeyCode::Char(c) = key_event.code else {
+        app.set_message("已取消");
+        return Ok(());
+    };
+    match pending {
+        macros::Pending::Record if Macros::is_register(c) => {
+            app.macros.start_recording(c);
+            app.set_message(format!("正在录制宏 @{}，再按一次录制键停止", c));
         }
-        KeyCode::Char('6') => {
-            app.set_volume(0.6);
+        macros::Pending::Play if c == '@' || Macros::is_register(c) => {
+            // 录制中执行宏时寄存器的名字也要录下来，和前面的 @ 一起重放
+            app.macros.record(key_event, true);
+            let register = match c {
+                '@' => app.macros.last,
+                c => Some(c),
+            };
+            match register {
+                Some(register) => replay_macro(app, register)?,
+                None => app.set_message("还没有执行过宏"),
+            }
         }
-        KeyCode::Char('7') => {
-            app.set_volume(0.7);
+        _ => app.set_message("寄存器只能是字母或数字"),
+    }
+    Ok(())
+}
+
+/// 把宏中的按键依次重新处理一遍，和用户亲手按下的效果相同
+fn replay_macro(app: &mut App, register: char) -> Result<()> {
+    let Some(keys) = app.macros.begin_replay(register) else {
+        match app.macros.is_replaying() {
+            true => app.set_message("宏嵌套太深，已停止执行"),
+            false => app.set_message(format!("宏 @{} 是空的", register)),
         }
-        KeyCode::Char('8') => {
-            app.set_volume(0.8);
+        return Ok(());
+    };
+    let result = keys.into_iter().try_for_each(|key| handle_key_event(app, key));
+    app.macros.end_replay();
+    result
+}
+
+fn handle_restore_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => app.restore_session()?,
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.restore_prompt = None,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_playlist_reload_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => app.reload_playlist(),
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.dismiss_playlist_reload(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_quit_confirm_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('q') | KeyCode::Char('Q') => {
+            return Err(anyhow::anyhow!("Quit"));
         }
-        KeyCode::Char('9') => {
-            app.set_volume(0.9);
+        KeyCode::Char('d') | KeyCode::Char('D') => {
+            app.confirm_quit = false;
+            app.request_detach();
         }
         KeyCode::Char('m') | KeyCode::Char('M') => {
-            app.set_volume(1.0);
+            app.confirm_quit = false;
+            app.minimize_requested = true;
         }
-        KeyCode::Right => {
-            app.next()?;
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            app.confirm_quit = false;
         }
-        KeyCode::Left => {
-            app.previous()?;
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_prompt_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Enter => app.submit_prompt()?,
+        KeyCode::Esc => app.prompt = None,
+        KeyCode::Tab => app.cycle_prompt_scope(),
+        KeyCode::Backspace => {
+            if let Some(prompt) = app.prompt.as_mut() {
+                prompt.input.pop();
+            }
         }
-        KeyCode::Up => {
-            let new_volume = (app.volume + 0.05).min(1.0);
-            app.set_volume(new_volume);
+        KeyCode::Char(c) => {
+            if let Some(prompt) = app.prompt.as_mut() {
+                prompt.input.push(c);
+            }
         }
-        KeyCode::Down => {
-            let new_volume = (app.volume - 0.05).max(0.0);
-            app.set_volume(new_volume);
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 歌曲属性窗口：↑/↓ 选择字段，←/→ 调整，Enter 保存，Esc 放弃
+fn handle_track_editor_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.move_track_field(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.move_track_field(1),
+        KeyCode::Left | KeyCode::Char('h') => app.adjust_track_field(-1),
+        KeyCode::Right | KeyCode::Char('l') => app.adjust_track_field(1),
+        KeyCode::PageDown => app.adjust_track_field(-10),
+        KeyCode::PageUp => app.adjust_track_field(10),
+        KeyCode::Backspace | KeyCode::Delete | KeyCode::Char('0') => app.reset_track_field(),
+        KeyCode::Enter => app.save_track_editor()?,
+        KeyCode::Esc => app.track_editor = None,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 歌曲详情窗口：方向键滚动，Esc/i/q 关闭
+fn handle_details_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.scroll_details(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.scroll_details(1),
+        KeyCode::PageUp => app.scroll_details(-10),
+        KeyCode::PageDown => app.scroll_details(10),
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('i') | KeyCode::Char('q') => app.details = None,
+        _ => {}
+    }
+}
+
+/// 全局搜索窗口：输入即查找，↑/↓ 选择，Enter 播放，Ctrl+A 加入队列，Esc 关闭
+fn handle_search_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+        if key_event.code == KeyCode::Char('a') {
+            app.enqueue_search_hit();
         }
+        return Ok(());
+    }
+    match key_event.code {
+        KeyCode::Up => app.move_search_cursor(-1),
+        KeyCode::Down => app.move_search_cursor(1),
+        KeyCode::PageUp => app.move_search_cursor(-10),
+        KeyCode::PageDown => app.move_search_cursor(10),
+        KeyCode::Enter => app.play_search_hit()?,
+        KeyCode::Esc => app.search = None,
+        KeyCode::Backspace => app.search_pop(),
+        KeyCode::Char(c) => app.search_push(&c.to_string()),
         _ => {}
     }
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// 拖动模式：方向键移动进度条上的虚拟光标，Enter 跳转，Esc 取消
+fn handle_scrub_key(app: &mut App, key_event: KeyEvent) -> Result<()> {
+    match key_event.code {
+        KeyCode::Left => app.move_scrub(-5),
+        KeyCode::Right => app.move_scrub(5),
+        KeyCode::Down => app.move_scrub(-30),
+        KeyCode::Up => app.move_scrub(30),
+        KeyCode::PageDown => app.move_scrub(-300),
+        KeyCode::PageUp => app.move_scrub(300),
+        KeyCode::Home => app.set_scrub(Duration::ZERO),
+        KeyCode::End => {
+            if let Some(total) = app.get_total_duration() {
+                app.set_scrub(total);
+            }
+        }
+        KeyCode::Enter => app.commit_scrub()?,
+        KeyCode::Esc | KeyCode::Char('s') => app.scrub = None,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 播放列表面板的按键，返回 false 时交给全局按键处理
+fn handle_playlist_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.move_playlist_cursor(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.move_playlist_cursor(1),
+        KeyCode::PageUp => app.move_playlist_cursor(-10),
+        KeyCode::PageDown => app.move_playlist_cursor(10),
+        KeyCode::Enter => app.play_selected()?,
+        KeyCode::Char('a') => app.enqueue_selected(),
+        KeyCode::Char('A') => app.play_next_selected(),
+        KeyCode::Char('e') => app.open_track_editor(),
+        KeyCode::Char('*') => app.cycle_selected_rating(),
+        KeyCode::Char('i') => app.open_details(true),
+        KeyCode::Char('r') => app.retry_selected_decode()?,
+        KeyCode::Char('o') => app.request_beets_edit(),
+        KeyCode::Char('c') => app.start_collection_prompt(),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// 编辑界面左侧曲库的按键：Enter/→ 加入队列，其余和播放列表相同
+fn handle_editor_library_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => app.add_selected_and_advance(),
+        KeyCode::Char('p') => app.play_selected()?,
+        KeyCode::Esc => app.toggle_editor_view(),
+        _ => return handle_playlist_key(app, key_event),
+    }
+    Ok(true)
+}
+
+/// 编辑界面右侧队列的按键：Enter/←/d 移出队列，K/J 调整顺序
+fn handle_editor_queue_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Enter | KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('d') | KeyCode::Delete => {
+            app.return_queue_selected()
+        }
+        KeyCode::Char('p') => app.play_queue_selected()?,
+        KeyCode::Esc => app.toggle_editor_view(),
+        _ => return handle_queue_key(app, key_event),
+    }
+    Ok(true)
+}
+
+/// 队列面板的按键
+fn handle_queue_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.move_queue_cursor(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.move_queue_cursor(1),
+        KeyCode::Enter => app.play_queue_selected()?,
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_queue_selected(),
+        KeyCode::Char('K') => app.move_queue_item(-1),
+        KeyCode::Char('J') => app.move_queue_item(1),
+        KeyCode::Char('c') => app.clear_queue(),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// 歌词面板的按键：选中一行后按 Enter 跳到这一行
+fn handle_lyrics_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.move_lyrics_cursor(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.move_lyrics_cursor(1),
+        KeyCode::PageUp => app.move_lyrics_cursor(-10),
+        KeyCode::PageDown => app.move_lyrics_cursor(10),
+        KeyCode::Enter => app.seek_to_lyric()?,
+        KeyCode::Esc => app.reset_lyrics_cursor(),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// 信息面板的按键
+fn handle_info_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.scroll_info(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.scroll_info(1),
+        KeyCode::PageUp => app.scroll_info(-10),
+        KeyCode::PageDown => app.scroll_info(10),
+        _ => return false,
+    }
+    true
+}
+
+/// 任务面板的按键
+fn handle_jobs_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.move_job_cursor(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.move_job_cursor(1),
+        KeyCode::Char('x') | KeyCode::Delete => app.cancel_selected_job(),
+        KeyCode::Char('c') => app.clear_finished_jobs(),
+        _ => return false,
+    }
+    true
+}
+
+/// 艺术家列表（按专辑艺术家分组，合辑归入 Various Artists）的按键：Enter 只显示选中艺术家的歌曲
+fn handle_artists_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_artist(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_artist(1),
+        KeyCode::PageUp => app.select_artist(-10),
+        KeyCode::PageDown => app.select_artist(10),
+        KeyCode::Enter => app.apply_selected_artist(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return false,
+    }
+    true
+}
+
+fn handle_collections_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_collection(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_collection(1),
+        KeyCode::Enter => app.apply_selected_collection(),
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_selected_collection(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return false,
+    }
+    true
+}
+
+fn handle_snapshots_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_snapshot(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_snapshot(1),
+        KeyCode::Enter => app.restore_selected_snapshot(),
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_selected_snapshot(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return false,
+    }
+    true
+}
+
+fn handle_genres_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_genre(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_genre(1),
+        KeyCode::Enter => app.accept_selected_genre(),
+        KeyCode::Char('e') => app.start_genre_prompt(),
+        KeyCode::Char('d') | KeyCode::Delete => app.reject_selected_genre(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return false,
+    }
+    true
+}
+
+fn handle_settings_key(app: &mut App, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_setting(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_setting(1),
+        KeyCode::PageUp => app.select_setting(-10),
+        KeyCode::PageDown => app.select_setting(10),
+        KeyCode::Enter => app.edit_selected_setting(),
+        KeyCode::Backspace | KeyCode::Delete => app.reset_selected_binding(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return false,
+    }
+    true
+}
+
+/// 书签视图中的按键，返回 false 时交给全局按键处理
+fn handle_bookmarks_key(app: &mut App, key_event: KeyEvent) -> Result<bool> {
+    match key_event.code {
+        KeyCode::Up | KeyCode::Char('k') => app.select_bookmark(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.select_bookmark(1),
+        KeyCode::Enter => app.jump_to_selected_bookmark()?,
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_selected_bookmark(),
+        KeyCode::Esc => app.view = View::Playlist,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::app::{scan_songs, Song};
+use crate::json;
+use crate::stats::Stats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+/// `music_tui export [--format json|csv] [--output 文件]`：导出曲库和收听统计
+pub fn run(args: &[OsString]) -> Result<()> {
+    let mut format = Format::Json;
+    let mut output: Option<PathBuf> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--format") | Some("-f") => {
+                format = match args.next().and_then(|value| value.to_str()) {
+                    Some("json") => Format::Json,
+                    Some("csv") => Format::Csv,
+                    _ => bail!("--format 只支持 json 或 csv"),
+                };
+            }
+            Some("--output") | Some("-o") => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => bail!("--output 需要文件路径"),
+            },
+            _ => bail!("用法: music_tui export [--format json|csv] [--output 文件]"),
+        }
+    }
+
+    let songs = scan_songs(Path::new("assets"))?;
+    let stats = Stats::load();
+    let content = match format {
+        Format::Json => to_json(&songs, &stats),
+        Format::Csv => to_csv(&songs, &stats),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(&path, content)?;
+            eprintln!("已导出 {} 首歌曲到 {}", songs.len(), path.display());
+        }
+        None => io::stdout().write_all(content.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn format_timestamp(timestamp: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(timestamp, 0).map(|time| time.to_rfc3339())
+}
+
+fn to_json(songs: &[Song], stats: &Stats) -> String {
+    let mut tracks = Vec::new();
+    let mut total_duration = 0.0;
+    let mut total_plays = 0;
+
+    for song in songs {
+        let play_stats = stats.get(&song.path).cloned().unwrap_or_default();
+        let duration = song.duration.map(|d| d.as_secs_f64());
+        total_duration += duration.unwrap_or(0.0);
+        total_plays += play_stats.plays;
+
+        let last_played = play_stats.last_played.and_then(format_timestamp);
+        let fields = [
+            ("path", json::string(&song.path.to_string_lossy())),
+            ("name", json::string(&song.name)),
+            ("title", json::opt_string(song.tags.title.as_deref())),
+            ("artist", json::opt_string(song.tags.artist.as_deref())),
+            ("album", json::opt_string(song.tags.album.as_deref())),
+            ("album_artist", json::opt_string(song.tags.album_artist.as_deref())),
+            ("genre", json::opt_string(song.tags.genre.as_deref())),
+            ("track_number", song.tags.track_number.map(|n| n.to_string()).unwrap_or_else(|| "null".to_string())),
+            ("duration_secs", duration.map(|d| format!("{:.3}", d)).unwrap_or_else(|| "null".to_string())),
+            ("plays", play_stats.plays.to_string()),
+            ("last_played", json::opt_string(last_played.as_deref())),
+        ];
+        let body: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("      \"{}\": {}", key, value))
+            .collect();
+        tracks.push(format!("    {{\n{}\n    }}", body.join(",\n")));
+    }
+
+    format!(
+        "{{\n  \"exported_at\": {},\n  \"summary\": {{\n    \"tracks\": {},\n    \"total_duration_secs\": {:.3},\n    \"total_plays\": {}\n  }},\n  \"tracks\": [\n{}\n  ]\n}}\n",
+        json::string(&chrono::Utc::now().to_rfc3339()),
+        songs.len(),
+        total_duration,
+        total_plays,
+        tracks.join(",\n"),
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(songs: &[Song], stats: &Stats) -> String {
+    let mut content = String::from(
+        "path,name,title,artist,album,album_artist,genre,track_number,duration_secs,plays,last_played\n",
+    );
+
+    for song in songs {
+        let play_stats = stats.get(&song.path).cloned().unwrap_or_default();
+        let fields = [
+            song.path.to_string_lossy().to_string(),
+            song.name.clone(),
+            song.tags.title.clone().unwrap_or_default(),
+            song.tags.artist.clone().unwrap_or_default(),
+            song.tags.album.clone().unwrap_or_default(),
+            song.tags.album_artist.clone().unwrap_or_default(),
+            song.tags.genre.clone().unwrap_or_default(),
+            song.tags.track_number.map(|n| n.to_string()).unwrap_or_default(),
+            song.duration.map(|d| format!("{:.3}", d.as_secs_f64())).unwrap_or_default(),
+            play_stats.plays.to_string(),
+            play_stats.last_played.and_then(format_timestamp).unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        content.push_str(&row.join(","));
+        content.push('\n');
+    }
+    content
+}
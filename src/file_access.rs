@@ -0,0 +1,98 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 第一次重试前的等待时间，之后每次失败翻倍
+const FIRST_RETRY: Duration = Duration::from_secs(5);
+const MAX_RETRY: Duration = Duration::from_secs(300);
+
+/// 暂时无法访问的文件或挂载点（例如网络共享断开或响应过慢）
+#[derive(Debug, Clone)]
+pub struct Unavailable {
+    pub failures: u32,
+    pub retry_at: Instant,
+    pub checking: bool,
+    pub reason: String,
+}
+
+impl Unavailable {
+    pub fn new(reason: String) -> Self {
+        Unavailable {
+            failures: 1,
+            retry_at: Instant::now() + FIRST_RETRY,
+            checking: false,
+            reason,
+        }
+    }
+
+    /// 又一次访问失败，按指数退避推迟下一次重试
+    pub fn failed_again(&mut self, reason: String) {
+        self.failures += 1;
+        self.checking = false;
+        self.reason = reason;
+        self.retry_at = Instant::now() + backoff(self.failures);
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    FIRST_RETRY
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(MAX_RETRY)
+}
+
+/// 在后台再次尝试访问文件或挂载点（读取目录），完成后发送 (路径, 错误信息)，成功时错误为 None。
+/// 不设超时：挂载点卡住时这个线程一直等到系统调用返回，期间不会再为同一个路径启动新的检查
+pub fn spawn_check(path: PathBuf, tx: Sender<(PathBuf, Option<String>)>) {
+    thread::spawn(move || {
+        let result = if path.is_dir() {
+            fs::read_dir(&path).map(|_| ())
+        } else {
+            File::open(&path).map(|_| ())
+        };
+        let _ = tx.send((path, result.err().map(|err| err.to_string())));
+    });
+}
+
+/// 文件所在的挂载点。网络共享卡住时同一个挂载点下的文件都打不开，按挂载点标记和重试。
+/// 读不到挂载表时（非 Linux 系统）使用文件所在的文件夹
+pub fn mount_point(path: &Path) -> PathBuf {
+    let mounts = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    longest_mount(path, &mounts).unwrap_or_else(|| path.parent().unwrap_or(path).to_path_buf())
+}
+
+/// 挂载表中包含 path 的最深的挂载点
+fn longest_mount(path: &Path, mounts: &str) -> Option<PathBuf> {
+    mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .map(|field| PathBuf::from(unescape_mount(field)))
+        .filter(|mount| path.starts_with(mount))
+        .max_by_key(|mount| mount.components().count())
+}
+
+/// 挂载表把空格、制表符、换行和反斜杠写成八进制转义
+fn unescape_mount(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_deepest_mount() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n\
+                      //nas/music /mnt/nas\\040music cifs rw 0 0\n\
+                      /dev/sdb1 /mnt ext4 rw 0 0\n";
+        let path = Path::new("/mnt/nas music/Album/01.flac");
+        assert_eq!(longest_mount(path, mounts), Some(PathBuf::from("/mnt/nas music")));
+        assert_eq!(longest_mount(Path::new("/home/me/a.mp3"), mounts), Some(PathBuf::from("/")));
+        assert_eq!(longest_mount(Path::new("/mnt/nas/a.mp3"), mounts), Some(PathBuf::from("/mnt")));
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::http_stream;
+use crate::storage;
+
+const FINGERPRINTS_FILE: &str = "fingerprints.tsv";
+
+/// 只读取文件开头这么多字节，整理文件时不需要把整个曲库读一遍
+const HEAD_BYTES: u64 = 1024 * 1024;
+
+/// 文件内容的指纹：大小和开头 1 MB 的哈希。只用来在移动、改名之后认出同一个文件，不防碰撞
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    pub size: u64,
+    pub hash: u64,
+}
+
+/// FNV-1a，结果写进文件，不能用每个版本都可能变化的 DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub fn compute(path: &Path) -> Option<Fingerprint> {
+    let file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut head = Vec::new();
+    file.take(HEAD_BYTES).read_to_end(&mut head).ok()?;
+    Some(Fingerprint { size, hash: fnv1a(&head) })
+}
+
+pub fn load() -> HashMap<PathBuf, Fingerprint> {
+    let records = storage::read_records(FINGERPRINTS_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 大小 \t 哈希（十六进制） \t 文件路径
+            let [size, hash, path] = <[String; 3]>::try_from(record).ok()?;
+            let fingerprint = Fingerprint {
+                size: size.parse().ok()?,
+                hash: u64::from_str_radix(&hash, 16).ok()?,
+            };
+            Some((storage::path_from_field(&path), fingerprint))
+        })
+        .collect()
+}
+
+pub fn save(fingerprints: &HashMap<PathBuf, Fingerprint>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = fingerprints
+        .iter()
+        .map(|(path, fingerprint)| {
+            vec![
+                fingerprint.size.to_string(),
+                format!("{:016x}", fingerprint.hash),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[2].cmp(&b[2]));
+    storage::write_records(FINGERPRINTS_FILE, &records)
+}
+
+/// 重新扫描曲库时更新指纹，并找出被移动或改名的文件：已经不存在的旧路径和新出现的文件指纹相同时
+/// 返回 (原路径, 新路径)。只给新文件和大小变化的文件计算指纹；同一指纹对应多个消失的文件时无法确定是哪一个，不处理
+pub fn reconcile(known: &mut HashMap<PathBuf, Fingerprint>, paths: &[PathBuf], cancel: &AtomicBool) -> Vec<(PathBuf, PathBuf)> {
+    let scanned: HashSet<&PathBuf> = paths.iter().collect();
+    let mut vanished: HashMap<Fingerprint, Option<PathBuf>> = HashMap::new();
+    for (path, fingerprint) in known.iter() {
+        if scanned.contains(path) || path.exists() {
+            continue;
+        }
+        vanished
+            .entry(*fingerprint)
+            .and_modify(|existing| *existing = None)
+            .or_insert_with(|| Some(path.clone()));
+    }
+
+    let mut moves = Vec::new();
+    for path in paths.iter().filter(|path| !http_stream::is_url(path)) {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let size = fs::metadata(path).map(|meta| meta.len()).ok();
+        if known.get(path).is_some_and(|fingerprint| Some(fingerprint.size) == size) {
+            continue;
+        }
+        let Some(fingerprint) = compute(path) else {
+            continue;
+        };
+        known.insert(path.clone(), fingerprint);
+        if let Some(Some(old)) = vanished.remove(&fingerprint) {
+            known.remove(&old);
+            moves.push((old, path.clone()));
+        }
+    }
+    moves
+}
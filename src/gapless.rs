@@ -0,0 +1,209 @@
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::app::{is_audio_file, scan_songs};
+use crate::metadata;
+
+/// MP3 解码器本身固定引入的延迟（样本数）
+const MP3_DECODER_DELAY: u32 = 529;
+
+/// 文件的无缝播放信息
+#[derive(Debug)]
+enum GaplessInfo {
+    /// 格式本身按样本精确存储，不需要裁剪
+    Native,
+    /// 编码器写入了延迟/填充信息
+    Trimmable { delay: u32, padding: u32, sample_rate: u32, source: &'static str },
+    /// 有损格式但没有延迟/填充信息，曲间会有无法消除的静音
+    Missing { reason: &'static str },
+    Unreadable(String),
+}
+
+/// `music_tui gapless [文件...]`：报告每个文件的编码器延迟/填充以及能否做到采样级无缝播放
+pub fn run(args: &[OsString]) -> Result<()> {
+    let paths: Vec<PathBuf> = if args.is_empty() {
+        scan_songs(Path::new("assets"))?.into_iter().map(|song| song.path).collect()
+    } else {
+        args.iter().map(PathBuf::from).filter(|path| is_audio_file(path)).collect()
+    };
+
+    let mut accurate = 0;
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match inspect(path) {
+            GaplessInfo::Native => {
+                accurate += 1;
+                println!("✓ {}\n    无损/样本精确格式，不需要裁剪", name);
+            }
+            GaplessInfo::Trimmable { delay, padding, sample_rate, source } => {
+                accurate += 1;
+                let ms = |samples: u32| samples as f64 * 1000.0 / sample_rate.max(1) as f64;
+                println!(
+                    "✓ {}\n    {}: 开头延迟 {} 样本 ({:.1} ms)，结尾填充 {} 样本 ({:.1} ms)，{} Hz",
+                    name,
+                    source,
+                    delay,
+                    ms(delay),
+                    padding,
+                    ms(padding),
+                    sample_rate
+                );
+            }
+            GaplessInfo::Missing { reason } => {
+                println!("✗ {}\n    {}，曲间会有编码器引入的静音", name, reason);
+            }
+            GaplessInfo::Unreadable(err) => println!("? {}\n    无法读取: {}", name, err),
+        }
+    }
+
+    println!();
+    println!("{}/{} 个文件具备采样级无缝播放所需的信息", accurate, paths.len());
+    println!("注意: 当前播放器在曲目结束后才创建下一首的播放流，也不会裁剪 MP3 的延迟/填充，");
+    println!("      因此即使文件信息完整，曲间仍可能听到短暂的间隙或咔哒声。");
+    Ok(())
+}
+
+fn inspect(path: &Path) -> GaplessInfo {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let result = match ext.as_str() {
+        "wav" | "flac" | "ogg" => return GaplessInfo::Native,
+        "mp3" => inspect_mp3(path),
+        "m4a" | "m4b" | "mp4a" => inspect_m4a(path),
+        _ => return GaplessInfo::Missing { reason: "未知格式" },
+    };
+    result.unwrap_or_else(|err| GaplessInfo::Unreadable(err.to_string()))
+}
+
+/// 跳过 ID3v2 标签，返回第一个音频帧附近的数据
+fn read_mp3_head(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header)?;
+
+    let mut offset = 0u64;
+    if &header[0..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 7) | (*byte as u64 & 0x7F));
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        offset = 10 + size + footer;
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::new();
+    file.take(16 * 1024).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn inspect_mp3(path: &Path) -> Result<GaplessInfo> {
+    let data = read_mp3_head(path)?;
+
+    // 找到第一个合法的帧头
+    let frame = (0..data.len().saturating_sub(4)).find(|&i| {
+        data[i] == 0xFF
+            && data[i + 1] & 0xE0 == 0xE0
+            && (data[i + 1] >> 3) & 0x03 != 0x01
+            && (data[i + 1] >> 1) & 0x03 != 0x00
+            && data[i + 2] >> 4 != 0x0F
+            && (data[i + 2] >> 2) & 0x03 != 0x03
+    });
+    let Some(frame) = frame else {
+        return Ok(GaplessInfo::Missing { reason: "找不到 MP3 帧" });
+    };
+
+    let version = (data[frame + 1] >> 3) & 0x03;
+    let sample_rate_index = ((data[frame + 2] >> 2) & 0x03) as usize;
+    let mono = (data[frame + 3] >> 6) & 0x03 == 0x03;
+
+    let base_rates = [44100, 48000, 32000];
+    let sample_rate = match version {
+        0x03 => base_rates[sample_rate_index],
+        0x02 => base_rates[sample_rate_index] / 2,
+        _ => base_rates[sample_rate_index] / 4,
+    };
+    let side_info = match (version == 0x03, mono) {
+        (true, false) => 32,
+        (true, true) => 17,
+        (false, false) => 17,
+        (false, true) => 9,
+    };
+
+    let xing = frame + 4 + side_info;
+    let tag = data.get(xing..xing + 4).unwrap_or_default();
+    if tag != b"Xing" && tag != b"Info" {
+        if data.get(frame + 36..frame + 40).is_some_and(|tag| tag == b"VBRI") {
+            return Ok(GaplessInfo::Missing { reason: "只有 VBRI 头，没有延迟/填充信息" });
+        }
+        return Ok(GaplessInfo::Missing { reason: "没有 Xing/LAME 头" });
+    }
+
+    // 文件在 Xing 标记之后就结束时没有标志位可读
+    let Some(flags) = data.get(xing + 4..xing + 8) else {
+        return Ok(GaplessInfo::Missing { reason: "Xing 头不完整" });
+    };
+    let flags = u32::from_be_bytes([flags[0], flags[1], flags[2], flags[3]]);
+    let mut lame = xing + 8;
+    for (flag, size) in [(0x01, 4), (0x02, 4), (0x04, 100), (0x08, 4)] {
+        if flags & flag != 0 {
+            lame += size;
+        }
+    }
+
+    // LAME 扩展头的第 21~23 字节保存 12 位延迟和 12 位填充
+    let Some(lame_tag) = data.get(lame..lame + 24) else {
+        return Ok(GaplessInfo::Missing { reason: "Xing 头后没有 LAME 扩展" });
+    };
+    if !lame_tag.starts_with(b"LAME") && !lame_tag.starts_with(b"Lavc") && !lame_tag.starts_with(b"Lavf") {
+        return Ok(GaplessInfo::Missing { reason: "Xing 头后没有 LAME 扩展" });
+    }
+    let delay = ((lame_tag[21] as u32) << 4) | ((lame_tag[22] as u32) >> 4);
+    let padding = (((lame_tag[22] as u32) & 0x0F) << 8) | lame_tag[23] as u32;
+
+    Ok(GaplessInfo::Trimmable {
+        delay: delay + MP3_DECODER_DELAY,
+        padding: padding.saturating_sub(MP3_DECODER_DELAY),
+        sample_rate,
+        source: "LAME 头",
+    })
+}
+
+/// iTunes 编码的 AAC 文件在 iTunSMPB 注释中记录延迟和填充
+fn inspect_m4a(path: &Path) -> Result<GaplessInfo> {
+    let mut data = Vec::new();
+    File::open(path)?.take(4 * 1024 * 1024).read_to_end(&mut data)?;
+
+    let marker = b"iTunSMPB";
+    let Some(start) = data.windows(marker.len()).position(|window| window == marker) else {
+        return Ok(GaplessInfo::Missing { reason: "没有 iTunSMPB 信息" });
+    };
+
+    // 值的格式: " 00000000 00000840 000001CA 00000000003F31F6 ..."
+    let text: String = data[start + marker.len()..]
+        .iter()
+        .take(200)
+        .map(|byte| *byte as char)
+        .filter(|c| c.is_ascii_hexdigit() || *c == ' ')
+        .collect();
+    let fields: Vec<u32> = text
+        .split_whitespace()
+        .filter(|field| field.len() == 8)
+        .filter_map(|field| u32::from_str_radix(field, 16).ok())
+        .collect();
+
+    match fields.as_slice() {
+        [_, delay, padding, ..] => Ok(GaplessInfo::Trimmable {
+            delay: *delay,
+            padding: *padding,
+            sample_rate: metadata::probe(path).sample_rate.unwrap_or(44100),
+            source: "iTunSMPB",
+        }),
+        _ => Ok(GaplessInfo::Missing { reason: "iTunSMPB 格式无法识别" }),
+    }
+}
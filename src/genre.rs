@@ -0,0 +1,209 @@
+use anyhow::{bail, Result};
+use rodio::Source;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::replaygain::Filter;
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+
+const GENRES_FILE: &str = "genre_suggestions.tsv";
+
+/// 跳过开头的前奏，从这里开始分析
+const SKIP_SECONDS: u64 = 30;
+const ANALYSIS_SECONDS: u64 = 60;
+const BLOCK_SECONDS: f64 = 0.05;
+const BASS_HZ: f64 = 150.0;
+const TREBLE_HZ: f64 = 4000.0;
+/// 比最响的段落低 40 dB 以上的段落当作静音，不计入动态
+const SILENCE_DB: f64 = 40.0;
+/// 和最接近的流派的距离超过这个值时不给出建议
+const MAX_DISTANCE: f64 = 3.0;
+
+/// 内置模型：每个流派的典型特征（速度、低音占比、高音占比、响度起伏、起音强度）。
+/// 只是按常见的制作风格估出来的大致位置，用最近的中心给出建议，需要用户确认
+const CENTROIDS: &[(&str, [f64; 5])] = &[
+    ("Electronic", [126.0, 0.75, 0.08, 3.5, 1.3]),
+    ("Hip-Hop", [90.0, 0.80, 0.05, 4.5, 1.6]),
+    ("Pop", [110.0, 0.62, 0.05, 4.5, 1.1]),
+    ("Rock", [120.0, 0.45, 0.08, 4.0, 1.0]),
+    ("Metal", [140.0, 0.35, 0.12, 3.0, 0.8]),
+    ("Jazz", [120.0, 0.40, 0.04, 6.5, 1.2]),
+    ("Folk", [100.0, 0.30, 0.03, 5.5, 0.8]),
+    ("Classical", [90.0, 0.25, 0.02, 9.0, 0.6]),
+];
+/// 各项特征的尺度，差这么多算作距离 1
+const SCALES: [f64; 5] = [25.0, 0.15, 0.03, 2.0, 0.4];
+
+/// 建议来自哪里
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    /// 内置模型，带置信度 (0-1)
+    Model(f32),
+    /// `genre_command` 配置的外部程序
+    Command,
+}
+
+/// 等待确认的流派建议。拒绝过的建议也记下来，下次分析时跳过
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub genre: String,
+    pub origin: Origin,
+    pub rejected: bool,
+}
+
+pub fn load() -> HashMap<PathBuf, Suggestion> {
+    let records = storage::read_records(GENRES_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 流派 \t 来源（command 或模型的置信度） \t pending/rejected \t 文件路径
+            let [genre, origin, state, path] = <[String; 4]>::try_from(record).ok()?;
+            let origin = match origin.as_str() {
+                "command" => Origin::Command,
+                confidence => Origin::Model(confidence.parse().ok()?),
+            };
+            let suggestion = Suggestion {
+                genre,
+                origin,
+                rejected: state == "rejected",
+            };
+            Some((storage::path_from_field(&path), suggestion))
+        })
+        .collect()
+}
+
+pub fn save(suggestions: &HashMap<PathBuf, Suggestion>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = suggestions
+        .iter()
+        .map(|(path, suggestion)| {
+            let origin = match suggestion.origin {
+                Origin::Model(confidence) => format!("{:.2}", confidence),
+                Origin::Command => "command".to_string(),
+            };
+            vec![
+                suggestion.genre.clone(),
+                origin,
+                if suggestion.rejected { "rejected" } else { "pending" }.to_string(),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[3].cmp(&b[3]));
+    storage::write_records(GENRES_FILE, &records)
+}
+
+/// 给一首歌建议流派。配置了外部程序时用程序输出的第一行，否则用内置模型；
+/// bpm 是已经分析过的速度，没有时按 120 BPM 计算。无法判断时返回 None
+pub fn classify(
+    path: &Path,
+    duration: Option<Duration>,
+    bpm: Option<f32>,
+    command: &str,
+    cancel: &AtomicBool,
+) -> Result<Option<Suggestion>> {
+    if !command.is_empty() {
+        return run_command(command, path);
+    }
+    let Some(features) = features(path, duration, bpm, cancel) else {
+        return Ok(None);
+    };
+    let mut distances: Vec<(f64, &str)> = CENTROIDS
+        .iter()
+        .map(|(genre, centroid)| {
+            let distance = features
+                .iter()
+                .zip(centroid)
+                .zip(SCALES)
+                .map(|((value, center), scale)| ((value - center) / scale).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            (distance, *genre)
+        })
+        .collect();
+    distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (best, genre) = distances[0];
+    if best > MAX_DISTANCE {
+        return Ok(None);
+    }
+    // 和第二接近的流派拉开的距离越大越可信
+    let confidence = 1.0 - best / distances[1].0.max(f64::EPSILON);
+    Ok(Some(Suggestion {
+        genre: genre.to_string(),
+        origin: Origin::Model(confidence.clamp(0.0, 1.0) as f32),
+        rejected: false,
+    }))
+}
+
+/// 外部程序（如包装 Essentia、musicnn 的脚本）以文件路径为参数，把流派打印在第一行，不输出表示无法判断
+fn run_command(command: &str, path: &Path) -> Result<Option<Suggestion>> {
+    let output = Command::new(command).arg(path).output()?;
+    if !output.status.success() {
+        bail!("{} 退出: {}", command, output.status);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let genre = stdout.lines().next().unwrap_or_default().trim();
+    Ok((!genre.is_empty()).then(|| Suggestion {
+        genre: genre.to_string(),
+        origin: Origin::Command,
+        rejected: false,
+    }))
+}
+
+/// 模型使用的特征：[速度, 150 Hz 以下的能量占比, 4 kHz 以上的能量占比, 每段响度的标准差 (dB), 平均起音强度 (dB)]
+fn features(path: &Path, duration: Option<Duration>, bpm: Option<f32>, cancel: &AtomicBool) -> Option<[f64; 5]> {
+    let start = match duration {
+        Some(duration) if duration.as_secs() > SKIP_SECONDS + ANALYSIS_SECONDS => Duration::from_secs(SKIP_SECONDS),
+        _ => Duration::ZERO,
+    };
+    let file = fs::File::open(path).ok()?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, start).ok()?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate().max(1) as f64;
+    let block = (sample_rate * BLOCK_SECONDS) as usize;
+    let bass = Filter::low_pass(BASS_HZ, sample_rate);
+    let treble = Filter::high_pass(TREBLE_HZ, sample_rate);
+    let (mut bass_state, mut treble_state) = ([0.0; 2], [0.0; 2]);
+
+    let mut samples = decoder.take((sample_rate * ANALYSIS_SECONDS as f64) as usize * channels);
+    let (mut total, mut low, mut high) = (0.0, 0.0, 0.0);
+    let mut blocks: Vec<f64> = Vec::new();
+    'blocks: loop {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut energy = 0.0;
+        for _ in 0..block {
+            // 混成单声道
+            let mut sum = 0.0;
+            for _ in 0..channels {
+                let Some(sample) = samples.next() else {
+                    break 'blocks;
+                };
+                sum += sample as f64;
+            }
+            let mono = sum / channels as f64;
+            let b = bass.process(&mut bass_state, mono);
+            let t = treble.process(&mut treble_state, mono);
+            energy += mono * mono;
+            low += b * b;
+            high += t * t;
+        }
+        total += energy;
+        blocks.push(10.0 * (energy / block.max(1) as f64).max(1e-12).log10());
+    }
+    if total <= 0.0 || blocks.len() < 2 {
+        return None;
+    }
+
+    let loudest = blocks.iter().copied().fold(f64::MIN, f64::max);
+    let loud: Vec<f64> = blocks.iter().copied().filter(|db| *db > loudest - SILENCE_DB).collect();
+    let mean = loud.iter().sum::<f64>() / loud.len() as f64;
+    let spread = (loud.iter().map(|db| (db - mean).powi(2)).sum::<f64>() / loud.len() as f64).sqrt();
+    let onsets = blocks.windows(2).map(|pair| (pair[1] - pair[0]).max(0.0)).sum::<f64>() / (blocks.len() - 1) as f64;
+    Some([bpm.unwrap_or(120.0) as f64, low / total, high / total, spread, onsets])
+}
@@ -0,0 +1,137 @@
+/// 界面中使用的符号风格。有些终端把 emoji 渲染成双倍宽度导致布局错乱，
+/// 可以改用 Nerd Font 图标或纯 ASCII
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphStyle {
+    Emoji,
+    NerdFont,
+    Ascii,
+}
+
+impl GlyphStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "emoji" => Some(GlyphStyle::Emoji),
+            "nerdfont" | "nerd-font" | "nerd_font" => Some(GlyphStyle::NerdFont),
+            "ascii" => Some(GlyphStyle::Ascii),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GlyphStyle::Emoji => "emoji",
+            GlyphStyle::NerdFont => "nerdfont",
+            GlyphStyle::Ascii => "ascii",
+        }
+    }
+
+    pub fn glyphs(self) -> &'static Glyphs {
+        match self {
+            GlyphStyle::Emoji => &EMOJI,
+            GlyphStyle::NerdFont => &NERD_FONT,
+            GlyphStyle::Ascii => &ASCII,
+        }
+    }
+}
+
+/// 一套界面符号
+#[derive(Debug)]
+pub struct Glyphs {
+    pub note: &'static str,
+    pub radio: &'static str,
+    pub playing: &'static str,
+    pub paused: &'static str,
+    pub stopped: &'static str,
+    pub shuffle: &'static str,
+    /// 无法解码的歌曲
+    pub error: &'static str,
+    pub alarm: &'static str,
+    /// 网络上的歌曲
+    pub remote: &'static str,
+    /// 评分的实心星和空心星
+    pub star: &'static str,
+    pub star_empty: &'static str,
+    /// 拖动进度时的虚拟光标
+    pub scrub_cursor: &'static str,
+    /// 进度条上章节开头和书签的标记
+    pub chapter: &'static str,
+    pub bookmark: &'static str,
+    /// 没有编号的 DJ 提示点（记忆提示点），热提示点显示编号
+    pub cue: &'static str,
+    /// 卡拉 OK 前奏的倒数圆点
+    pub countdown: &'static str,
+    pub arrow_left: &'static str,
+    pub arrow_right: &'static str,
+    pub arrows_horizontal: &'static str,
+    pub arrows_vertical: &'static str,
+}
+
+const EMOJI: Glyphs = Glyphs {
+    note: "🎵",
+    radio: "📻",
+    playing: "▶️",
+    paused: "⏸️",
+    stopped: "⏹️",
+    shuffle: "🔀",
+    error: "⚠️",
+    alarm: "⏰",
+    remote: "🌐",
+    star: "★",
+    star_empty: "☆",
+    scrub_cursor: "┃",
+    chapter: "│",
+    bookmark: "◆",
+    cue: "▼",
+    countdown: "●",
+    arrow_left: "←",
+    arrow_right: "→",
+    arrows_horizontal: "←/→",
+    arrows_vertical: "↑/↓",
+};
+
+/// Nerd Font 的 Font Awesome 图标，都在单倍宽度的私有区
+const NERD_FONT: Glyphs = Glyphs {
+    note: "\u{f001}",
+    radio: "\u{f09e}",
+    playing: "\u{f04b}",
+    paused: "\u{f04c}",
+    stopped: "\u{f04d}",
+    shuffle: "\u{f074}",
+    error: "\u{f071}",
+    alarm: "\u{f0f3}",
+    remote: "\u{f0ac}",
+    star: "\u{f005}",
+    star_empty: "\u{f006}",
+    scrub_cursor: "┃",
+    chapter: "│",
+    bookmark: "\u{f02e}",
+    cue: "\u{f0d7}",
+    countdown: "\u{f111}",
+    arrow_left: "\u{f177}",
+    arrow_right: "\u{f178}",
+    arrows_horizontal: "←/→",
+    arrows_vertical: "↑/↓",
+};
+
+const ASCII: Glyphs = Glyphs {
+    note: "~",
+    radio: "((o))",
+    playing: ">",
+    paused: "||",
+    stopped: "[]",
+    shuffle: "<>",
+    error: "!",
+    alarm: "@",
+    remote: "~",
+    star: "*",
+    star_empty: ".",
+    scrub_cursor: "|",
+    chapter: "|",
+    bookmark: "*",
+    cue: "v",
+    countdown: "o",
+    arrow_left: "<-",
+    arrow_right: "->",
+    arrows_horizontal: "Left/Right",
+    arrows_vertical: "Up/Down",
+};
@@ -0,0 +1,137 @@
+use anyhow::{bail, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+const USER_AGENT: &str = concat!("CuperMusicPlayer/", env!("CARGO_PKG_VERSION"), " ( https://github.com/liberal-laird/CuperMuiscPlayer )");
+
+/// 通过系统的 curl 发起 GET 请求，返回响应内容
+pub fn get(url: &str) -> Result<String> {
+    get_with_headers(url, &[])
+}
+
+/// 带额外请求头的 GET 请求
+pub fn get_with_headers(url: &str, headers: &[(&str, &str)]) -> Result<String> {
+    let mut command = Command::new("curl");
+    command.args(["-sSfL", "--max-time", "15", "-A", USER_AGENT]);
+    for (name, value) in headers {
+        command.arg("-H").arg(format!("{}: {}", name, value));
+    }
+    let output = command.arg(url).output()?;
+
+    if !output.status.success() {
+        bail!("请求失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// POST 一段 JSON，请求体从标准输入交给 curl，只关心是否成功
+pub fn post_json(url: &str, body: &str) -> Result<()> {
+    send_json("POST", url, body)
+}
+
+/// 用 PUT 发送 JSON，如 Hue 桥接器的接口
+pub fn put_json(url: &str, body: &str) -> Result<()> {
+    send_json("PUT", url, body)
+}
+
+fn send_json(method: &str, url: &str, body: &str) -> Result<()> {
+    let mut child = Command::new("curl")
+        .args(["-sSf", "-X", method, "--max-time", "15", "-A", USER_AGENT, "-H", "Content-Type: application/json"])
+        .args(["--data-binary", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        bail!("请求失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// HEAD 请求，返回最终响应（跟随重定向之后）的响应头，名称统一为小写
+pub fn head(url: &str) -> Result<Vec<(String, String)>> {
+    let output = Command::new("curl")
+        .args(["-sSfIL", "--netrc-optional", "--max-time", "15", "-A", USER_AGENT])
+        .arg(url)
+        .output()?;
+
+    if !output.status.success() {
+        bail!("请求失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    // 每次重定向都会输出一组响应头，只取最后一组
+    let last = text.split("\r\n\r\n").filter(|block| !block.trim().is_empty()).last().unwrap_or_default();
+    Ok(last
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect())
+}
+
+/// 从 start 字节处开始下载，响应内容通过子进程的 stdout 逐块读取
+pub fn spawn_range(url: &str, start: u64) -> Result<Child> {
+    let mut command = Command::new("curl");
+    command.args(["-sSfL", "--netrc-optional", "--connect-timeout", "15", "-A", USER_AGENT]);
+    if start > 0 {
+        command.arg("-r").arg(format!("{}-", start));
+    }
+    Ok(command.arg(url).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?)
+}
+
+/// 下载文件到指定路径
+pub fn download(url: &str, dest: &Path) -> Result<()> {
+    let output = Command::new("curl")
+        .args(["-sSfL", "--max-time", "600", "-A", USER_AGENT, "-o"])
+        .arg(dest)
+        .arg(url)
+        .output()?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(dest);
+        bail!("下载失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// 解码 URL 中的 %XX 转义
+pub fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// URL 查询参数编码
+pub fn encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,494 @@
+use anyhow::bail;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use symphonia::core::io::MediaSource;
+
+use crate::http;
+use crate::metrics;
+
+/// 缓冲区按块记录哪些部分已经下载
+const CHUNK_SIZE: u64 = 64 * 1024;
+/// 读取位置在下载位置之后这么多块以内时等待下载追上，否则从读取位置重新发起范围请求
+const CATCH_UP_CHUNKS: u64 = 16;
+/// 读取位置之后连续已下载的数据少于这个量时暂停播放等待缓冲
+const LOW_WATER: u64 = 128 * 1024;
+/// 缓冲恢复到这个量后继续播放
+const HIGH_WATER: u64 = 1024 * 1024;
+/// 读取时等待数据的最长时间，超时后按读取错误处理
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// 长度未知的流（网络电台）在内存中最多保留这么多数据，解码器跟不上时暂停下载
+const RING_SIZE: usize = 4 * 1024 * 1024;
+
+/// 路径是否是网络地址（http(s)，或者远程曲库的 sftp）
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| {
+        path.starts_with("http://") || path.starts_with("https://") || path.starts_with("sftp://")
+    })
+}
+
+/// 地址最后一段（去掉查询参数）作为歌曲名
+pub fn url_file_name(url: &str) -> String {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.trim_end_matches('/').rsplit('/').next())
+        .map(http::decode)
+        .unwrap_or_default()
+}
+
+/// 长度已知的文件下载到临时文件的对应位置，内存占用和文件大小无关。关闭后删除
+#[derive(Debug)]
+struct Spill {
+    path: PathBuf,
+    file: File,
+}
+
+impl Spill {
+    fn create(len: u64) -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("cuper-stream-{}-{}.part", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let path = env::temp_dir().join(name);
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        let spill = Spill { path, file };
+        spill.file.set_len(len)?;
+        Ok(spill)
+    }
+
+    fn write_at(&self, pos: u64, bytes: &[u8]) -> io::Result<()> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(pos))?;
+        file.write_all(bytes)
+    }
+
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(buf)
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// 长度已知时下载的数据
+    spill: Option<Spill>,
+    /// 长度未知时最近下载的数据，ring_start 是其中第一个字节在流中的位置
+    ring: VecDeque<u8>,
+    ring_start: u64,
+    /// 每块是否已经下载完整（长度未知时不使用，已下载的就是 ring 的全部）
+    present: Vec<bool>,
+    len: Option<u64>,
+    /// 服务器支持范围请求，可以从任意位置开始下载
+    ranges: bool,
+    /// 当前下载写入的位置
+    download_pos: u64,
+    /// 每次重新发起下载加一，旧的下载线程发现不一致后退出
+    generation: u64,
+    downloading: bool,
+    closed: bool,
+    error: Option<String>,
+    /// 解码器最近一次读取的位置
+    read_pos: u64,
+}
+
+impl State {
+    fn available(&self, pos: u64) -> bool {
+        match self.len {
+            Some(_) => self.present.get((pos / CHUNK_SIZE) as usize).copied().unwrap_or(false),
+            None => (self.ring_start..self.ring_end()).contains(&pos),
+        }
+    }
+
+    fn ring_end(&self) -> u64 {
+        self.ring_start + self.ring.len() as u64
+    }
+
+    /// pos 之后连续已下载的字节数
+    fn ahead(&self, pos: u64) -> u64 {
+        let Some(len) = self.len else {
+            return self.ring_end().saturating_sub(pos);
+        };
+        let mut end = pos;
+        while end < len && self.available(end) {
+            end = (end / CHUNK_SIZE + 1) * CHUNK_SIZE;
+        }
+        end.min(len).saturating_sub(pos)
+    }
+
+    /// 数据是否已经连续下载到文件末尾
+    fn reaches_end(&self, pos: u64) -> bool {
+        match self.len {
+            Some(len) => pos + self.ahead(pos) >= len,
+            None => !self.downloading && self.error.is_none(),
+        }
+    }
+
+    fn downloaded(&self) -> u64 {
+        match self.len {
+            Some(len) => {
+                let chunks = self.present.iter().filter(|present| **present).count() as u64;
+                (chunks * CHUNK_SIZE).min(len)
+            }
+            None => self.ring_end(),
+        }
+    }
+
+    /// 写入下载到的数据，返回写入的字节数。长度未知时缓冲区满了、解码器还没读到的数据不能丢，
+    /// 可能只写入一部分或者写不进去
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let start = self.download_pos;
+        let end = start + bytes.len() as u64;
+        match (self.len, &self.spill) {
+            (Some(len), Some(spill)) => {
+                let end = end.min(len);
+                spill.write_at(start, &bytes[..(end - start) as usize])?;
+                // 下载总是从块边界开始，写到块尾（或文件末尾）的块就是完整的
+                for chunk in start / CHUNK_SIZE..end.div_ceil(CHUNK_SIZE) {
+                    let chunk_end = ((chunk + 1) * CHUNK_SIZE).min(len);
+                    if chunk_end <= end {
+                        self.present[chunk as usize] = true;
+                    }
+                }
+                self.download_pos = end;
+                Ok(bytes.len())
+            }
+            _ => {
+                // 丢掉解码器已经读过的数据腾出空间
+                let overflow = (self.ring.len() + bytes.len()).saturating_sub(RING_SIZE);
+                let consumed = self.read_pos.saturating_sub(self.ring_start) as usize;
+                let drop = overflow.min(consumed);
+                self.ring.drain(..drop);
+                self.ring_start += drop as u64;
+
+                let written = bytes.len().min(RING_SIZE - self.ring.len());
+                self.ring.extend(&bytes[..written]);
+                self.download_pos += written as u64;
+                Ok(written)
+            }
+        }
+    }
+
+    /// 把 pos 开始的 buf.len() 个字节复制到 buf，调用前已确认这些数据都已下载
+    fn read(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        match &self.spill {
+            Some(spill) => spill.read_at(pos, buf),
+            None => {
+                let offset = (pos - self.ring_start) as usize;
+                let end = offset + buf.len();
+                for (target, byte) in buf.iter_mut().zip(self.ring.range(offset..end)) {
+                    *target = *byte;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 边下载边播放的网络音频。长度已知时数据保存在临时文件中，已经下载过的部分跳转时不再重复请求；
+/// 长度未知时只在内存中保留最近的一段
+pub struct HttpStream {
+    url: String,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// 缓冲状态，用于界面显示
+#[derive(Debug, Clone, Copy)]
+pub struct BufferStatus {
+    pub downloaded: u64,
+    pub len: Option<u64>,
+}
+
+impl BufferStatus {
+    /// 已下载的比例，长度未知时为 None
+    pub fn fill(&self) -> Option<f64> {
+        self.len.filter(|len| *len > 0).map(|len| self.downloaded as f64 / len as f64)
+    }
+}
+
+impl HttpStream {
+    /// 查询文件长度和是否支持范围请求，并开始在后台下载。会阻塞到服务器回应，不要在界面线程中调用。
+    /// 无法访问时不在这里报错，解码器读取数据时会得到下载错误；只有无法创建临时文件时返回错误
+    pub fn open(url: &str) -> io::Result<Arc<Self>> {
+        let headers = http::head(url).unwrap_or_default();
+        let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+        let len = header("content-length").and_then(|len| len.parse::<u64>().ok()).filter(|len| *len > 0);
+        let ranges = len.is_some() && header("accept-ranges").is_some_and(|value| value.contains("bytes"));
+
+        let state = State {
+            spill: len.map(Spill::create).transpose()?,
+            present: vec![false; len.map(|len| len.div_ceil(CHUNK_SIZE)).unwrap_or_default() as usize],
+            len,
+            ranges,
+            ..Default::default()
+        };
+        let stream = Arc::new(HttpStream {
+            url: url.to_string(),
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+        });
+        let mut state = stream.lock();
+        stream.start_download(&mut state, 0);
+        drop(state);
+        Ok(stream)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn reader(self: &Arc<Self>) -> HttpReader {
+        HttpReader { stream: self.clone(), pos: 0 }
+    }
+
+    pub fn status(&self) -> BufferStatus {
+        let state = self.lock();
+        BufferStatus { downloaded: state.downloaded(), len: state.len }
+    }
+
+    /// 读取位置之后的数据不够了，应该暂停播放等待缓冲
+    pub fn is_starved(&self) -> bool {
+        let state = self.lock();
+        !state.reaches_end(state.read_pos) && state.ahead(state.read_pos) < LOW_WATER
+    }
+
+    /// 已经完整下载时把数据复制到 dest，用于缓存到本地。没有下载完时返回 false
+    pub fn save_complete(&self, dest: &Path) -> io::Result<bool> {
+        let state = self.lock();
+        let Some(spill) = state.spill.as_ref().filter(|_| state.present.iter().all(|present| *present)) else {
+            return Ok(false);
+        };
+        fs::copy(&spill.path, dest)?;
+        Ok(true)
+    }
+
+    /// 缓冲已经足够继续播放（或者下载出错，继续播放让解码器结束这首歌）
+    pub fn is_ready(&self) -> bool {
+        let state = self.lock();
+        state.error.is_some() || state.reaches_end(state.read_pos) || state.ahead(state.read_pos) >= HIGH_WATER
+    }
+
+    /// 停止后台下载（切歌后不再需要这首歌的数据）
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.downloading = false;
+        state.closed = true;
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    fn start_download(self: &Arc<Self>, state: &mut State, start: u64) {
+        state.generation += 1;
+        state.download_pos = start;
+        state.downloading = true;
+        state.error = None;
+        let generation = state.generation;
+        let stream = self.clone();
+        thread::spawn(move || stream.download(generation, start));
+    }
+
+    fn download(&self, generation: u64, start: u64) {
+        let result = http::spawn_range(&self.url, start).and_then(|mut child| {
+            let mut stdout = child.stdout.take().expect("stdout 已设置为管道");
+            let mut buffer = vec![0; 16 * 1024];
+            loop {
+                let read = stdout.read(&mut buffer)?;
+                let mut state = self.lock();
+                let mut pending = &buffer[..read];
+                loop {
+                    if state.generation != generation {
+                        drop(state);
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Ok(());
+                    }
+                    let written = state.write(pending)?;
+                    pending = &pending[written..];
+                    if pending.is_empty() {
+                        break;
+                    }
+                    // 缓冲区满了，等解码器读走一些（暂停时 curl 也随之暂停读取）
+                    self.changed.notify_all();
+                    state = self.changed.wait(state).unwrap_or_else(|err| err.into_inner());
+                }
+                drop(state);
+                if read == 0 {
+                    break;
+                }
+                metrics::record_bytes(read);
+                self.changed.notify_all();
+            }
+
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            if !child.wait()?.success() {
+                bail!("下载失败: {}", stderr.trim());
+            }
+            Ok(())
+        });
+
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.downloading = false;
+            if let Err(err) = result {
+                state.error = Some(err.to_string());
+            }
+        }
+        drop(state);
+        self.changed.notify_all();
+    }
+}
+
+/// 交给解码器的读取端，数据还没下载到时阻塞等待
+pub struct HttpReader {
+    stream: Arc<HttpStream>,
+    pos: u64,
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let mut state = self.stream.lock();
+        state.read_pos = self.pos;
+        loop {
+            if state.closed {
+                return Err(io::Error::other("网络流已关闭"));
+            }
+            if state.len.is_some_and(|len| self.pos >= len) {
+                return Ok(0);
+            }
+            if state.available(self.pos) {
+                let ahead = state.ahead(self.pos).min(buf.len() as u64) as usize;
+                state.read(self.pos, &mut buf[..ahead])?;
+                self.pos += ahead as u64;
+                state.read_pos = self.pos;
+                drop(state);
+                // 下载可能在等缓冲区腾出空间
+                self.stream.changed.notify_all();
+                return Ok(ahead);
+            }
+            if state.len.is_none() && self.pos < state.ring_start {
+                return Err(io::Error::other("网络流中这部分数据已经丢弃"));
+            }
+            if !state.downloading {
+                if let Some(error) = &state.error {
+                    return Err(io::Error::other(error.clone()));
+                }
+                if state.len.is_none() {
+                    return Ok(0);
+                }
+                if !state.ranges {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "下载的数据不完整"));
+                }
+            }
+
+            // 跳转到了还没下载、下载也不会很快到达的位置：从这里重新请求
+            let chunk = self.pos / CHUNK_SIZE;
+            let download_chunk = state.download_pos / CHUNK_SIZE;
+            let catching_up = state.downloading && (download_chunk..=download_chunk + CATCH_UP_CHUNKS).contains(&chunk);
+            if state.ranges && !catching_up {
+                self.stream.start_download(&mut state, chunk * CHUNK_SIZE);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "等待网络数据超时"));
+            }
+            state = self
+                .stream
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.stream.lock().len;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => len.and_then(|len| len.checked_add_signed(offset)),
+        };
+        self.pos = target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无效的跳转位置"))?;
+        Ok(self.pos)
+    }
+}
+
+impl MediaSource for HttpReader {
+    fn is_seekable(&self) -> bool {
+        self.stream.lock().ranges
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.stream.lock().len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_keeps_unread_data_and_drops_read_data() {
+        let mut state = State::default();
+        let block = vec![7u8; RING_SIZE / 2];
+        assert_eq!(state.write(&block).unwrap(), block.len());
+        assert_eq!(state.write(&block).unwrap(), block.len());
+        // 解码器还没读过任何数据，缓冲区满了就写不进去
+        assert_eq!(state.write(&[1, 2, 3]).unwrap(), 0);
+
+        state.read_pos = 2;
+        assert_eq!(state.write(&[1, 2, 3]).unwrap(), 2);
+        assert_eq!(state.ring.len(), RING_SIZE);
+        assert_eq!(state.ring_start, 2);
+        assert!(!state.available(1));
+        assert_eq!(state.downloaded(), RING_SIZE as u64 + 2);
+
+        let mut buf = [0; 4];
+        state.read(RING_SIZE as u64 - 2, &mut buf).unwrap();
+        assert_eq!(buf, [7, 7, 1, 2]);
+    }
+
+    #[test]
+    fn known_length_is_written_to_a_temp_file() {
+        let len = CHUNK_SIZE * 2 + 10;
+        let spill = Spill::create(len).unwrap();
+        let path = spill.path.clone();
+        let mut state = State {
+            spill: Some(spill),
+            present: vec![false; 3],
+            len: Some(len),
+            ..Default::default()
+        };
+        state.download_pos = CHUNK_SIZE;
+        let data: Vec<u8> = (0..CHUNK_SIZE + 10).map(|i| i as u8).collect();
+        assert_eq!(state.write(&data).unwrap(), data.len());
+        assert_eq!(state.present, [false, true, true]);
+        assert_eq!(state.ahead(CHUNK_SIZE + 5), CHUNK_SIZE + 5);
+        assert!(state.ring.is_empty());
+
+        let mut buf = [0; 3];
+        state.read(CHUNK_SIZE * 2 + 7, &mut buf).unwrap();
+        assert_eq!(buf, [7, 8, 9]);
+        assert!(path.is_file());
+        drop(state);
+        assert!(!path.exists());
+    }
+}
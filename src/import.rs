@@ -0,0 +1,508 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::{scan_songs, Song};
+use crate::migrate;
+use crate::playlists;
+
+/// 外部播放列表中的一首歌
+#[derive(Debug, Clone)]
+pub struct ExternalTrack {
+    pub artist: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalPlaylist {
+    pub name: String,
+    pub tracks: Vec<ExternalTrack>,
+}
+
+/// `music_tui import <文件>...`：导入 iTunes/Apple Music XML、Spotify CSV 或 MPD 的 M3U 播放列表，
+/// 以及 MPD sticker、beets、Clementine 数据库中的播放次数、评分和播放列表
+pub fn run(args: &[OsString]) -> Result<()> {
+    if args.is_empty() {
+        bail!("用法: music_tui import <Library.xml|playlist.csv|playlist.m3u|sticker.sql|library.db|clementine.db>...");
+    }
+
+    let library = scan_songs(Path::new("assets"))?;
+    println!("曲库中共有 {} 首歌曲", library.len());
+
+    for arg in args {
+        let path = PathBuf::from(arg);
+        if let Some(source) = migrate::detect(&path)? {
+            migrate::run(source, &path, &library)?;
+            continue;
+        }
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8")) {
+            migrate::import_m3u(&path, &library)?;
+            continue;
+        }
+        for playlist in parse_file(&path)? {
+            import_playlist(&playlist, &library)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_file(path: &Path) -> Result<Vec<ExternalPlaylist>> {
+    let content = fs::read_to_string(path)?;
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "xml" => parse_itunes_xml(&content),
+        "csv" => {
+            let name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            Ok(vec![parse_spotify_csv(&name, &content)?])
+        }
+        _ => bail!("不支持的播放列表格式: {}", path.display()),
+    }
+}
+
+fn import_playlist(playlist: &ExternalPlaylist, library: &[Song]) -> Result<()> {
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+
+    for track in &playlist.tracks {
+        match find_match(track, library) {
+            Some(song) => matched.push(song.path.clone()),
+            None => unmatched.push(track),
+        }
+    }
+
+    println!();
+    println!(
+        "播放列表「{}」: 匹配 {}/{} 首",
+        playlist.name,
+        matched.len(),
+        playlist.tracks.len()
+    );
+
+    if !matched.is_empty() {
+        let file = playlists::save_m3u(&playlist.name, &matched)?;
+        println!("  已保存到 {}", file.display());
+    }
+    for track in unmatched {
+        println!("  ✗ 未找到: {} - {}", track.artist, track.title);
+    }
+    Ok(())
+}
+
+/// 在曲库中模糊匹配 艺术家+标题，找不到足够相似的歌曲时返回 None
+pub fn find_match<'a>(track: &ExternalTrack, library: &'a [Song]) -> Option<&'a Song> {
+    let title = normalize(&track.title);
+    if title.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(f64, &Song)> = None;
+    for song in library {
+        let title_score = similarity(&title, &normalize(&song.title()));
+        let score = match song.artist() {
+            Some(artist) if !track.artist.is_empty() => {
+                let artist_score = artist_similarity(&track.artist, &artist);
+                if artist_score < 0.5 {
+                    continue;
+                }
+                title_score * 0.7 + artist_score * 0.3
+            }
+            // 没有艺术家信息时只比较标题，要求更严格
+            _ if title_score >= 0.9 => title_score - 0.1,
+            _ => continue,
+        };
+
+        if score >= 0.8 && best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, song));
+        }
+    }
+    best.map(|(_, song)| song)
+}
+
+/// 多位艺术家（"A, B & C"）时取任意一位的最佳相似度
+fn artist_similarity(a: &str, b: &str) -> f64 {
+    let split = |s: &str| -> Vec<String> {
+        s.split([',', '&', ';', '/'])
+            .map(normalize)
+            .filter(|part| !part.is_empty())
+            .collect()
+    };
+
+    let whole = similarity(&normalize(a), &normalize(b));
+    let parts_a = split(a);
+    let parts_b = split(b);
+    parts_a
+        .iter()
+        .flat_map(|x| parts_b.iter().map(move |y| similarity(x, y)))
+        .fold(whole, f64::max)
+}
+
+/// 统一大小写，去掉括号内容、"feat." 和标点，便于比较
+fn normalize(s: &str) -> String {
+    let mut lower = s.to_lowercase();
+    for marker in [" feat.", " feat ", " ft.", " featuring "] {
+        if let Some(index) = lower.find(marker) {
+            lower.truncate(index);
+        }
+    }
+
+    let mut result = String::new();
+    let mut depth = 0;
+    for c in lower.chars() {
+        match c {
+            '(' | '[' | '（' | '【' => depth += 1,
+            ')' | ']' | '）' | '】' => depth = (depth - 1).max(0),
+            _ if depth > 0 => {}
+            c if c.is_alphanumeric() => result.push(c),
+            _ => {
+                if !result.ends_with(' ') {
+                    result.push(' ');
+                }
+            }
+        }
+    }
+    result.trim().to_string()
+}
+
+/// 基于编辑距离的相似度，1.0 表示完全相同
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// 解析 Spotify 导出的 CSV（Exportify 等工具），按表头查找曲名和艺术家列
+pub fn parse_spotify_csv(name: &str, content: &str) -> Result<ExternalPlaylist> {
+    let mut rows = parse_csv(content).into_iter();
+    let header = rows.next().ok_or_else(|| anyhow!("CSV 文件为空"))?;
+
+    let find_column = |candidates: &[&str]| {
+        header.iter().position(|column| {
+            let column = column.trim().to_lowercase();
+            candidates.iter().any(|candidate| column == *candidate)
+        })
+    };
+    let title_column = find_column(&["track name", "track_name", "title", "name"])
+        .ok_or_else(|| anyhow!("CSV 中找不到曲名列"))?;
+    let artist_column = find_column(&["artist name(s)", "artist name", "artist_name", "artist", "artists"]);
+
+    let tracks = rows
+        .filter_map(|row| {
+            let title = row.get(title_column)?.trim().to_string();
+            let artist = artist_column
+                .and_then(|column| row.get(column))
+                .map(|artist| artist.trim().to_string())
+                .unwrap_or_default();
+            (!title.is_empty()).then_some(ExternalTrack { artist, title })
+        })
+        .collect();
+
+    Ok(ExternalPlaylist {
+        name: name.to_string(),
+        tracks,
+    })
+}
+
+/// 简单的 CSV 解析，支持引号、转义引号和引号内换行
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[derive(Debug)]
+enum Plist {
+    Dict(Vec<(String, Plist)>),
+    Array(Vec<Plist>),
+    String(String),
+    Integer(i64),
+    Other,
+}
+
+impl Plist {
+    fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Plist::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Plist::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum XmlToken {
+    Start(String),
+    End(String),
+    Empty(String),
+    Text(String),
+}
+
+/// 解析 iTunes/Apple Music 导出的 Library.xml（plist 格式）中的所有用户播放列表
+pub fn parse_itunes_xml(content: &str) -> Result<Vec<ExternalPlaylist>> {
+    let tokens = tokenize_xml(content);
+    let mut tokens = tokens.into_iter().peekable();
+    let root = parse_plist_value(&mut tokens).ok_or_else(|| anyhow!("无法解析 iTunes XML"))?;
+
+    let mut tracks_by_id = HashMap::new();
+    if let Some(Plist::Dict(tracks)) = root.get("Tracks") {
+        for (id, track) in tracks {
+            let title = track.get("Name").and_then(Plist::as_str).unwrap_or_default();
+            let artist = track.get("Artist").and_then(Plist::as_str).unwrap_or_default();
+            tracks_by_id.insert(
+                id.clone(),
+                ExternalTrack {
+                    artist: artist.to_string(),
+                    title: title.to_string(),
+                },
+            );
+        }
+    }
+
+    let mut playlists = Vec::new();
+    if let Some(Plist::Array(items)) = root.get("Playlists") {
+        for item in items {
+            // 跳过"资料库"、"音乐"等系统播放列表
+            if item.get("Master").is_some() || item.get("Distinguished Kind").is_some() {
+                continue;
+            }
+            let name = item.get("Name").and_then(Plist::as_str).unwrap_or("iTunes");
+            let tracks = match item.get("Playlist Items") {
+                Some(Plist::Array(entries)) => entries
+                    .iter()
+                    .filter_map(|entry| match entry.get("Track ID") {
+                        Some(Plist::Integer(id)) => tracks_by_id.get(&id.to_string()).cloned(),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            playlists.push(ExternalPlaylist {
+                name: name.to_string(),
+                tracks,
+            });
+        }
+    }
+    Ok(playlists)
+}
+
+fn parse_plist_value<I>(tokens: &mut std::iter::Peekable<I>) -> Option<Plist>
+where
+    I: Iterator<Item = XmlToken>,
+{
+    loop {
+        match tokens.next()? {
+            XmlToken::Text(_) => continue,
+            XmlToken::Empty(tag) => return Some(if tag == "string" { Plist::String(String::new()) } else { Plist::Other }),
+            XmlToken::End(_) => return None,
+            XmlToken::Start(tag) => {
+                return Some(match tag.as_str() {
+                    "plist" => {
+                        let value = parse_plist_value(tokens);
+                        skip_until_end(tokens, "plist");
+                        value?
+                    }
+                    "dict" => {
+                        let mut entries = Vec::new();
+                        loop {
+                            match tokens.next()? {
+                                XmlToken::End(tag) if tag == "dict" => break,
+                                XmlToken::Start(tag) if tag == "key" => {
+                                    let key = read_text(tokens, "key");
+                                    let value = parse_plist_value(tokens)?;
+                                    entries.push((key, value));
+                                }
+                                _ => {}
+                            }
+                        }
+                        Plist::Dict(entries)
+                    }
+                    "array" => {
+                        let mut items = Vec::new();
+                        loop {
+                            match tokens.peek()? {
+                                XmlToken::End(tag) if tag == "array" => {
+                                    tokens.next();
+                                    break;
+                                }
+                                XmlToken::Text(_) => {
+                                    tokens.next();
+                                }
+                                _ => items.push(parse_plist_value(tokens)?),
+                            }
+                        }
+                        Plist::Array(items)
+                    }
+                    "string" => Plist::String(read_text(tokens, "string")),
+                    "integer" => read_text(tokens, "integer").trim().parse().map(Plist::Integer).unwrap_or(Plist::Other),
+                    other => {
+                        skip_until_end(tokens, other);
+                        Plist::Other
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn read_text<I>(tokens: &mut std::iter::Peekable<I>, tag: &str) -> String
+where
+    I: Iterator<Item = XmlToken>,
+{
+    let mut text = String::new();
+    for token in tokens.by_ref() {
+        match token {
+            XmlToken::Text(t) => text.push_str(&t),
+            XmlToken::End(end) if end == tag => break,
+            _ => {}
+        }
+    }
+    text
+}
+
+fn skip_until_end<I>(tokens: &mut std::iter::Peekable<I>, tag: &str)
+where
+    I: Iterator<Item = XmlToken>,
+{
+    for token in tokens.by_ref() {
+        if matches!(&token, XmlToken::End(end) if end == tag) {
+            break;
+        }
+    }
+}
+
+fn tokenize_xml(content: &str) -> Vec<XmlToken> {
+    let mut tokens = Vec::new();
+    let mut rest = content;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('<') {
+            // 声明、DOCTYPE 和注释直接跳过
+            let end = if after.starts_with("!--") {
+                after.find("-->").map(|i| i + 3)
+            } else {
+                after.find('>').map(|i| i + 1)
+            };
+            let Some(end) = end else {
+                break;
+            };
+            let tag = &after[..end - 1];
+            rest = &after[end..];
+
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                tokens.push(XmlToken::End(name.trim().to_string()));
+            } else if let Some(name) = tag.strip_suffix('/') {
+                tokens.push(XmlToken::Empty(tag_name(name)));
+            } else {
+                tokens.push(XmlToken::Start(tag_name(tag)));
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            tokens.push(XmlToken::Text(decode_entities(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.split_whitespace().next().unwrap_or_default().to_string()
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
@@ -0,0 +1,181 @@
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::http;
+use crate::json::{self, Value};
+use crate::storage;
+
+/// 后台获取的结果：(缓存键, 信息行或错误)
+pub type InfoResult = (String, Result<Vec<String>, String>);
+
+pub fn cache_key(artist: &str, album: Option<&str>) -> String {
+    match album {
+        Some(album) => format!("{} - {}", artist, album),
+        None => artist.to_string(),
+    }
+}
+
+fn cache_path(key: &str) -> PathBuf {
+    storage::cache_dir()
+        .join("info")
+        .join(format!("{}.txt", storage::sanitize_file_name(key)))
+}
+
+pub fn load_cached(key: &str) -> Option<Vec<String>> {
+    let content = fs::read_to_string(cache_path(key)).ok()?;
+    Some(content.lines().map(str::to_string).collect())
+}
+
+fn save_cache(key: &str, lines: &[String]) -> Result<()> {
+    let path = cache_path(key);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, lines.join("\n"))?;
+    Ok(())
+}
+
+/// 在后台线程中获取艺术家和专辑信息，完成后写入缓存
+pub fn spawn_fetch(key: String, artist: String, album: Option<String>) -> Receiver<InfoResult> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = fetch(&artist, album.as_deref()).map_err(|err| err.to_string());
+        if let Ok(lines) = &result {
+            let _ = save_cache(&key, lines);
+        }
+        let _ = tx.send((key, result));
+    });
+    rx
+}
+
+fn fetch(artist: &str, album: Option<&str>) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+
+    let url = format!(
+        "https://musicbrainz.org/ws/2/artist/?query={}&fmt=json&limit=1",
+        http::encode(&format!("artist:\"{}\"", artist))
+    );
+    let response = json::parse(&http::get(&url)?)?;
+    match response.get("artists").and_then(Value::as_array).and_then(|a| a.first()) {
+        Some(found) => lines.extend(describe_artist(found)),
+        None => lines.push(format!("MusicBrainz 中找不到艺术家: {}", artist)),
+    }
+
+    // last.fm 的艺术家简介需要 API key
+    if let Ok(api_key) = env::var("CUPER_LASTFM_API_KEY") {
+        let url = format!(
+            "https://ws.audioscrobbler.com/2.0/?method=artist.getinfo&artist={}&api_key={}&format=json",
+            http::encode(artist),
+            http::encode(&api_key)
+        );
+        if let Ok(response) = http::get(&url).and_then(|body| json::parse(&body)) {
+            let summary = response
+                .get("artist")
+                .and_then(|a| a.get("bio"))
+                .and_then(|bio| bio.get("summary"))
+                .and_then(Value::as_str)
+                .map(strip_html)
+                .unwrap_or_default();
+            if !summary.is_empty() {
+                lines.push(String::new());
+                lines.push(format!("简介: {}", summary));
+            }
+        }
+    }
+
+    if let Some(album) = album {
+        let url = format!(
+            "https://musicbrainz.org/ws/2/release/?query={}&fmt=json&limit=1",
+            http::encode(&format!("release:\"{}\" AND artist:\"{}\"", album, artist))
+        );
+        let response = json::parse(&http::get(&url)?)?;
+        lines.push(String::new());
+        match response.get("releases").and_then(Value::as_array).and_then(|r| r.first()) {
+            Some(release) => lines.extend(describe_release(release)),
+            None => lines.push(format!("MusicBrainz 中找不到专辑: {}", album)),
+        }
+    }
+
+    Ok(lines)
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str).filter(|s| !s.is_empty())
+}
+
+fn describe_artist(artist: &Value) -> Vec<String> {
+    let mut lines = Vec::new();
+    let name = field(artist, "name").unwrap_or("?");
+    let details: Vec<&str> = [field(artist, "type"), field(artist, "country")]
+        .into_iter()
+        .flatten()
+        .collect();
+    if details.is_empty() {
+        lines.push(format!("艺术家: {}", name));
+    } else {
+        lines.push(format!("艺术家: {} ({})", name, details.join(", ")));
+    }
+
+    if let Some(disambiguation) = field(artist, "disambiguation") {
+        lines.push(format!("说明: {}", disambiguation));
+    }
+
+    if let Some(life_span) = artist.get("life-span") {
+        if let Some(begin) = field(life_span, "begin") {
+            lines.push(format!("活跃: {} – {}", begin, field(life_span, "end").unwrap_or("至今")));
+        }
+    }
+
+    if let Some(tags) = artist.get("tags").and_then(Value::as_array) {
+        let names: Vec<&str> = tags.iter().filter_map(|tag| field(tag, "name")).take(5).collect();
+        if !names.is_empty() {
+            lines.push(format!("风格: {}", names.join(", ")));
+        }
+    }
+    lines
+}
+
+fn describe_release(release: &Value) -> Vec<String> {
+    let mut lines = vec![format!("专辑: {}", field(release, "title").unwrap_or("?"))];
+
+    let details: Vec<&str> = [field(release, "date"), field(release, "country")]
+        .into_iter()
+        .flatten()
+        .collect();
+    if !details.is_empty() {
+        lines.push(format!("发行: {}", details.join(" · ")));
+    }
+
+    let label = release
+        .get("label-info")
+        .and_then(Value::as_array)
+        .and_then(|labels| labels.first())
+        .and_then(|info| info.get("label"))
+        .and_then(|label| field(label, "name"));
+    if let Some(label) = label {
+        lines.push(format!("厂牌: {}", label));
+    }
+
+    if let Some(Value::Number(count)) = release.get("track-count") {
+        lines.push(format!("曲目数: {}", count));
+    }
+    lines
+}
+
+fn strip_html(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped.trim().to_string()
+}
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use rodio::Source;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::app::Song;
+use crate::lyrics;
+use crate::replaygain::Filter;
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+
+const INSTRUMENTALS_FILE: &str = "instrumentals.tsv";
+
+/// 标题、专辑、流派或文件名中出现这些词时直接当作纯音乐
+const INSTRUMENTAL_WORDS: &[&str] = &["instrumental", "inst.", "off vocal", "karaoke", "伴奏", "纯音乐", "純音樂"];
+/// 歌词网站给纯音乐返回的占位歌词，如网易云音乐的“纯音乐，请欣赏”
+const PLACEHOLDER_LYRICS: &[&str] = &["纯音乐", "純音樂", "instrumental"];
+
+/// 跳过开头的前奏，从这里开始分析
+const SKIP_SECONDS: u64 = 30;
+const ANALYSIS_SECONDS: u64 = 60;
+const BLOCK_SECONDS: f64 = 0.05;
+/// 人声的主要频段
+const VOCAL_LOW_HZ: f64 = 250.0;
+const VOCAL_HIGH_HZ: f64 = 3500.0;
+/// 人声频段中间声道的能量是两侧的这么多倍（约 9 dB）时，这一段算作有居中的人声
+const CENTER_RATIO: f64 = 8.0;
+/// 有声的段落中有居中人声的比例低于这个值时判断为纯音乐
+const INSTRUMENTAL_SHARE: f64 = 0.3;
+/// 两侧声道的能量不到中间声道的这个比例时是单声道录音，人声和乐器分不开
+const MONO_SIDE_RATIO: f64 = 0.01;
+/// 比最响的段落低 30 dB 以上的段落当作静音
+const SILENCE_RATIO: f64 = 0.001;
+
+/// 判断的依据
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Evidence {
+    Tags,
+    Lyrics,
+    Audio,
+}
+
+impl Evidence {
+    fn name(self) -> &'static str {
+        match self {
+            Evidence::Tags => "tags",
+            Evidence::Lyrics => "lyrics",
+            Evidence::Audio => "audio",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tags" => Some(Evidence::Tags),
+            "lyrics" => Some(Evidence::Lyrics),
+            "audio" => Some(Evidence::Audio),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Evidence::Tags => "标签",
+            Evidence::Lyrics => "歌词",
+            Evidence::Audio => "音频分析",
+        }
+    }
+}
+
+/// 一首歌的识别结果。instrumental 为 None 表示无法判断（如单声道录音），同样记下来，下次不再分析
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub instrumental: Option<bool>,
+    pub evidence: Evidence,
+}
+
+pub fn load() -> HashMap<PathBuf, Detection> {
+    let records = storage::read_records(INSTRUMENTALS_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: instrumental/vocal/unknown \t 依据 \t 文件路径
+            let [verdict, evidence, path] = <[String; 3]>::try_from(record).ok()?;
+            let instrumental = match verdict.as_str() {
+                "instrumental" => Some(true),
+                "vocal" => Some(false),
+                _ => None,
+            };
+            let evidence = Evidence::parse(&evidence)?;
+            Some((storage::path_from_field(&path), Detection { instrumental, evidence }))
+        })
+        .collect()
+}
+
+pub fn save(detections: &HashMap<PathBuf, Detection>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = detections
+        .iter()
+        .map(|(path, detection)| {
+            let verdict = match detection.instrumental {
+                Some(true) => "instrumental",
+                Some(false) => "vocal",
+                None => "unknown",
+            };
+            vec![
+                verdict.to_string(),
+                detection.evidence.name().to_string(),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[2].cmp(&b[2]));
+    storage::write_records(INSTRUMENTALS_FILE, &records)
+}
+
+/// 标签或文件名写明是纯音乐、伴奏
+pub fn tagged(song: &Song) -> bool {
+    let tags = [&song.tags.title, &song.tags.album, &song.tags.genre];
+    std::iter::once(&song.name)
+        .chain(tags.into_iter().flatten())
+        .any(|text| {
+            let text = text.to_lowercase();
+            INSTRUMENTAL_WORDS.iter().any(|word| text.contains(word))
+        })
+}
+
+/// 依次看标签、本地保存的歌词和音频本身。有真正的歌词就是有人声，
+/// 音频分析只看人声频段里居中的声音有多少，纯音乐的主奏乐器也常在正中间，结果只是参考
+pub fn detect(song: &Song, cancel: &AtomicBool) -> Detection {
+    if tagged(song) {
+        return Detection {
+            instrumental: Some(true),
+            evidence: Evidence::Tags,
+        };
+    }
+    if let Some(lyrics) = lyrics::load_local(&song.path, song.artist().as_deref(), &song.title()) {
+        let placeholder = lyrics
+            .lines
+            .iter()
+            .map(|(_, line)| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty())
+            .all(|line| PLACEHOLDER_LYRICS.iter().any(|word| line.contains(word)));
+        return Detection {
+            instrumental: Some(placeholder),
+            evidence: Evidence::Lyrics,
+        };
+    }
+    Detection {
+        instrumental: center_share(&song.path, song.duration, cancel).map(|share| share < INSTRUMENTAL_SHARE),
+        evidence: Evidence::Audio,
+    }
+}
+
+/// 有声的段落中，人声频段的中间声道 (L+R)/2 明显强过两侧 (L-R)/2 的比例。
+/// 只能分析立体声，单声道录音返回 None
+fn center_share(path: &Path, duration: Option<Duration>, cancel: &AtomicBool) -> Option<f64> {
+    let start = match duration {
+        Some(duration) if duration.as_secs() > SKIP_SECONDS + ANALYSIS_SECONDS => Duration::from_secs(SKIP_SECONDS),
+        _ => Duration::ZERO,
+    };
+    let file = fs::File::open(path).ok()?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, start).ok()?;
+    if decoder.channels() != 2 {
+        return None;
+    }
+    let sample_rate = decoder.sample_rate().max(1) as f64;
+    let block = (sample_rate * BLOCK_SECONDS) as usize;
+    let band = [Filter::high_pass(VOCAL_LOW_HZ, sample_rate), Filter::low_pass(VOCAL_HIGH_HZ, sample_rate)];
+    let filter = |state: &mut [[f64; 2]; 2], input: f64| {
+        band.iter().zip(state.iter_mut()).fold(input, |value, (filter, state)| filter.process(state, value))
+    };
+    let mut mid_state = [[0.0; 2]; 2];
+    let mut side_state = [[0.0; 2]; 2];
+
+    let mut samples = decoder.take((sample_rate * ANALYSIS_SECONDS as f64) as usize * 2);
+    // 每段的 (中间, 两侧) 能量
+    let mut blocks: Vec<(f64, f64)> = Vec::new();
+    'blocks: loop {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let (mut mid, mut side) = (0.0, 0.0);
+        for _ in 0..block {
+            let (Some(left), Some(right)) = (samples.next(), samples.next()) else {
+                break 'blocks;
+            };
+            let m = filter(&mut mid_state, (left + right) as f64 / 2.0);
+            let s = filter(&mut side_state, (left - right) as f64 / 2.0);
+            mid += m * m;
+            side += s * s;
+        }
+        blocks.push((mid, side));
+    }
+
+    let total_mid: f64 = blocks.iter().map(|(mid, _)| mid).sum();
+    let total_side: f64 = blocks.iter().map(|(_, side)| side).sum();
+    if total_side < total_mid * MONO_SIDE_RATIO {
+        return None;
+    }
+    let loudest = blocks.iter().map(|(mid, side)| mid + side).fold(0.0, f64::max);
+    let loud: Vec<&(f64, f64)> = blocks.iter().filter(|(mid, side)| mid + side > loudest * SILENCE_RATIO).collect();
+    if loud.is_empty() {
+        return None;
+    }
+    let centered = loud.iter().filter(|(mid, side)| *mid > side * CENTER_RATIO).count();
+    Some(centered as f64 / loud.len() as f64)
+}
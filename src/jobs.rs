@@ -0,0 +1,195 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::app::{BoxedSource, Opened, Song};
+use crate::genre::Suggestion;
+use crate::http_stream::HttpStream;
+use crate::instrumental::Detection;
+use crate::replaygain::Loudness;
+use crate::waveform::Waveform;
+
+/// 后台任务和界面线程共享的进度
+#[derive(Debug, Default)]
+pub struct JobProgress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+    status: Mutex<String>,
+}
+
+impl JobProgress {
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// 开始处理下一项，status 显示在任务面板中
+    pub fn set_status(&self, status: impl Into<String>) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status.into();
+        }
+    }
+
+    pub fn advance(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+
+    /// 任务在每一项之间调用，被取消时返回错误以结束任务
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            bail!("已取消");
+        }
+        Ok(())
+    }
+}
+
+/// 任务完成后交给界面线程处理的结果
+pub enum JobOutput {
+    Message(String),
+    /// 重新扫描得到的曲库和按指纹认出的移动过的文件: (原路径, 新路径)
+    Library(Vec<Song>, Vec<(PathBuf, PathBuf)>),
+    /// 找回的移动过的文件: (原路径, 新路径)
+    Relocated(Vec<(PathBuf, PathBuf)>),
+    /// 下载完成、需要加入队列的文件
+    Enqueue(Vec<PathBuf>),
+    /// 远程曲库中的歌曲，追加到曲库末尾
+    AddSongs(Vec<Song>),
+    /// 解析得到的网络歌曲（带标题等信息），加入曲库末尾和队列
+    EnqueueSongs(Vec<Song>),
+    /// 响度分析的结果和完成消息
+    Loudness(Vec<(PathBuf, Loudness)>, String),
+    /// 纯音乐识别的结果和完成消息
+    Instrumentals(Vec<(PathBuf, Detection)>, String),
+    /// 生成的波形缩略图和完成消息
+    Waveforms(Vec<(PathBuf, Waveform)>, String),
+    /// 流派识别的建议和完成消息
+    Genres(Vec<(PathBuf, Suggestion)>, String),
+    /// 连接好的网络音频、从开头解码的音源和声道布局
+    Stream(Arc<HttpStream>, BoxedSource, Option<u32>),
+    /// 在后台打开的本地文件
+    Opened(Opened),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    Running,
+    Finished(String),
+    Failed(String),
+    Cancelled,
+}
+
+pub struct Job {
+    pub name: String,
+    pub state: JobState,
+    progress: Arc<JobProgress>,
+    rx: Receiver<Result<JobOutput>>,
+}
+
+impl Job {
+    /// 在后台线程中运行任务
+    pub fn spawn<F>(name: impl Into<String>, task: F) -> Job
+    where
+        F: FnOnce(&JobProgress) -> Result<JobOutput> + Send + 'static,
+    {
+        let progress = Arc::new(JobProgress::default());
+        let (tx, rx) = mpsc::channel();
+        let shared = progress.clone();
+        thread::spawn(move || {
+            let _ = tx.send(task(&shared));
+        });
+
+        Job {
+            name: name.into(),
+            state: JobState::Running,
+            progress,
+            rx,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == JobState::Running
+    }
+
+    pub fn cancel(&self) {
+        if self.is_running() {
+            self.progress.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// (已完成, 总数)，总数未知时为 0
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.progress.done.load(Ordering::Relaxed),
+            self.progress.total.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn ratio(&self) -> f64 {
+        match (&self.state, self.counts()) {
+            (JobState::Finished(_), _) => 1.0,
+            (_, (_, 0)) => 0.0,
+            (_, (done, total)) => (done as f64 / total as f64).min(1.0),
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match &self.state {
+            JobState::Running if self.progress.cancelled.load(Ordering::Relaxed) => "正在取消…".to_string(),
+            JobState::Running => self.progress.status.lock().map(|s| s.clone()).unwrap_or_default(),
+            JobState::Finished(message) => message.clone(),
+            JobState::Failed(err) => format!("失败: {}", err),
+            JobState::Cancelled => "已取消".to_string(),
+        }
+    }
+
+    /// 检查任务是否结束，结束时更新状态并返回结果
+    pub fn poll(&mut self) -> Option<JobOutput> {
+        if !self.is_running() {
+            return None;
+        }
+        match self.rx.try_recv() {
+            Ok(Ok(output)) => {
+                self.state = match &output {
+                    JobOutput::Message(message) => JobState::Finished(message.clone()),
+                    JobOutput::Library(songs, moves) if moves.is_empty() => {
+                        JobState::Finished(format!("共 {} 首歌曲", songs.len()))
+                    }
+                    JobOutput::Library(songs, moves) => {
+                        JobState::Finished(format!("共 {} 首歌曲，{} 首移动过的歌曲已跟随新路径", songs.len(), moves.len()))
+                    }
+                    JobOutput::Relocated(moves) => JobState::Finished(format!("找回 {} 首歌曲", moves.len())),
+                    JobOutput::Enqueue(paths) => JobState::Finished(format!("已加入队列 {} 首", paths.len())),
+                    JobOutput::AddSongs(songs) => JobState::Finished(format!("共 {} 首歌曲", songs.len())),
+                    JobOutput::EnqueueSongs(songs) => JobState::Finished(format!("已加入队列 {} 首", songs.len())),
+                    JobOutput::Loudness(_, message) => JobState::Finished(message.clone()),
+                    JobOutput::Instrumentals(_, message) => JobState::Finished(message.clone()),
+                    JobOutput::Waveforms(_, message) => JobState::Finished(message.clone()),
+                    JobOutput::Genres(_, message) => JobState::Finished(message.clone()),
+                    JobOutput::Stream(..) => JobState::Finished("已连接".to_string()),
+                    JobOutput::Opened(_) => JobState::Finished("已打开".to_string()),
+                };
+                Some(output)
+            }
+            Ok(Err(_)) if self.progress.cancelled.load(Ordering::Relaxed) => {
+                self.state = JobState::Cancelled;
+                None
+            }
+            Ok(Err(err)) => {
+                self.state = JobState::Failed(err.to_string());
+                None
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.state = JobState::Failed("任务线程意外退出".to_string());
+                None
+            }
+        }
+    }
+}
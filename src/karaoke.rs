@@ -0,0 +1,126 @@
+use rodio::Source;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 低于这个频率的中间声道保留：贝斯和底鼓通常也在正中间，全部去掉会显得很单薄
+const BASS_KEEP_HZ: f64 = 150.0;
+
+/// 播放线程和界面线程共享的卡拉 OK 开关
+#[derive(Debug, Default)]
+pub struct KaraokeControl {
+    enabled: AtomicBool,
+}
+
+impl KaraokeControl {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+/// 削弱人声的 Source 适配器：人声通常混在左右声道正中间，去掉中间声道 (L+R)/2
+/// 只保留两侧的差值 (L-R)/2，再把中间声道的低频加回去。单声道音源无法处理，原样输出
+pub struct VocalReducer<S> {
+    input: S,
+    control: Arc<KaraokeControl>,
+    /// 二阶 Butterworth 低通的系数 b0..b2、a1、a2
+    coefficients: [f64; 5],
+    state: [f64; 4],
+    /// 已经处理好、还没输出的右声道样本
+    pending_right: Option<f32>,
+    /// 关闭时原样输出，记下下一个样本是不是右声道，重新开启时才能对齐
+    at_right: bool,
+}
+
+impl<S> VocalReducer<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, control: Arc<KaraokeControl>) -> Self {
+        let sample_rate = input.sample_rate().max(1) as f64;
+        VocalReducer {
+            input,
+            control,
+            coefficients: lowpass(BASS_KEEP_HZ, sample_rate),
+            state: [0.0; 4],
+            pending_right: None,
+            at_right: false,
+        }
+    }
+
+    fn bass(&mut self, input: f64) -> f64 {
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        let [x1, x2, y1, y2] = self.state;
+        let output = b0 * input + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        self.state = [input, x1, output, y1];
+        output
+    }
+}
+
+/// RBJ 音频 EQ 手册中的低通滤波器，Q = 1/√2
+fn lowpass(frequency: f64, sample_rate: f64) -> [f64; 5] {
+    let w0 = 2.0 * PI * frequency / sample_rate;
+    let alpha = w0.sin() / 2f64.sqrt();
+    let a0 = 1.0 + alpha;
+    let b1 = (1.0 - w0.cos()) / a0;
+    [b1 / 2.0, b1, b1 / 2.0, -2.0 * w0.cos() / a0, (1.0 - alpha) / a0]
+}
+
+impl<S> Iterator for VocalReducer<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+        // 只处理立体声；开关在一帧的中间切换时这一帧保持原样
+        if self.input.channels() != 2 || !self.control.is_enabled() || self.at_right {
+            self.at_right = self.input.channels() == 2 && !self.at_right;
+            return self.input.next();
+        }
+        let left = self.input.next()?;
+        let Some(right) = self.input.next() else {
+            return Some(left);
+        };
+
+        let mid = (left as f64 + right as f64) / 2.0;
+        let side = (left as f64 - right as f64) / 2.0;
+        let bass = self.bass(mid);
+        self.pending_right = Some((bass - side) as f32);
+        Some((bass + side) as f32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for VocalReducer<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let pending = usize::from(self.pending_right.is_some());
+        self.input.current_frame_len().map(|len| len + pending)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::storage;
+
+const KEYMAP_FILE: &str = "keys.toml";
+/// 没有设置 leader 时的前缀键
+const DEFAULT_LEADER: KeyCode = KeyCode::Char('\\');
+
+/// 可以重新绑定按键的全局操作（面板内的按键和 Tab、数字音量键不能改）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    PlayPause,
+    Next,
+    Previous,
+    Scrub,
+    Undo,
+    Details,
+    Shuffle,
+    ShuffleQueue,
+    FollowPlayback,
+    Bookmark,
+    Bookmarks,
+    Editor,
+    Info,
+    Lyrics,
+    Karaoke,
+    Radio,
+    Equalizer,
+    Leveler,
+    PowerSaver,
+    Visualizer,
+    SortOrder,
+    BpmFilter,
+    Artists,
+    Collections,
+    Settings,
+    CopyPath,
+    CopyTitle,
+    RemoveMissing,
+    Relocate,
+    Jobs,
+    Rescan,
+    Loudness,
+    VolumeUp,
+    VolumeDown,
+    VolumeMax,
+    Top,
+    Bottom,
+    InstrumentalFilter,
+    DetectInstrumentals,
+    RetryAudio,
+    SaveSnapshot,
+    Snapshots,
+    RecordMacro,
+    PlayMacro,
+    ClassifyGenres,
+    Genres,
+    WebPairing,
+    AbLoop,
+    ExportSegment,
+    NextMarker,
+    PreviousMarker,
+}
+
+/// (操作, 配置文件中的名字, 说明, 默认按键)
+pub const ACTIONS: [(Action, &str, &str, &str); 52] = [
+    (Action::Quit, "quit", "退出", "q Q"),
+    (Action::PlayPause, "play_pause", "播放/暂停", "Space"),
+    (Action::Next, "next", "下一曲", "n N Right"),
+    (Action::Previous, "previous", "上一曲", "p P Left"),
+    (Action::Scrub, "scrub", "拖动跳转", "s"),
+    (Action::AbLoop, "ab_loop", "A-B 循环", "l"),
+    (Action::ExportSegment, "export_segment", "把 A-B 循环的片段导出为新文件", "gx"),
+    (Action::NextMarker, "next_marker", "跳到下一个标记", "]"),
+    (Action::PreviousMarker, "previous_marker", "跳到上一个标记", "["),
+    (Action::Undo, "undo", "撤销", "u"),
+    (Action::Details, "details", "歌曲信息", "i"),
+    (Action::Shuffle, "shuffle", "切换随机模式", "S"),
+    (Action::ShuffleQueue, "shuffle_queue", "打乱播放队列", "z"),
+    (Action::FollowPlayback, "follow_playback", "光标跟随播放", "f"),
+    (Action::Bookmark, "bookmark", "添加书签", "b"),
+    (Action::Bookmarks, "bookmarks", "书签列表", "B"),
+    (Action::Editor, "editor", "队列编辑界面", "W"),
+    (Action::Info, "info", "艺术家/专辑信息面板", "I"),
+    (Action::Lyrics, "lyrics", "歌词面板", "t"),
+    (Action::Karaoke, "karaoke", "卡拉 OK 模式", "Z"),
+    (Action::Radio, "radio", "推荐电台", "R"),
+    (Action::Equalizer, "equalizer", "切换均衡器预设", "E"),
+    (Action::Leveler, "leveler", "响度均衡", "V"),
+    (Action::PowerSaver, "power_saver", "省电模式", "gp"),
+    (Action::Visualizer, "visualizer", "切换可视化", "v"),
+    (Action::SortOrder, "sort_order", "切换排序方式", "O"),
+    (Action::BpmFilter, "bpm_filter", "按 BPM 过滤", "T"),
+    (Action::Artists, "artists", "按专辑艺术家浏览", "C"),
+    (Action::Collections, "collections", "收藏集", "H"),
+    (Action::Settings, "settings", "设置界面", ","),
+    (Action::CopyPath, "copy_path", "复制文件路径", "y"),
+    (Action::CopyTitle, "copy_title", "复制艺术家 - 标题", "Y"),
+    (Action::RemoveMissing, "remove_missing", "移除缺失的文件", "X"),
+    (Action::Relocate, "relocate", "查找移动过的文件", "F"),
+    (Action::Jobs, "jobs", "后台任务面板", "J"),
+    (Action::Rescan, "rescan", "重新扫描曲库", "L"),
+    (Action::Loudness, "loudness", "计算 ReplayGain", "G"),
+    (Action::VolumeUp, "volume_up", "增大音量", "+ ="),
+    (Action::VolumeDown, "volume_down", "减小音量", "-"),
+    (Action::VolumeMax, "volume_max", "最大音量", "m M"),
+    (Action::Top, "top", "跳到列表开头", "gg Home"),
+    (Action::Bottom, "bottom", "跳到列表末尾", "End"),
+    (Action::InstrumentalFilter, "instrumental_filter", "只显示纯音乐", "gi"),
+    (Action::DetectInstrumentals, "detect_instrumentals", "识别纯音乐", "gI"),
+    (Action::RetryAudio, "retry_audio", "重新打开音频设备", "ga"),
+    (Action::SaveSnapshot, "save_snapshot", "保存队列快照", "gq"),
+    (Action::Snapshots, "snapshots", "队列快照列表", "gQ"),
+    (Action::RecordMacro, "record_macro", "录制/停止录制键盘宏", "gm"),
+    (Action::PlayMacro, "play_macro", "执行键盘宏", "@"),
+    (Action::ClassifyGenres, "classify_genres", "识别没有流派的歌曲", "gc"),
+    (Action::Genres, "genres", "确认流派建议", "gC"),
+    (Action::WebPairing, "web_pairing", "网页遥控配对二维码", "gw"),
+];
+
+/// 按顺序按下的一组键，如 `gg`、`<Leader>r`；大多数操作只有一个键
+pub type KeySequence = Vec<KeyCode>;
+
+/// 已按下的键在按键绑定中的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatch {
+    Action(Action),
+    /// 是某个组合键的开头，等待下一个键
+    Prefix,
+    None,
+}
+
+/// 全局操作的按键绑定，保存在配置目录的 keys.toml 中（每行 `操作 = "按键 按键"`），只写入改过的操作。
+/// 几个键连写表示组合键（`gg`），`<Leader>` 代表 `leader = "键"` 设置的前缀键
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    leader: KeyCode,
+    bindings: Vec<Vec<KeySequence>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::with_leader(DEFAULT_LEADER)
+    }
+}
+
+impl Keymap {
+    fn path() -> PathBuf {
+        storage::config_dir().join(KEYMAP_FILE)
+    }
+
+    fn with_leader(leader: KeyCode) -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .map(|(_, _, _, keys)| parse_keys(keys, leader).unwrap_or_default())
+            .collect();
+        Keymap { leader, bindings }
+    }
+
+    /// 读取按键绑定，无法解析的行忽略
+    pub fn load() -> Self {
+        let Ok(content) = fs::read_to_string(Self::path()) else {
+            return Keymap::default();
+        };
+        let lines: Vec<(&str, &str)> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim(), value.trim().trim_matches('"')))
+            .collect();
+
+        // 先确定 leader，写在哪一行都可以
+        let leader = lines
+            .iter()
+            .find(|(name, _)| *name == "leader")
+            .and_then(|(_, value)| parse_key(value))
+            .unwrap_or(DEFAULT_LEADER);
+        let mut keymap = Keymap::with_leader(leader);
+        for (name, value) in lines {
+            let action = ACTIONS.iter().position(|(_, action, _, _)| *action == name);
+            if let (Some(index), Some(keys)) = (action, parse_keys(value, leader)) {
+                keymap.bindings[index] = keys;
+            }
+        }
+        keymap
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = storage::config_dir();
+        fs::create_dir_all(&dir)?;
+
+        let defaults = Keymap::with_leader(self.leader);
+        let mut content = String::from("# CuperMusicPlayer 按键绑定（只列出改过的操作）\n");
+        if self.leader != DEFAULT_LEADER {
+            content.push_str(&format!("leader = \"{}\"\n", key_name(self.leader)));
+        }
+        for (index, (_, name, _, _)) in ACTIONS.iter().enumerate() {
+            if self.bindings[index] != defaults.bindings[index] {
+                content.push_str(&format!("{} = \"{}\"\n", name, self.label(index)));
+            }
+        }
+        fs::write(Self::path(), content)?;
+        Ok(())
+    }
+
+    /// 已按下的一组键对应的操作，同一组键绑定了多个操作时取列表中靠前的
+    pub fn lookup(&self, keys: &[KeyCode]) -> KeyMatch {
+        if let Some(index) = self.bindings.iter().position(|sequences| sequences.iter().any(|bound| bound == keys)) {
+            return KeyMatch::Action(ACTIONS[index].0);
+        }
+        match self.bindings.iter().flatten().any(|bound| bound.len() > keys.len() && bound.starts_with(keys)) {
+            true => KeyMatch::Prefix,
+            false => KeyMatch::None,
+        }
+    }
+
+    /// 第几个操作的按键，如 `n N Right`、`gg Home`，没有绑定时为空字符串
+    pub fn label(&self, index: usize) -> String {
+        let names: Vec<String> = self.bindings[index].iter().map(|keys| self.sequence_label(keys)).collect();
+        names.join(" ")
+    }
+
+    /// 某个操作的按键，用在提示消息中
+    pub fn action_label(&self, action: Action) -> String {
+        ACTIONS
+            .iter()
+            .position(|(other, ..)| *other == action)
+            .map(|index| self.label(index))
+            .unwrap_or_default()
+    }
+
+    /// 一组键的写法：单个键用键名，组合键连写，命名键和 leader 用尖括号括起来
+    pub fn sequence_label(&self, keys: &[KeyCode]) -> String {
+        if let [key] = keys {
+            return key_name(*key);
+        }
+        keys.iter()
+            .enumerate()
+            .map(|(position, key)| match key {
+                _ if position == 0 && *key == self.leader => "<Leader>".to_string(),
+                KeyCode::Char(c) if *c != ' ' && *c != '<' => c.to_string(),
+                _ => format!("<{}>", key_name(*key)),
+            })
+            .collect()
+    }
+
+    /// 除了第 `index` 个操作以外，还有哪些操作绑定了这组键。一组是另一组的开头时也算冲突，
+    /// 否则较长的那组永远按不出来
+    pub fn conflicts(&self, index: usize, keys: &[KeyCode]) -> Vec<usize> {
+        (0..self.bindings.len())
+            .filter(|&other| other != index && self.bindings[other].iter().any(|bound| overlaps(bound, keys)))
+            .collect()
+    }
+
+    /// 第 `index` 个操作的按键是否和其他操作重复
+    pub fn has_conflict(&self, index: usize) -> bool {
+        self.bindings[index].iter().any(|keys| !self.conflicts(index, keys).is_empty())
+    }
+
+    /// 把操作绑定到一个新键上，同时从其他操作中去掉冲突的按键
+    pub fn bind(&mut self, index: usize, key: KeyCode) {
+        for sequences in &mut self.bindings {
+            sequences.retain(|bound| !overlaps(bound, &[key]));
+        }
+        self.bindings[index] = vec![vec![key]];
+    }
+
+    pub fn reset(&mut self, index: usize) {
+        self.bindings[index] = Keymap::default().bindings[index].clone();
+    }
+}
+
+/// 不能绑定给全局操作的键：面板导航、焦点切换和数字音量键
+pub fn is_reserved(key: KeyCode) -> bool {
+    match key {
+        KeyCode::Tab | KeyCode::BackTab | KeyCode::Enter | KeyCode::Esc => true,
+        KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => true,
+        KeyCode::Backspace | KeyCode::Delete => true,
+        KeyCode::Char(c) => c.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+/// 两组键相同，或者一组是另一组的开头
+fn overlaps(a: &[KeyCode], b: &[KeyCode]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let key = match name {
+        "Space" => KeyCode::Char(' '),
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "Insert" => KeyCode::Insert,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next()?, chars.next()) {
+                (c, None) => KeyCode::Char(c),
+                ('F', Some(_)) => KeyCode::F(name[1..].parse().ok()?),
+                _ => return None,
+            }
+        }
+    };
+    Some(key).filter(|key| !is_reserved(*key))
+}
+
+/// 一个按键或连写的组合键，如 `Home`、`gg`、`<Leader>r`、`g<End>`
+fn parse_sequence(token: &str, leader: KeyCode) -> Option<KeySequence> {
+    if let Some(key) = parse_key(token) {
+        return Some(vec![key]);
+    }
+    let mut keys = Vec::new();
+    let mut rest = token;
+    while let Some(c) = rest.chars().next() {
+        let named = rest.strip_prefix('<').and_then(|inner| inner.split_once('>'));
+        if let Some((name, after)) = named {
+            keys.push(match name {
+                "Leader" => leader,
+                _ => parse_key(name)?,
+            });
+            rest = after;
+            continue;
+        }
+        keys.push(parse_key(&c.to_string())?);
+        rest = &rest[c.len_utf8()..];
+    }
+    Some(keys).filter(|keys| !keys.is_empty())
+}
+
+/// 空格分隔的按键列表，有一个无法识别时整行无效
+fn parse_keys(value: &str, leader: KeyCode) -> Option<Vec<KeySequence>> {
+    value.split_whitespace().map(|token| parse_sequence(token, leader)).collect()
+}
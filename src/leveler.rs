@@ -0,0 +1,312 @@
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::replaygain::{self, Filter};
+
+/// 低于这个响度 (LUFS) 的片段（静音、淡入淡出）不参与测量，避免把安静的段落放大
+const GATE_LUFS: f64 = -50.0;
+/// 动态增益的上下限 (dB)
+const MAX_BOOST_DB: f64 = 12.0;
+const MAX_CUT_DB: f64 = -18.0;
+/// 响度估计的时间常数（秒）：变响时跟得较快，突然变响的广告能很快压下去；
+/// 变轻时足够慢，不会把歌曲本身的强弱变化抹平
+const LOUDNESS_RISE_SECS: f64 = 0.4;
+const LOUDNESS_FALL_SECS: f64 = 3.0;
+/// 降低音量较快，恢复音量较慢，避免忽大忽小
+const ATTACK_SECS: f64 = 0.3;
+const RELEASE_SECS: f64 = 4.0;
+/// 响度表按 EBU R128 的短期响度测量：最近 30 个 100ms 片段（3 秒）
+const SHORT_TERM_SEGMENTS: usize = 30;
+
+/// 响度表的读数：均衡前后的短期响度 (LUFS) 和当前增益
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeterReading {
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    pub gain_db: f64,
+}
+
+/// 播放线程和界面线程共享的响度均衡开关、目标响度和响度表
+#[derive(Debug, Default)]
+pub struct LevelerControl {
+    enabled: AtomicBool,
+    /// 目标响度 (LUFS)，在设置界面中修改后正在播放的歌曲立即生效
+    target_lufs: AtomicI32,
+    /// 显示响度表时播放线程才测量
+    metering: AtomicBool,
+    reading: Mutex<MeterReading>,
+}
+
+impl LevelerControl {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_target(&self, lufs: i32) {
+        self.target_lufs.store(lufs, Ordering::Relaxed);
+    }
+
+    pub fn target_lufs(&self) -> f64 {
+        self.target_lufs.load(Ordering::Relaxed) as f64
+    }
+
+    pub fn set_metering(&self, metering: bool) {
+        if self.metering.swap(metering, Ordering::Relaxed) && !metering {
+            self.publish(MeterReading::default());
+        }
+    }
+
+    fn is_metering(&self) -> bool {
+        self.metering.load(Ordering::Relaxed)
+    }
+
+    pub fn reading(&self) -> MeterReading {
+        self.reading.lock().map(|reading| *reading).unwrap_or_default()
+    }
+
+    fn publish(&self, reading: MeterReading) {
+        if let Ok(mut current) = self.reading.lock() {
+            *current = reading;
+        }
+    }
+}
+
+/// 短期响度的测量：K 加权后每 100ms 一个片段，取最近 3 秒的平均能量
+struct ShortTermMeter {
+    filters: [Filter; 2],
+    states: Vec<[[f64; 2]; 2]>,
+    channel: usize,
+    energy: f64,
+    frames: usize,
+    segment_frames: usize,
+    segments: VecDeque<f64>,
+}
+
+impl ShortTermMeter {
+    fn new(sample_rate: f64, channels: usize) -> Self {
+        ShortTermMeter {
+            filters: replaygain::k_weighting(sample_rate),
+            states: vec![[[0.0; 2]; 2]; channels],
+            channel: 0,
+            energy: 0.0,
+            frames: 0,
+            segment_frames: (sample_rate / 10.0).max(1.0) as usize,
+            segments: VecDeque::with_capacity(SHORT_TERM_SEGMENTS + 1),
+        }
+    }
+
+    /// 输入一个样本，凑满一个片段时返回 true
+    fn push(&mut self, sample: f32) -> bool {
+        let state = &mut self.states[self.channel];
+        let shelved = self.filters[0].process(&mut state[0], sample as f64);
+        let weighted = self.filters[1].process(&mut state[1], shelved);
+        self.energy += weighted * weighted;
+        self.channel += 1;
+        if self.channel < self.states.len() {
+            return false;
+        }
+        self.channel = 0;
+        self.frames += 1;
+        if self.frames < self.segment_frames {
+            return false;
+        }
+        self.segments.push_back(self.energy / self.frames as f64);
+        if self.segments.len() > SHORT_TERM_SEGMENTS {
+            self.segments.pop_front();
+        }
+        self.energy = 0.0;
+        self.frames = 0;
+        true
+    }
+
+    /// 最近 3 秒的响度，静音时为 None
+    fn loudness(&self) -> Option<f64> {
+        let energy = self.segments.iter().sum::<f64>() / self.segments.len().max(1) as f64;
+        Some(replaygain::block_loudness(energy)).filter(|lufs| *lufs > GATE_LUFS)
+    }
+}
+
+/// 把响度调整到目标值的 Source 适配器。有 ReplayGain 标签的歌曲直接使用标签中的增益；
+/// 没有标签的歌曲（包括网络电台）用缓慢的自动增益控制持续测量响度并调整
+pub struct Leveler<S> {
+    input: S,
+    control: Arc<LevelerControl>,
+    /// ReplayGain 标签中的单曲增益，None 表示使用动态增益
+    track_gain_db: Option<f32>,
+    /// 按标签和目标响度计算的固定增益：(计算时的目标响度, 增益)
+    fixed_gain: Option<(f64, f32)>,
+    filters: [Filter; 2],
+    states: Vec<[[f64; 2]; 2]>,
+    channel: usize,
+    /// 当前 100ms 片段累计的 K 加权能量
+    energy: f64,
+    frames: usize,
+    segment_frames: usize,
+    /// 平滑后的能量估计，还没有测到有效片段时为 None
+    loudness_energy: Option<f64>,
+    wanted_gain: f32,
+    gain: f32,
+    attack: f32,
+    release: f32,
+    before: ShortTermMeter,
+    after: ShortTermMeter,
+}
+
+impl<S> Leveler<S>
+where
+    S: Source<Item = f32>,
+{
+    /// track_gain_db 是 ReplayGain 标签中的单曲增益（相对 -18 LUFS 的参考响度）
+    pub fn new(input: S, control: Arc<LevelerControl>, track_gain_db: Option<f32>) -> Self {
+        let sample_rate = input.sample_rate().max(1) as f64;
+        let channels = input.channels().max(1) as usize;
+        Leveler {
+            input,
+            control,
+            track_gain_db,
+            fixed_gain: None,
+            filters: replaygain::k_weighting(sample_rate),
+            states: vec![[[0.0; 2]; 2]; channels],
+            channel: 0,
+            energy: 0.0,
+            frames: 0,
+            segment_frames: (sample_rate / 10.0).max(1.0) as usize,
+            loudness_energy: None,
+            wanted_gain: 1.0,
+            gain: 1.0,
+            attack: (1.0 / (ATTACK_SECS * sample_rate)) as f32,
+            release: (1.0 / (RELEASE_SECS * sample_rate)) as f32,
+            before: ShortTermMeter::new(sample_rate, channels),
+            after: ShortTermMeter::new(sample_rate, channels),
+        }
+    }
+
+    /// 按标签计算的固定增益，目标响度改变后重新计算
+    fn fixed_gain(&mut self) -> Option<f32> {
+        let track_gain = self.track_gain_db?;
+        let target = self.control.target_lufs();
+        match self.fixed_gain {
+            Some((computed_for, gain)) if computed_for == target => Some(gain),
+            _ => {
+                let gain_db = track_gain as f64 + target - replaygain::REFERENCE_LUFS;
+                let gain = 10f64.powf(gain_db / 20.0) as f32;
+                self.fixed_gain = Some((target, gain));
+                Some(gain)
+            }
+        }
+    }
+
+    /// 每个 100ms 片段结束时更新响度估计和期望增益
+    fn finish_segment(&mut self) {
+        let energy = self.energy / self.segment_frames as f64;
+        self.energy = 0.0;
+        self.frames = 0;
+        if energy <= 0.0 || replaygain::block_loudness(energy) < GATE_LUFS {
+            return;
+        }
+
+        let estimate = match self.loudness_energy {
+            Some(previous) => {
+                let window = if energy > previous { LOUDNESS_RISE_SECS } else { LOUDNESS_FALL_SECS };
+                previous + (energy - previous) * (0.1 / window)
+            }
+            None => energy,
+        };
+        self.loudness_energy = Some(estimate);
+        let gain_db =
+            (self.control.target_lufs() - replaygain::block_loudness(estimate)).clamp(MAX_CUT_DB, MAX_BOOST_DB);
+        self.wanted_gain = 10f64.powf(gain_db / 20.0) as f32;
+    }
+
+    fn level(&mut self, sample: f32) -> f32 {
+        if !self.control.is_enabled() {
+            return sample;
+        }
+        if let Some(gain) = self.fixed_gain() {
+            return (sample * gain).clamp(-1.0, 1.0);
+        }
+
+        let state = &mut self.states[self.channel];
+        let shelved = self.filters[0].process(&mut state[0], sample as f64);
+        let weighted = self.filters[1].process(&mut state[1], shelved);
+        self.energy += weighted * weighted;
+
+        self.channel += 1;
+        if self.channel == self.states.len() {
+            self.channel = 0;
+            self.frames += 1;
+            if self.frames == self.segment_frames {
+                self.finish_segment();
+            }
+            // 每帧向期望增益靠近一点，所有声道使用相同的增益
+            let rate = if self.wanted_gain < self.gain { self.attack } else { self.release };
+            self.gain += (self.wanted_gain - self.gain) * rate;
+        }
+        (sample * self.gain).clamp(-1.0, 1.0)
+    }
+
+    /// 同时测量均衡前后的响度，每个片段结束时交给界面
+    fn meter(&mut self, before: f32, after: f32) {
+        self.before.push(before);
+        if !self.after.push(after) {
+            return;
+        }
+        let gain = match self.control.is_enabled() {
+            false => 1.0,
+            true => self.fixed_gain.map_or(self.gain, |(_, gain)| gain),
+        };
+        self.control.publish(MeterReading {
+            before: self.before.loudness(),
+            after: self.after.loudness(),
+            gain_db: 20.0 * (gain.max(f32::MIN_POSITIVE) as f64).log10(),
+        });
+    }
+}
+
+impl<S> Iterator for Leveler<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let output = self.level(sample);
+        if self.control.is_metering() {
+            self.meter(sample, output);
+        }
+        Some(output)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Leveler<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::app::Song;
+
+/// 合辑在按艺术家浏览时归入的名称
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+/// 同一张专辑（同一目录、同名专辑）里有这么多位不同的艺术家、又没有专辑艺术家标签时当作合辑
+const COMPILATION_MIN_ARTISTS: usize = 3;
+const UNKNOWN_ARTIST: &str = "未知艺术家";
+
+/// 按专辑艺术家浏览时的一项
+#[derive(Debug, Clone)]
+pub struct ArtistEntry {
+    pub name: String,
+    pub albums: usize,
+    pub songs: Vec<PathBuf>,
+}
+
+/// 各种写法的“群星”：Various Artists、Various、VA、V.A.、群星 等
+pub fn is_various_artists(name: &str) -> bool {
+    let key: String = name.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    matches!(key.as_str(), "variousartists" | "various" | "va" | "群星" | "多位艺术家" | "合辑")
+}
+
+/// 每首歌在浏览时归入的专辑艺术家：ALBUMARTIST 标签优先，合辑标记或看起来像合辑的专辑
+/// 归入 Various Artists，其余使用艺术家
+pub fn album_artists(songs: &[Song]) -> Vec<String> {
+    // 没有专辑艺术家标签的专辑中有几位不同的艺术家
+    let mut album_members: HashMap<(Option<&Path>, &str), HashSet<String>> = HashMap::new();
+    for song in songs.iter().filter(|song| song.tags.album_artist.is_none()) {
+        if let (Some(album), Some(artist)) = (&song.tags.album, song.artist()) {
+            album_members.entry((song.path.parent(), album)).or_default().insert(artist.to_lowercase());
+        }
+    }
+
+    songs
+        .iter()
+        .map(|song| {
+            if let Some(album_artist) = &song.tags.album_artist {
+                return match is_various_artists(album_artist) {
+                    true => VARIOUS_ARTISTS.to_string(),
+                    false => album_artist.clone(),
+                };
+            }
+            let looks_like_compilation = song
+                .tags
+                .album
+                .as_deref()
+                .and_then(|album| album_members.get(&(song.path.parent(), album)))
+                .is_some_and(|artists| artists.len() >= COMPILATION_MIN_ARTISTS);
+            if song.tags.compilation || looks_like_compilation {
+                return VARIOUS_ARTISTS.to_string();
+            }
+            match song.artist() {
+                Some(artist) if is_various_artists(&artist) => VARIOUS_ARTISTS.to_string(),
+                Some(artist) => artist,
+                None => UNKNOWN_ARTIST.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// 按专辑艺术家分组，只有大小写或空格不同的名字合并为一项。
+/// 按名称排序，Various Artists 和未知艺术家排在最后
+pub fn artists(songs: &[Song]) -> Vec<ArtistEntry> {
+    let mut entries: Vec<ArtistEntry> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut albums: Vec<HashSet<String>> = Vec::new();
+    for (song, name) in songs.iter().zip(album_artists(songs)) {
+        let key = name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let position = *positions.entry(key).or_insert_with(|| {
+            entries.push(ArtistEntry { name, albums: 0, songs: Vec::new() });
+            albums.push(HashSet::new());
+            entries.len() - 1
+        });
+        entries[position].songs.push(song.path.clone());
+        if let Some(album) = &song.tags.album {
+            albums[position].insert(album.to_lowercase());
+        }
+    }
+    for (entry, albums) in entries.iter_mut().zip(albums) {
+        entry.albums = albums.len();
+    }
+
+    entries.sort_by_cached_key(|entry| {
+        let last = entry.name == VARIOUS_ARTISTS || entry.name == UNKNOWN_ARTIST;
+        (last, entry.name.to_lowercase())
+    });
+    entries
+}
@@ -0,0 +1,297 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::http;
+
+pub type Rgb = (u8, u8, u8);
+
+/// 封面缩小到 16×16 后统计颜色
+const SAMPLE_SIZE: usize = 16;
+/// 文件中没有内嵌封面时在同一文件夹中查找的图片
+const COVER_FILES: [&str; 6] = ["cover.jpg", "cover.png", "folder.jpg", "folder.png", "front.jpg", "front.png"];
+/// 按色相分成的组数，像素最多（按饱和度和明度加权）的一组就是主色调
+const HUE_BUCKETS: usize = 12;
+/// 换歌时颜色渐变的时间，单位 0.1 秒
+const TRANSITION: u32 = 10;
+/// 平时的亮度，节拍时闪到最亮
+const BRIGHTNESS: u8 = 180;
+/// WLED 闪到最亮后保持的时间
+const PULSE: Duration = Duration::from_millis(100);
+/// Hue 桥接器每秒只能处理约一次灯组命令，节拍闪烁之间至少间隔这么久
+const HUE_BEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 灯光控制器
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// WLED 控制器的地址
+    Wled(String),
+    /// Hue 桥接器的地址、用户名（API key）和灯组编号，灯组 0 表示所有灯
+    Hue { bridge: String, username: String, group: String },
+}
+
+impl Target {
+    /// `wled://地址` 或 `hue://桥接器地址/用户名[/灯组]`
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some(host) = spec.strip_prefix("wled://") {
+            let host = host.trim_end_matches('/');
+            if host.is_empty() {
+                bail!("{} 缺少 WLED 控制器的地址", spec);
+            }
+            return Ok(Target::Wled(host.to_string()));
+        }
+        if let Some(rest) = spec.strip_prefix("hue://") {
+            let mut parts = rest.split('/').filter(|part| !part.is_empty());
+            let (Some(bridge), Some(username)) = (parts.next(), parts.next()) else {
+                bail!("{} 的格式应为 hue://桥接器地址/用户名[/灯组]", spec);
+            };
+            let group = parts.next().unwrap_or("0");
+            return Ok(Target::Hue {
+                bridge: bridge.to_string(),
+                username: username.to_string(),
+                group: group.to_string(),
+            });
+        }
+        bail!("不支持的灯光地址 {}，应以 wled:// 或 hue:// 开头", spec)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Target::Wled(host) => format!("WLED {}", host),
+            Target::Hue { bridge, group, .. } => format!("Hue {} 灯组 {}", bridge, group),
+        }
+    }
+
+    fn set_color(&self, (r, g, b): Rgb) -> Result<()> {
+        match self {
+            Target::Wled(host) => {
+                let body = format!(
+                    "{{\"on\":true,\"bri\":{},\"tt\":{},\"seg\":{{\"col\":[[{},{},{}]]}}}}",
+                    BRIGHTNESS, TRANSITION, r, g, b
+                );
+                http::post_json(&format!("http://{}/json/state", host), &body)
+            }
+            Target::Hue { .. } => {
+                let (x, y) = hue_xy((r, g, b));
+                let body = format!(
+                    "{{\"on\":true,\"xy\":[{:.4},{:.4}],\"bri\":{},\"transitiontime\":{}}}",
+                    x, y, BRIGHTNESS, TRANSITION
+                );
+                http::put_json(&self.hue_url(), &body)
+            }
+        }
+    }
+
+    /// 节拍：WLED 闪到最亮再回落，Hue 用桥接器自带的单次闪烁
+    fn pulse(&self) -> Result<()> {
+        match self {
+            Target::Wled(host) => {
+                let url = format!("http://{}/json/state", host);
+                http::post_json(&url, "{\"bri\":255,\"tt\":0}")?;
+                thread::sleep(PULSE);
+                http::post_json(&url, &format!("{{\"bri\":{},\"tt\":2}}", BRIGHTNESS))
+            }
+            Target::Hue { .. } => http::put_json(&self.hue_url(), "{\"alert\":\"select\"}"),
+        }
+    }
+
+    fn hue_url(&self) -> String {
+        match self {
+            Target::Hue { bridge, username, group } => format!("http://{}/api/{}/groups/{}/action", bridge, username, group),
+            Target::Wled(_) => String::new(),
+        }
+    }
+}
+
+enum Message {
+    /// 开始播放一首歌：文件路径，以及没有封面时用来生成颜色的名称（专辑名）
+    Track { path: PathBuf, fallback: String },
+    /// 每分钟拍数和第一拍（歌曲开头）对应的时刻；None 表示暂停或不需要节拍
+    Tempo(Option<(f32, Instant)>),
+}
+
+/// 后台控制灯光的线程：换歌时把所有灯设为封面的主色调，播放中按歌曲的速度在每一拍闪烁
+pub struct Lighting {
+    tx: Sender<Message>,
+    /// 控制失败的灯光和错误，同一个灯光恢复之前只报告一次
+    pub errors: Receiver<String>,
+}
+
+impl Lighting {
+    pub fn start(targets: Vec<Target>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (error_tx, errors) = mpsc::channel();
+        thread::spawn(move || run(targets, rx, error_tx));
+        Lighting { tx, errors }
+    }
+
+    pub fn track(&self, path: &Path, fallback: &str) {
+        let _ = self.tx.send(Message::Track {
+            path: path.to_path_buf(),
+            fallback: fallback.to_string(),
+        });
+    }
+
+    pub fn tempo(&self, tempo: Option<(f32, Instant)>) {
+        let _ = self.tx.send(Message::Tempo(tempo));
+    }
+}
+
+fn run(targets: Vec<Target>, rx: Receiver<Message>, errors: Sender<String>) {
+    let mut failing = vec![false; targets.len()];
+    let mut report = |index: usize, result: Result<()>| match result {
+        Ok(()) => failing[index] = false,
+        Err(err) => {
+            if !std::mem::replace(&mut failing[index], true) {
+                let _ = errors.send(format!("{}: {}", targets[index].name(), err));
+            }
+        }
+    };
+    let mut tempo: Option<(f32, Instant)> = None;
+    let mut hue_pulsed: Option<Instant> = None;
+    loop {
+        let message = match tempo.map(next_beat) {
+            Some(beat) => rx.recv_timeout(beat.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match message {
+            Ok(Message::Track { path, fallback }) => {
+                let color = cover_color(&path).unwrap_or_else(|| name_color(&fallback));
+                for (index, target) in targets.iter().enumerate() {
+                    report(index, target.set_color(color));
+                }
+            }
+            Ok(Message::Tempo(new)) => tempo = new,
+            Err(RecvTimeoutError::Timeout) => {
+                let hue_ready = hue_pulsed.is_none_or(|time| time.elapsed() >= HUE_BEAT_INTERVAL);
+                for (index, target) in targets.iter().enumerate() {
+                    match target {
+                        Target::Hue { .. } if !hue_ready => {}
+                        Target::Hue { .. } => {
+                            hue_pulsed = Some(Instant::now());
+                            report(index, target.pulse());
+                        }
+                        Target::Wled(_) => report(index, target.pulse()),
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// 下一拍的时刻
+fn next_beat((bpm, start): (f32, Instant)) -> Instant {
+    let beat = Duration::from_secs_f64(60.0 / bpm.max(1.0) as f64);
+    let elapsed = Instant::now().saturating_duration_since(start);
+    let count = (elapsed.as_secs_f64() / beat.as_secs_f64()).floor() as u32 + 1;
+    start + beat * count
+}
+
+/// 封面的主色调：先找文件内嵌的封面，再找同一文件夹中的封面图片。用 ffmpeg 解码，没有安装时返回 None
+pub fn cover_color(path: &Path) -> Option<Rgb> {
+    if !path.is_file() {
+        return None;
+    }
+    let folder = path.parent()?;
+    let covers = COVER_FILES.iter().map(|name| folder.join(name)).filter(|cover| cover.is_file());
+    let pixels = std::iter::once(path.to_path_buf()).chain(covers).find_map(|image| sample(&image))?;
+    Some(dominant(&pixels))
+}
+
+/// 用 ffmpeg 把图片（或音频文件中的封面）缩小成 16×16 的 RGB 像素
+fn sample(image: &Path) -> Option<Vec<Rgb>> {
+    let scale = format!("scale={}:{}", SAMPLE_SIZE, SAMPLE_SIZE);
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(image)
+        .args(["-an", "-frames:v", "1", "-vf", &scale, "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() || output.stdout.len() != SAMPLE_SIZE * SAMPLE_SIZE * 3 {
+        return None;
+    }
+    Some(output.stdout.chunks(3).map(|pixel| (pixel[0], pixel[1], pixel[2])).collect())
+}
+
+/// 按色相分组，取鲜艳的像素最多的一组的平均颜色；几乎是黑白的封面取所有像素的平均。结果调到最亮
+fn dominant(pixels: &[Rgb]) -> Rgb {
+    let mut buckets = [(0.0, [0.0; 3]); HUE_BUCKETS];
+    let mut total = [0.0; 3];
+    for &(r, g, b) in pixels {
+        let (hue, saturation, value) = hsv((r, g, b));
+        let weight = saturation * value;
+        let bucket = &mut buckets[(hue / 360.0 * HUE_BUCKETS as f64) as usize % HUE_BUCKETS];
+        bucket.0 += weight;
+        for (sum, channel) in bucket.1.iter_mut().zip([r, g, b]) {
+            *sum += channel as f64 * weight;
+        }
+        for (sum, channel) in total.iter_mut().zip([r, g, b]) {
+            *sum += channel as f64;
+        }
+    }
+    let (weight, sums) = buckets.iter().copied().fold((0.0, [0.0; 3]), |best, bucket| if bucket.0 > best.0 { bucket } else { best });
+    let [r, g, b] = if weight > pixels.len() as f64 * 0.05 { sums } else { total };
+    let max = r.max(g).max(b).max(1.0);
+    let scale = |channel: f64| (channel / max * 255.0).round() as u8;
+    (scale(r), scale(g), scale(b))
+}
+
+/// 没有封面时按名称生成一个固定的鲜艳颜色，同一张专辑的颜色相同
+fn name_color(name: &str) -> Rgb {
+    let hash = name.bytes().fold(2166136261u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(16777619));
+    let hue = (hash % 360) as f64;
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |value: f64| (value * 255.0).round() as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+/// (色相 0-360, 饱和度 0-1, 明度 0-1)
+fn hsv((r, g, b): Rgb) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = match delta {
+        delta if delta <= 0.0 => 0.0,
+        _ if max == r => 60.0 * ((g - b) / delta).rem_euclid(6.0),
+        _ if max == g => 60.0 * ((b - r) / delta + 2.0),
+        _ => 60.0 * ((r - g) / delta + 4.0),
+    };
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    (hue, saturation, max)
+}
+
+/// sRGB 转换为 Hue 使用的 CIE xy 色度坐标
+fn hue_xy((r, g, b): Rgb) -> (f64, f64) {
+    let linear = |channel: u8| {
+        let value = channel as f64 / 255.0;
+        if value > 0.04045 {
+            ((value + 0.055) / 1.055).powf(2.4)
+        } else {
+            value / 12.92
+        }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+    let x = r * 0.664511 + g * 0.154324 + b * 0.162028;
+    let y = r * 0.283881 + g * 0.668433 + b * 0.047685;
+    let z = r * 0.000088 + g * 0.072310 + b * 0.986039;
+    let sum = x + y + z;
+    if sum <= 0.0 {
+        // 黑色没有色度，用白点
+        return (0.3227, 0.329);
+    }
+    (x / sum, y / sum)
+}
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, bail, Result};
+use std::ffi::OsString;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::http;
+use crate::import::ExternalTrack;
+use crate::json::{self, Value};
+use crate::storage;
+
+const API_ROOT: &str = "https://api.listenbrainz.org/1";
+const CREDENTIALS_FILE: &str = "listenbrainz.tsv";
+
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub user_name: String,
+    pub token: String,
+}
+
+pub fn load_credentials() -> Option<Credentials> {
+    let records = storage::read_records(CREDENTIALS_FILE).ok()?;
+    let [user_name, token] = <[String; 2]>::try_from(records.into_iter().next()?).ok()?;
+    Some(Credentials { user_name, token })
+}
+
+/// `music_tui listenbrainz login <token>` / `music_tui listenbrainz logout`
+pub fn run(args: &[OsString]) -> Result<()> {
+    let args: Vec<&str> = args.iter().filter_map(|arg| arg.to_str()).collect();
+    match args.as_slice() {
+        ["login", token] => {
+            let user_name = validate_token(token)?;
+            storage::write_records(CREDENTIALS_FILE, &[vec![user_name.clone(), token.to_string()]])?;
+            println!("已登录 ListenBrainz 用户: {}", user_name);
+            Ok(())
+        }
+        ["logout"] => {
+            storage::write_records(CREDENTIALS_FILE, &[])?;
+            println!("已退出 ListenBrainz");
+            Ok(())
+        }
+        _ => bail!("用法: music_tui listenbrainz login <用户令牌> | logout"),
+    }
+}
+
+fn auth_header(token: &str) -> String {
+    format!("Token {}", token)
+}
+
+/// 校验用户令牌，返回对应的用户名
+fn validate_token(token: &str) -> Result<String> {
+    let auth = auth_header(token);
+    let body = http::get_with_headers(&format!("{}/validate-token", API_ROOT), &[("Authorization", &auth)])?;
+    let response = json::parse(&body)?;
+    if response.get("valid") != Some(&Value::Bool(true)) {
+        bail!("ListenBrainz 令牌无效");
+    }
+    response
+        .get("user_name")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("ListenBrainz 没有返回用户名"))
+}
+
+/// 在后台获取推荐歌曲
+pub fn spawn_recommendations(credentials: Credentials) -> Receiver<Result<Vec<ExternalTrack>, String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = fetch_recommendations(&credentials).map_err(|err| err.to_string());
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+fn fetch_recommendations(credentials: &Credentials) -> Result<Vec<ExternalTrack>> {
+    let auth = auth_header(&credentials.token);
+    let headers = [("Authorization", auth.as_str())];
+
+    let url = format!(
+        "{}/cf/recommendation/user/{}/recording?count=100",
+        API_ROOT,
+        http::encode(&credentials.user_name)
+    );
+    let body = http::get_with_headers(&url, &headers)?;
+    if body.trim().is_empty() {
+        // 还没有为该用户生成推荐时返回空内容
+        return Ok(Vec::new());
+    }
+
+    let response = json::parse(&body)?;
+    let mbids: Vec<&str> = response
+        .get("payload")
+        .and_then(|payload| payload.get("mbids"))
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| entry.get("recording_mbid").and_then(Value::as_str))
+        .collect();
+
+    let mut tracks = Vec::new();
+    // 推荐只包含 MBID，分批查询曲名和艺术家
+    for chunk in mbids.chunks(25) {
+        let url = format!(
+            "{}/metadata/recording/?recording_mbids={}&inc=artist",
+            API_ROOT,
+            chunk.join(",")
+        );
+        let metadata = json::parse(&http::get_with_headers(&url, &headers)?)?;
+        for mbid in chunk {
+            let Some(entry) = metadata.get(mbid) else {
+                continue;
+            };
+            let title = entry
+                .get("recording")
+                .and_then(|recording| recording.get("name"))
+                .and_then(Value::as_str);
+            let artist = entry
+                .get("artist")
+                .and_then(|artist| artist.get("name"))
+                .and_then(Value::as_str);
+            if let Some(title) = title {
+                tracks.push(ExternalTrack {
+                    artist: artist.unwrap_or_default().to_string(),
+                    title: title.to_string(),
+                });
+            }
+        }
+    }
+    Ok(tracks)
+}
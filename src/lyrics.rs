@@ -0,0 +1,266 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::http;
+use crate::json::{self, Value};
+use crate::storage;
+
+/// 后台获取的结果：(歌曲路径, 歌词和来源，或者错误)
+pub type LyricsResult = (PathBuf, Result<(Lyrics, &'static str), String>);
+
+/// 在线歌词来源，按配置 `lyrics_providers` 中的顺序依次尝试
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Lrclib,
+    Netease,
+}
+
+impl Provider {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "lrclib" => Some(Provider::Lrclib),
+            "netease" | "163" => Some(Provider::Netease),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Provider::Lrclib => "LRCLIB",
+            Provider::Netease => "网易云音乐",
+        }
+    }
+}
+
+/// 解析后的歌词。没有时间标签的纯文本歌词 synced 为 false，所有行的时间都是 0
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+    pub lines: Vec<(Duration, String)>,
+    pub synced: bool,
+}
+
+impl Lyrics {
+    /// 播放到 position 时应该高亮的行
+    pub fn current_line(&self, position: Duration) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+        self.lines.iter().rposition(|(time, _)| *time <= position)
+    }
+}
+
+/// 解析 LRC 格式：一行可以有多个 `[mm:ss.xx]` 时间标签，`[offset:毫秒]` 整体调整时间，
+/// `[ar:...]` 等其他标签忽略。没有任何时间标签时当作纯文本歌词
+pub fn parse_lrc(text: &str) -> Lyrics {
+    let mut lines = Vec::new();
+    let mut offset_ms: i64 = 0;
+    let mut plain = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[').and_then(|tag| tag.split_once(']')) {
+            let (tag, after) = tag;
+            if let Some(time) = parse_time(tag) {
+                times.push(time);
+            } else if let Some(offset) = tag.strip_prefix("offset:") {
+                offset_ms = offset.trim().parse().unwrap_or(0);
+            }
+            rest = after.trim_start();
+        }
+        if times.is_empty() {
+            if !line.trim_start().starts_with('[') {
+                plain.push(line.trim().to_string());
+            }
+            continue;
+        }
+        for time in times {
+            lines.push((time, rest.trim().to_string()));
+        }
+    }
+
+    if lines.is_empty() {
+        // 去掉开头和结尾的空行
+        let start = plain.iter().position(|line| !line.is_empty()).unwrap_or(plain.len());
+        let end = plain.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+        return Lyrics {
+            lines: plain[start..end].iter().map(|line| (Duration::ZERO, line.clone())).collect(),
+            synced: false,
+        };
+    }
+
+    // offset 为正表示歌词提前显示
+    for (time, _) in &mut lines {
+        let ms = time.as_millis() as i64 - offset_ms;
+        *time = Duration::from_millis(ms.max(0) as u64);
+    }
+    lines.sort_by_key(|(time, _)| *time);
+    Lyrics { lines, synced: true }
+}
+
+/// `mm:ss`、`mm:ss.xx` 或 `mm:ss:xx`
+fn parse_time(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let seconds: f64 = seconds.trim().replacen(':', ".", 1).parse().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// 和音频文件同名的 .lrc 文件
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("lrc")
+}
+
+fn cache_path(artist: &str, title: &str) -> PathBuf {
+    storage::cache_dir()
+        .join("lyrics")
+        .join(format!("{}.lrc", storage::sanitize_file_name(&format!("{} - {}", artist, title))))
+}
+
+/// 先找音频文件旁边的 .lrc，再找之前下载到缓存目录的歌词
+pub fn load_local(path: &Path, artist: Option<&str>, title: &str) -> Option<Lyrics> {
+    let text = fs::read_to_string(sidecar_path(path))
+        .ok()
+        .or_else(|| artist.and_then(|artist| fs::read_to_string(cache_path(artist, title)).ok()))?;
+    let lyrics = parse_lrc(&text);
+    (!lyrics.lines.is_empty()).then_some(lyrics)
+}
+
+/// 要在线查找的歌曲
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub path: PathBuf,
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// 在后台依次向各个来源查找歌词，找到后保存到文件旁边（next_to_file 且是本地文件时）或缓存目录
+pub fn spawn_fetch(query: Query, providers: Vec<Provider>, next_to_file: bool) -> Receiver<LyricsResult> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut errors = Vec::new();
+        for provider in providers {
+            let text = match provider {
+                Provider::Lrclib => fetch_lrclib(&query),
+                Provider::Netease => fetch_netease(&query),
+            };
+            match text {
+                Ok(text) => {
+                    let lyrics = parse_lrc(&text);
+                    if lyrics.lines.is_empty() {
+                        continue;
+                    }
+                    let _ = save(&query, &text, next_to_file);
+                    let _ = tx.send((query.path, Ok((lyrics, provider.label()))));
+                    return;
+                }
+                Err(err) => errors.push(format!("{}: {}", provider.label(), err)),
+            }
+        }
+        let error = match errors.is_empty() {
+            true => "没有找到歌词".to_string(),
+            false => errors.join("；"),
+        };
+        let _ = tx.send((query.path, Err(error)));
+    });
+    rx
+}
+
+fn save(query: &Query, text: &str, next_to_file: bool) -> Result<()> {
+    if next_to_file && query.path.is_file() {
+        fs::write(sidecar_path(&query.path), text)?;
+        return Ok(());
+    }
+    let path = cache_path(&query.artist, &query.title);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// LRCLIB：先按歌名、艺术家、专辑和时长精确查找，找不到再搜索，优先使用有时间轴的歌词
+fn fetch_lrclib(query: &Query) -> Result<String> {
+    let mut url = format!(
+        "https://lrclib.net/api/get?artist_name={}&track_name={}",
+        http::encode(&query.artist),
+        http::encode(&query.title)
+    );
+    if let Some(album) = &query.album {
+        url.push_str(&format!("&album_name={}", http::encode(album)));
+    }
+    if let Some(duration) = query.duration {
+        url.push_str(&format!("&duration={}", duration.as_secs()));
+    }
+    // 找不到时返回 404，curl 会报错
+    if let Some(text) = http::get(&url).ok().and_then(|body| lrclib_text(&json::parse(&body).ok()?)) {
+        return Ok(text);
+    }
+
+    let url = format!(
+        "https://lrclib.net/api/search?artist_name={}&track_name={}",
+        http::encode(&query.artist),
+        http::encode(&query.title)
+    );
+    let response = json::parse(&http::get(&url)?)?;
+    let results = response.as_array().unwrap_or_default();
+    results
+        .iter()
+        .find(|result| result.get("syncedLyrics").and_then(Value::as_str).is_some_and(|text| !text.is_empty()))
+        .or(results.first())
+        .and_then(lrclib_text)
+        .ok_or_else(|| anyhow::anyhow!("没有找到歌词"))
+}
+
+fn lrclib_text(result: &Value) -> Option<String> {
+    ["syncedLyrics", "plainLyrics"]
+        .into_iter()
+        .filter_map(|key| result.get(key).and_then(Value::as_str))
+        .find(|text| !text.trim().is_empty())
+        .map(str::to_string)
+}
+
+/// 网易云音乐：搜索第一首匹配的歌曲，再取它的 LRC 歌词
+fn fetch_netease(query: &Query) -> Result<String> {
+    let headers = [("Referer", "https://music.163.com/")];
+    let url = format!(
+        "https://music.163.com/api/search/get?s={}&type=1&limit=5",
+        http::encode(&format!("{} {}", query.artist, query.title))
+    );
+    let response = json::parse(&http::get_with_headers(&url, &headers)?)?;
+    let songs = response
+        .get("result")
+        .and_then(|result| result.get("songs"))
+        .and_then(Value::as_array)
+        .unwrap_or_default();
+    // 时长相差太多的多半是别的版本
+    let song = songs
+        .iter()
+        .find(|song| {
+            let expected = query.duration.map(|duration| duration.as_millis() as f64);
+            let actual = song.get("duration").and_then(Value::as_f64);
+            match (expected, actual) {
+                (Some(expected), Some(actual)) => (expected - actual).abs() < 10_000.0,
+                _ => true,
+            }
+        })
+        .or(songs.first());
+    let Some(id) = song.and_then(|song| song.get("id")).and_then(Value::as_f64) else {
+        bail!("没有找到歌曲");
+    };
+
+    let url = format!("https://music.163.com/api/song/lyric?id={}&lv=1", id as u64);
+    let response = json::parse(&http::get_with_headers(&url, &headers)?)?;
+    match response.get("lrc").and_then(|lrc| lrc.get("lyric")).and_then(Value::as_str) {
+        Some(text) if !text.trim().is_empty() => Ok(text.to_string()),
+        _ => bail!("没有找到歌词"),
+    }
+}
@@ -0,0 +1,82 @@
+use crossterm::event::KeyEvent;
+use std::collections::HashMap;
+
+/// 宏里再执行宏时最多嵌套的层数，防止宏执行自己时无限递归
+const MAX_DEPTH: usize = 8;
+
+/// 按下录制或执行宏的按键后，等待选择寄存器
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pending {
+    Record,
+    Play,
+}
+
+/// 键盘宏：把一串按键录进以字母或数字命名的寄存器，之后原样重放。只保存在内存中，退出后清空
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<KeyEvent>>,
+    /// 正在录制的寄存器和已录下的按键
+    recording: Option<(char, Vec<KeyEvent>)>,
+    /// 当前按键所属的组合键在录制内容中开始的位置，停止录制时去掉停止录制的组合键
+    chord_start: usize,
+    pub pending: Option<Pending>,
+    /// 上一次执行的宏，`@@` 再执行一次
+    pub last: Option<char>,
+    depth: usize,
+}
+
+impl Macros {
+    pub fn is_register(c: char) -> bool {
+        c.is_ascii_alphanumeric()
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+        self.chord_start = 0;
+    }
+
+    /// 停止录制并保存，返回寄存器和录下的按键数
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, mut keys) = self.recording.take()?;
+        keys.truncate(self.chord_start);
+        let count = keys.len();
+        self.registers.insert(register, keys);
+        Some((register, count))
+    }
+
+    /// 录下一个按键；重放宏时按键来自宏本身，不再录制
+    pub fn record(&mut self, key: KeyEvent, in_chord: bool) {
+        if self.depth > 0 {
+            return;
+        }
+        if let Some((_, keys)) = self.recording.as_mut() {
+            if !in_chord {
+                self.chord_start = keys.len();
+            }
+            keys.push(key);
+        }
+    }
+
+    /// 开始重放，返回要重放的按键；寄存器为空或者嵌套太深时返回 None
+    pub fn begin_replay(&mut self, register: char) -> Option<Vec<KeyEvent>> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        let keys = self.registers.get(&register)?.clone();
+        self.last = Some(register);
+        self.depth += 1;
+        Some(keys)
+    }
+
+    pub fn end_replay(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.depth > 0
+    }
+}
@@ -1,46 +1,208 @@
+mod alarm;
+mod alert;
 mod app;
+mod audio_output;
+mod automix;
+mod beets;
+mod bookmarks;
+mod bpm;
+mod clipboard;
+mod clock;
+mod collections;
+mod config;
+mod cues;
+mod downmix;
+mod detached;
+mod dsd;
+mod equalizer;
 mod event;
+mod export;
+mod file_access;
+mod fingerprint;
+mod genre;
+mod gapless;
+mod glyphs;
+mod http;
+mod http_stream;
+mod import;
+mod info;
+mod instrumental;
+mod jobs;
+mod json;
+mod karaoke;
+mod keymap;
+mod leveler;
+mod library;
+mod lighting;
+mod listenbrainz;
+mod lyrics;
+mod macros;
+mod metadata;
+mod metadata_cache;
+mod metrics;
+mod migrate;
+mod mqtt;
+mod now_playing;
+mod playlists;
+mod qr;
+mod ratings;
+mod remote;
+mod remote_library;
+mod replaygain;
+mod scan_filter;
+mod search;
+mod session;
+mod settings;
+mod simulate;
+mod snapshots;
+mod sqlite;
+mod stats;
+mod status_line;
+mod storage;
+mod streaming;
+mod tag_writer;
+mod track_settings;
+mod trim;
 mod ui;
+mod undo;
+mod visualizer;
+mod waveform;
+mod web;
+mod webhook;
+mod ytdlp;
 
 use anyhow::Result;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::Instant;
 
 use app::App;
-use event::{EventHandler, handle_events, restore_terminal, setup_terminal};
+use event::{EventHandler, handle_events, restore_terminal, run_minimized, run_external, setup_terminal};
 
 fn main() -> Result<()> {
+    let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
+    
+    // 子命令
+    match args.first().and_then(|arg| arg.to_str()) {
+        Some("import") => return import::run(&args[1..]),
+        Some("export") => return export::run(&args[1..]),
+        Some("listenbrainz") => return listenbrainz::run(&args[1..]),
+        Some("bpm") => return bpm::run(&args[1..]),
+        Some("gapless") => return gapless::run(&args[1..]),
+        Some("replaygain") => return replaygain::run(&args[1..]),
+        Some("folder-playlists") => return playlists::run_folder_playlists(&args[1..]),
+        Some("ctl") => return remote::run(&args[1..]),
+        Some("devices") => return audio_output::run_devices(),
+        Some("--detached") => return detached::run(&args[1..]),
+        Some(arg) if arg.starts_with("--simulate") => return simulate::run(&args),
+        _ => {}
+    }
+    
+    // 可选参数：要加载的 M3U 播放列表或目录，默认扫描 assets 目录；
+    // 单个音频文件则进入预览模式，立即播放并把所在文件夹作为播放列表
+    let playlist = args.first().map(PathBuf::from);
+    
+    // 有脱离终端运行的播放器时先让它保存会话退出，由界面接着播放
+    let preview = playlist.as_deref().is_some_and(|path| path.is_file() && app::is_audio_file(path));
+    let attached = !preview && remote::attach(config::Config::load().remote_port);
+    
     // 创建应用程序
-    let mut app = App::new()?;
+    let mut app = App::new(playlist.clone())?;
+    if attached {
+        app.take_over_session()?;
+        app.set_message("已接回后台播放");
+    }
     
     // 设置终端
     let mut terminal = setup_terminal()?;
     
     // 创建事件处理器
-    let mut event_handler = EventHandler::new(Duration::from_millis(100));
+    let mut event_handler = EventHandler::new(app.tick_rate());
+    let mut last_frame: Option<Instant> = None;
     
     // 主循环
     loop {
         // 检查播放状态，自动播放下一曲
         app.check_and_auto_next()?;
+        if app.exit_requested || app.detach_requested {
+            break;
+        }
+        
+        // 闹钟的开始/停止时间和音量渐强
+        app.update_schedule()?;
+        
+        // ctl 子命令等外部控制命令
+        app.poll_remote()?;
         
         // 更新播放时间
         app.update_play_time();
         
-        // 渲染界面
-        terminal.draw(|frame| {
-            ui::render(frame, &app).unwrap();
-        })?;
+        // 示波器和频谱图
+        app.update_visualizer();
+        
+        // 处理后台任务（艺术家信息、推荐电台、任务面板等）的结果
+        app.poll_background();
         
-        // 处理事件
-        if let Some(event) = event_handler.next()? {
-            if let Err(_) = handle_events(&mut app, event) {
+        // 播放中或有任务运行时每个 tick 重绘一次，其余时候只在状态变化时重绘
+        event_handler.tick_rate = app.tick_rate();
+        let frame_interval = app.frame_interval();
+        if event_handler.tick() && app.is_animating() {
+            app.dirty = true;
+        }
+        
+        // 渲染界面，两帧之间至少间隔 frame_interval；终端大小变化时立即重绘
+        if std::mem::take(&mut app.resized) {
+            last_frame = None;
+        }
+        let next_frame = last_frame.map(|frame| frame + frame_interval);
+        if app.dirty && next_frame.is_none_or(|frame| Instant::now() >= frame) {
+            app.dirty = false;
+            terminal.draw(|frame| {
+                ui::render(frame, &app).unwrap();
+            })?;
+            last_frame = Some(Instant::now());
+        }
+        
+        // 处理事件；还有没画出来的变化时最多等到下一帧
+        let deadline = if app.dirty { next_frame } else { None };
+        if let Some(event) = event_handler.next_before(deadline)? {
+            app.dirty = true;
+            if handle_events(&mut app, event).is_err() {
+                break;
+            }
+        }
+        
+        // 在 beets 中修改标签：暂时交出终端给编辑器
+        if let Some(path) = app.beets_edit.take() {
+            let command = beets::edit_command(&app.config.beets_command, &path);
+            let result = run_external(&mut terminal, command);
+            app.finish_beets_edit(&path, result);
+            app.dirty = true;
+        }
+        
+        // 收起界面：只保留一行状态，播放继续，按 Enter 恢复
+        if app.minimize_requested {
+            app.minimize_requested = false;
+            if !run_minimized(&mut terminal, &mut app, &mut event_handler)? {
                 break;
             }
+            app.dirty = true;
         }
     }
     
     // 恢复终端
     restore_terminal(&mut terminal)?;
     
+    // 正常退出时也保存播放会话，下次启动可以继续
+    if let Err(err) = app.save_session_on_exit() {
+        eprintln!("保存播放会话失败: {}", err);
+    }
+    
+    // 脱离终端：先关闭音频输出和控制端口，再由后台播放器接着播放
+    if app.detach_requested {
+        drop(app);
+        detached::spawn(playlist.as_deref())?;
+        println!("已在后台继续播放。再次运行 music_tui 接回界面，music_tui ctl quit 停止播放");
+    }
+    
     Ok(())
 }
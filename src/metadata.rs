@@ -0,0 +1,397 @@
+use anyhow::Result;
+use symphonia::core::codecs::{
+    CodecType, CODEC_TYPE_AAC, CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MP2, CODEC_TYPE_MP3, CODEC_TYPE_NULL,
+    CODEC_TYPE_OPUS, CODEC_TYPE_VORBIS, CODEC_TYPE_WAVPACK,
+};
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::dsd;
+use crate::ratings;
+
+#[derive(Debug, Clone, Default)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    /// 标签中的评分（1 到 5 星）
+    pub rating: Option<u8>,
+    /// ReplayGain 单曲增益 (dB)
+    pub replaygain_db: Option<f32>,
+    /// 合辑标记（ID3 的 TCMP、MP4 的 cpil、Vorbis comment 的 COMPILATION）
+    pub compilation: bool,
+    /// 含有不适合儿童的内容（ITUNESADVISORY 为 1，或自定义的 EXPLICIT 标签）
+    pub explicit: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProbeResult {
+    pub duration: Option<Duration>,
+    pub sample_rate: Option<u32>,
+    /// 编码格式的简称，如 FLAC、MP3、OPUS
+    pub codec: Option<String>,
+    pub bits_per_sample: Option<u32>,
+    /// 按文件大小和时长估算的平均比特率
+    pub bitrate_kbps: Option<u32>,
+    pub tags: Tags,
+}
+
+/// 播放列表中的格式标记，如 `FLAC 24/96`、`MP3 320`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    pub label: String,
+    pub lossless: bool,
+    pub bitrate_kbps: Option<u32>,
+    /// 无损格式的位深
+    pub bits_per_sample: Option<u32>,
+}
+
+impl ProbeResult {
+    pub fn badge(&self) -> Option<Badge> {
+        let codec = self.codec.as_ref()?;
+        let lossless = matches!(codec.as_str(), "FLAC" | "ALAC" | "PCM" | "WAVPACK") || codec.starts_with("DSD");
+        let label = if lossless {
+            match (self.bits_per_sample, self.sample_rate) {
+                (Some(bits), Some(rate)) => format!("{} {}/{}", codec, bits, format_khz(rate)),
+                (None, Some(rate)) => format!("{} {}", codec, format_khz(rate)),
+                _ => codec.clone(),
+            }
+        } else {
+            match self.bitrate_kbps {
+                Some(kbps) => format!("{} {}", codec, nominal_bitrate(kbps)),
+                None => codec.clone(),
+            }
+        };
+        Some(Badge {
+            label,
+            lossless,
+            bitrate_kbps: self.bitrate_kbps,
+            bits_per_sample: self.bits_per_sample.filter(|_| lossless),
+        })
+    }
+}
+
+/// 平均比特率是按文件大小估算的，标签和封面会让它略高于实际码率。
+/// 与常见的固定码率相差不到 4% 时显示该码率
+fn nominal_bitrate(kbps: u32) -> u32 {
+    const COMMON: [u32; 14] = [32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    COMMON
+        .into_iter()
+        .find(|common| kbps.abs_diff(*common) * 25 <= *common)
+        .unwrap_or(kbps)
+}
+
+/// 44100 → "44.1"，96000 → "96"
+fn format_khz(rate: u32) -> String {
+    if rate % 1000 == 0 {
+        (rate / 1000).to_string()
+    } else {
+        format!("{:.1}", rate as f64 / 1000.0)
+    }
+}
+
+fn codec_name(codec: CodecType) -> Option<String> {
+    let name = match codec {
+        CODEC_TYPE_FLAC => "FLAC",
+        CODEC_TYPE_ALAC => "ALAC",
+        CODEC_TYPE_WAVPACK => "WAVPACK",
+        CODEC_TYPE_MP3 => "MP3",
+        CODEC_TYPE_MP2 => "MP2",
+        CODEC_TYPE_AAC => "AAC",
+        CODEC_TYPE_VORBIS => "VORBIS",
+        CODEC_TYPE_OPUS => "OPUS",
+        CODEC_TYPE_NULL => return None,
+        other => {
+            let short_name = symphonia::default::get_codecs().get_codec(other)?.short_name;
+            // 各种 PCM 编码（pcm_s16le 等）统一显示为 PCM
+            if short_name.starts_with("pcm") {
+                "PCM"
+            } else {
+                return Some(short_name.to_uppercase());
+            }
+        }
+    };
+    Some(name.to_string())
+}
+
+/// 用 symphonia 探测音频文件的时长和标签，无法解析时返回空结果
+pub fn probe(path: &Path) -> ProbeResult {
+    // symphonia 不支持 DSD
+    if dsd::is_dsd(path) {
+        return dsd::probe(path);
+    }
+    let mut result = ProbeResult::default();
+
+    let (src, size) = match std::fs::File::open(path) {
+        Ok(file) => {
+            let size = file.metadata().map(|meta| meta.len()).unwrap_or_default();
+            (MediaSourceStream::new(Box::new(file), Default::default()), size)
+        }
+        Err(_) => return result,
+    };
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension() {
+        if let Some(extension_str) = extension.to_str() {
+            hint.with_extension(extension_str);
+        }
+    }
+
+    let meta_opts: MetadataOptions = Default::default();
+    let fmt_opts: FormatOptions = Default::default();
+
+    let mut probed = match symphonia::default::get_probe().format(&hint, src, &fmt_opts, &meta_opts) {
+        Ok(probed) => probed,
+        Err(_) => return result,
+    };
+
+    // ID3v2 等位于容器之前的标签
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            read_tags(revision, &mut result.tags);
+        }
+    }
+
+    let mut format = probed.format;
+    // 容器内部的标签（FLAC/OGG 的 Vorbis comment、MP4 atom 等）
+    if let Some(revision) = format.metadata().current() {
+        read_tags(revision, &mut result.tags);
+    }
+
+    if let Some(track) = format.tracks().iter().next() {
+        result.sample_rate = track.codec_params.sample_rate;
+        result.codec = codec_name(track.codec_params.codec);
+        result.bits_per_sample = track.codec_params.bits_per_sample;
+        let time_base = track.codec_params.time_base;
+        let duration = track.codec_params.n_frames;
+
+        if let (Some(tb), Some(n_frames)) = (time_base, duration) {
+            let time = tb.calc_time(n_frames);
+            // 修复时间计算：使用正确的秒数计算
+            let total_seconds = time.seconds as f64 + time.frac;
+            result.duration = Some(Duration::from_secs_f64(total_seconds));
+            if total_seconds > 0.0 {
+                result.bitrate_kbps = Some((size as f64 * 8.0 / total_seconds / 1000.0).round() as u32);
+            }
+        }
+    }
+
+    result
+}
+
+fn read_tags(revision: &MetadataRevision, tags: &mut Tags) {
+    for tag in revision.tags() {
+        let value = tag.value.to_string().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        let slot = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => &mut tags.title,
+            Some(StandardTagKey::Artist) => &mut tags.artist,
+            Some(StandardTagKey::Album) => &mut tags.album,
+            Some(StandardTagKey::AlbumArtist) => &mut tags.album_artist,
+            Some(StandardTagKey::Genre) => &mut tags.genre,
+            Some(StandardTagKey::TrackNumber) => {
+                // 形如 "3/12" 的音轨号只取前半部分
+                if tags.track_number.is_none() {
+                    tags.track_number = value.split('/').next().and_then(|n| n.trim().parse().ok());
+                }
+                continue;
+            }
+            Some(StandardTagKey::ReplayGainTrackGain) => {
+                // 形如 "-6.52 dB"
+                if tags.replaygain_db.is_none() {
+                    tags.replaygain_db = value.trim_end_matches("dB").trim().parse().ok();
+                }
+                continue;
+            }
+            Some(StandardTagKey::Compilation) => {
+                tags.compilation |= value == "1" || value.eq_ignore_ascii_case("true");
+                continue;
+            }
+            Some(StandardTagKey::Rating) => {
+                // ID3v2 的 POPM 帧是 0-255，Vorbis comment 的 RATING 通常是 0-100
+                let stars = match tag.key.starts_with("POPM") {
+                    true => value.parse().ok().map(ratings::stars_from_popm),
+                    false => value.parse().ok().map(ratings::stars_from_percent),
+                };
+                if tags.rating.is_none() {
+                    tags.rating = stars.filter(|stars| *stars > 0);
+                }
+                continue;
+            }
+            None if is_advisory_key(&tag.key) => {
+                tags.explicit |= is_explicit_value(&value);
+                continue;
+            }
+            _ => continue,
+        };
+
+        if slot.is_none() {
+            *slot = Some(value);
+        }
+    }
+}
+
+/// 内容分级标签：ITUNESADVISORY，或用户自己写入的 EXPLICIT。
+/// ID3 的 TXXX 和 MP4 的自定义标签带有前缀，只比较最后一段
+fn is_advisory_key(key: &str) -> bool {
+    let name = key.rsplit(':').next().unwrap_or(key);
+    name.eq_ignore_ascii_case("ITUNESADVISORY") || name.eq_ignore_ascii_case("EXPLICIT")
+}
+
+/// ITUNESADVISORY 中 1 表示含有不当内容、2 表示已净化的版本
+pub fn is_explicit_value(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+    matches!(value.as_str(), "1" | "true" | "yes" | "explicit")
+}
+
+/// 属性窗口中的一组信息: (标题, [(名称, 值)])
+pub type DetailSection = (&'static str, Vec<(String, String)>);
+
+/// 读取文件的完整属性：文件信息、编解码参数、ReplayGain 和所有标签
+/// 标签的原始键名和值
+type RawTags = Vec<(String, String)>;
+
+/// 打开文件，返回容器和文件中所有的原始标签（容器之前的 ID3v2 等和容器内部的）
+fn open_with_tags(path: &Path) -> Result<(Box<dyn FormatReader>, RawTags)> {
+    let file = std::fs::File::open(path)?;
+    let src = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        src,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut tags: RawTags = Vec::new();
+    let mut collect = |revision: &MetadataRevision| {
+        for tag in revision.tags() {
+            let value = tag.value.to_string().trim().to_string();
+            if !value.is_empty() {
+                tags.push((tag.key.clone(), value));
+            }
+        }
+    };
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+        collect(revision);
+    }
+    let mut format = probed.format;
+    if let Some(revision) = format.metadata().current() {
+        collect(revision);
+    }
+    Ok((format, tags))
+}
+
+pub fn details(path: &Path) -> Result<Vec<DetailSection>> {
+    let size = std::fs::metadata(path)?.len();
+    let (format, tags) = open_with_tags(path)?;
+
+    let mut file_info = vec![
+        ("路径".to_string(), path.display().to_string()),
+        ("大小".to_string(), format_size(size)),
+    ];
+    let mut audio = Vec::new();
+    if let Some(track) = format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL) {
+        let params = &track.codec_params;
+        let codec = symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|descriptor| format!("{} ({})", descriptor.short_name, descriptor.long_name))
+            .unwrap_or_else(|| "未知".to_string());
+        audio.push(("编解码器".to_string(), codec));
+        if let Some(rate) = params.sample_rate {
+            audio.push(("采样率".to_string(), format!("{} Hz", rate)));
+        }
+        if let Some(bits) = params.bits_per_sample.or(params.bits_per_coded_sample) {
+            audio.push(("位深".to_string(), format!("{} bit", bits)));
+        }
+        if let Some(channels) = params.channels {
+            audio.push(("声道".to_string(), channels.count().to_string()));
+        }
+        if let (Some(time_base), Some(frames)) = (params.time_base, params.n_frames) {
+            let time = time_base.calc_time(frames);
+            let seconds = time.seconds as f64 + time.frac;
+            file_info.push(("时长".to_string(), format!("{:.1} 秒", seconds)));
+            if seconds > 0.0 {
+                let kbps = size as f64 * 8.0 / seconds / 1000.0;
+                audio.push(("平均比特率".to_string(), format!("{:.0} kbps", kbps)));
+            }
+        }
+    }
+
+    let (replaygain, tags): (Vec<_>, Vec<_>) = tags
+        .into_iter()
+        .partition(|(key, _)| key.to_uppercase().contains("REPLAYGAIN"));
+
+    let mut sections = vec![("文件", file_info), ("音频", audio)];
+    if !replaygain.is_empty() {
+        sections.push(("ReplayGain", replaygain));
+    }
+    sections.push(("标签", tags));
+    Ok(sections)
+}
+
+/// 读取一个原始标签的值，键名不区分大小写。用于其他软件写入的自定义标签
+pub fn raw_tag(path: &Path, key: &str) -> Option<String> {
+    let (_, tags) = open_with_tags(path).ok()?;
+    tags.into_iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value)
+}
+
+/// 章节的开始时间和标题，按时间排序
+pub type Chapter = (Duration, String);
+
+/// Vorbis comment 中的章节（`CHAPTER001=00:01:30.000`、`CHAPTER001NAME=标题`），有声书和长录音常用。
+/// 没有章节或读不出标签时为空
+pub fn chapters(path: &Path) -> Vec<Chapter> {
+    let Ok((_, tags)) = open_with_tags(path) else {
+        return Vec::new();
+    };
+    let find = |key: &str| tags.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value);
+    let mut chapters: Vec<Chapter> = tags
+        .iter()
+        .filter_map(|(key, value)| {
+            let number = key.to_uppercase().strip_prefix("CHAPTER")?.to_string();
+            if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let start = parse_chapter_time(value)?;
+            let name = find(&format!("CHAPTER{}NAME", number))
+                .cloned()
+                .unwrap_or_else(|| format!("第 {} 章", number.trim_start_matches('0')));
+            Some((start, name))
+        })
+        .collect();
+    chapters.sort_by_key(|(start, _)| *start);
+    chapters
+}
+
+/// `HH:MM:SS.mmm`，小时可以省略
+fn parse_chapter_time(value: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    for part in value.trim().split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    (seconds >= 0.0 && seconds.is_finite()).then(|| Duration::from_secs_f64(seconds))
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
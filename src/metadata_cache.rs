@@ -0,0 +1,205 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::metadata::{self, ProbeResult, Tags};
+use crate::storage;
+
+const CACHE_FILE: &str = "metadata.tsv";
+
+/// 自动选择时并行探测的线程数上限，同时也是同时打开的文件数上限
+const MAX_AUTO_WORKERS: usize = 8;
+
+/// 探测线程数：配置为 0 时按 CPU 核数自动选择
+pub fn worker_count(configured: usize) -> usize {
+    match configured {
+        0 => thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_AUTO_WORKERS),
+        n => n,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    modified_ms: u128,
+    size: u64,
+    probe: ProbeResult,
+}
+
+/// 路径 → (修改时间, 大小, 时长和标签) 的缓存，避免每次启动都用 symphonia 重新探测所有文件。
+/// 文件的修改时间或大小变化时缓存失效。
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    dirty: bool,
+}
+
+fn cache_path() -> PathBuf {
+    storage::cache_dir().join(CACHE_FILE)
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// 文件的 (修改时间毫秒, 大小)，文件不存在时返回 None
+fn file_stamp(path: &Path) -> Option<(u128, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    Some((modified, meta.len()))
+}
+
+impl MetadataCache {
+    pub fn load() -> Self {
+        let records = storage::read_records_at(&cache_path()).unwrap_or_default();
+        let entries = records
+            .into_iter()
+            .filter_map(|record| {
+                // 格式: 修改时间 \t 大小 \t 时长(ms) \t 采样率 \t 编码 \t 位深 \t 比特率 \t
+                //       标题 \t 艺术家 \t 专辑 \t 专辑艺术家 \t 流派 \t 音轨号 \t 评分 \t
+                //       ReplayGain 增益 \t 合辑 \t 不当内容 \t 路径
+                let [modified, size, duration, sample_rate, codec, bits, bitrate, title, artist, album, album_artist, genre, track, rating, replaygain, compilation, explicit, path] =
+                    <[String; 18]>::try_from(record).ok()?;
+                let probe = ProbeResult {
+                    duration: duration.parse().ok().map(Duration::from_millis),
+                    sample_rate: sample_rate.parse().ok(),
+                    codec: non_empty(codec),
+                    bits_per_sample: bits.parse().ok(),
+                    bitrate_kbps: bitrate.parse().ok(),
+                    tags: Tags {
+                        title: non_empty(title),
+                        artist: non_empty(artist),
+                        album: non_empty(album),
+                        album_artist: non_empty(album_artist),
+                        genre: non_empty(genre),
+                        track_number: track.parse().ok(),
+                        rating: rating.parse().ok(),
+                        replaygain_db: replaygain.parse().ok(),
+                        compilation: compilation == "1",
+                        explicit: explicit == "1",
+                    },
+                };
+                let entry = CacheEntry {
+                    modified_ms: modified.parse().ok()?,
+                    size: size.parse().ok()?,
+                    probe,
+                };
+                Some((storage::path_from_field(&path), entry))
+            })
+            .collect();
+        MetadataCache { entries, dirty: false }
+    }
+
+    /// 批量探测：缓存命中的直接返回，其余文件用最多 workers 个线程并行探测。
+    /// 每处理完一个文件调用一次 progress，cancel 被设置后剩余文件返回空结果。
+    pub fn probe_all(
+        &mut self,
+        paths: &[PathBuf],
+        workers: usize,
+        cancel: &AtomicBool,
+        mut progress: impl FnMut(),
+    ) -> Vec<ProbeResult> {
+        let mut results = vec![ProbeResult::default(); paths.len()];
+        let mut pending = Vec::new();
+        for (index, path) in paths.iter().enumerate() {
+            let Some(stamp) = file_stamp(path) else {
+                progress();
+                continue;
+            };
+            match self.entries.get(path).filter(|entry| (entry.modified_ms, entry.size) == stamp) {
+                Some(entry) => {
+                    results[index] = entry.probe.clone();
+                    progress();
+                }
+                None => pending.push((index, stamp)),
+            }
+        }
+        if pending.is_empty() {
+            return results;
+        }
+
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..workers.clamp(1, pending.len()) {
+                let tx = tx.clone();
+                let (next, pending) = (&next, &pending);
+                scope.spawn(move || {
+                    while !cancel.load(Ordering::Relaxed) {
+                        let Some(&(index, stamp)) = pending.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                            break;
+                        };
+                        let _ = tx.send((index, stamp, metadata::probe(&paths[index])));
+                    }
+                });
+            }
+            drop(tx);
+
+            for (index, (modified_ms, size), probe) in rx {
+                self.entries.insert(
+                    paths[index].clone(),
+                    CacheEntry { modified_ms, size, probe: probe.clone() },
+                );
+                results[index] = probe;
+                progress();
+            }
+        });
+        self.dirty = true;
+        results
+    }
+
+    /// 有变化时写回缓存文件
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut records: Vec<Vec<String>> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| {
+                let tags = &entry.probe.tags;
+                vec![
+                    entry.modified_ms.to_string(),
+                    entry.size.to_string(),
+                    optional(&entry.probe.duration.map(|duration| duration.as_millis())),
+                    optional(&entry.probe.sample_rate),
+                    optional(&entry.probe.codec),
+                    optional(&entry.probe.bits_per_sample),
+                    optional(&entry.probe.bitrate_kbps),
+                    optional(&tags.title),
+                    optional(&tags.artist),
+                    optional(&tags.album),
+                    optional(&tags.album_artist),
+                    optional(&tags.genre),
+                    optional(&tags.track_number),
+                    optional(&tags.rating),
+                    optional(&tags.replaygain_db),
+                    u8::from(tags.compilation).to_string(),
+                    u8::from(tags.explicit).to_string(),
+                    storage::path_to_field(path),
+                ]
+            })
+            .collect();
+        records.sort_by(|a, b| a[17].cmp(&b[17]));
+        storage::write_records_at(&cache_path(), &records)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
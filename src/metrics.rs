@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 计数器是全局的：网络流的下载线程拿不到播放器状态，也要能累加下载量
+static TRACKS_PLAYED: AtomicU64 = AtomicU64::new(0);
+static DECODE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static BYTES_STREAMED: AtomicU64 = AtomicU64::new(0);
+static PLAYING: AtomicBool = AtomicBool::new(false);
+/// 音量百分比
+static VOLUME: AtomicU64 = AtomicU64::new(0);
+static QUEUE_LENGTH: AtomicU64 = AtomicU64::new(0);
+static LIBRARY_TRACKS: AtomicU64 = AtomicU64::new(0);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// 从头开始播放一首歌
+pub fn record_track() {
+    TRACKS_PLAYED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_decode_error() {
+    DECODE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 网络音频下载的字节数
+pub fn record_bytes(bytes: usize) {
+    BYTES_STREAMED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// 更新当前状态，主循环中每次轮询时调用
+pub fn set_state(playing: bool, volume: f32, queue_length: usize, library_tracks: usize) {
+    PLAYING.store(playing, Ordering::Relaxed);
+    VOLUME.store((volume * 100.0).round() as u64, Ordering::Relaxed);
+    QUEUE_LENGTH.store(queue_length as u64, Ordering::Relaxed);
+    LIBRARY_TRACKS.store(library_tracks as u64, Ordering::Relaxed);
+}
+
+/// 在 `地址:端口` 上提供 Prometheus 格式的 `/metrics`，给在无界面的播放盒子上用 Grafana 监控的用户
+pub fn serve(address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).map_err(|err| anyhow!("无法监听统计端口 {}: {}", address, err))?;
+    STARTED.get_or_init(Instant::now);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = respond(stream);
+        }
+    });
+    Ok(())
+}
+
+fn respond(stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // 读完请求头，否则关闭连接时客户端可能收到 RST 而不是响应
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let path = path.split('?').next().unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        ("GET" | "HEAD", "/metrics") => ("200 OK", "text/plain; version=0.0.4; charset=utf-8", render()),
+        _ => ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string()),
+    };
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        writer.write_all(body.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Prometheus 文本格式
+fn render() -> String {
+    let uptime = STARTED.get().map(|started| started.elapsed().as_secs_f64()).unwrap_or_default();
+    let metrics: [(&str, &str, &str, String); 8] = [
+        ("tracks_played_total", "counter", "Tracks started from the beginning", load(&TRACKS_PLAYED)),
+        ("decode_errors_total", "counter", "Files that failed to decode", load(&DECODE_ERRORS)),
+        ("streamed_bytes_total", "counter", "Bytes downloaded for network audio", load(&BYTES_STREAMED)),
+        ("uptime_seconds", "gauge", "Seconds since the player started", format!("{:.0}", uptime)),
+        ("playing", "gauge", "1 while audio is playing", u8::from(PLAYING.load(Ordering::Relaxed)).to_string()),
+        ("volume_percent", "gauge", "Player volume", load(&VOLUME)),
+        ("queue_length", "gauge", "Songs in the play queue", load(&QUEUE_LENGTH)),
+        ("library_tracks", "gauge", "Songs in the library", load(&LIBRARY_TRACKS)),
+    ];
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics {
+        let _ = writeln!(text, "# HELP cupermusic_{} {}", name, help);
+        let _ = writeln!(text, "# TYPE cupermusic_{} {}", name, kind);
+        let _ = writeln!(text, "cupermusic_{} {}", name, value);
+    }
+    text
+}
+
+fn load(value: &AtomicU64) -> String {
+    value.load(Ordering::Relaxed).to_string()
+}
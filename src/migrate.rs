@@ -0,0 +1,302 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::Song;
+use crate::http;
+use crate::import::{self, ExternalTrack};
+use crate::json::Value;
+use crate::playlists;
+use crate::ratings::{self, Ratings};
+use crate::sqlite::{number, query, text};
+use crate::stats::Stats;
+
+/// 可以迁移的其他播放器的数据库
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// MPD 的 sticker 数据库（sticker.sql），myMPD、ncmpcpp 等客户端把评分和播放次数存在这里
+    MpdStickers,
+    /// beets 的 library.db，播放次数和评分来自 mpdstats 等插件写入的自定义字段
+    Beets,
+    /// Clementine（以及 Strawberry）的 clementine.db
+    Clementine,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::MpdStickers => "MPD sticker",
+            Source::Beets => "beets",
+            Source::Clementine => "Clementine",
+        }
+    }
+}
+
+/// 从另一个播放器迁移过来的一首歌
+#[derive(Debug, Default)]
+struct ForeignSong {
+    path: String,
+    artist: String,
+    title: String,
+    plays: u32,
+    last_played: Option<i64>,
+    stars: u8,
+}
+
+/// SQLite 文件按其中的表判断是哪个播放器的数据库，不是 SQLite 文件时返回 None
+pub fn detect(path: &Path) -> Result<Option<Source>> {
+    let mut header = [0u8; 16];
+    let is_sqlite = fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+        .is_ok_and(|_| &header == b"SQLite format 3\0");
+    if !is_sqlite {
+        return Ok(None);
+    }
+
+    let tables: Vec<String> = query(path, "SELECT name FROM sqlite_master WHERE type = 'table'")?
+        .iter()
+        .filter_map(|row| row.get("name").and_then(Value::as_str).map(str::to_string))
+        .collect();
+    let has = |name: &str| tables.iter().any(|table| table == name);
+    if has("sticker") {
+        Ok(Some(Source::MpdStickers))
+    } else if has("items") && has("item_attributes") {
+        Ok(Some(Source::Beets))
+    } else if has("songs") && has("playlist_items") {
+        Ok(Some(Source::Clementine))
+    } else {
+        bail!("无法识别的数据库: {}（支持 MPD sticker.sql、beets library.db 和 clementine.db）", path.display())
+    }
+}
+
+/// 导入播放次数、评分和播放列表。已有的评分不覆盖，播放次数取两边较大的一个，重复导入不会累加
+pub fn run(source: Source, db: &Path, library: &[Song]) -> Result<()> {
+    let songs = match source {
+        Source::MpdStickers => read_mpd_stickers(db)?,
+        Source::Beets => read_beets(db)?,
+        Source::Clementine => read_clementine(db)?,
+    };
+    println!();
+    println!("{} 数据库: {} 首歌曲有播放记录或评分", source.label(), songs.len());
+
+    let matcher = PathMatcher::new(library);
+    let mut stats = Stats::load();
+    let mut ratings = Ratings::load();
+    let (mut matched, mut plays, mut rated) = (0, 0, 0);
+    let mut unmatched = Vec::new();
+    for song in &songs {
+        let Some(path) = matcher.find(&song.path, &song.artist, &song.title) else {
+            unmatched.push(song);
+            continue;
+        };
+        matched += 1;
+        if song.plays > 0 && stats.import(path, song.plays, song.last_played) {
+            plays += 1;
+        }
+        if ratings.import(path, song.stars) {
+            rated += 1;
+        }
+    }
+    stats.save()?;
+    ratings.save()?;
+    println!("  匹配 {}/{} 首，导入 {} 首的播放次数、{} 首的评分", matched, songs.len(), plays, rated);
+    for song in unmatched.iter().take(20) {
+        println!("  ✗ 未找到: {}", song.path);
+    }
+    if unmatched.len() > 20 {
+        println!("  …… 还有 {} 首未找到", unmatched.len() - 20);
+    }
+
+    if source == Source::Clementine {
+        for (name, tracks) in read_clementine_playlists(db)? {
+            let paths: Vec<PathBuf> = tracks
+                .iter()
+                .filter_map(|song| matcher.find(&song.path, &song.artist, &song.title))
+                .map(Path::to_path_buf)
+                .collect();
+            println!("播放列表「{}」: 匹配 {}/{} 首", name, paths.len(), tracks.len());
+            if !paths.is_empty() {
+                println!("  已保存到 {}", playlists::save_m3u(&name, &paths)?.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// MPD 等播放器保存的 M3U 播放列表：路径通常相对于对方的音乐目录，按路径末尾匹配到曲库中的文件
+pub fn import_m3u(file: &Path, library: &[Song]) -> Result<()> {
+    let content = fs::read_to_string(file)?;
+    let entries: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let name = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+    let matcher = PathMatcher::new(library);
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+    for entry in &entries {
+        match matcher.find(entry, "", "") {
+            Some(path) => matched.push(path.to_path_buf()),
+            None => unmatched.push(entry),
+        }
+    }
+
+    println!();
+    println!("播放列表「{}」: 匹配 {}/{} 首", name, matched.len(), entries.len());
+    if !matched.is_empty() {
+        println!("  已保存到 {}", playlists::save_m3u(&name, &matched)?.display());
+    }
+    for entry in unmatched {
+        println!("  ✗ 未找到: {}", entry);
+    }
+    Ok(())
+}
+
+/// 0 到 1 的评分（Clementine、beets mpdstats）换算为星级
+fn stars_from_fraction(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * ratings::MAX_STARS as f64).round() as u8
+}
+
+/// MPD 的 sticker 按“名称 = 值”保存，不同客户端的名称不同：评分通常是 0-10，播放次数和最近播放时间是 myMPD 的写法
+fn read_mpd_stickers(db: &Path) -> Result<Vec<ForeignSong>> {
+    let rows = query(db, "SELECT uri, name, value FROM sticker WHERE type = 'song'")?;
+    let mut songs: HashMap<String, ForeignSong> = HashMap::new();
+    for row in &rows {
+        let uri = text(row, "uri");
+        let name = text(row, "name").to_lowercase().replace('_', "");
+        let value = number(row, "value");
+        let song = songs.entry(uri.clone()).or_insert_with(|| ForeignSong { path: uri, ..Default::default() });
+        match (name.as_str(), value) {
+            ("rating", Some(rating)) => song.stars = ((rating.clamp(0.0, 10.0) + 1.0) / 2.0) as u8,
+            ("playcount", Some(plays)) => song.plays = plays.max(0.0) as u32,
+            ("lastplayed", Some(time)) if time > 0.0 => song.last_played = Some(time as i64),
+            _ => {}
+        }
+    }
+    let mut songs: Vec<ForeignSong> = songs.into_values().collect();
+    songs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(songs)
+}
+
+/// beets 的播放次数和评分是自定义字段（item_attributes 表）；评分可能是 mpdstats 的 0-1，也可能是手动设置的 1-5 星
+fn read_beets(db: &Path) -> Result<Vec<ForeignSong>> {
+    let sql = "SELECT CAST(i.path AS TEXT) AS path, i.artist, i.title, \
+               (SELECT value FROM item_attributes WHERE entity_id = i.id AND key = 'play_count') AS plays, \
+               (SELECT value FROM item_attributes WHERE entity_id = i.id AND key = 'last_played') AS last_played, \
+               (SELECT value FROM item_attributes WHERE entity_id = i.id AND key = 'rating') AS rating \
+               FROM items i";
+    Ok(query(db, sql)?
+        .iter()
+        .map(|row| ForeignSong {
+            path: text(row, "path"),
+            artist: text(row, "artist"),
+            title: text(row, "title"),
+            plays: number(row, "plays").unwrap_or(0.0).max(0.0) as u32,
+            last_played: number(row, "last_played").filter(|time| *time > 0.0).map(|time| time as i64),
+            stars: match number(row, "rating") {
+                Some(rating) if rating > 1.0 => rating.min(ratings::MAX_STARS as f64).round() as u8,
+                Some(rating) => stars_from_fraction(rating),
+                None => 0,
+            },
+        })
+        .filter(|song| song.plays > 0 || song.stars > 0)
+        .collect())
+}
+
+/// Clementine 的文件名是 file:// 地址；未评分是 -1，没播放过的最近播放时间也是 -1
+fn read_clementine(db: &Path) -> Result<Vec<ForeignSong>> {
+    let sql = "SELECT CAST(filename AS TEXT) AS filename, artist, title, playcount, lastplayed, rating \
+               FROM songs WHERE unavailable = 0 AND (playcount > 0 OR rating > 0)";
+    Ok(query(db, sql)?
+        .iter()
+        .map(|row| ForeignSong {
+            path: file_url_path(&text(row, "filename")),
+            artist: text(row, "artist"),
+            title: text(row, "title"),
+            plays: number(row, "playcount").unwrap_or(0.0).max(0.0) as u32,
+            last_played: number(row, "lastplayed").filter(|time| *time > 0.0).map(|time| time as i64),
+            stars: number(row, "rating").filter(|rating| *rating > 0.0).map_or(0, stars_from_fraction),
+        })
+        .collect())
+}
+
+/// Clementine 的播放列表，不在曲库中的条目自己带有文件名和标签
+fn read_clementine_playlists(db: &Path) -> Result<Vec<(String, Vec<ForeignSong>)>> {
+    let sql = "SELECT p.name AS playlist, \
+               CAST(COALESCE(s.filename, i.filename) AS TEXT) AS filename, \
+               COALESCE(s.artist, i.artist) AS artist, COALESCE(s.title, i.title) AS title \
+               FROM playlist_items i JOIN playlists p ON p.ROWID = i.playlist \
+               LEFT JOIN songs s ON s.ROWID = i.library_id \
+               ORDER BY i.playlist, i.ROWID";
+    let mut playlists: Vec<(String, Vec<ForeignSong>)> = Vec::new();
+    for row in query(db, sql)? {
+        let name = text(&row, "playlist");
+        let song = ForeignSong {
+            path: file_url_path(&text(&row, "filename")),
+            artist: text(&row, "artist"),
+            title: text(&row, "title"),
+            ..Default::default()
+        };
+        match playlists.last_mut() {
+            Some((last, tracks)) if *last == name => tracks.push(song),
+            _ => playlists.push((name, vec![song])),
+        }
+    }
+    Ok(playlists)
+}
+
+fn file_url_path(value: &str) -> String {
+    match value.strip_prefix("file://") {
+        Some(path) => http::decode(path),
+        None => value.to_string(),
+    }
+}
+
+/// 按文件路径把其他播放器的歌曲对应到曲库：两边的音乐目录通常不同，
+/// 先找文件名相同的歌曲，再比较末尾有几级目录相同；都不行时按艺术家和标题模糊匹配
+struct PathMatcher<'a> {
+    library: &'a [Song],
+    by_name: HashMap<String, Vec<&'a Song>>,
+}
+
+impl<'a> PathMatcher<'a> {
+    fn new(library: &'a [Song]) -> Self {
+        let mut by_name: HashMap<String, Vec<&Song>> = HashMap::new();
+        for song in library {
+            if let Some(name) = song.path.file_name() {
+                by_name.entry(name.to_string_lossy().to_lowercase()).or_default().push(song);
+            }
+        }
+        PathMatcher { library, by_name }
+    }
+
+    fn find(&self, path: &str, artist: &str, title: &str) -> Option<&'a Path> {
+        let foreign: Vec<String> = path
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let candidates = foreign.last().and_then(|name| self.by_name.get(name));
+        if let Some(candidates) = candidates {
+            let common = |song: &Song| {
+                let ours: Vec<String> = song.path.iter().map(|part| part.to_string_lossy().to_lowercase()).collect();
+                ours.iter().rev().zip(foreign.iter().rev()).take_while(|(a, b)| a == b).count()
+            };
+            let best = candidates.iter().map(|song| common(song)).max().unwrap_or(0);
+            let mut matches = candidates.iter().filter(|song| common(song) == best);
+            // 同名文件只有一个，或者目录更接近的只有一个
+            if let (Some(song), None) = (matches.next(), matches.next()) {
+                return Some(&song.path);
+            }
+        }
+
+        let track = ExternalTrack {
+            artist: artist.to_string(),
+            title: title.to_string(),
+        };
+        import::find_match(&track, self.library).map(|song| song.path.as_path())
+    }
+}
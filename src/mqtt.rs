@@ -0,0 +1,279 @@
+use anyhow::{anyhow, bail, Result};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::remote::Command;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const DEFAULT_PORT: u16 = 1883;
+
+/// 连接 MQTT 服务器所需的配置
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// `主机` 或 `主机:端口`
+    pub broker: String,
+    /// 主题前缀：状态发布到 `前缀/state`，命令从 `前缀/command` 接收，在线状态在 `前缀/availability`
+    pub topic: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// 后台连接发给播放器的事件
+#[derive(Debug)]
+pub enum Event {
+    Command(Command),
+    Connected,
+    /// 连接失败或断开，之后会自动重连
+    Disconnected(String),
+}
+
+/// 后台运行的 MQTT 连接：断开后按退避时间自动重连，重连后重新发布最近的状态
+pub struct Mqtt {
+    state_tx: Sender<String>,
+    pub events: Receiver<Event>,
+}
+
+impl Mqtt {
+    pub fn start(settings: Settings) -> Self {
+        let (state_tx, state_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        thread::spawn(move || run(settings, state_rx, event_tx));
+        Mqtt { state_tx, events }
+    }
+
+    /// 发布播放状态（保留消息），和上一次相同时不重复发送
+    pub fn publish_state(&self, state: String) {
+        let _ = self.state_tx.send(state);
+    }
+}
+
+fn run(settings: Settings, state_rx: Receiver<String>, event_tx: Sender<Event>) {
+    let mut last_state: Option<String> = None;
+    let mut backoff = Duration::from_secs(1);
+    // 只在第一次连接失败和连上后断开时通知，反复重连失败时不刷屏
+    let mut notify = true;
+    loop {
+        if let Err(err) = session(&settings, &state_rx, &event_tx, &mut last_state, &mut notify) {
+            if notify {
+                if event_tx.send(Event::Disconnected(err.to_string())).is_err() {
+                    return;
+                }
+                // 刚断开时很快重连一次
+                notify = false;
+                backoff = Duration::from_secs(1);
+            }
+        } else {
+            return;
+        }
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        // 等待重连期间的状态只保留最新的一个
+        loop {
+            match state_rx.try_recv() {
+                Ok(state) => last_state = Some(state),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// 一次连接，播放器退出（状态通道关闭）时返回 Ok
+fn session(
+    settings: &Settings,
+    state_rx: &Receiver<String>,
+    event_tx: &Sender<Event>,
+    last_state: &mut Option<String>,
+    notify: &mut bool,
+) -> Result<()> {
+    let address = match settings.broker.contains(':') {
+        true => settings.broker.clone(),
+        false => format!("{}:{}", settings.broker, DEFAULT_PORT),
+    };
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("无法解析 MQTT 服务器地址: {}", settings.broker))?;
+    let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+
+    let availability = format!("{}/availability", settings.topic);
+    stream.write_all(&connect_packet(settings, &availability))?;
+    let (header, body) = read_packet(&mut stream)?;
+    if header != 0x20 || body.len() < 2 {
+        bail!("MQTT 服务器的响应无效");
+    }
+    match body[1] {
+        0 => {}
+        4 | 5 => bail!("MQTT 服务器拒绝连接：用户名或密码错误"),
+        code => bail!("MQTT 服务器拒绝连接（错误码 {}）", code),
+    }
+    let command_topic = format!("{}/command", settings.topic);
+    stream.write_all(&subscribe_packet(&command_topic))?;
+    stream.write_all(&publish_packet(&availability, b"online", true))?;
+    if let Some(state) = last_state {
+        stream.write_all(&publish_packet(&format!("{}/state", settings.topic), state.as_bytes(), true))?;
+    }
+    *notify = true;
+    let _ = event_tx.send(Event::Connected);
+
+    // 读取在单独的线程中阻塞进行，收到的命令直接转给播放器
+    let closed = Arc::new(AtomicBool::new(false));
+    let mut reader = stream.try_clone()?;
+    reader.set_read_timeout(None)?;
+    {
+        let closed = closed.clone();
+        let event_tx = event_tx.clone();
+        thread::spawn(move || {
+            while let Ok((header, body)) = read_packet(&mut reader) {
+                if header & 0xF0 != 0x30 {
+                    continue;
+                }
+                let Some((topic, payload)) = parse_publish(header, &body) else {
+                    continue;
+                };
+                let name = String::from_utf8_lossy(payload).trim().to_lowercase();
+                if topic == command_topic {
+                    if let Some(command) = Command::parse(&name) {
+                        if event_tx.send(Event::Command(command)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            closed.store(true, Ordering::Relaxed);
+        });
+    }
+
+    let state_topic = format!("{}/state", settings.topic);
+    let mut last_sent = Instant::now();
+    loop {
+        if closed.load(Ordering::Relaxed) {
+            bail!("MQTT 连接已断开");
+        }
+        match state_rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(mut state) => {
+                while let Ok(newer) = state_rx.try_recv() {
+                    state = newer;
+                }
+                if last_state.as_ref() != Some(&state) {
+                    stream.write_all(&publish_packet(&state_topic, state.as_bytes(), true))?;
+                    last_sent = Instant::now();
+                    *last_state = Some(state);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                // 正常退出：先标记为离线再断开，不触发遗嘱消息
+                stream.write_all(&publish_packet(&availability, b"offline", true))?;
+                stream.write_all(&[0xE0, 0x00])?;
+                return Ok(());
+            }
+        }
+        if last_sent.elapsed() >= KEEP_ALIVE / 2 {
+            stream.write_all(&[0xC0, 0x00])?;
+            last_sent = Instant::now();
+        }
+    }
+}
+
+/// MQTT 3.1.1 的 CONNECT 报文；遗嘱消息在连接意外断开时把在线状态设为 offline
+fn connect_packet(settings: &Settings, availability: &str) -> Vec<u8> {
+    let mut flags = 0x02 | 0x04 | 0x20; // 清除会话、遗嘱、遗嘱保留
+    if !settings.username.is_empty() {
+        flags |= 0x80;
+        if !settings.password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+    let mut body = Vec::new();
+    push_string(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    push_string(&mut body, format!("cupermusic-{}", std::process::id()).as_bytes());
+    push_string(&mut body, availability.as_bytes());
+    push_string(&mut body, b"offline");
+    if !settings.username.is_empty() {
+        push_string(&mut body, settings.username.as_bytes());
+        if !settings.password.is_empty() {
+            push_string(&mut body, settings.password.as_bytes());
+        }
+    }
+    packet(0x10, body)
+}
+
+fn subscribe_packet(topic: &str) -> Vec<u8> {
+    let mut body = vec![0x00, 0x01];
+    push_string(&mut body, topic.as_bytes());
+    body.push(0);
+    packet(0x82, body)
+}
+
+/// QoS 0 的 PUBLISH 报文
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(0x30 | u8::from(retain), body)
+}
+
+/// 收到的 PUBLISH 报文：(主题, 内容)。QoS 1/2 的报文在主题后面还有两个字节的报文 ID
+fn parse_publish(header: u8, body: &[u8]) -> Option<(String, &[u8])> {
+    let length = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = String::from_utf8_lossy(body.get(2..2 + length)?).to_string();
+    let mut rest = &body[2 + length..];
+    if (header >> 1) & 0x03 > 0 {
+        rest = rest.get(2..)?;
+    }
+    Some((topic, rest))
+}
+
+fn push_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+/// 固定报头 + 变长编码的剩余长度 + 内容
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend(body);
+    packet
+}
+
+fn read_packet(stream: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte)?;
+    let header = byte[0];
+    let mut length = 0usize;
+    for shift in 0..4 {
+        stream.read_exact(&mut byte)?;
+        length |= ((byte[0] & 0x7F) as usize) << (7 * shift);
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body)?;
+    Ok((header, body))
+}
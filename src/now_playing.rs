@@ -0,0 +1,164 @@
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+
+use crate::app::{App, PlaybackState};
+use crate::status_line;
+
+/// 除了状态栏的变量，播放状态面板还可以使用这些变量
+pub const VARIABLES: [&str; 8] = [
+    "status", "buffer", "feat", "main_artist", "main_title", "genre", "format", "bpm",
+];
+
+/// `{颜色}` 可以使用的颜色，`{/}` 恢复默认颜色
+const COLORS: [(&str, Color); 10] = [
+    ("white", Color::White),
+    ("gray", Color::Gray),
+    ("darkgray", Color::DarkGray),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("black", Color::Black),
+];
+
+/// 标题或艺术家中标出合作艺术家的写法，按顺序查找
+const FEAT_MARKERS: [&str; 8] = [" (feat. ", " (ft. ", " [feat. ", " [ft. ", " feat. ", " ft. ", " featuring ", " (with "];
+
+/// 按模板生成播放状态面板的各行：`%变量%` 替换为对应的值，`{颜色}` 切换之后文字的颜色，
+/// `[...]` 中的变量有一个为空时整段不显示（如 `[ feat. %feat%]`），`\n` 换行，`\` 加其他字符表示这个字符本身
+pub fn lines(template: &str, app: &App) -> Vec<Line<'static>> {
+    let lookup = |name: &str| match name {
+        name if VARIABLES.contains(&name) => Some(value(name, app)),
+        name if status_line::VARIABLES.contains(&name) => Some(status_line::value(name, app)),
+        _ => None,
+    };
+    let mut chars = template.chars();
+    let mut output = vec![Vec::new()];
+    let mut color = None;
+    parse(&mut chars, &lookup, &mut color, &mut output, false);
+    output.into_iter().map(Line::from).collect()
+}
+
+/// 解析到模板结尾，或者 in_condition 时解析到对应的 `]`。返回其中的变量是否都有值
+fn parse(
+    chars: &mut std::str::Chars,
+    lookup: &impl Fn(&str) -> Option<String>,
+    color: &mut Option<Color>,
+    output: &mut Vec<Vec<Span<'static>>>,
+    in_condition: bool,
+) -> bool {
+    let mut complete = true;
+    let push = |output: &mut Vec<Vec<Span<'static>>>, text: String, color: Option<Color>| {
+        if text.is_empty() {
+            return;
+        }
+        let style = color.map(|color| Style::default().fg(color)).unwrap_or_default();
+        let line = output.last_mut().expect("至少有一行");
+        // 颜色相同的文字合成一段
+        match line.last_mut().filter(|span| span.style == style) {
+            Some(span) => span.content.to_mut().push_str(&text),
+            None => line.push(Span::styled(text, style)),
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => output.push(Vec::new()),
+                Some(c) => push(output, c.to_string(), *color),
+                None => push(output, "\\".to_string(), *color),
+            },
+            ']' if in_condition => return complete,
+            '[' => {
+                let mut inner = vec![Vec::new()];
+                let mut inner_color = *color;
+                if parse(chars, lookup, &mut inner_color, &mut inner, true) {
+                    let mut inner = inner.into_iter();
+                    output.last_mut().expect("至少有一行").extend(inner.next().unwrap_or_default());
+                    output.extend(inner);
+                    *color = inner_color;
+                }
+            }
+            '{' => {
+                let rest: String = chars.clone().take_while(|c| *c != '}').collect();
+                let named = match rest.as_str() {
+                    "/" => Some(None),
+                    name => COLORS.iter().find(|(color, _)| *color == name).map(|(_, color)| Some(*color)),
+                };
+                match named {
+                    Some(named) => {
+                        chars.nth(rest.chars().count());
+                        *color = named;
+                    }
+                    None => push(output, c.to_string(), *color),
+                }
+            }
+            '%' => {
+                let rest: String = chars.clone().take_while(|c| *c != '%').collect();
+                match lookup(&rest).filter(|_| chars.clone().nth(rest.chars().count()).is_some()) {
+                    Some(value) => {
+                        chars.nth(rest.chars().count());
+                        complete &= !value.is_empty();
+                        push(output, value, *color);
+                    }
+                    None => push(output, c.to_string(), *color),
+                }
+            }
+            c => push(output, c.to_string(), *color),
+        }
+    }
+    // 没有对应的 `]` 时不显示这一段
+    !in_condition && complete
+}
+
+/// 拆出合作艺术家：(主体, 合作艺术家)
+fn split_feat(text: &str) -> (String, Option<String>) {
+    // 只转换 ASCII 字母，位置和原文一致
+    let lower = text.to_ascii_lowercase();
+    for marker in FEAT_MARKERS {
+        if let Some(position) = lower.find(marker) {
+            let feat = text[position + marker.len()..].trim_end_matches([')', ']']).trim();
+            return (text[..position].trim().to_string(), Some(feat.to_string()).filter(|feat| !feat.is_empty()));
+        }
+    }
+    (text.to_string(), None)
+}
+
+pub fn value(name: &str, app: &App) -> String {
+    let song = app.get_current_song();
+    let title = song.map(|song| song.title()).unwrap_or_default();
+    let artist = song.and_then(|song| song.artist()).unwrap_or_default();
+    match name {
+        "status" => {
+            let glyphs = app.config.glyphs.glyphs();
+            match app.playback_state {
+                PlaybackState::Playing if app.buffering => format!("{} 缓冲中…", glyphs.paused),
+                PlaybackState::Playing => format!("{} 播放中", glyphs.playing),
+                PlaybackState::Paused => format!("{} 暂停", glyphs.paused),
+                PlaybackState::Stopped => format!("{} 停止", glyphs.stopped),
+            }
+        }
+        // 网络音频显示已下载的比例
+        "buffer" => match &app.stream {
+            Some(stream) => {
+                let buffer = stream.status();
+                match buffer.fill() {
+                    Some(fill) => format!("  [已缓冲 {:.0}%]", fill * 100.0),
+                    None => format!("  [已缓冲 {:.1} MB]", buffer.downloaded as f64 / 1_048_576.0),
+                }
+            }
+            None => String::new(),
+        },
+        "feat" => split_feat(&title).1.or_else(|| split_feat(&artist).1).unwrap_or_default(),
+        "main_artist" => split_feat(&artist).0,
+        "main_title" => split_feat(&title).0,
+        "genre" => song.and_then(|song| song.tags.genre.clone()).unwrap_or_default(),
+        "format" => song.and_then(|song| song.badge.as_ref()).map(|badge| badge.label.clone()).unwrap_or_default(),
+        "bpm" => song
+            .and_then(|song| app.bpms.get(&song.path))
+            .map(|bpm| format!("{:.0}", bpm))
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
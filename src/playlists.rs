@@ -0,0 +1,503 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::app::{load_cached, scan_paths_recursive, Song};
+use crate::http_stream;
+use crate::metadata_cache::{self, MetadataCache};
+use crate::storage;
+
+/// 保存的播放列表目录
+pub fn playlists_dir() -> PathBuf {
+    storage::data_dir().join("playlists")
+}
+
+/// 读取 M3U 播放列表，相对路径按播放列表所在目录解析。按字节读取，
+/// 不是 UTF-8 的文件名（如 GBK 编码的旧播放列表）在 Unix 上也能找到对应的文件，其他系统上跳过这样的行
+pub fn load_m3u(path: &Path) -> Result<Vec<PathBuf>> {
+    let content = fs::read(path)?;
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&content);
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+
+    Ok(content
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.trim_ascii())
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .filter_map(|line| resolve_entry(base, line))
+        .collect())
+}
+
+/// 播放列表中的一行对应的路径。网址和绝对路径原样保留，包括在其他系统上生成的
+/// `C:\Music\...`、`\\服务器\共享\...`；非 Windows 系统上相对路径中的反斜杠当作目录分隔符
+fn resolve_entry(base: &Path, line: &[u8]) -> Option<PathBuf> {
+    let entry = bytes_to_path(line.to_vec())?;
+    if entry.is_absolute() || http_stream::is_url(&entry) || is_windows_absolute(line) {
+        return Some(entry);
+    }
+    if cfg!(windows) {
+        return Some(base.join(entry));
+    }
+    let line: Vec<u8> = line.iter().map(|byte| if *byte == b'\\' { b'/' } else { *byte }).collect();
+    Some(base.join(bytes_to_path(line)?))
+}
+
+/// 带盘符或 UNC 共享的 Windows 绝对路径
+fn is_windows_absolute(line: &[u8]) -> bool {
+    let drive = matches!(line, [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic());
+    drive || line.starts_with(b"\\\\")
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// 其他系统上文件名是 Unicode，不是 UTF-8 的行对应不到任何文件，不猜测编码
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// 以名称保存 M3U 播放列表（写入绝对路径），返回写入的文件路径
+pub fn save_m3u(name: &str, paths: &[PathBuf]) -> Result<PathBuf> {
+    let dir = playlists_dir();
+    fs::create_dir_all(&dir)?;
+
+    let file = dir.join(format!("{}.m3u", storage::sanitize_file_name(name)));
+    write_m3u(&file, paths)?;
+    Ok(file)
+}
+
+/// 把路径列表写入新的 M3U 文件（写入绝对路径）
+pub fn write_m3u(file: &Path, paths: &[PathBuf]) -> Result<()> {
+    let mut content = b"#EXTM3U\n".to_vec();
+    for path in paths {
+        content.extend(entry_line(Path::new(""), path, false));
+        content.push(b'\n');
+    }
+    storage::write_atomic(file, &content)?;
+    Ok(())
+}
+
+/// 按行修改已有的 M3U 文件，没有改动的行（注释、`#EXTINF`、相对路径、换行符）原样保留。
+/// edit 对文件中的每个条目返回 None 表示删除这一条（连同紧挨在它前面的 `#EXTINF` 等行），
+/// 返回其他路径表示改成这个路径；append 中的路径以绝对路径追加到末尾。有改动时才写入，返回是否写入
+fn patch_m3u(file: &Path, append: &[PathBuf], mut edit: impl FnMut(&Path) -> Option<PathBuf>) -> Result<bool> {
+    let content = fs::read(file)?;
+    let (bom, content) = match content.strip_prefix(b"\xEF\xBB\xBF") {
+        Some(rest) => (&b"\xEF\xBB\xBF"[..], rest),
+        None => (&b""[..], &content[..]),
+    };
+    let base = file.parent().unwrap_or_else(|| Path::new("."));
+    let crlf = content.windows(2).any(|pair| pair == b"\r\n");
+
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    let mut changed = false;
+    // 紧挨在当前行之前、属于下一个条目的 #EXT 行数
+    let mut directives = 0;
+    for raw in content.split(|byte| *byte == b'\n') {
+        let line = raw.trim_ascii();
+        let entry = match line.first() {
+            None => None,
+            Some(b'#') => {
+                let directive = line.starts_with(b"#EXT") && !line.starts_with(b"#EXTM3U");
+                directives = if directive { directives + 1 } else { 0 };
+                lines.push(raw.to_vec());
+                continue;
+            }
+            Some(_) => resolve_entry(base, line),
+        };
+        let Some(entry) = entry else {
+            directives = 0;
+            lines.push(raw.to_vec());
+            continue;
+        };
+        match edit(&entry) {
+            None => {
+                lines.truncate(lines.len() - directives);
+                changed = true;
+            }
+            Some(new) if new != entry => {
+                let written = bytes_to_path(line.to_vec()).unwrap_or_default();
+                let relative = written.is_relative() && !is_windows_absolute(line) && !http_stream::is_url(&written);
+                let mut new_line = entry_line(base, &new, relative);
+                if raw.ends_with(b"\r") {
+                    new_line.push(b'\r');
+                }
+                lines.push(new_line);
+                changed = true;
+            }
+            Some(_) => lines.push(raw.to_vec()),
+        }
+        directives = 0;
+    }
+
+    if !append.is_empty() {
+        // 文件以换行结尾时最后是一个空行，追加的条目放在它前面
+        if lines.last().is_some_and(|last| last.is_empty()) {
+            lines.pop();
+        }
+        for path in append {
+            let mut line = entry_line(base, path, false);
+            if crlf {
+                line.push(b'\r');
+            }
+            lines.push(line);
+        }
+        lines.push(Vec::new());
+        changed = true;
+    }
+
+    if changed {
+        let mut content = bom.to_vec();
+        content.extend(lines.join(&b'\n'));
+        storage::write_atomic(file, &content)?;
+    }
+    Ok(changed)
+}
+
+/// 写入播放列表的一行。relative 时在播放列表所在目录下的文件写成相对路径，其他写成绝对路径
+fn entry_line(base: &Path, path: &Path, relative: bool) -> Vec<u8> {
+    if http_stream::is_url(path) {
+        return path_to_bytes(path);
+    }
+    let path = storage::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if relative {
+        let base = storage::canonicalize(base).unwrap_or_else(|_| base.to_path_buf());
+        if let Ok(relative) = path.strip_prefix(&base) {
+            return path_to_bytes(relative);
+        }
+    }
+    path_to_bytes(&path)
+}
+
+/// 播放列表文件被其他程序修改，等待确认是否重新加载
+#[derive(Debug, Clone)]
+pub struct PlaylistChange {
+    /// 文件中现在的条目
+    pub entries: Vec<PathBuf>,
+    pub added: usize,
+    pub removed: usize,
+    /// 本地新加入、文件中没有的条目数，重新加载后保留
+    pub unsaved: usize,
+}
+
+/// 作为曲库打开的 M3U 文件：记下上次读写时的修改时间和条目，用来发现其他程序对它的修改
+#[derive(Debug, Clone)]
+pub struct WatchedPlaylist {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+    /// 上次从文件读到或写入文件的条目，合并时作为双方共同的基础
+    entries: Vec<PathBuf>,
+    /// 出现在文件中过的条目。只有这些歌曲会写回文件，粘贴加入的歌曲、闹钟播放列表等只留在内存中
+    origin: HashSet<PathBuf>,
+}
+
+impl WatchedPlaylist {
+    pub fn new(path: &Path, entries: Vec<PathBuf>) -> Self {
+        WatchedPlaylist {
+            path: path.to_path_buf(),
+            modified: modified_time(path),
+            origin: keys(&entries),
+            entries,
+        }
+    }
+
+    /// 把本地曲库的修改写回文件：删除本地已经移除的条目，改写移动过的条目（moves），
+    /// 撤销删除后又回到曲库的条目追加到末尾。其余内容原样保留，不是来自文件的歌曲不写入
+    pub fn save(&mut self, local: &[PathBuf], moves: &[(PathBuf, PathBuf)]) -> Result<()> {
+        let moves: Vec<(PathBuf, PathBuf)> = moves.iter().map(|(old, new)| (entry_key(old), new.clone())).collect();
+        let moved = |entry: &Path| match moves.iter().find(|(old, _)| *old == entry_key(entry)) {
+            Some((_, new)) => new.clone(),
+            None => entry.to_path_buf(),
+        };
+        self.origin.extend(moves.iter().map(|(_, new)| entry_key(new)));
+        let in_file: HashSet<PathBuf> = load_m3u(&self.path)?.iter().map(|entry| entry_key(&moved(entry))).collect();
+        let restored: Vec<PathBuf> = local
+            .iter()
+            .filter(|path| {
+                let key = entry_key(path);
+                self.origin.contains(&key) && !in_file.contains(&key)
+            })
+            .cloned()
+            .collect();
+
+        let local = keys(local);
+        patch_m3u(&self.path, &restored, |entry| {
+            let entry = moved(entry);
+            local.contains(&entry_key(&entry)).then_some(entry)
+        })?;
+        self.sync(load_m3u(&self.path)?);
+        Ok(())
+    }
+
+    /// 修改时间变了时重新读取，条目和上次不同时返回文件中的新条目。
+    /// 文件被删除或读取失败时不算修改，等它重新出现
+    pub fn check(&mut self) -> Option<Vec<PathBuf>> {
+        let modified = modified_time(&self.path)?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        let entries = load_m3u(&self.path).ok()?;
+        (keys(&entries) != keys(&self.entries)).then_some(entries)
+    }
+
+    /// 程序自己写入文件或者采用了文件中的条目之后调用，之后以这些条目为基础
+    pub fn sync(&mut self, entries: Vec<PathBuf>) {
+        self.modified = modified_time(&self.path);
+        self.origin.extend(keys(&entries));
+        self.entries = entries;
+    }
+
+    /// 文件中的条目相对上次的变化，以及本地还没有写入文件的新条目
+    pub fn change(&self, entries: Vec<PathBuf>, local: &[PathBuf]) -> PlaylistChange {
+        let base = keys(&self.entries);
+        let disk = keys(&entries);
+        PlaylistChange {
+            added: disk.difference(&base).count(),
+            removed: base.difference(&disk).count(),
+            unsaved: keys(local).iter().filter(|key| !base.contains(*key) && !disk.contains(*key)).count(),
+            entries,
+        }
+    }
+
+    /// 把文件中的新条目和本地的修改合并：按文件中的顺序，去掉文件中删除了的和本地删除了的，
+    /// 最后加上本地新加入、文件中没有的条目
+    pub fn merge(&self, disk: &[PathBuf], local: &[PathBuf]) -> Vec<PathBuf> {
+        let base = keys(&self.entries);
+        let disk_keys = keys(disk);
+        let local_keys = keys(local);
+        let mut merged: Vec<PathBuf> = disk
+            .iter()
+            .filter(|path| {
+                let key = entry_key(path);
+                !base.contains(&key) || local_keys.contains(&key)
+            })
+            .cloned()
+            .collect();
+        merged.extend(
+            local
+                .iter()
+                .filter(|path| {
+                    let key = entry_key(path);
+                    !base.contains(&key) && !disk_keys.contains(&key)
+                })
+                .cloned(),
+        );
+        merged
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// 比较条目时统一成绝对路径，同一个文件的相对路径和绝对路径写法算作同一条
+fn entry_key(path: &Path) -> PathBuf {
+    storage::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn keys(paths: &[PathBuf]) -> HashSet<PathBuf> {
+    paths.iter().map(|path| entry_key(path)).collect()
+}
+
+/// 文件移动后更新保存的播放列表中的条目，只重写包含这些文件的播放列表
+pub fn rename_entries(moves: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let Ok(files) = fs::read_dir(playlists_dir()) else {
+        return Ok(());
+    };
+    for file in files.flatten() {
+        let path = file.path();
+        let is_m3u = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"));
+        if !is_m3u {
+            continue;
+        }
+        patch_m3u(&path, &[], |entry| match moves.iter().find(|(old, _)| old == entry) {
+            Some((_, new)) => Some(new.clone()),
+            None => Some(entry.to_path_buf()),
+        })?;
+    }
+    Ok(())
+}
+
+/// `music_tui folder-playlists [目录]`：把每个包含音频文件的文件夹保存为一个播放列表，
+/// 曲目按标签中的音轨号排序
+pub fn run_folder_playlists(args: &[OsString]) -> Result<()> {
+    let root = args.first().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("assets"));
+    let mut paths = Vec::new();
+    scan_paths_recursive(&root, 16, &mut paths);
+
+    let mut cache = MetadataCache::load();
+    let mut folders: BTreeMap<PathBuf, Vec<Song>> = BTreeMap::new();
+    for song in load_cached(paths, &mut cache, metadata_cache::worker_count(0)) {
+        let folder = song.path.parent().unwrap_or(&root).to_path_buf();
+        folders.entry(folder).or_default().push(song);
+    }
+    cache.save()?;
+
+    let mut used_names: Vec<String> = Vec::new();
+    for (folder, mut songs) in folders {
+        // 没有音轨号的歌曲排在后面，再按文件名排序
+        songs.sort_by(|a, b| {
+            let key = |song: &Song| song.tags.track_number.unwrap_or(u32::MAX);
+            key(a).cmp(&key(b)).then_with(|| a.path.cmp(&b.path))
+        });
+
+        let name = folder_playlist_name(&folder, &used_names);
+        let paths: Vec<PathBuf> = songs.into_iter().map(|song| song.path).collect();
+        let file = save_m3u(&name, &paths)?;
+        println!("{} ({} 首) -> {}", name, paths.len(), file.display());
+        used_names.push(name);
+    }
+
+    if used_names.is_empty() {
+        println!("{} 下没有找到音频文件", root.display());
+    }
+    Ok(())
+}
+
+/// 以文件夹名命名，重名时加上上级文件夹名（如 "Artist - Album"）
+fn folder_playlist_name(folder: &Path, used: &[String]) -> String {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "untitled".to_string())
+    };
+
+    let name = file_name(folder);
+    if !used.contains(&name) {
+        return name;
+    }
+    match folder.parent() {
+        Some(parent) => format!("{} - {}", file_name(parent), name),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn load(name: &str, content: &[u8]) -> Vec<PathBuf> {
+        let dir = env::temp_dir().join(format!("cuper-playlists-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("list.m3u");
+        fs::write(&file, content).unwrap();
+        let entries = load_m3u(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        entries.into_iter().map(|entry| entry.strip_prefix(&dir).map(Path::to_path_buf).unwrap_or(entry)).collect()
+    }
+
+    #[test]
+    fn crlf_lines_and_comments() {
+        let entries = load("crlf", b"\xEF\xBB\xBF#EXTM3U\r\n#EXTINF:123,Artist - Title\r\na.mp3\r\n\r\nsub/b.flac\r\n");
+        assert_eq!(entries, vec![PathBuf::from("a.mp3"), PathBuf::from("sub/b.flac")]);
+    }
+
+    #[test]
+    fn windows_absolute_paths() {
+        assert!(is_windows_absolute(br"C:\Music\a.mp3"));
+        assert!(is_windows_absolute(b"d:/Music/a.mp3"));
+        assert!(is_windows_absolute(br"\\nas\share\a.mp3"));
+        assert!(!is_windows_absolute(b"C:a.mp3"));
+        assert!(!is_windows_absolute(br"Music\a.mp3"));
+        assert!(!is_windows_absolute(b"1:/a.mp3"));
+
+        let base = Path::new("/playlists");
+        assert_eq!(resolve_entry(base, br"C:\Music\a.mp3"), Some(PathBuf::from(r"C:\Music\a.mp3")));
+        assert_eq!(resolve_entry(base, br"\\nas\share\a.mp3"), Some(PathBuf::from(r"\\nas\share\a.mp3")));
+        assert_eq!(resolve_entry(base, b"http://radio/live"), Some(PathBuf::from("http://radio/live")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_backslash_entries_on_unix() {
+        let base = Path::new("/playlists");
+        assert_eq!(resolve_entry(base, br"Album\01.flac"), Some(PathBuf::from("/playlists/Album/01.flac")));
+        assert_eq!(resolve_entry(base, b"/music/a.mp3"), Some(PathBuf::from("/music/a.mp3")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_entries_on_unix() {
+        use std::os::unix::ffi::OsStrExt;
+        let entries = load("gbk", b"#EXTM3U\n/music/\xc7\xe7\xcc\xec.mp3\n");
+        assert_eq!(entries, vec![PathBuf::from(std::ffi::OsStr::from_bytes(b"/music/\xc7\xe7\xcc\xec.mp3"))]);
+        assert_eq!(path_to_bytes(&entries[0]), b"/music/\xc7\xe7\xcc\xec.mp3");
+    }
+
+    #[test]
+    fn save_patches_only_changed_entries() {
+        let dir = env::temp_dir().join(format!("cuper-playlists-{}-save", std::process::id()));
+        for file in ["a.mp3", "sub/b.mp3", "moved/a.mp3", "pasted.mp3"] {
+            fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            fs::write(dir.join(file), b"").unwrap();
+        }
+        let file = dir.join("list.m3u");
+        let original = "#EXTM3U\r\n#EXTINF:1,A\r\na.mp3\r\n#EXTINF:2,B\r\nsub/b.mp3\r\n# 电台\r\nhttp://radio/live\r\n";
+        fs::write(&file, original).unwrap();
+        let mut watch = WatchedPlaylist::new(&file, load_m3u(&file).unwrap());
+        let read = || fs::read_to_string(&file).unwrap();
+
+        // 粘贴加入的歌曲不写入，删除的条目连同 #EXTINF 一起去掉，其他行不变
+        let url = PathBuf::from("http://radio/live");
+        watch.save(&[dir.join("a.mp3"), url.clone(), dir.join("pasted.mp3")], &[]).unwrap();
+        assert_eq!(read(), "#EXTM3U\r\n#EXTINF:1,A\r\na.mp3\r\n# 电台\r\nhttp://radio/live\r\n");
+        assert!(!dir.join("list.m3u.tmp").exists());
+
+        // 移动过的文件仍然写成相对路径
+        let moves = [(dir.join("a.mp3"), dir.join("moved/a.mp3"))];
+        watch.save(&[dir.join("moved/a.mp3"), url.clone()], &moves).unwrap();
+        assert_eq!(read(), "#EXTM3U\r\n#EXTINF:1,A\r\nmoved/a.mp3\r\n# 电台\r\nhttp://radio/live\r\n");
+
+        // 撤销删除后回到曲库的条目追加到末尾
+        watch.save(&[dir.join("moved/a.mp3"), dir.join("sub/b.mp3"), url], &[]).unwrap();
+        let restored = storage::canonicalize(&dir.join("sub/b.mp3")).unwrap();
+        let expected = format!("#EXTM3U\r\n#EXTINF:1,A\r\nmoved/a.mp3\r\n# 电台\r\nhttp://radio/live\r\n{}\r\n", restored.display());
+        assert_eq!(read(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renamed_entries_keep_the_rest_of_the_file() {
+        let dir = env::temp_dir().join(format!("cuper-playlists-{}-rename", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("list.m3u");
+        fs::write(&file, "#EXTM3U\n#EXTINF:1,A\n/old/a.mp3\n/music/b.mp3").unwrap();
+        let changed = patch_m3u(&file, &[], |entry| match entry == Path::new("/old/a.mp3") {
+            true => Some(PathBuf::from("/new/a.mp3")),
+            false => Some(entry.to_path_buf()),
+        })
+        .unwrap();
+        assert!(changed);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "#EXTM3U\n#EXTINF:1,A\n/new/a.mp3\n/music/b.mp3");
+        assert!(!patch_m3u(&file, &[], |entry| Some(entry.to_path_buf())).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn non_utf8_entries_are_skipped() {
+        let entries = load("gbk", b"#EXTM3U\r\nC:\\music\\\xc7\xe7.mp3\r\nC:\\music\\ok.mp3\r\n");
+        assert_eq!(entries, vec![PathBuf::from(r"C:\music\ok.mp3")]);
+    }
+}
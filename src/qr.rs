@@ -0,0 +1,375 @@
+use anyhow::{bail, Result};
+
+/// 二维码（QR Code）编码：字节模式、L 级纠错，版本 1-10，最多 271 字节，足够放下带令牌的网址。
+/// 按 ISO/IEC 18004 生成，选择惩罚分最低的掩码
+pub struct QrCode {
+    pub size: usize,
+    modules: Vec<bool>,
+}
+
+/// 版本 1-10 在 L 级纠错下的 (码字总数, 每块纠错码字数, 块数)
+const VERSIONS: [(usize, usize, usize); 10] = [
+    (26, 7, 1),
+    (44, 10, 1),
+    (70, 15, 1),
+    (100, 20, 1),
+    (134, 26, 1),
+    (172, 18, 2),
+    (196, 20, 2),
+    (242, 24, 2),
+    (292, 30, 2),
+    (346, 18, 4),
+];
+
+/// 各版本校正图形的中心坐标
+const ALIGNMENT: [&[usize]; 10] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+];
+
+/// 格式信息中 L 级纠错的编号
+const ECC_LOW: u32 = 1;
+
+impl QrCode {
+    pub fn encode(data: &[u8]) -> Result<Self> {
+        let Some(version) = (1..=VERSIONS.len()).find(|version| data.len() <= capacity(*version)) else {
+            bail!("内容太长（{} 字节），无法生成二维码", data.len());
+        };
+        let (total, ecc_len, blocks) = VERSIONS[version - 1];
+        let data_len = total - ecc_len * blocks;
+
+        // 模式 0100（字节），字符数，数据，终止符，补齐到整字节后用 0xEC 0x11 填充
+        let mut bits = Bits::default();
+        bits.push(0b0100, 4);
+        bits.push(data.len() as u32, if version < 10 { 8 } else { 16 });
+        for byte in data {
+            bits.push(*byte as u32, 8);
+        }
+        bits.push(0, (data_len * 8 - bits.len()).min(4));
+        bits.push(0, (8 - bits.len() % 8) % 8);
+        let mut codewords = bits.into_bytes();
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if codewords.len() >= data_len {
+                break;
+            }
+            codewords.push(pad);
+        }
+
+        let mut qr = QrCode {
+            size: version * 4 + 17,
+            modules: vec![false; (version * 4 + 17).pow(2)],
+        };
+        let mut function = vec![false; qr.modules.len()];
+        qr.draw_function_patterns(version, &mut function);
+        qr.draw_codewords(&interleave(&codewords, total, ecc_len, blocks), &function);
+
+        // 依次试 8 种掩码，保留惩罚分最低的
+        let mut best = (u32::MAX, 0);
+        for mask in 0..8 {
+            qr.apply_mask(mask, &function);
+            qr.draw_format_bits(mask, &mut function);
+            let penalty = qr.penalty();
+            if penalty < best.0 {
+                best = (penalty, mask);
+            }
+            qr.apply_mask(mask, &function);
+        }
+        qr.apply_mask(best.1, &function);
+        qr.draw_format_bits(best.1, &mut function);
+        Ok(qr)
+    }
+
+    /// 第 y 行第 x 列是否为深色
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, dark: bool, function: &mut [bool]) {
+        let index = y * self.size + x;
+        self.modules[index] = dark;
+        function[index] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize, function: &mut [bool]) {
+        let size = self.size;
+        // 定时图形
+        for i in 0..size {
+            self.set(6, i, i % 2 == 0, function);
+            self.set(i, 6, i % 2 == 0, function);
+        }
+        // 三个角上的位置探测图形和分隔符
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if (0..size as i32).contains(&x) && (0..size as i32).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set(x as usize, y as usize, distance != 2 && distance != 4, function);
+                    }
+                }
+            }
+        }
+        // 校正图形，和位置探测图形重叠的三个位置除外
+        let positions = ALIGNMENT[version - 1];
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let (x, y) = ((cx as i32 + dx) as usize, (cy as i32 + dy) as usize);
+                        self.set(x, y, dx.abs().max(dy.abs()) != 1, function);
+                    }
+                }
+            }
+        }
+        // 先占住格式信息的位置，选好掩码后再写入
+        self.draw_format_bits(0, function);
+        // 版本 7 及以上的版本信息
+        if version >= 7 {
+            let mut remainder = version as u32;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = (version as u32) << 12 | remainder;
+            for i in 0..18 {
+                let dark = bits >> i & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set(a, b, dark, function);
+                self.set(b, a, dark, function);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32, function: &mut [bool]) {
+        let data = ECC_LOW << 3 | mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 != 0;
+        let size = self.size;
+
+        for i in 0..6 {
+            self.set(8, i, bit(i), function);
+        }
+        self.set(8, 7, bit(6), function);
+        self.set(8, 8, bit(7), function);
+        self.set(7, 8, bit(8), function);
+        for i in 9..15 {
+            self.set(14 - i, 8, bit(i), function);
+        }
+        for i in 0..8 {
+            self.set(size - 1 - i, 8, bit(i), function);
+        }
+        for i in 8..15 {
+            self.set(8, size - 15 + i, bit(i), function);
+        }
+        // 固定的深色模块
+        self.set(8, size - 8, true, function);
+    }
+
+    /// 从右下角开始，每两列一组上下来回填入数据，跳过功能图形
+    fn draw_codewords(&mut self, data: &[u8], function: &[bool]) {
+        let size = self.size;
+        let mut index = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { size - 1 - vertical } else { vertical };
+                    if !function[y * size + x] && index < data.len() * 8 {
+                        self.modules[y * size + x] = data[index / 8] >> (7 - index % 8) & 1 != 0;
+                        index += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// 对数据区域应用掩码，再应用一次即可恢复
+    fn apply_mask(&mut self, mask: u32, function: &[bool]) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if invert && !function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    /// 标准中的四项惩罚分：连续同色、2×2 同色块、类似位置探测图形的序列、深浅比例失衡
+    fn penalty(&self) -> u32 {
+        let size = self.size;
+        let mut penalty = 0;
+        let finder_like = [
+            [true, false, true, true, true, false, true, false, false, false, false],
+            [false, false, false, false, true, false, true, true, true, false, true],
+        ];
+        for transpose in [false, true] {
+            let at = |i: usize, j: usize| if transpose { self.get(i, j) } else { self.get(j, i) };
+            for i in 0..size {
+                let mut run = 1;
+                for j in 1..size {
+                    if at(i, j) == at(i, j - 1) {
+                        run += 1;
+                        if run == 5 {
+                            penalty += 3;
+                        } else if run > 5 {
+                            penalty += 1;
+                        }
+                    } else {
+                        run = 1;
+                    }
+                }
+                for j in 0..size.saturating_sub(10) {
+                    if finder_like.iter().any(|pattern| pattern.iter().enumerate().all(|(k, dark)| at(i, j + k) == *dark)) {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.get(x, y);
+                if color == self.get(x + 1, y) && color == self.get(x, y + 1) && color == self.get(x + 1, y + 1) {
+                    penalty += 3;
+                }
+            }
+        }
+        let total = self.modules.len() as u32;
+        let dark = self.modules.iter().filter(|dark| **dark).count() as u32;
+        let k = (dark * 20).abs_diff(total * 10).div_ceil(total).saturating_sub(1);
+        penalty + k * 10
+    }
+}
+
+/// 字节模式下能放下的最多字节数
+fn capacity(version: usize) -> usize {
+    let (total, ecc_len, blocks) = VERSIONS[version - 1];
+    let header_bits = 4 + if version < 10 { 8 } else { 16 };
+    ((total - ecc_len * blocks) * 8 - header_bits) / 8
+}
+
+/// 分块计算纠错码，然后按列交错排列数据码字和纠错码字
+fn interleave(data: &[u8], total: usize, ecc_len: usize, blocks: usize) -> Vec<u8> {
+    let short_blocks = blocks - total % blocks;
+    let short_len = total / blocks;
+    let divisor = rs_divisor(ecc_len);
+    let mut split = Vec::new();
+    let mut offset = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let block = &data[offset..offset + len];
+        offset += len;
+        split.push((block.to_vec(), rs_remainder(block, &divisor)));
+    }
+    let mut result = Vec::with_capacity(total);
+    for i in 0..short_len - ecc_len + 1 {
+        for (block, _) in &split {
+            if let Some(byte) = block.get(i) {
+                result.push(*byte);
+            }
+        }
+    }
+    for i in 0..ecc_len {
+        for (_, ecc) in &split {
+            result.push(ecc[i]);
+        }
+    }
+    result
+}
+
+/// GF(256) 上的乘法，本原多项式 x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+/// 里德-所罗门生成多项式的系数（最高次项为 1，省略）
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (value, coefficient) in result.iter_mut().zip(divisor) {
+            *value ^= gf_multiply(*coefficient, factor);
+        }
+    }
+    result
+}
+
+#[derive(Default)]
+struct Bits {
+    bits: Vec<bool>,
+}
+
+impl Bits {
+    fn push(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            self.bits.push(value >> i & 1 != 0);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bits
+            .chunks(8)
+            .map(|chunk| chunk.iter().fold(0u8, |byte, bit| byte << 1 | u8::from(*bit)))
+            .collect()
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::storage;
+
+const RATINGS_FILE: &str = "ratings.tsv";
+
+/// 最高星级
+pub const MAX_STARS: u8 = 5;
+
+/// 歌曲评分（1 到 5 星），没有记录的歌曲视为未评分
+#[derive(Debug, Default)]
+pub struct Ratings {
+    entries: HashMap<PathBuf, u8>,
+}
+
+impl Ratings {
+    pub fn load() -> Self {
+        let records = storage::read_records(RATINGS_FILE).unwrap_or_default();
+        let entries = records
+            .into_iter()
+            .filter_map(|record| {
+                // 格式: 星级 \t 文件路径
+                let [stars, path] = <[String; 2]>::try_from(record).ok()?;
+                let stars: u8 = stars.parse().ok().filter(|stars| (1..=MAX_STARS).contains(stars))?;
+                Some((storage::path_from_field(&path), stars))
+            })
+            .collect();
+        Ratings { entries }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut records: Vec<Vec<String>> = self
+            .entries
+            .iter()
+            .map(|(path, stars)| vec![stars.to_string(), storage::path_to_field(path)])
+            .collect();
+        records.sort_by(|a, b| a[1].cmp(&b[1]));
+        storage::write_records(RATINGS_FILE, &records)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<u8> {
+        self.entries.get(path).copied()
+    }
+
+    /// 设置评分，0 表示清除
+    pub fn set(&mut self, path: &Path, stars: u8) {
+        match stars.min(MAX_STARS) {
+            0 => self.entries.remove(path),
+            stars => self.entries.insert(path.to_path_buf(), stars),
+        };
+    }
+
+    /// 还没有评分时使用文件标签中的评分，返回是否有变化
+    pub fn import(&mut self, path: &Path, stars: u8) -> bool {
+        if stars == 0 || self.entries.contains_key(path) {
+            return false;
+        }
+        self.entries.insert(path.to_path_buf(), stars.min(MAX_STARS));
+        true
+    }
+
+    /// 文件被移动后把评分转到新路径下
+    pub fn rename(&mut self, old: &Path, new: &Path) {
+        if let Some(stars) = self.entries.remove(old) {
+            self.entries.insert(new.to_path_buf(), stars);
+        }
+    }
+}
+
+/// ID3v2 POPM 帧的评分字节 (0-255) 转换为星级，按 Windows Media Player 的约定：1/64/128/196/255
+pub fn stars_from_popm(value: u8) -> u8 {
+    match value {
+        0 => 0,
+        1..=31 => 1,
+        32..=95 => 2,
+        96..=159 => 3,
+        160..=223 => 4,
+        _ => 5,
+    }
+}
+
+pub fn stars_to_popm(stars: u8) -> u8 {
+    match stars {
+        0 => 0,
+        1 => 1,
+        2 => 64,
+        3 => 128,
+        4 => 196,
+        _ => 255,
+    }
+}
+
+/// Vorbis comment 的 RATING 通常是 0-100，也有播放器直接写 1-5 星
+pub fn stars_from_percent(value: u32) -> u8 {
+    match value {
+        0..=5 => value as u8,
+        _ => ((value.min(100) + 10) / 20) as u8,
+    }
+}
+
+pub fn stars_to_percent(stars: u8) -> u32 {
+    stars.min(MAX_STARS) as u32 * 20
+}
+
+/// 界面中显示的星级，如 `★★★☆☆`
+pub fn stars_label(stars: u8, filled: &str, empty: &str) -> String {
+    let stars = stars.min(MAX_STARS) as usize;
+    format!("{}{}", filled.repeat(stars), empty.repeat(MAX_STARS as usize - stars))
+}
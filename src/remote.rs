@@ -0,0 +1,406 @@
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::json::{self, Value};
+use crate::storage;
+
+/// 可以从其他程序发给正在运行的播放器的命令。终端没有焦点时收不到按键，
+/// 在桌面环境中把快捷键绑定到 `music_tui ctl <命令>` 就能在其他程序中控制播放。
+/// 播放器不注册全局热键，组合键只能在桌面环境中配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+    VolumeUp,
+    VolumeDown,
+    Shuffle,
+    Seek(Seek),
+    /// 退出播放器，主要用来停止脱离终端运行的播放器。只能通过 ctl 发送
+    Quit,
+    /// 脱离终端运行的播放器保存会话后退出，由新打开的界面接着播放
+    Attach,
+}
+
+/// `seek` 命令的目标：`+10`、`-10` 相对当前位置，`90` 或 `1:30` 是绝对位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seek {
+    Forward(Duration),
+    Backward(Duration),
+    To(Duration),
+}
+
+pub const COMMANDS: [(&str, Command); 7] = [
+    ("play-pause", Command::PlayPause),
+    ("next", Command::Next),
+    ("previous", Command::Previous),
+    ("stop", Command::Stop),
+    ("volume-up", Command::VolumeUp),
+    ("volume-down", Command::VolumeDown),
+    ("shuffle", Command::Shuffle),
+];
+
+/// 简写，方便在脚本和快捷键中使用
+const ALIASES: [(&str, Command); 2] = [("toggle", Command::PlayPause), ("prev", Command::Previous)];
+
+/// 接管脱离终端的播放器时最多等它退出这么久
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Command {
+    /// 解析一行命令，如 `next`、`toggle`、`seek +10`
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?;
+        let argument = words.next();
+        if words.next().is_some() {
+            return None;
+        }
+        match (name, argument) {
+            ("seek", Some(argument)) => Seek::parse(argument).map(Command::Seek),
+            (_, Some(_)) => None,
+            (name, None) => COMMANDS
+                .iter()
+                .chain(ALIASES.iter())
+                .find(|(command, _)| *command == name)
+                .map(|(_, command)| *command),
+        }
+    }
+}
+
+impl Seek {
+    fn parse(value: &str) -> Option<Self> {
+        if let Some(seconds) = value.strip_prefix('+') {
+            return parse_position(seconds).map(Seek::Forward);
+        }
+        if let Some(seconds) = value.strip_prefix('-') {
+            return parse_position(seconds).map(Seek::Backward);
+        }
+        parse_position(value).map(Seek::To)
+    }
+
+    /// 当前位置为 position 时要跳到的位置
+    pub fn target(self, position: Duration) -> Duration {
+        match self {
+            Seek::Forward(offset) => position + offset,
+            Seek::Backward(offset) => position.saturating_sub(offset),
+            Seek::To(target) => target,
+        }
+    }
+}
+
+/// 秒数或 `分:秒`
+fn parse_position(value: &str) -> Option<Duration> {
+    let seconds = match value.split_once(':') {
+        Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?,
+        None => value.parse().ok()?,
+    };
+    Some(Duration::from_secs(seconds))
+}
+
+fn command_names() -> String {
+    let mut names: Vec<&str> = COMMANDS.iter().map(|(name, _)| *name).collect();
+    names.extend([
+        "quit",
+        "toggle",
+        "prev",
+        "seek <+秒|-秒|分:秒>",
+        "status [--json] [--follow] [--format=text|json|waybar]",
+    ]);
+    names.join(" | ")
+}
+
+/// `status` 命令的回复，由播放器每次轮询时更新
+#[derive(Debug, Default)]
+struct Status {
+    text: String,
+    json: String,
+}
+
+/// 在控制端口上运行的监听线程：命令交给播放器执行，`status` 直接用最近的状态回复
+pub struct Listener {
+    pub commands: Receiver<Command>,
+    status: Arc<Mutex<Status>>,
+    /// 脱离终端运行，接受 `attach`
+    detached: Arc<AtomicBool>,
+}
+
+impl Listener {
+    /// 更新 `status` 的回复：text 是状态行，json 和 MQTT 发布的内容相同
+    pub fn set_status(&self, text: String, json: String) {
+        if let Ok(mut status) = self.status.lock() {
+            *status = Status { text, json };
+        }
+    }
+
+    /// 脱离终端运行的播放器调用，之后新打开的界面可以用 `attach` 接管播放
+    pub fn set_detached(&self) {
+        self.detached.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 控制令牌保存的位置。本机的其他用户也能连上 127.0.0.1 的端口，
+/// 每行命令前面要带上只有当前用户能读取的令牌
+fn token_file() -> PathBuf {
+    storage::data_dir().join("remote_token")
+}
+
+/// 每次启动生成新的令牌，写入只有当前用户可以读写的文件
+fn write_token() -> io::Result<String> {
+    let token = format!("{:032x}", rand::thread_rng().gen::<u128>());
+    let path = token_file();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // 删掉旧文件重新创建，确保权限是新设置的
+    let _ = fs::remove_file(&path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+fn read_token() -> Result<String> {
+    let path = token_file();
+    let token = fs::read_to_string(&path).map_err(|_| anyhow!("没有正在运行的播放器（找不到 {}）", path.display()))?;
+    Ok(token.trim().to_string())
+}
+
+/// 在本机端口上监听控制命令，每个连接发送一行 `令牌 命令`，回复一行
+pub fn listen(port: u16) -> Result<Listener> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|err| anyhow!("无法监听控制端口 {}: {}", port, err))?;
+    // 端口被另一个播放器占用时上面已经失败，不会覆盖它的令牌
+    let token = write_token().map_err(|err| anyhow!("无法写入控制令牌: {}", err))?;
+    let (tx, commands) = mpsc::channel();
+    let status = Arc::new(Mutex::new(Status::default()));
+    let shared = status.clone();
+    let detached = Arc::new(AtomicBool::new(false));
+    let attachable = detached.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            if reader.read_line(&mut line).is_err() {
+                continue;
+            }
+            let Some(line) = line.trim().strip_prefix(token.as_str()).and_then(|rest| rest.strip_prefix(' ')) else {
+                let _ = writeln!(&stream, "控制令牌不正确");
+                continue;
+            };
+            let command = match line {
+                "quit" => Some(Command::Quit),
+                "attach" if attachable.load(Ordering::Relaxed) => Some(Command::Attach),
+                line => Command::parse(line),
+            };
+            let reply = match (line, command) {
+                ("status", _) => shared.lock().map(|status| status.text.clone()).unwrap_or_default(),
+                ("status --json", _) => shared.lock().map(|status| status.json.clone()).unwrap_or_default(),
+                ("attach", None) => "播放器没有脱离终端".to_string(),
+                (_, Some(command)) => {
+                    if tx.send(command).is_err() {
+                        break;
+                    }
+                    "ok".to_string()
+                }
+                (_, None) => format!("未知命令: {}", line),
+            };
+            let _ = writeln!(&stream, "{}", reply);
+        }
+    });
+    Ok(Listener { commands, status, detached })
+}
+
+/// `status` 的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusFormat {
+    /// 和状态栏相同的一行文字（polybar 等直接显示文字的状态栏）
+    Text,
+    /// 和 MQTT 状态消息相同的 JSON
+    Json,
+    /// waybar 自定义模块的 JSON（text、tooltip、class、alt、percentage）
+    Waybar,
+}
+
+/// `status [--json] [--follow] [--format=text|json|waybar]`，不是 status 命令时返回 None
+fn parse_status(words: &[&str]) -> Option<Result<(StatusFormat, bool)>> {
+    let (first, options) = words.split_first()?;
+    if *first != "status" {
+        return None;
+    }
+    let mut format = StatusFormat::Text;
+    let mut follow = false;
+    for option in options {
+        format = match *option {
+            "--follow" => {
+                follow = true;
+                continue;
+            }
+            "--json" | "--format=json" => StatusFormat::Json,
+            "--format=text" => StatusFormat::Text,
+            "--format=waybar" => StatusFormat::Waybar,
+            other => {
+                return Some(Err(anyhow!(
+                    "status 不支持的参数: {}（可用 --json、--follow、--format=text|json|waybar）",
+                    other
+                )))
+            }
+        };
+    }
+    Some(Ok((format, follow)))
+}
+
+/// `music_tui ctl <命令>`：把命令发给正在运行的播放器，`status` 打印播放状态
+pub fn run(args: &[OsString]) -> Result<()> {
+    let usage = || anyhow!("用法: music_tui ctl <{}>", command_names());
+    let words: Vec<&str> = args.iter().map(|arg| arg.to_str()).collect::<Option<_>>().ok_or_else(usage)?;
+    let status = parse_status(&words).transpose()?;
+    let line = words.join(" ");
+    if status.is_none() && line != "quit" && Command::parse(&line).is_none() {
+        return Err(usage());
+    }
+
+    let port = Config::load().remote_port;
+    if port == 0 {
+        bail!("远程控制已关闭（配置文件中 remote_port = 0）");
+    }
+    match status {
+        Some((format, true)) => follow_status(port, format),
+        Some((format, false)) => {
+            println!("{}", format_status(&query_status(port, format)?, format));
+            Ok(())
+        }
+        None => match send(port, &line)?.as_str() {
+            "ok" => Ok(()),
+            reply => bail!("{}", reply),
+        },
+    }
+}
+
+/// 打开界面之前接管脱离终端运行的播放器：让它保存会话后退出，等控制端口关闭。
+/// 接管成功时返回 true，没有脱离终端的播放器时返回 false
+pub fn attach(port: u16) -> bool {
+    if port == 0 || send(port, "attach").ok().as_deref() != Some("ok") {
+        return false;
+    }
+    let deadline = Instant::now() + ATTACH_TIMEOUT;
+    while Instant::now() < deadline {
+        // 端口关闭说明播放器已经保存会话并退出
+        if TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+/// 带上令牌发送一行命令，返回一行回复
+fn send(port: u16, line: &str) -> Result<String> {
+    let token = read_token()?;
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .map_err(|_| anyhow!("没有正在运行的播放器（端口 {}）", port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    writeln!(stream, "{} {}", token, line)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
+
+/// 文字格式直接取播放器的状态行，其他格式取 JSON 再转换
+fn query_status(port: u16, format: StatusFormat) -> Result<String> {
+    match format {
+        StatusFormat::Text => send(port, "status"),
+        StatusFormat::Json | StatusFormat::Waybar => send(port, "status --json"),
+    }
+}
+
+fn format_status(reply: &str, format: StatusFormat) -> String {
+    match format {
+        StatusFormat::Text | StatusFormat::Json => reply.to_string(),
+        StatusFormat::Waybar => waybar(json::parse(reply).ok().as_ref()),
+    }
+}
+
+/// 每秒查询一次，状态变化时输出一行，给状态栏的常驻脚本使用。
+/// 播放器没有运行时输出空状态并继续等待，而不是退出
+fn follow_status(port: u16, format: StatusFormat) -> Result<()> {
+    let mut last = None;
+    let mut stdout = io::stdout();
+    loop {
+        let line = match query_status(port, format) {
+            Ok(reply) => format_status(&reply, format),
+            Err(_) => match format {
+                StatusFormat::Text => String::new(),
+                StatusFormat::Json => "{\"state\":\"stopped\"}".to_string(),
+                StatusFormat::Waybar => waybar(None),
+            },
+        };
+        if last.as_ref() != Some(&line) {
+            // 状态栏关闭管道后退出
+            if writeln!(stdout, "{}", line).and_then(|_| stdout.flush()).is_err() {
+                return Ok(());
+            }
+            last = Some(line);
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// waybar 自定义模块（`return-type: json`）的一行
+fn waybar(state: Option<&Value>) -> String {
+    let field = |key: &str| state.and_then(|state| state.get(key)).and_then(|value| value.as_str()).unwrap_or_default();
+    let number = |key: &str| state.and_then(|state| state.get(key)).and_then(|value| value.as_f64());
+    let status = match field("state") {
+        "" => "stopped",
+        status => status,
+    };
+    let (title, artist, album) = (field("title"), field("artist"), field("album"));
+    let text = match (status, artist) {
+        ("stopped", _) => String::new(),
+        (_, "") => title.to_string(),
+        (_, artist) => format!("{} - {}", artist, title),
+    };
+    let mut tooltip: Vec<String> = [title, artist, album]
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect();
+    let (position, duration) = (number("position"), number("duration"));
+    if let Some(position) = position {
+        let time = |seconds: f64| format!("{}:{:02}", seconds as u64 / 60, seconds as u64 % 60);
+        tooltip.push(match duration {
+            Some(duration) => format!("{} / {}", time(position), time(duration)),
+            None => time(position),
+        });
+    }
+    let percentage = match (position, duration) {
+        (Some(position), Some(duration)) if duration > 0.0 => (position / duration * 100.0).round() as u64,
+        _ => 0,
+    };
+    format!(
+        "{{\"text\":{},\"tooltip\":{},\"class\":{},\"alt\":{},\"percentage\":{}}}",
+        json::string(&text),
+        json::string(&tooltip.join("\n")),
+        json::string(status),
+        json::string(status),
+        percentage
+    )
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
+
+use crate::app::{is_audio_file, Song};
+use crate::http::encode;
+use crate::http_stream::{self, HttpStream};
+use crate::metadata_cache::MetadataCache;
+use crate::storage;
+
+/// 远程目录最多向下查找的层数
+const MAX_DEPTH: usize = 8;
+
+/// 配置中的远程曲库：`sftp://用户@主机/目录` 或 WebDAV 的 `https://主机/目录`。
+/// 列目录、下载都通过 curl 完成，SFTP 使用 ~/.ssh 中的密钥，WebDAV 的密码写在 ~/.netrc 中
+pub fn source_of<'a>(path: &Path, sources: &'a [String]) -> Option<&'a str> {
+    let path = path.to_str()?;
+    sources
+        .iter()
+        .map(|source| source.trim_end_matches('/'))
+        .find(|source| path.strip_prefix(source).is_some_and(|rest| rest.starts_with('/')))
+}
+
+/// 列出所有远程曲库中的音频文件，返回文件地址和无法访问的曲库的错误信息
+pub fn list_all(sources: &[String]) -> (Vec<PathBuf>, Vec<String>) {
+    let mut paths = Vec::new();
+    let mut errors = Vec::new();
+    for source in sources {
+        let dir = format!("{}/", source.trim_end_matches('/'));
+        match list_recursive(&dir, MAX_DEPTH, &mut paths) {
+            Ok(()) => {}
+            Err(err) => errors.push(format!("{}: {}", source, err)),
+        }
+    }
+    paths.sort();
+    (paths, errors)
+}
+
+fn list_recursive(dir: &str, depth: usize, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for (url, is_dir) in list_dir(dir)? {
+        if is_dir {
+            if depth > 0 {
+                // 子目录出错时跳过，不影响其他目录
+                let _ = list_recursive(&url, depth - 1, paths);
+            }
+        } else if is_audio_file(Path::new(&http_stream::url_file_name(&url))) {
+            paths.push(PathBuf::from(url));
+        }
+    }
+    Ok(())
+}
+
+/// 列出一个目录的直接子项: (地址, 是否是目录)
+fn list_dir(dir: &str) -> Result<Vec<(String, bool)>> {
+    let sftp = dir.starts_with("sftp://");
+    let mut command = Command::new("curl");
+    command.args(["-sS", "--netrc-optional", "--max-time", "60"]);
+    if !sftp {
+        command.args(["-X", "PROPFIND", "-H", "Depth: 1"]);
+    }
+    let output = command.arg(dir).output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let entries = if sftp {
+        text.lines()
+            .filter_map(parse_ls_line)
+            .filter(|(name, _)| name != "." && name != "..")
+            .map(|(name, is_dir)| (format!("{}{}", dir, encode(&name)), is_dir))
+            .map(|(url, is_dir)| if is_dir { (format!("{}/", url), true) } else { (url, false) })
+            .collect()
+    } else {
+        parse_propfind(&text, dir)
+    };
+    Ok(entries)
+}
+
+/// 解析 curl 列 SFTP 目录时输出的 `ls -l` 格式: 权限、链接数、用户、组、大小、日期（三列）、文件名
+fn parse_ls_line(line: &str) -> Option<(String, bool)> {
+    let mut rest = line.trim_end();
+    let kind = rest.chars().next()?;
+    for _ in 0..8 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+    let name = rest.trim_start();
+    (!name.is_empty()).then(|| (name.to_string(), kind == 'd'))
+}
+
+/// 解析 WebDAV PROPFIND 的响应，只取每个 response 的 href 和是否是集合（目录）
+fn parse_propfind(text: &str, dir: &str) -> Vec<(String, bool)> {
+    // 只保留协议和主机部分，href 通常是绝对路径
+    let origin_end = dir.find("://").map(|start| start + 3).and_then(|start| {
+        dir[start..].find('/').map(|slash| start + slash)
+    });
+    let origin = &dir[..origin_end.unwrap_or(dir.len())];
+    let dir_path = &dir[origin.len()..];
+
+    text.split("response>")
+        .filter_map(|block| {
+            let start = block.find("href>")? + "href>".len();
+            let end = start + block[start..].find('<')?;
+            let href = block[start..end].trim();
+            let href = href.strip_prefix(origin).unwrap_or(href);
+            let is_dir = block.contains("collection");
+            Some((href.to_string(), is_dir))
+        })
+        .filter(|(href, _)| href.trim_end_matches('/') != dir_path.trim_end_matches('/'))
+        .map(|(href, is_dir)| (format!("{}{}", origin, href), is_dir))
+        .collect()
+}
+
+/// 远程歌曲在本地缓存中的副本
+pub fn cached_copy(url: &Path) -> PathBuf {
+    let url = url.to_string_lossy();
+    let name = storage::sanitize_file_name(url.split_once("://").map_or(&*url, |(_, rest)| rest));
+    // 文件名过长时保留末尾（包括扩展名）
+    let name: String = name.chars().rev().take(200).collect::<Vec<_>>().into_iter().rev().collect();
+    storage::cache_dir().join("remote").join(name)
+}
+
+/// 用本地缓存副本的标签创建歌曲，还没播放过（没有缓存）的歌曲只有文件名
+pub fn songs(paths: Vec<PathBuf>, cache: &mut MetadataCache, workers: usize) -> Vec<Song> {
+    let copies: Vec<PathBuf> = paths.iter().map(|path| cached_copy(path)).collect();
+    let probes = cache.probe_all(&copies, workers, &AtomicBool::new(false), || {});
+    paths
+        .into_iter()
+        .zip(probes)
+        .map(|(path, probe)| Song::from_probe(path, probe))
+        .collect()
+}
+
+/// 播放时已经完整下载的远程歌曲在后台写入缓存，并把缓存控制在 limit_mb 以内（最近播放的优先保留）
+pub fn store(url: &Path, stream: Arc<HttpStream>, limit_mb: u64) {
+    let path = cached_copy(url);
+    thread::spawn(move || {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let _ = fs::create_dir_all(dir);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".part");
+        let tmp = PathBuf::from(tmp);
+        match stream.save_complete(&tmp) {
+            Ok(true) if fs::rename(&tmp, &path).is_ok() => prune(dir, limit_mb * 1024 * 1024),
+            Ok(false) => {}
+            _ => {
+                let _ = fs::remove_file(&tmp);
+            }
+        }
+    });
+}
+
+/// 打开缓存副本时更新修改时间，作为最近播放的时间
+pub fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+fn prune(dir: &Path, limit: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+
+    let mut total = 0;
+    for (_, size, path) in files {
+        total += size;
+        if total > limit {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::{anyhow, bail, Result};
+use rodio::Source;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::f64::consts::PI;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::app::{is_audio_file, scan_songs};
+use crate::metadata;
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+use crate::tag_writer;
+
+const LOUDNESS_FILE: &str = "loudness.tsv";
+
+/// ReplayGain 2.0 的参考响度 (LUFS)
+pub const REFERENCE_LUFS: f64 = -18.0;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+/// 真峰值测量的过采样倍数和插值滤波器的长度
+const OVERSAMPLE: usize = 4;
+const TAPS: usize = 16;
+
+/// 一首歌的响度分析结果
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// 每个 400ms 门限块的均方能量（K 加权后）
+    blocks: Vec<f64>,
+    pub peak: f32,
+    /// 过采样后的峰值，采样点之间的波形也算在内
+    pub true_peak: f32,
+}
+
+impl Analysis {
+    pub fn loudness(&self) -> Option<f64> {
+        gated_loudness(&self.blocks)
+    }
+
+    /// 保存下来给界面显示的结果，静音文件返回 None
+    pub fn summary(&self) -> Option<Loudness> {
+        Some(Loudness {
+            lufs: self.loudness()?,
+            true_peak: self.true_peak,
+        })
+    }
+}
+
+/// 歌曲属性窗口中显示的响度和真峰值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// 积分响度 (LUFS)
+    pub lufs: f64,
+    pub true_peak: f32,
+}
+
+impl Loudness {
+    /// 真峰值 (dBTP)
+    pub fn true_peak_db(&self) -> f64 {
+        20.0 * (self.true_peak.max(f32::MIN_POSITIVE) as f64).log10()
+    }
+
+    /// 加上 gain_db 的增益后超出 0 dBTP 的分贝数，不会削波时返回 None
+    pub fn clip_db(&self, gain_db: f64) -> Option<f64> {
+        let over = self.true_peak_db() + gain_db;
+        (over > 0.0).then_some(over)
+    }
+}
+
+pub fn load() -> HashMap<PathBuf, Loudness> {
+    let records = storage::read_records(LOUDNESS_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 响度 LUFS \t 真峰值 \t 文件路径
+            let [lufs, true_peak, path] = <[String; 3]>::try_from(record).ok()?;
+            let loudness = Loudness {
+                lufs: lufs.parse().ok()?,
+                true_peak: true_peak.parse().ok()?,
+            };
+            Some((storage::path_from_field(&path), loudness))
+        })
+        .collect()
+}
+
+pub fn save(loudness: &HashMap<PathBuf, Loudness>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = loudness
+        .iter()
+        .map(|(path, loudness)| {
+            vec![
+                format!("{:.2}", loudness.lufs),
+                format!("{:.6}", loudness.true_peak),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[2].cmp(&b[2]));
+    storage::write_records(LOUDNESS_FILE, &records)
+}
+
+/// ReplayGain 增益: 把响度调整到参考值需要的 dB
+pub fn gain_for(loudness: f64) -> f64 {
+    REFERENCE_LUFS - loudness
+}
+
+/// 单曲增益和峰值标签，静音文件返回 None
+pub fn track_tags(analysis: &Analysis) -> Option<Vec<(&'static str, String)>> {
+    let loudness = analysis.loudness()?;
+    Some(vec![
+        ("REPLAYGAIN_TRACK_GAIN", format!("{:.2} dB", gain_for(loudness))),
+        ("REPLAYGAIN_TRACK_PEAK", format!("{:.6}", analysis.peak)),
+    ])
+}
+
+/// `music_tui replaygain [--album] [--dry-run] [文件或目录...]`：
+/// 计算 ReplayGain 2.0 增益并写回文件标签
+pub fn run(args: &[OsString]) -> Result<()> {
+    let album_mode = args.iter().any(|arg| arg == "--album");
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let targets: Vec<&OsString> = args.iter().filter(|arg| !arg.to_string_lossy().starts_with("--")).collect();
+
+    let mut paths = Vec::new();
+    if targets.is_empty() {
+        paths.extend(scan_songs(Path::new("assets"))?.into_iter().map(|song| song.path));
+    }
+    for target in targets {
+        let path = PathBuf::from(target);
+        if path.is_dir() {
+            paths.extend(scan_songs(&path)?.into_iter().map(|song| song.path));
+        } else if is_audio_file(&path) {
+            paths.push(path);
+        }
+    }
+
+    // 专辑模式下按 (专辑艺术家, 专辑) 分组，没有专辑标签的歌曲只计算单曲增益
+    let mut groups: BTreeMap<Option<(String, String)>, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        let key = if album_mode { album_key(&path) } else { None };
+        groups.entry(key).or_default().push(path);
+    }
+
+    let total: usize = groups.values().map(Vec::len).sum();
+    let mut done = 0;
+    let mut failed = 0;
+    for (album, paths) in &groups {
+        if let Some((artist, title)) = album {
+            println!("专辑: {} - {}", artist, title);
+        }
+
+        let mut analyses = Vec::new();
+        for path in paths {
+            done += 1;
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            match analyze(path, &AtomicBool::new(false)) {
+                Ok(analysis) => {
+                    match analysis.loudness() {
+                        Some(loudness) => println!(
+                            "[{}/{}] {:+6.2} dB  峰值 {:.6}  真峰值 {:+.1} dBTP  {}",
+                            done,
+                            total,
+                            gain_for(loudness),
+                            analysis.peak,
+                            20.0 * analysis.true_peak.max(f32::MIN_POSITIVE).log10(),
+                            name
+                        ),
+                        None => println!("[{}/{}]  静音         {}", done, total, name),
+                    }
+                    analyses.push((path, analysis));
+                }
+                Err(err) => {
+                    failed += 1;
+                    println!("[{}/{}] 分析失败: {}: {}", done, total, name, err);
+                }
+            }
+        }
+
+        let album_tags = album.as_ref().and_then(|_| {
+            let blocks: Vec<f64> = analyses.iter().flat_map(|(_, a)| a.blocks.iter().copied()).collect();
+            let peak = analyses.iter().map(|(_, a)| a.peak).fold(0.0, f32::max);
+            let gain = gain_for(gated_loudness(&blocks)?);
+            println!("    专辑增益 {:+.2} dB  峰值 {:.6}", gain, peak);
+            Some([
+                ("REPLAYGAIN_ALBUM_GAIN", format!("{:.2} dB", gain)),
+                ("REPLAYGAIN_ALBUM_PEAK", format!("{:.6}", peak)),
+            ])
+        });
+
+        if dry_run {
+            continue;
+        }
+        for (path, analysis) in &analyses {
+            let Some(mut tags) = track_tags(analysis) else {
+                continue;
+            };
+            if let Some(album_tags) = &album_tags {
+                tags.extend(album_tags.iter().cloned());
+            }
+            if let Err(err) = tag_writer::write_text_tags(path, &tags) {
+                failed += 1;
+                println!("    写入标签失败: {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    println!();
+    println!("处理 {} 个文件，{} 个失败{}", total, failed, if dry_run { "（未写入标签）" } else { "" });
+    Ok(())
+}
+
+fn album_key(path: &Path) -> Option<(String, String)> {
+    let tags = metadata::probe(path).tags;
+    let album = tags.album?;
+    let artist = tags.album_artist.or(tags.artist).unwrap_or_default();
+    Some((artist, album))
+}
+
+/// 二阶 IIR 滤波器（直接 II 型）
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    b: [f64; 3],
+    a: [f64; 3],
+}
+
+impl Filter {
+    /// 二阶 Butterworth 低通
+    pub fn low_pass(frequency: f64, sample_rate: f64) -> Self {
+        let (cos, alpha) = Self::angle(frequency, sample_rate);
+        let a0 = 1.0 + alpha;
+        let b = (1.0 - cos) / a0;
+        Filter {
+            b: [b / 2.0, b, b / 2.0],
+            a: [1.0, -2.0 * cos / a0, (1.0 - alpha) / a0],
+        }
+    }
+
+    /// 二阶 Butterworth 高通
+    pub fn high_pass(frequency: f64, sample_rate: f64) -> Self {
+        let (cos, alpha) = Self::angle(frequency, sample_rate);
+        let a0 = 1.0 + alpha;
+        let b = (1.0 + cos) / a0;
+        Filter {
+            b: [b / 2.0, -b, b / 2.0],
+            a: [1.0, -2.0 * cos / a0, (1.0 - alpha) / a0],
+        }
+    }
+
+    fn angle(frequency: f64, sample_rate: f64) -> (f64, f64) {
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        (w0.cos(), w0.sin() / 2f64.sqrt())
+    }
+
+    pub fn process(&self, state: &mut [f64; 2], input: f64) -> f64 {
+        let w = input - self.a[1] * state[0] - self.a[2] * state[1];
+        let output = self.b[0] * w + self.b[1] * state[0] + self.b[2] * state[1];
+        state[1] = state[0];
+        state[0] = w;
+        output
+    }
+}
+
+/// ITU-R BS.1770 的 K 加权滤波器（高架 + 高通），按采样率计算系数
+pub fn k_weighting(sample_rate: f64) -> [Filter; 2] {
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Filter {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Filter {
+        b: [1.0, -2.0, 1.0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+    [shelf, high_pass]
+}
+
+/// 按 BS.1770 的做法把每个声道 4 倍过采样后取峰值，
+/// 找出采样点本身没超过满幅、重建出的波形却超过的地方
+struct TruePeakMeter {
+    /// 每个插值相位的加窗 sinc 系数
+    phases: [[f32; TAPS]; OVERSAMPLE - 1],
+    history: Vec<VecDeque<f32>>,
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    fn new(channels: usize) -> Self {
+        let mut phases = [[0.0; TAPS]; OVERSAMPLE - 1];
+        for (phase, coefficients) in phases.iter_mut().enumerate() {
+            // 插值点位于窗口中间两个采样点之间
+            let position = (TAPS / 2 - 1) as f64 + (phase + 1) as f64 / OVERSAMPLE as f64;
+            for (tap, coefficient) in coefficients.iter_mut().enumerate() {
+                let x = position - tap as f64;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.5 + 0.5 * (PI * x / (TAPS / 2) as f64).cos();
+                *coefficient = (sinc * window) as f32;
+            }
+        }
+        TruePeakMeter {
+            phases,
+            history: vec![VecDeque::from(vec![0.0; TAPS]); channels],
+            peak: 0.0,
+        }
+    }
+
+    fn push(&mut self, channel: usize, sample: f32) {
+        self.peak = self.peak.max(sample.abs());
+        let history = &mut self.history[channel];
+        history.pop_front();
+        history.push_back(sample);
+        for coefficients in &self.phases {
+            let value: f32 = history.iter().zip(coefficients).map(|(sample, c)| sample * c).sum();
+            self.peak = self.peak.max(value.abs());
+        }
+    }
+}
+
+/// 解码整首歌并计算门限块能量、采样峰值和真峰值
+pub fn analyze(path: &Path, cancel: &AtomicBool) -> Result<Analysis> {
+    let file = fs::File::open(path)?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, Duration::ZERO)
+        .map_err(|err| anyhow!("无法解码: {}", err))?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate().max(1);
+
+    let filters = k_weighting(sample_rate as f64);
+    let mut states = vec![[[0.0f64; 2]; 2]; channels];
+
+    // 以 100ms 为一段累计能量，4 段组成一个 400ms 的块，块间重叠 75%
+    let segment_frames = (sample_rate / 10).max(1) as usize;
+    let mut segments: Vec<f64> = Vec::new();
+    let mut energy = 0.0;
+    let mut frames = 0;
+    let mut channel = 0;
+    let mut peak = 0.0f32;
+    let mut true_peak = TruePeakMeter::new(channels);
+
+    for sample in decoder {
+        peak = peak.max(sample.abs());
+        true_peak.push(channel, sample);
+        let state = &mut states[channel];
+        let shelved = filters[0].process(&mut state[0], sample as f64);
+        let weighted = filters[1].process(&mut state[1], shelved);
+        energy += weighted * weighted;
+
+        channel += 1;
+        if channel == channels {
+            channel = 0;
+            frames += 1;
+            if frames == segment_frames {
+                segments.push(energy / segment_frames as f64);
+                energy = 0.0;
+                frames = 0;
+                if cancel.load(Ordering::Relaxed) {
+                    bail!("已取消");
+                }
+            }
+        }
+    }
+
+    let blocks = segments.windows(4).map(|window| window.iter().sum::<f64>() / 4.0).collect();
+    Ok(Analysis {
+        blocks,
+        peak,
+        true_peak: true_peak.peak,
+    })
+}
+
+/// 均方能量对应的响度 (LUFS)
+pub fn block_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// 两级门限后的积分响度 (LUFS)，全部低于绝对门限时返回 None
+fn gated_loudness(blocks: &[f64]) -> Option<f64> {
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+    let above_absolute: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|energy| *energy > 0.0 && block_loudness(*energy) > ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return None;
+    }
+
+    let threshold = block_loudness(mean(&above_absolute)) + RELATIVE_GATE;
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|energy| block_loudness(*energy) > threshold)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+    Some(block_loudness(mean(&gated)))
+}
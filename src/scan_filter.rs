@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::app::Song;
+use crate::config::Config;
+
+/// 扫描曲库时的排除规则：glob 忽略模式和最短时长
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    ignore: Vec<String>,
+    min_duration: Duration,
+}
+
+impl ScanFilter {
+    pub fn from_config(config: &Config) -> Self {
+        ScanFilter {
+            ignore: config.scan_ignore.clone(),
+            min_duration: Duration::from_secs(config.min_duration_secs),
+        }
+    }
+
+    /// 不含 `/` 的模式只匹配文件名，其余模式匹配完整路径
+    pub fn allows_path(&self, path: &Path) -> bool {
+        let full = path.to_string_lossy().replace('\\', "/");
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        !self.ignore.iter().any(|pattern| {
+            if pattern.contains('/') {
+                glob_match(pattern, &full)
+            } else {
+                glob_match(pattern, &name)
+            }
+        })
+    }
+
+    /// 时长未知的文件保留，避免因为探测失败而丢歌
+    pub fn allows_song(&self, song: &Song) -> bool {
+        song.duration.is_none_or(|duration| duration >= self.min_duration)
+    }
+}
+
+/// 简单的 glob 匹配：`*` 不跨越目录，`**` 匹配任意层目录，`?` 匹配单个字符
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // "**/" 也可以匹配零层目录
+            if rest.first() == Some(&'/') && matches(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| matches(rest, &text[i..]))
+        }
+        Some('*') => {
+            let limit = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=limit).any(|i| matches(&pattern[1..], &text[i..]))
+        }
+        Some('?') => text.first().is_some_and(|c| *c != '/') && matches(&pattern[1..], &text[1..]),
+        Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::scan_paths;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn ignore_patterns_apply_to_nested_folders() {
+        let dir = std::env::temp_dir().join(format!("cuper-scan-{}", std::process::id()));
+        for file in ["a.mp3", "album/b.flac", "album/ringtones/c.mp3", "ringtones/d.mp3", "album/e.spam.mp3", "notes.txt"] {
+            fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            fs::write(dir.join(file), b"").unwrap();
+        }
+        let filter = ScanFilter {
+            ignore: vec!["**/ringtones/**".to_string(), "*.spam.mp3".to_string()],
+            min_duration: Duration::ZERO,
+        };
+        let kept: Vec<PathBuf> = scan_paths(&dir)
+            .unwrap()
+            .into_iter()
+            .filter(|path| filter.allows_path(path))
+            .map(|path| path.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(kept, [PathBuf::from("a.mp3"), PathBuf::from("album/b.flac")]);
+    }
+}
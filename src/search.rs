@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use crate::app::Song;
+use crate::playlists;
+use crate::remote_library;
+
+/// 每个来源最多列出的结果数
+const GROUP_LIMIT: usize = 50;
+
+/// 曲库快照中的一首歌：打开搜索界面时生成一次，之后每次输入都在后台线程中查找
+pub struct SearchEntry {
+    /// 生成快照时在曲库中的位置，只用来给同分的结果排序
+    index: usize,
+    path: PathBuf,
+    name: String,
+    album: Option<String>,
+    /// 小写的文件名、标题、艺术家和专辑
+    text: String,
+    /// 远程曲库的地址，本地歌曲为 None
+    source: Option<String>,
+}
+
+pub fn build_index(songs: &[Song], remote_sources: &[String], include: impl Fn(&Song) -> bool) -> Arc<Vec<SearchEntry>> {
+    let entries = songs
+        .iter()
+        .enumerate()
+        .filter(|(_, song)| include(song))
+        .map(|(index, song)| {
+            let tags = [&song.tags.title, &song.tags.artist, &song.tags.album];
+            let mut text = song.name.clone();
+            for tag in tags.into_iter().flatten() {
+                text.push(' ');
+                text.push_str(tag);
+            }
+            SearchEntry {
+                index,
+                path: song.path.clone(),
+                name: song.name.clone(),
+                album: song.tags.album.clone(),
+                text: text.to_lowercase(),
+                source: remote_library::source_of(&song.path, remote_sources).map(str::to_string),
+            }
+        })
+        .collect();
+    Arc::new(entries)
+}
+
+/// 搜索结果。歌曲按路径保存：搜索期间曲库可能重新扫描，播放或加入队列时再按路径找到歌曲
+#[derive(Debug, Clone)]
+pub enum SearchHit {
+    /// 曲库中的歌曲
+    Song { path: PathBuf, name: String, album: Option<String> },
+    /// 保存的播放列表和其中能在曲库中找到的歌曲
+    Playlist { name: String, songs: Vec<PathBuf>, matched: usize },
+}
+
+/// 同一个来源的结果，按匹配程度排序
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    pub source: String,
+    pub hits: Vec<SearchHit>,
+}
+
+/// 在后台线程中查找曲库、远程曲库和保存的播放列表
+pub fn spawn(query: String, index: Arc<Vec<SearchEntry>>) -> Receiver<Vec<SearchGroup>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(search(&query, &index));
+    });
+    rx
+}
+
+fn search(query: &str, index: &[SearchEntry]) -> Vec<SearchGroup> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let score = |text: &str| terms.iter().map(|term| fuzzy_score(term, text)).sum::<Option<i32>>();
+
+    let mut songs: Vec<(i32, &SearchEntry)> = index
+        .iter()
+        .filter_map(|entry| score(&entry.text).map(|score| (score, entry)))
+        .collect();
+    songs.sort_by_key(|(score, entry)| (-score, entry.index));
+    let matched: HashMap<&PathBuf, i32> = songs.iter().map(|(score, entry)| (&entry.path, *score)).collect();
+
+    // 本地曲库在前，每个远程曲库各成一组
+    let mut groups: Vec<SearchGroup> = Vec::new();
+    for (_, entry) in &songs {
+        let source = entry.source.clone().unwrap_or_else(|| "曲库".to_string());
+        let group = match groups.iter().position(|group| group.source == source) {
+            Some(position) => &mut groups[position],
+            None => {
+                groups.push(SearchGroup { source, hits: Vec::new() });
+                groups.last_mut().unwrap()
+            }
+        };
+        if group.hits.len() < GROUP_LIMIT {
+            group.hits.push(SearchHit::Song {
+                path: entry.path.clone(),
+                name: entry.name.clone(),
+                album: entry.album.clone(),
+            });
+        }
+    }
+    groups.sort_by_key(|group| group.source != "曲库");
+
+    // 播放列表的名称匹配，或者其中有匹配的歌曲
+    let in_index: HashSet<&PathBuf> = index.iter().map(|entry| &entry.path).collect();
+    let mut playlists: Vec<(i32, SearchHit)> = Vec::new();
+    for file in fs::read_dir(playlists::playlists_dir()).into_iter().flatten().flatten() {
+        let path = file.path();
+        let is_m3u = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"));
+        let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).filter(|_| is_m3u) else {
+            continue;
+        };
+        let Ok(entries) = playlists::load_m3u(&path) else {
+            continue;
+        };
+        let scores: Vec<i32> = entries.iter().filter_map(|entry| matched.get(entry).copied()).collect();
+        let Some(best) = score(&name.to_lowercase()).or_else(|| scores.iter().max().copied()) else {
+            continue;
+        };
+        let songs = entries.iter().filter(|entry| in_index.contains(entry)).cloned().collect();
+        playlists.push((best, SearchHit::Playlist { name, songs, matched: scores.len() }));
+    }
+    if !playlists.is_empty() {
+        playlists.sort_by_key(|(score, _)| -score);
+        groups.push(SearchGroup {
+            source: "播放列表".to_string(),
+            hits: playlists.into_iter().take(GROUP_LIMIT).map(|(_, hit)| hit).collect(),
+        });
+    }
+    groups
+}
+
+/// pattern 的字符按顺序出现在 text 中时返回分数，连续匹配和匹配在词首的得分更高
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i32> {
+    // 整段出现的排在最前面，逐字匹配只按第一次出现的位置计分，可能低估
+    if let Some(position) = text.find(pattern) {
+        let word_start = text[..position].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+        return Some(pattern.chars().count() as i32 * 6 + if word_start { 13 } else { 10 });
+    }
+    let mut score = 0;
+    let mut pattern = pattern.chars().peekable();
+    let mut previous: Option<char> = None;
+    let mut consecutive = false;
+    for c in text.chars() {
+        let Some(&wanted) = pattern.peek() else {
+            break;
+        };
+        if c == wanted {
+            pattern.next();
+            score += 1;
+            if consecutive {
+                score += 5;
+            }
+            if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+            consecutive = true;
+        } else {
+            consecutive = false;
+        }
+        previous = Some(c);
+    }
+    pattern.peek().is_none().then_some(score)
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::storage;
+
+const SESSION_FILE: &str = "session.tsv";
+
+/// 播放会话：当前歌曲、播放位置和队列。播放过程中定期写入，退出时再写一次，
+/// 程序崩溃或终端被关闭后下次启动可以恢复
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub current: Option<(PathBuf, Duration)>,
+    pub queue: Vec<PathBuf>,
+    /// 上次是否正常退出
+    pub clean_exit: bool,
+    /// 保存时是否暂停，界面和后台播放器互相接管时保持暂停
+    pub paused: bool,
+}
+
+impl Session {
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+}
+
+pub fn load() -> Option<Session> {
+    let records = storage::read_records(SESSION_FILE).ok()?;
+    let mut session = Session {
+        current: None,
+        queue: Vec::new(),
+        clean_exit: false,
+        paused: false,
+    };
+    // 格式: clean \t 0|1、paused \t 0|1、current \t 位置(ms) \t 路径、queue \t 路径
+    for record in records {
+        match record.as_slice() {
+            [kind, clean] if kind == "clean" => session.clean_exit = clean == "1",
+            [kind, paused] if kind == "paused" => session.paused = paused == "1",
+            [kind, position, path] if kind == "current" => {
+                let position = Duration::from_millis(position.parse().unwrap_or_default());
+                session.current = Some((storage::path_from_field(path), position));
+            }
+            [kind, path] if kind == "queue" => session.queue.push(storage::path_from_field(path)),
+            _ => {}
+        }
+    }
+    (!session.is_empty()).then_some(session)
+}
+
+pub fn save(session: &Session) -> Result<()> {
+    let flag = |value: bool| if value { "1" } else { "0" }.to_string();
+    let mut records = vec![
+        vec!["clean".to_string(), flag(session.clean_exit)],
+        vec!["paused".to_string(), flag(session.paused)],
+    ];
+    if let Some((path, position)) = &session.current {
+        records.push(vec![
+            "current".to_string(),
+            position.as_millis().to_string(),
+            storage::path_to_field(path),
+        ]);
+    }
+    for path in &session.queue {
+        records.push(vec!["queue".to_string(), storage::path_to_field(path)]);
+    }
+    storage::write_records(SESSION_FILE, &records)
+}
@@ -0,0 +1,127 @@
+/// 设置界面中每个配置项的说明
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("controls_height", "控制说明面板的高度，0 隐藏"),
+    ("now_playing_height", "播放状态面板的高度（3-6）"),
+    ("info_width", "信息面板的宽度百分比（20-80）"),
+    ("open_timeout_ms", "打开音频文件的超时（毫秒）"),
+    ("scan_ignore", "扫描曲库时忽略的 glob 模式"),
+    ("min_duration_secs", "扫描时跳过短于这个秒数的文件"),
+    ("clean_mode", "儿童模式：隐藏标记为不当内容的歌曲"),
+    ("probe_workers", "并行探测标签的线程数，0 自动"),
+    ("glyphs", "界面符号：emoji、nerdfont 或 ascii"),
+    ("tick_ms", "检查播放状态的间隔（毫秒）"),
+    ("max_fps", "每秒最多重绘次数"),
+    ("power_saver", "省电模式：每秒刷新一次，关闭可视化和波形"),
+    ("alarm_time", "闹钟时间 HH:MM，留空关闭"),
+    ("alarm_stop_time", "每天自动暂停的时间 HH:MM"),
+    ("alarm_playlist", "闹钟播放的 M3U 播放列表"),
+    ("alarm_ramp_secs", "闹钟音量渐强的秒数"),
+    ("remote_port", "ctl 控制命令的本机端口，0 关闭"),
+    ("download_urls", "粘贴的网址先下载再播放"),
+    ("ytdlp", "解析视频网站地址的 yt-dlp 程序"),
+    ("remote_sources", "远程曲库（sftp:// 或 WebDAV）"),
+    ("remote_cache_mb", "远程歌曲缓存上限（MB）"),
+    ("status_format", "底部状态栏模板，留空隐藏"),
+    ("now_playing_format", "播放状态面板模板，\\n 换行，留空使用默认"),
+    ("write_rating_tags", "评分同时写入文件标签"),
+    ("playlist_waveforms", "播放列表中显示波形缩略图"),
+    ("watch_playlist", "播放列表文件被修改后提示重新加载"),
+    ("leveler", "响度均衡"),
+    ("leveler_target_lufs", "响度均衡的目标响度（LUFS）"),
+    ("output_latency_ms", "输出缓冲延迟（毫秒）"),
+    ("output_sample_rate", "输出采样率，0 使用设备默认"),
+    ("output_sample_format", "输出样本格式：i16、i32、f32 等，留空使用设备默认"),
+    ("resampler", "重采样质量"),
+    ("dither", "输出 16 位及以下整数格式时的抖动：off 或 tpdf"),
+    ("output_backend", "音频输出方式"),
+    ("output_client_name", "PipeWire 节点名（jack-stdin 不支持自定义）"),
+    ("jack_ports", "JACK 输出端口，每个端口一个声道"),
+    ("output_device", "输出设备名，留空使用默认设备"),
+    ("match_sample_rate", "按歌曲采样率切换设备（位精确输出）"),
+    ("surround_passthrough", "设备声道数足够时多声道文件不混成立体声"),
+    ("dsd_output", "DSD 文件：pcm 转换播放，dop 交给支持 DSD 的 DAC"),
+    ("dsd_filter", "DSD 转 PCM 的滤波器：fast 或 sharp"),
+    ("visualizer", "可视化：off、oscilloscope 或 spectrogram"),
+    ("visualizer_height", "可视化区域的高度"),
+    ("lyrics_providers", "在线查找歌词的来源"),
+    ("lyrics_next_to_file", "下载的歌词保存在音频文件旁边"),
+    ("previous_restart_secs", "上一首先从头播放的秒数，0 关闭"),
+    ("track_alert", "切歌提醒：off、flash、bell 或 both"),
+    ("queue_warning_mins", "队列剩余不到这么多分钟时提醒，0 关闭"),
+    ("follow_playback", "切歌时光标跟着移到新歌曲"),
+    ("switch_fade_ms", "手动切歌时淡出淡入的毫秒数，0 直接切换"),
+    ("automix", "实验性的自动混音：歌曲结尾和下一首重叠并对齐节拍"),
+    ("automix_beats", "自动混音时重叠的拍数"),
+    ("automix_tolerance", "速度相差不超过这个百分比时对齐速度"),
+    ("preview_exit", "以单个文件启动时，播放完就退出"),
+    ("beets_library", "beets 曲库 library.db 的路径"),
+    ("beets_command", "beets 的命令行程序"),
+    ("genre_command", "识别流派的外部程序，留空使用内置模型"),
+    ("rekordbox_xml", "Rekordbox 导出的 XML 曲库，读取提示点"),
+    ("mqtt_broker", "MQTT 服务器，留空关闭"),
+    ("mqtt_topic", "MQTT 主题前缀"),
+    ("mqtt_username", "MQTT 用户名"),
+    ("mqtt_password", "MQTT 密码"),
+    ("metrics_address", "Prometheus 统计的监听地址，留空关闭"),
+    ("web_address", "网页遥控的监听地址，留空关闭"),
+    ("web_token", "网页遥控的访问令牌"),
+    ("webhook_urls", "播放事件 POST 到这些地址"),
+    ("webhook_template", "webhook 的 JSON 模板，留空发送播放状态"),
+    ("webhook_retries", "webhook 发送失败后的重试次数"),
+    ("lighting_targets", "随封面颜色变化的 WLED/Hue 灯光"),
+    ("lighting_beats", "灯光随节拍闪烁"),
+];
+
+/// 只在启动时读取的配置项，修改后要重启播放器才生效
+const RESTART_KEYS: &[&str] = &[
+    "remote_port",
+    "output_latency_ms",
+    "output_sample_rate",
+    "output_sample_format",
+    "output_backend",
+    "output_client_name",
+    "jack_ports",
+    "output_device",
+    "mqtt_broker",
+    "mqtt_topic",
+    "mqtt_username",
+    "mqtt_password",
+    "metrics_address",
+    "web_address",
+    "web_token",
+    "webhook_urls",
+    "webhook_retries",
+    "lighting_targets",
+];
+
+/// 不能在设置界面中修改、只能编辑配置文件的配置项
+const LOCKED_KEYS: &[&str] = &["clean_mode"];
+
+pub fn description(key: &str) -> &'static str {
+    DESCRIPTIONS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, description)| *description)
+        .unwrap_or_default()
+}
+
+pub fn needs_restart(key: &str) -> bool {
+    RESTART_KEYS.contains(&key)
+}
+
+pub fn is_locked(key: &str) -> bool {
+    LOCKED_KEYS.contains(&key)
+}
+
+/// 列表中显示的值，密码不显示出来
+pub fn display_value(key: &str, value: &str) -> String {
+    match key {
+        "mqtt_password" | "web_token" if value != "\"\"" => "\"******\"".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// 输入框中显示的值：去掉字符串外面的引号
+pub fn edit_value(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
@@ -0,0 +1,259 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::{App, PlaybackState, SIMULATED_UNKNOWN_DURATION};
+use crate::clock::Clock;
+use crate::ui::format_duration;
+
+/// 模拟时钟每次前进的时间
+const STEP: Duration = Duration::from_secs(1);
+const DEFAULT_HOURS: f64 = 24.0;
+
+/// `music_tui --simulate[=小时] [播放列表]`：不打开界面、不输出声音，用模拟时钟快进播放，
+/// 打印每首歌开始播放的时间，用来检查自动切歌、队列和闹钟等逻辑。默认模拟 24 小时
+pub fn run(args: &[OsString]) -> Result<()> {
+    let mut hours = DEFAULT_HOURS;
+    let mut playlist = None;
+    for arg in args {
+        let text = arg.to_string_lossy();
+        if let Some(value) = text.strip_prefix("--simulate") {
+            if let Some(value) = value.strip_prefix('=') {
+                hours = match value.parse::<f64>() {
+                    Ok(hours) if hours > 0.0 => hours,
+                    _ => bail!("--simulate 的时长应为小时数，如 --simulate=2.5"),
+                };
+            }
+        } else {
+            playlist = Some(PathBuf::from(arg));
+        }
+    }
+
+    let clock = Clock::simulated(chrono::Local::now());
+    let mut app = App::with_clock(playlist, clock.clone())?;
+    if app.songs.is_empty() {
+        bail!("没有可以播放的歌曲");
+    }
+    let limit = Duration::from_secs_f64(hours * 3600.0);
+    println!("模拟播放 {} 首歌曲，共 {} 小时", app.songs.len(), hours);
+
+    app.play()?;
+    let mut last = None;
+    let mut plays = 0;
+    let mut played = HashSet::new();
+    while clock.elapsed() < limit {
+        app.check_and_auto_next()?;
+        app.update_schedule()?;
+        app.update_play_time();
+
+        // 切歌（包括单曲重复）或播放状态变化时打印一行
+        let state = (app.current_index, app.playback_state, app.playback_id);
+        if last != Some(state) {
+            let time = format_elapsed(clock.elapsed());
+            match app.playback_state {
+                PlaybackState::Playing if last.is_none_or(|(_, _, id)| id != app.playback_id) => {
+                    let song = &app.songs[app.current_index];
+                    let duration = song.duration.map(format_duration).unwrap_or_else(|| {
+                        format!("时长未知，按 {} 计算", format_duration(SIMULATED_UNKNOWN_DURATION))
+                    });
+                    println!("[{}] 播放 {} ({})", time, song.name, duration);
+                    plays += 1;
+                    played.insert(app.current_index);
+                }
+                PlaybackState::Playing => println!("[{}] 继续播放", time),
+                PlaybackState::Paused => println!("[{}] 暂停", time),
+                PlaybackState::Stopped => println!("[{}] 停止", time),
+            }
+            last = Some(state);
+        }
+        if let Some((message, _)) = app.message.take() {
+            println!("[{}] {}", format_elapsed(clock.elapsed()), message);
+        }
+        clock.advance(STEP);
+    }
+
+    println!("共播放 {} 次，{} 首不同的歌曲", plays, played.len());
+    Ok(())
+}
+
+/// 模拟开始以来的时间，如 `1:02:03`
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::Scheduler;
+    use crate::config::Config;
+    use chrono::{Local, NaiveTime, TimeZone};
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Once;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    /// 配置、缓存和数据都放进临时目录，测试不读写用户自己的文件
+    fn isolate() {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| {
+            let home = env::temp_dir().join(format!("cuper-simulate-{}", std::process::id()));
+            for name in ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_CACHE_HOME"] {
+                let dir = home.join(name);
+                fs::create_dir_all(&dir).unwrap();
+                env::set_var(name, dir);
+            }
+        });
+    }
+
+    /// 把测试音频按给定的文件名复制到新的文件夹，文件夹里的歌曲按文件名排序
+    fn library(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cuper-simulate-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (fixture, file) in files {
+            fs::copy(Path::new(FIXTURES).join(fixture), dir.join(file)).unwrap();
+        }
+        dir
+    }
+
+    fn start() -> chrono::DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 1, 1, 6, 59, 0).unwrap()
+    }
+
+    fn app(name: &str, files: &[(&str, &str)], clock: &Clock) -> App {
+        isolate();
+        let app = App::with_clock(Some(library(name, files)), clock.clone()).unwrap();
+        assert_eq!(app.songs.len(), files.len());
+        app
+    }
+
+    /// 按 step 推进模拟时钟 total 这么久，返回每次开始播放的歌曲序号
+    fn run_for(app: &mut App, clock: &Clock, total: Duration, step: Duration) -> Vec<usize> {
+        let mut started = vec![app.current_index];
+        let mut id = app.playback_id;
+        let end = clock.elapsed() + total;
+        while clock.elapsed() < end {
+            clock.advance(step);
+            app.check_and_auto_next().unwrap();
+            app.update_schedule().unwrap();
+            app.update_play_time();
+            if app.playback_id != id {
+                id = app.playback_id;
+                started.push(app.current_index);
+            }
+        }
+        started
+    }
+
+    #[test]
+    fn tracks_advance_on_the_simulated_clock() {
+        let clock = Clock::simulated(start());
+        let mut app = app("advance", &[("alac.m4a", "1.m4a"), ("aac-lc.m4a", "2.m4a")], &clock);
+        let durations: Vec<Duration> = app.songs.iter().map(|song| song.duration.unwrap()).collect();
+        app.play().unwrap();
+        assert_eq!(app.playback_state, PlaybackState::Playing);
+
+        // 第一首播完之前不切歌
+        let step = Duration::from_millis(10);
+        assert_eq!(run_for(&mut app, &clock, durations[0] - step * 2, step), [0]);
+        // 两首都播完后回到第一首
+        let started = run_for(&mut app, &clock, durations[0] + durations[1], step);
+        assert_eq!(started, [0, 1, 0]);
+    }
+
+    #[test]
+    fn unknown_duration_falls_back_to_fixed_length() {
+        let clock = Clock::simulated(start());
+        let mut app = app("unknown", &[("alac.m4a", "1.m4a"), ("adts.aac", "2.aac")], &clock);
+        app.songs[1].duration = None;
+        app.current_index = 1;
+        app.play().unwrap();
+
+        let step = Duration::from_secs(1);
+        assert_eq!(run_for(&mut app, &clock, SIMULATED_UNKNOWN_DURATION - step * 2, step), [1]);
+        assert_eq!(run_for(&mut app, &clock, step * 2, step), [1, 0]);
+    }
+
+    #[test]
+    fn clean_mode_never_plays_explicit_tracks() {
+        let clock = Clock::simulated(start());
+        let mut app = app("clean", &[("alac.m4a", "1.m4a"), ("aac-lc.m4a", "2.m4a"), ("adts.aac", "3.aac")], &clock);
+        app.config.clean_mode = true;
+        app.songs[0].tags.explicit = true;
+        app.current_index = 1;
+        app.play().unwrap();
+
+        // 队列中的不当内容被丢弃，按顺序也跳过它
+        app.queue.push_back(0);
+        app.next().unwrap();
+        assert_eq!(app.current_index, 2);
+        app.next().unwrap();
+        assert_eq!(app.current_index, 1);
+
+        // 都被排除时停止播放，不退回到范围之外
+        for song in &mut app.songs {
+            song.tags.explicit = true;
+        }
+        app.next().unwrap();
+        assert_eq!(app.playback_state, PlaybackState::Stopped);
+        let message = app.message.as_ref().map(|(message, _)| message.as_str()).unwrap_or_default();
+        assert!(message.starts_with("当前范围内没有可以播放的歌曲"), "{}", message);
+    }
+
+    #[test]
+    fn alarm_fires_on_the_simulated_clock() {
+        let clock = Clock::simulated(start());
+        let mut app = app("alarm", &[("alac.m4a", "1.m4a")], &clock);
+        app.config.alarm_time = NaiveTime::from_hms_opt(7, 0, 0);
+        app.config.alarm_stop_time = NaiveTime::from_hms_opt(7, 30, 0);
+        app.scheduler = Scheduler::from_config(&app.config, clock.local_now());
+        assert_eq!(app.playback_state, PlaybackState::Stopped);
+
+        let step = Duration::from_secs(1);
+        run_for(&mut app, &clock, Duration::from_secs(59), step);
+        assert_eq!(app.playback_state, PlaybackState::Stopped);
+        run_for(&mut app, &clock, step * 2, step);
+        assert_eq!(app.playback_state, PlaybackState::Playing);
+        assert_eq!(app.scheduler.next_alarm, Some(start() + chrono::Duration::minutes(1) + chrono::Duration::days(1)));
+
+        // 到了停止时间暂停播放
+        run_for(&mut app, &clock, Duration::from_secs(30 * 60), step);
+        assert_eq!(app.playback_state, PlaybackState::Paused);
+    }
+
+    #[test]
+    fn alarm_keeps_the_playlist_error() {
+        let clock = Clock::simulated(start());
+        let mut app = app("alarm-error", &[("alac.m4a", "1.m4a")], &clock);
+        app.config.alarm_time = NaiveTime::from_hms_opt(7, 0, 0);
+        app.config.alarm_playlist = "/nonexistent/alarm.m3u".to_string();
+        app.scheduler = Scheduler::from_config(&app.config, clock.local_now());
+
+        let step = Duration::from_secs(1);
+        run_for(&mut app, &clock, Duration::from_secs(61), step);
+        let message = app.message.as_ref().map(|(message, _)| message.as_str()).unwrap_or_default();
+        assert!(message.starts_with("无法读取闹钟播放列表"), "{}", message);
+    }
+
+    #[test]
+    fn scheduler_fires_once_per_day() {
+        let config = Config { alarm_time: NaiveTime::from_hms_opt(7, 0, 0), ..Config::default() };
+        let clock = Clock::simulated(start());
+        let mut scheduler = Scheduler::from_config(&config, clock.local_now());
+
+        let mut fired = Vec::new();
+        for _ in 0..(3 * 24 * 60) {
+            clock.advance(Duration::from_secs(60));
+            if scheduler.alarm_due(clock.local_now()) {
+                fired.push(clock.local_now());
+            }
+        }
+        let day = |n| start() + chrono::Duration::minutes(1) + chrono::Duration::days(n);
+        assert_eq!(fired, [day(0), day(1), day(2)]);
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::storage;
+
+const SNAPSHOTS_FILE: &str = "snapshots.tsv";
+
+/// 自动保存的快照名称的前缀，同名的自动快照只保留最新的一个
+pub const AUTO_PREFIX: &str = "自动: ";
+
+/// 以名称保存的播放队列，用来在试过打乱、推荐电台之后找回精心排好的队列
+#[derive(Debug, Clone)]
+pub struct QueueSnapshot {
+    pub name: String,
+    /// 保存时间（Unix 秒）
+    pub saved_at: i64,
+    pub paths: Vec<PathBuf>,
+}
+
+pub fn load() -> Vec<QueueSnapshot> {
+    let records = storage::read_records(SNAPSHOTS_FILE).unwrap_or_default();
+    let mut snapshots: Vec<QueueSnapshot> = Vec::new();
+    for record in records {
+        // 格式: 名称 \t 保存时间 \t 文件路径，每首歌一行，按队列顺序排列
+        let Ok([name, saved_at, path]) = <[String; 3]>::try_from(record) else {
+            continue;
+        };
+        let Ok(saved_at) = saved_at.parse() else {
+            continue;
+        };
+        match snapshots.last_mut().filter(|snapshot| snapshot.name == name) {
+            Some(snapshot) => snapshot.paths.push(storage::path_from_field(&path)),
+            None => snapshots.push(QueueSnapshot {
+                name,
+                saved_at,
+                paths: vec![storage::path_from_field(&path)],
+            }),
+        }
+    }
+    snapshots
+}
+
+pub fn save(snapshots: &[QueueSnapshot]) -> Result<()> {
+    let records: Vec<Vec<String>> = snapshots
+        .iter()
+        .flat_map(|snapshot| {
+            snapshot.paths.iter().map(|path| {
+                vec![
+                    snapshot.name.clone(),
+                    snapshot.saved_at.to_string(),
+                    storage::path_to_field(path),
+                ]
+            })
+        })
+        .collect();
+    storage::write_records(SNAPSHOTS_FILE, &records)
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::json::{self, Value};
+
+/// 用 sqlite3 命令行工具以只读方式查询，每行是一个 JSON 对象
+pub fn query(db: &Path, sql: &str) -> Result<Vec<Value>> {
+    let output = Command::new("sqlite3")
+        .args(["-readonly", "-json"])
+        .arg(db)
+        .arg(sql)
+        .output()
+        .map_err(|err| anyhow!("读取数据库需要 sqlite3 命令: {}", err))?;
+    if !output.status.success() {
+        bail!("sqlite3: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    // 没有结果时 sqlite3 什么也不输出
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(json::parse(&text)?.as_array().unwrap_or_default().to_vec())
+}
+
+/// 文本字段，数字也转成文本，NULL 为空字符串
+pub fn text(row: &Value, key: &str) -> String {
+    match row.get(key) {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Number(number)) => number.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// 数字字段，sqlite3 对 TEXT 类型的列输出字符串
+pub fn number(row: &Value, key: &str) -> Option<f64> {
+    match row.get(key)? {
+        Value::Number(number) => Some(*number),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// SQL 字符串字面量
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::storage;
+
+const STATS_FILE: &str = "stats.tsv";
+
+/// 单首歌曲的收听统计
+#[derive(Debug, Clone, Default)]
+pub struct PlayStats {
+    pub plays: u32,
+    /// 最近一次播放的 Unix 时间戳（秒）
+    pub last_played: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    entries: HashMap<PathBuf, PlayStats>,
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        let records = storage::read_records(STATS_FILE).unwrap_or_default();
+        let entries = records
+            .into_iter()
+            .filter_map(|record| {
+                // 格式: 播放次数 \t 最近播放时间 \t 文件路径
+                let [plays, last_played, path] = <[String; 3]>::try_from(record).ok()?;
+                let stats = PlayStats {
+                    plays: plays.parse().ok()?,
+                    last_played: last_played.parse().ok(),
+                };
+                Some((storage::path_from_field(&path), stats))
+            })
+            .collect();
+        Stats { entries }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut records: Vec<Vec<String>> = self
+            .entries
+            .iter()
+            .map(|(path, stats)| {
+                vec![
+                    stats.plays.to_string(),
+                    stats.last_played.map(|t| t.to_string()).unwrap_or_default(),
+                    storage::path_to_field(path),
+                ]
+            })
+            .collect();
+        records.sort_by(|a, b| a[2].cmp(&b[2]));
+        storage::write_records(STATS_FILE, &records)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&PlayStats> {
+        self.entries.get(path)
+    }
+
+    /// 记录一次播放
+    pub fn record_play(&mut self, path: &Path) {
+        let entry = self.entries.entry(path.to_path_buf()).or_default();
+        entry.plays += 1;
+        entry.last_played = Some(chrono::Utc::now().timestamp());
+    }
+    
+    /// 导入其他播放器的收听记录：播放次数取较大的一个，重复导入不会累加。返回是否有变化
+    pub fn import(&mut self, path: &Path, plays: u32, last_played: Option<i64>) -> bool {
+        let entry = self.entries.entry(path.to_path_buf()).or_default();
+        let before = (entry.plays, entry.last_played);
+        entry.plays = entry.plays.max(plays);
+        entry.last_played = entry.last_played.max(last_played);
+        (entry.plays, entry.last_played) != before
+    }
+    
+    /// 文件被移动后把统计转到新路径下
+    pub fn rename(&mut self, old: &Path, new: &Path) {
+        if let Some(stats) = self.entries.remove(old) {
+            self.entries.insert(new.to_path_buf(), stats);
+        }
+    }
+}
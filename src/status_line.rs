@@ -0,0 +1,81 @@
+use crate::app::{App, PlaybackState, ShuffleMode};
+use crate::equalizer;
+use crate::ratings;
+use crate::ui::format_duration;
+
+/// 状态栏模板中可以使用的变量
+pub const VARIABLES: [&str; 14] = [
+    "title", "artist", "album", "file", "elapsed", "total", "remaining", "vol", "state", "shuffle", "eq", "queue",
+    "position", "rating",
+];
+
+/// 按模板生成状态栏文字：`%变量名%` 替换为对应的值，其他 `%` 原样保留
+pub fn format(template: &str, app: &App) -> String {
+    expand(template, |name| VARIABLES.contains(&name).then(|| value(name, app)))
+}
+
+/// 把模板中的 `%变量名%` 替换为 lookup 返回的值，lookup 返回 None 的保留原样
+pub fn expand(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let variable = after
+            .find('%')
+            .map(|end| &after[..end])
+            .and_then(|name| lookup(name).map(|value| (name, value)));
+        match variable {
+            Some((name, value)) => {
+                output.push_str(&value);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                output.push('%');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+pub fn value(name: &str, app: &App) -> String {
+    let song = app.get_current_song();
+    match name {
+        "title" => song.map(|song| song.title()).unwrap_or_else(|| "没有歌曲".to_string()),
+        "artist" => song.and_then(|song| song.artist()).unwrap_or_else(|| "未知艺术家".to_string()),
+        "album" => song.and_then(|song| song.tags.album.clone()).unwrap_or_default(),
+        "file" => song.map(|song| song.name.clone()).unwrap_or_else(|| "没有歌曲".to_string()),
+        "elapsed" => format_duration(app.get_current_time()),
+        "total" => app.get_total_duration().map(format_duration).unwrap_or_else(|| "--:--".to_string()),
+        "remaining" => app
+            .get_total_duration()
+            .map(|total| format_duration(total.saturating_sub(app.get_current_time())))
+            .unwrap_or_else(|| "--:--".to_string()),
+        "vol" => format!("{:.0}", app.volume * 100.0),
+        "state" => {
+            let glyphs = app.config.glyphs.glyphs();
+            match app.playback_state {
+                PlaybackState::Playing if app.buffering => format!("{} 缓冲中…", glyphs.paused),
+                PlaybackState::Playing => glyphs.playing.to_string(),
+                PlaybackState::Paused => glyphs.paused.to_string(),
+                PlaybackState::Stopped => glyphs.stopped.to_string(),
+            }
+        }
+        "shuffle" => match app.shuffle {
+            ShuffleMode::Off => String::new(),
+            mode => format!("{} {}", app.config.glyphs.glyphs().shuffle, mode.label()),
+        },
+        "eq" => equalizer::PRESETS[app.eq_preset].name.to_string(),
+        "queue" => app.queue.len().to_string(),
+        "position" => format!("{}/{}", app.current_index + 1, app.songs.len()),
+        "rating" => {
+            let glyphs = app.config.glyphs.glyphs();
+            song.and_then(|song| app.ratings.get(&song.path))
+                .map(|stars| ratings::stars_label(stars, glyphs.star, glyphs.star_empty))
+                .unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,351 @@
+use anyhow::Result;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// 程序数据目录（书签等持久化数据）
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("cuper");
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("cuper");
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".local").join("share").join("cuper");
+    }
+    PathBuf::from(".cuper")
+}
+
+/// 配置目录
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("cuper");
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("cuper");
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".config").join("cuper");
+    }
+    PathBuf::from(".cuper")
+}
+
+/// 缓存目录（可以随时删除的数据，例如在线获取的信息）
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("cuper");
+    }
+    if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        return PathBuf::from(dir).join("cuper").join("cache");
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".cache").join("cuper");
+    }
+    PathBuf::from(".cuper").join("cache")
+}
+
+/// 去掉文件名中不允许出现的字符
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    match sanitized.trim() {
+        "" => "untitled".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('0') => unescaped.push('\0'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// 绝对路径。Windows 上 fs::canonicalize 返回 `\\?\C:\...` 形式的路径，
+/// 写进播放列表或和 beets 等其他程序的路径比较时对不上，换回普通的盘符和 UNC 形式
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let path = fs::canonicalize(path)?;
+    if !cfg!(windows) {
+        return Ok(path);
+    }
+    Ok(strip_verbatim(path))
+}
+
+/// `\\?\C:\...` 换成 `C:\...`，`\\?\UNC\服务器\共享\...` 换成 `\\服务器\共享\...`，其他路径原样返回
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let mut components = path.components();
+    let plain = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::VerbatimDisk(letter) => format!("{}:", letter as char),
+            Prefix::VerbatimUNC(server, share) => {
+                format!("\\\\{}\\{}", server.to_string_lossy(), share.to_string_lossy())
+            }
+            _ => return path,
+        },
+        _ => return path,
+    };
+    // 服务器和共享名几乎都是 ASCII，其余部分原样拼接
+    let mut plain = PathBuf::from(plain);
+    plain.push(Component::RootDir);
+    plain.extend(components.filter(|component| *component != Component::RootDir));
+    plain
+}
+
+/// 路径不是合法的 UTF-8 时记录字段以这个字符开头。路径中不可能出现 NUL，不会和普通路径混淆
+const RAW_PATH_MARKER: char = '\0';
+
+/// 记录文件中保存的路径。能表示成 UTF-8 的原样保存，Unix 上不是 UTF-8 的文件名、
+/// Windows 上不成对的代理项写成 %XX / %uXXXX，读回来是同一个路径，不会像 to_string_lossy 那样变成 �
+pub fn path_to_field(path: &Path) -> String {
+    if let Some(text) = path.to_str() {
+        return text.to_string();
+    }
+    let mut field = String::from(RAW_PATH_MARKER);
+    encode_raw_path(path.as_os_str(), &mut field);
+    field
+}
+
+pub fn path_from_field(field: &str) -> PathBuf {
+    match field.strip_prefix(RAW_PATH_MARKER) {
+        Some(raw) => PathBuf::from(decode_raw_path(raw)),
+        None => PathBuf::from(field),
+    }
+}
+
+fn push_escaped_text(text: &str, field: &mut String) {
+    for c in text.chars() {
+        match c {
+            '%' => field.push_str("%25"),
+            c => field.push(c),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn encode_raw_path(path: &std::ffi::OsStr, field: &mut String) {
+    use std::os::unix::ffi::OsStrExt;
+    for chunk in path.as_bytes().utf8_chunks() {
+        push_escaped_text(chunk.valid(), field);
+        for byte in chunk.invalid() {
+            field.push_str(&format!("%{:02X}", byte));
+        }
+    }
+}
+
+#[cfg(unix)]
+fn decode_raw_path(raw: &str) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(c) = rest.chars().next() {
+        let byte = rest.strip_prefix('%').and_then(|hex| hex.get(..2)).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                bytes.push(byte);
+                rest = &rest[3..];
+            }
+            None => {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    OsString::from_vec(bytes)
+}
+
+#[cfg(windows)]
+fn encode_raw_path(path: &std::ffi::OsStr, field: &mut String) {
+    use std::os::windows::ffi::OsStrExt;
+    for unit in char::decode_utf16(path.encode_wide()) {
+        match unit {
+            Ok(c) => push_escaped_text(c.encode_utf8(&mut [0; 4]), field),
+            Err(error) => field.push_str(&format!("%u{:04X}", error.unpaired_surrogate())),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn decode_raw_path(raw: &str) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    let mut units: Vec<u16> = Vec::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(c) = rest.chars().next() {
+        let surrogate = rest.strip_prefix("%u").and_then(|hex| hex.get(..4)).and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        if let Some(unit) = surrogate {
+            units.push(unit);
+            rest = &rest[6..];
+        } else if rest.starts_with("%25") {
+            units.push(u16::from(b'%'));
+            rest = &rest[3..];
+        } else {
+            units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    OsString::from_wide(&units)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn encode_raw_path(path: &std::ffi::OsStr, field: &mut String) {
+    push_escaped_text(&path.to_string_lossy(), field);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn decode_raw_path(raw: &str) -> OsString {
+    OsString::from(raw.replace("%25", "%"))
+}
+
+/// 读取制表符分隔的记录文件，文件不存在时返回空列表
+pub fn read_records(name: &str) -> Result<Vec<Vec<String>>> {
+    read_records_at(&data_file(name))
+}
+
+/// 读取任意位置的记录文件（例如缓存目录中的文件）
+pub fn read_records_at(path: &Path) -> Result<Vec<Vec<String>>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split('\t').map(unescape_field).collect())
+        .collect())
+}
+
+/// 写入制表符分隔的记录文件，先写临时文件再替换，避免写到一半时损坏
+pub fn write_records(name: &str, records: &[Vec<String>]) -> Result<()> {
+    write_records_at(&data_file(name), records)
+}
+
+pub fn write_records_at(path: &Path, records: &[Vec<String>]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut content = String::new();
+    for record in records {
+        let fields: Vec<String> = record.iter().map(|field| escape_field(field)).collect();
+        content.push_str(&fields.join("\t"));
+        content.push('\n');
+    }
+    write_atomic(path, content.as_bytes())?;
+    Ok(())
+}
+
+/// 先写入同目录下的临时文件再替换，写到一半时中断不会损坏原文件
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, content)?;
+    fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 路径经过 path_to_field、记录文件的转义和读回之后不变
+    fn round_trip(path: &Path) -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("cuper-storage-{}-{}.tsv", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let file = env::temp_dir().join(name);
+        write_records_at(&file, &[vec![path_to_field(path), "x".to_string()]]).unwrap();
+        let records = read_records_at(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][1], "x");
+        path_from_field(&records[0][0])
+    }
+
+    #[test]
+    fn utf8_paths_are_stored_as_is() {
+        let path = Path::new("/音乐/周杰伦/晴天.flac");
+        assert_eq!(path_to_field(path), "/音乐/周杰伦/晴天.flac");
+        assert_eq!(round_trip(path), path);
+    }
+
+    #[test]
+    fn percent_tab_and_newline_round_trip() {
+        for path in ["/music/100% Pure.mp3", "/music/a\tb.mp3", "/music/line\nbreak\r.mp3", "/music/%41\\0.mp3"] {
+            assert_eq!(round_trip(Path::new(path)), Path::new(path));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_unix_paths_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+        use std::ffi::OsStr;
+        // GBK 编码的“晴天”，后面跟一个字面的 %41 和制表符
+        let path = Path::new(OsStr::from_bytes(b"/music/\xc7\xe7\xcc\xec %41\t100%.mp3"));
+        let field = path_to_field(path);
+        assert!(field.starts_with(RAW_PATH_MARKER));
+        assert_eq!(path_from_field(&field), path);
+        assert_eq!(round_trip(path), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn verbatim_prefix_is_stripped() {
+        assert_eq!(strip_verbatim(PathBuf::from(r"\\?\C:\Music\a.flac")), Path::new(r"C:\Music\a.flac"));
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"\\?\UNC\nas\share\Music\a.flac")),
+            Path::new(r"\\nas\share\Music\a.flac")
+        );
+        assert_eq!(strip_verbatim(PathBuf::from(r"C:\Music\a.flac")), Path::new(r"C:\Music\a.flac"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unpaired_surrogates_round_trip() {
+        use std::os::windows::ffi::OsStringExt;
+        let path = PathBuf::from(OsString::from_wide(&[0x43, 0x3A, 0x5C, 0xD800, 0x25, 0x2E, 0x6D, 0x70, 0x33]));
+        assert_eq!(round_trip(&path), path);
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{anyhow, bail, Result};
+use rodio::Source;
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::audio::{Channels, SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+
+/// 逐个数据包解码的音频源。文件通过固定大小的缓冲区分块读取，
+/// 内存占用只取决于单个数据包的大小，与文件大小无关；
+/// 跳转时由容器直接定位，不需要从头解码，适合几个 GB 的 DJ 混音和有声书。
+pub struct StreamingDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    buffer: Option<SampleBuffer<f32>>,
+    position: usize,
+    /// 跳转后还需要丢弃的样本数（容器只能定位到数据包边界）
+    skip_samples: usize,
+    spec: SignalSpec,
+    total_duration: Option<Duration>,
+}
+
+impl StreamingDecoder {
+    /// 打开文件（或网络流）并从 start 处开始解码，path 只用于根据扩展名猜测格式
+    pub fn new(media: Box<dyn MediaSource>, path: &Path, start: Duration) -> Result<Self> {
+        let source = MediaSourceStream::new(media, MediaSourceStreamOptions::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            source,
+            &FormatOptions { enable_gapless: true, ..Default::default() },
+            &MetadataOptions::default(),
+        )?;
+        let format = probed.format;
+
+        // iTunes 的 m4a 里除了音频还可能有章节、歌词等文本轨道，只选有采样率的音轨
+        let track = format
+            .tracks()
+            .iter()
+            .filter(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .find(|track| track.codec_params.sample_rate.is_some())
+            .or_else(|| format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL))
+            .ok_or_else(|| anyhow!("文件中没有可以解码的音轨"))?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+        let decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+
+        // HE-AAC 等格式的容器里可能没有写采样率和声道数，要解码第一个数据包才知道
+        let spec = match (params.sample_rate, params.channels) {
+            (Some(sample_rate), Some(channels)) => Some(SignalSpec::new(sample_rate, channels)),
+            _ => None,
+        };
+        let total_duration = match (params.time_base, params.n_frames) {
+            (Some(time_base), Some(frames)) => {
+                let time = time_base.calc_time(frames);
+                Some(Duration::from_secs_f64(time.seconds as f64 + time.frac))
+            }
+            _ => None,
+        };
+
+        let mut stream = StreamingDecoder {
+            format,
+            decoder,
+            track_id,
+            buffer: None,
+            position: 0,
+            skip_samples: 0,
+            spec: spec.unwrap_or(SignalSpec::new(0, Channels::empty())),
+            total_duration,
+        };
+        if spec.is_none() && !stream.decode_next() {
+            bail!("无法确定采样率和声道数");
+        }
+        if !start.is_zero() {
+            stream.seek(start);
+        }
+        Ok(stream)
+    }
+
+    /// symphonia 的声道掩码，给多声道混音确定每个声道的位置
+    pub fn channel_layout(&self) -> u32 {
+        self.spec.channels.bits()
+    }
+
+    fn seek(&mut self, position: Duration) {
+        let frames_per_second = self.spec.rate as f64;
+        let channels = self.spec.channels.count().max(1);
+        let seek_to = SeekTo::Time {
+            time: Time::new(position.as_secs(), position.subsec_nanos() as f64 / 1e9),
+            track_id: Some(self.track_id),
+        };
+
+        // 已经解码出来的数据包在跳转目标之前，丢弃
+        self.position = self.buffer.as_ref().map_or(0, |buffer| buffer.samples().len());
+        match self.format.seek(SeekMode::Accurate, seek_to) {
+            Ok(seeked) => {
+                self.decoder.reset();
+                // 容器定位到的数据包可能早于目标位置，差值换算成样本后丢弃
+                let time_base = self.decoder.codec_params().time_base;
+                let behind = seeked.required_ts.saturating_sub(seeked.actual_ts);
+                let frames = match time_base {
+                    Some(time_base) => {
+                        let time = time_base.calc_time(behind);
+                        ((time.seconds as f64 + time.frac) * frames_per_second) as usize
+                    }
+                    None => behind as usize,
+                };
+                self.skip_samples = frames * channels;
+            }
+            // 不支持定位的流只能从头解码再丢弃
+            Err(_) => {
+                self.skip_samples = (position.as_secs_f64() * frames_per_second) as usize * channels;
+            }
+        }
+    }
+
+    /// 解码下一个数据包，流结束或出现无法恢复的错误时返回 false
+    fn decode_next(&mut self) -> bool {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => return false,
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let capacity = decoded.capacity() as u64;
+                    let reuse = self.buffer.as_ref().is_some_and(|buffer| {
+                        buffer.capacity() as u64 >= capacity * spec.channels.count() as u64 && self.spec == spec
+                    });
+                    if !reuse {
+                        self.buffer = Some(SampleBuffer::new(capacity, spec));
+                        self.spec = spec;
+                    }
+                    if let Some(buffer) = self.buffer.as_mut() {
+                        buffer.copy_interleaved_ref(decoded);
+                        self.position = 0;
+                        if !buffer.samples().is_empty() {
+                            return true;
+                        }
+                    }
+                }
+                // 损坏的数据包直接跳过
+                Err(Error::DecodeError(_)) => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.buffer
+            .as_ref()
+            .map(|buffer| buffer.samples().len().saturating_sub(self.position))
+            .unwrap_or(0)
+    }
+}
+
+impl Iterator for StreamingDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if self.remaining() == 0 && !self.decode_next() {
+                return None;
+            }
+            let sample = self.buffer.as_ref()?.samples()[self.position];
+            self.position += 1;
+            if self.skip_samples > 0 {
+                self.skip_samples -= 1;
+                continue;
+            }
+            return Some(sample);
+        }
+    }
+}
+
+impl Source for StreamingDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        // 声道数或采样率只可能在数据包之间变化
+        match self.remaining() {
+            0 => None,
+            remaining => Some(remaining),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.spec.channels.count() as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.spec.rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::PathBuf;
+
+    use crate::app::rodio_decoder;
+
+    /// tests/fixtures 中的文件由 make_fixtures.py 生成
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+    }
+
+    fn decode(name: &str) -> (u16, u32, Vec<f32>) {
+        let path = fixture(name);
+        let decoder = StreamingDecoder::new(Box::new(File::open(&path).unwrap()), &path, Duration::ZERO).unwrap();
+        (decoder.channels(), decoder.sample_rate(), decoder.collect())
+    }
+
+    /// make_fixtures.py 中写进 ALAC 文件的正弦波
+    fn sine(index: usize, channel: usize) -> f32 {
+        let phase = 2.0 * std::f64::consts::PI * (440.0 + 220.0 * channel as f64) * index as f64 / 44100.0;
+        (8000.0 * phase.sin()) as i16 as f32 / 32768.0
+    }
+
+    #[test]
+    fn aac_in_mp4() {
+        let (channels, rate, samples) = decode("aac-lc.m4a");
+        assert_eq!((channels, rate), (1, 44100));
+        assert_eq!(samples.len(), 10 * 1024);
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn adts_aac() {
+        let (channels, rate, samples) = decode("adts.aac");
+        assert_eq!((channels, rate), (1, 44100));
+        assert_eq!(samples.len(), 10 * 1024);
+    }
+
+    #[test]
+    fn alac_in_mp4_is_lossless() {
+        let (channels, rate, samples) = decode("alac.m4a");
+        assert_eq!((channels, rate), (2, 44100));
+        // 第二帧只有 1000 个样本
+        assert_eq!(samples.len(), (4096 + 1000) * 2);
+        for (index, frame) in samples.chunks(2).enumerate() {
+            assert_eq!(frame, [sine(index, 0), sine(index, 1)], "样本 {}", index);
+        }
+    }
+
+    #[test]
+    fn seek_inside_alac() {
+        let path = fixture("alac.m4a");
+        let start = Duration::from_secs_f64(5000.0 / 44100.0);
+        let decoder = StreamingDecoder::new(Box::new(File::open(&path).unwrap()), &path, start).unwrap();
+        let samples: Vec<f32> = decoder.collect();
+        assert_eq!(samples.len(), 96 * 2);
+        assert_eq!(samples[..2], [sine(5000, 0), sine(5000, 1)]);
+    }
+
+    #[test]
+    fn rodio_fallback_decoders() {
+        for (name, channels, length) in [("aac-lc.m4a", 1, 10 * 1024), ("adts.aac", 1, 10 * 1024), ("alac.m4a", 2, 5096 * 2)] {
+            let path = fixture(name);
+            let decoder = rodio_decoder(BufReader::new(File::open(&path).unwrap()), &path).unwrap();
+            assert_eq!((decoder.channels(), decoder.sample_rate()), (channels, 44100), "{}", name);
+            assert_eq!(decoder.count(), length, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::ratings;
+
+/// 新建 ID3v2 标签时预留的填充，之后再改标签时不必挪动音频数据
+const ID3_PADDING: usize = 1024;
+
+/// 写入 POPM 帧时使用的邮箱字段。大多数播放器都读取 Windows Media Player 写入的评分
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+/// ID3v2 帧: (帧 ID, 标志, 内容)
+pub type Id3Frame = ([u8; 4], [u8; 2], Vec<u8>);
+
+/// 把自定义文本标签写回文件，已有的同名标签会被替换。
+/// FLAC 写入 Vorbis comment，MP3 写入 ID3v2 的 TXXX 帧。
+pub fn write_text_tags(path: &Path, tags: &[(&str, String)]) -> Result<()> {
+    update_file(
+        path,
+        |comments| {
+            comments.retain(|comment| {
+                let key = comment.split('=').next().unwrap_or_default();
+                !tags.iter().any(|(name, _)| name.eq_ignore_ascii_case(key))
+            });
+            comments.extend(tags.iter().map(|(name, value)| format!("{}={}", name, value)));
+        },
+        |frames| {
+            frames.retain(|(id, _, body)| {
+                if id != b"TXXX" {
+                    return true;
+                }
+                let description = txxx_description(body);
+                !tags.iter().any(|(name, _)| name.eq_ignore_ascii_case(&description))
+            });
+            for (name, value) in tags {
+                // 编码 0 (ISO-8859-1)，描述和值之间以 0 分隔
+                let mut body = vec![0u8];
+                body.extend(name.as_bytes());
+                body.push(0);
+                body.extend(value.as_bytes());
+                frames.push((*b"TXXX", [0, 0], body));
+            }
+        },
+    )
+}
+
+/// 把评分写回文件，0 星表示清除评分。
+/// FLAC 写入 0-100 的 RATING，MP3 写入 POPM 帧（替换其他播放器写入的评分）
+pub fn write_rating(path: &Path, stars: u8) -> Result<()> {
+    update_file(
+        path,
+        |comments| {
+            comments.retain(|comment| {
+                let key = comment.split('=').next().unwrap_or_default();
+                !key.eq_ignore_ascii_case("RATING")
+            });
+            if stars > 0 {
+                comments.push(format!("RATING={}", ratings::stars_to_percent(stars)));
+            }
+        },
+        |frames| {
+            frames.retain(|(id, _, _)| id != b"POPM");
+            if stars > 0 {
+                // 邮箱以 0 结尾，然后是评分字节（不写播放次数）
+                let mut body = POPM_EMAIL.as_bytes().to_vec();
+                body.push(0);
+                body.push(ratings::stars_to_popm(stars));
+                frames.push((*b"POPM", [0, 0], body));
+            }
+        },
+    )
+}
+
+/// 把流派写回文件，替换已有的流派。FLAC 写入 GENRE，MP3 写入 TCON 帧
+pub fn write_genre(path: &Path, genre: &str) -> Result<()> {
+    update_file(
+        path,
+        |comments| {
+            comments.retain(|comment| {
+                let key = comment.split('=').next().unwrap_or_default();
+                !key.eq_ignore_ascii_case("GENRE")
+            });
+            comments.push(format!("GENRE={}", genre));
+        },
+        |frames| {
+            frames.retain(|(id, _, _)| id != b"TCON");
+            // 编码 1 (带 BOM 的 UTF-16)，ID3v2.3 和 2.4 都支持，流派可以有中文
+            let mut body = vec![1u8, 0xFF, 0xFE];
+            body.extend(genre.encode_utf16().flat_map(u16::to_le_bytes));
+            frames.push((*b"TCON", [0, 0], body));
+        },
+    )
+}
+
+/// 按文件格式修改 Vorbis comment 或 ID3v2 帧，然后写回文件
+fn update_file(
+    path: &Path,
+    edit_comments: impl FnOnce(&mut Vec<String>),
+    edit_frames: impl FnOnce(&mut Vec<Id3Frame>),
+) -> Result<()> {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let data = fs::read(path)?;
+
+    let updated = match ext.as_str() {
+        "flac" => update_flac(&data, edit_comments)?,
+        "mp3" => update_mp3(&data, edit_frames)?,
+        _ => bail!("暂不支持写入 {} 文件的标签", ext),
+    };
+    replace_file(path, &updated)
+}
+
+/// 先写入同目录下的临时文件再改名，写到一半出错也不会损坏原文件
+fn replace_file(path: &Path, data: &[u8]) -> Result<()> {
+    let name = path.file_name().ok_or_else(|| anyhow!("无效的文件路径"))?;
+    let temp = path.with_file_name(format!(".{}.cuper-tmp", name.to_string_lossy()));
+    fs::write(&temp, data)?;
+    if let Err(err) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+fn update_flac(data: &[u8], edit: impl FnOnce(&mut Vec<String>)) -> Result<Vec<u8>> {
+    if !data.starts_with(b"fLaC") {
+        bail!("不是有效的 FLAC 文件");
+    }
+
+    // 读出所有元数据块: (类型, 内容)
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut offset = 4;
+    loop {
+        let header = data.get(offset..offset + 4).ok_or_else(|| anyhow!("FLAC 元数据块不完整"))?;
+        let last = header[0] & 0x80 != 0;
+        let kind = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let body = data
+            .get(offset + 4..offset + 4 + length)
+            .ok_or_else(|| anyhow!("FLAC 元数据块不完整"))?;
+        blocks.push((kind, body.to_vec()));
+        offset += 4 + length;
+        if last {
+            break;
+        }
+    }
+
+    let (vendor, mut comments) = match blocks.iter().find(|(kind, _)| *kind == 4) {
+        Some((_, body)) => parse_vorbis_comment(body)?,
+        None => ("cuper".to_string(), Vec::new()),
+    };
+    edit(&mut comments);
+
+    let mut body = Vec::new();
+    body.extend((vendor.len() as u32).to_le_bytes());
+    body.extend(vendor.as_bytes());
+    body.extend((comments.len() as u32).to_le_bytes());
+    for comment in &comments {
+        body.extend((comment.len() as u32).to_le_bytes());
+        body.extend(comment.as_bytes());
+    }
+
+    match blocks.iter_mut().find(|(kind, _)| *kind == 4) {
+        Some(block) => block.1 = body,
+        // 紧跟在 STREAMINFO 之后插入
+        None => blocks.insert(1.min(blocks.len()), (4, body)),
+    }
+
+    let mut output = b"fLaC".to_vec();
+    let count = blocks.len();
+    for (i, (kind, body)) in blocks.iter().enumerate() {
+        let flag = if i + 1 == count { 0x80 } else { 0 };
+        if body.len() >= 1 << 24 {
+            bail!("FLAC 元数据块过大");
+        }
+        output.push(flag | kind);
+        output.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        output.extend(body);
+    }
+    output.extend(&data[offset..]);
+    Ok(output)
+}
+
+fn parse_vorbis_comment(body: &[u8]) -> Result<(String, Vec<String>)> {
+    let mut offset = 0;
+    let read_string = |offset: &mut usize| -> Result<String> {
+        let length = body
+            .get(*offset..*offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            .ok_or_else(|| anyhow!("Vorbis comment 不完整"))?;
+        let text = body
+            .get(*offset + 4..*offset + 4 + length)
+            .ok_or_else(|| anyhow!("Vorbis comment 不完整"))?;
+        *offset += 4 + length;
+        Ok(String::from_utf8_lossy(text).to_string())
+    };
+
+    let vendor = read_string(&mut offset)?;
+    let count = body
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| anyhow!("Vorbis comment 不完整"))?;
+    offset += 4;
+
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        comments.push(read_string(&mut offset)?);
+    }
+    Ok((vendor, comments))
+}
+
+fn synchsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, byte| (acc << 7) | (*byte as usize & 0x7F))
+}
+
+fn to_synchsafe(value: usize) -> [u8; 4] {
+    [
+        ((value >> 21) & 0x7F) as u8,
+        ((value >> 14) & 0x7F) as u8,
+        ((value >> 7) & 0x7F) as u8,
+        (value & 0x7F) as u8,
+    ]
+}
+
+/// 读取 ID3v2 标签中的所有帧。data 从文件开头开始，至少包含整个标签；没有 ID3v2 标签时为空
+pub fn read_id3_frames(data: &[u8]) -> Result<Vec<Id3Frame>> {
+    Ok(parse_id3(data)?.1)
+}
+
+/// ID3v2 标签: (版本, 帧, 音频数据开始的位置)
+fn parse_id3(data: &[u8]) -> Result<(u8, Vec<Id3Frame>, usize)> {
+    if !data.starts_with(b"ID3") || data.len() < 10 {
+        return Ok((4, Vec::new(), 0));
+    }
+    let version = data[3];
+    let flags = data[5];
+    if version != 3 && version != 4 {
+        bail!("不支持 ID3v2.{} 标签", version);
+    }
+    if flags & 0xC0 != 0 {
+        bail!("不支持带非同步化或扩展头的 ID3v2 标签");
+    }
+    let size = synchsafe(&data[6..10]);
+    let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+    let tag = data.get(10..10 + size).ok_or_else(|| anyhow!("ID3v2 标签不完整"))?;
+
+    let mut frames: Vec<Id3Frame> = Vec::new();
+    let mut offset = 0;
+    while offset + 10 <= tag.len() && tag[offset] != 0 {
+        let id = [tag[offset], tag[offset + 1], tag[offset + 2], tag[offset + 3]];
+        let length = if version == 4 {
+            synchsafe(&tag[offset + 4..offset + 8])
+        } else {
+            u32::from_be_bytes([tag[offset + 4], tag[offset + 5], tag[offset + 6], tag[offset + 7]]) as usize
+        };
+        let frame_flags = [tag[offset + 8], tag[offset + 9]];
+        let body = tag
+            .get(offset + 10..offset + 10 + length)
+            .ok_or_else(|| anyhow!("ID3v2 帧不完整"))?;
+        frames.push((id, frame_flags, body.to_vec()));
+        offset += 10 + length;
+    }
+    Ok((version, frames, 10 + size + footer))
+}
+
+/// ID3v2 标签的总长度（包括 10 字节的头），header 是文件开头的 10 个字节
+pub fn id3_tag_length(header: &[u8]) -> Option<usize> {
+    (header.len() >= 10 && header.starts_with(b"ID3")).then(|| 10 + synchsafe(&header[6..10]))
+}
+
+fn update_mp3(data: &[u8], edit: impl FnOnce(&mut Vec<Id3Frame>)) -> Result<Vec<u8>> {
+    // 已有的 ID3v2 帧
+    let (version, mut frames, audio_start) = parse_id3(data)?;
+
+    edit(&mut frames);
+
+    let mut tag = Vec::new();
+    for (id, flags, body) in &frames {
+        tag.extend(id);
+        if version == 4 {
+            tag.extend(to_synchsafe(body.len()));
+        } else {
+            tag.extend((body.len() as u32).to_be_bytes());
+        }
+        tag.extend(flags);
+        tag.extend(body);
+    }
+    tag.extend(vec![0u8; ID3_PADDING]);
+
+    let mut output = b"ID3".to_vec();
+    output.extend([version, 0, 0]);
+    output.extend(to_synchsafe(tag.len()));
+    output.extend(tag);
+    output.extend(data.get(audio_start..).unwrap_or_default());
+    Ok(output)
+}
+
+/// TXXX 帧的描述部分
+fn txxx_description(body: &[u8]) -> String {
+    let Some((encoding, text)) = body.split_first() else {
+        return String::new();
+    };
+    match encoding {
+        // UTF-16，描述以两个 0 字节结束
+        1 | 2 => {
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|pair| {
+                    if *encoding == 2 {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                })
+                .take_while(|unit| *unit != 0)
+                .filter(|unit| *unit != 0xFEFF)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => {
+            let end = text.iter().position(|byte| *byte == 0).unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).to_string()
+        }
+    }
+}
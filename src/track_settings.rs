@@ -0,0 +1,225 @@
+use anyhow::Result;
+use rodio::Source;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::Song;
+use crate::storage;
+
+const SETTINGS_FILE: &str = "track_settings.tsv";
+const ALBUM_FILE: &str = "album_settings.tsv";
+
+/// 可以在歌曲属性窗口中编辑的字段，最后一项是整张专辑的设置
+pub const FIELDS: [&str; 4] = ["跳过开头", "结尾淡出", "前置增益", "专辑跳过"];
+pub const ALBUM_FIELD: usize = 3;
+
+/// 专辑设置的键: (专辑艺术家或艺术家, 专辑名)，不区分大小写
+pub type AlbumKey = (String, String);
+
+/// 单首歌曲的播放设置，每次播放该文件时自动应用
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrackSettings {
+    /// 从头播放时跳过的秒数
+    pub intro_skip: f32,
+    /// 结尾淡出的秒数
+    pub outro_fade: f32,
+    /// 前置增益 (dB)
+    pub preamp_db: f32,
+}
+
+impl TrackSettings {
+    pub fn is_default(&self) -> bool {
+        *self == TrackSettings::default()
+    }
+
+    pub fn intro_skip(&self) -> Duration {
+        Duration::from_secs_f32(self.intro_skip.max(0.0))
+    }
+
+    /// 按步调整一个字段：时长每步 0.5 秒，增益每步 0.5 dB
+    pub fn adjust(&mut self, field: usize, steps: i32) {
+        let delta = steps as f32 * 0.5;
+        match field {
+            0 => self.intro_skip = (self.intro_skip + delta).clamp(0.0, 600.0),
+            1 => self.outro_fade = (self.outro_fade + delta).clamp(0.0, 30.0),
+            2 => self.preamp_db = (self.preamp_db + delta).clamp(-12.0, 12.0),
+            _ => {}
+        }
+    }
+
+    pub fn reset(&mut self, field: usize) {
+        match field {
+            0 => self.intro_skip = 0.0,
+            1 => self.outro_fade = 0.0,
+            2 => self.preamp_db = 0.0,
+            _ => {}
+        }
+    }
+
+    /// 实际跳过的开头：单曲没有设置时使用整张专辑的设置（现场专辑开头的掌声、有声书的片头等）
+    pub fn effective_intro_skip(&self, album_skip: Option<f32>) -> Duration {
+        match self.intro_skip > 0.0 {
+            true => self.intro_skip(),
+            false => Duration::from_secs_f32(album_skip.unwrap_or_default().max(0.0)),
+        }
+    }
+
+    pub fn label(&self, field: usize) -> String {
+        match field {
+            0 if self.intro_skip > 0.0 => format!("{:.1} 秒", self.intro_skip),
+            1 if self.outro_fade > 0.0 => format!("{:.1} 秒", self.outro_fade),
+            2 => format!("{:+.1} dB", self.preamp_db),
+            _ => "关闭".to_string(),
+        }
+    }
+}
+
+pub fn load() -> HashMap<PathBuf, TrackSettings> {
+    let records = storage::read_records(SETTINGS_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 跳过开头秒数 \t 淡出秒数 \t 前置增益 dB \t 文件路径
+            let [intro_skip, outro_fade, preamp_db, path] = <[String; 4]>::try_from(record).ok()?;
+            let settings = TrackSettings {
+                intro_skip: intro_skip.parse().ok()?,
+                outro_fade: outro_fade.parse().ok()?,
+                preamp_db: preamp_db.parse().ok()?,
+            };
+            Some((storage::path_from_field(&path), settings))
+        })
+        .collect()
+}
+
+pub fn save(settings: &HashMap<PathBuf, TrackSettings>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = settings
+        .iter()
+        .filter(|(_, settings)| !settings.is_default())
+        .map(|(path, settings)| {
+            vec![
+                format!("{:.1}", settings.intro_skip),
+                format!("{:.1}", settings.outro_fade),
+                format!("{:.1}", settings.preamp_db),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[3].cmp(&b[3]));
+    storage::write_records(SETTINGS_FILE, &records)
+}
+
+pub fn album_key(song: &Song) -> Option<AlbumKey> {
+    let album = song.tags.album.as_ref()?;
+    let artist = song.tags.album_artist.as_ref().or(song.tags.artist.as_ref());
+    Some((artist.map(|artist| artist.to_lowercase()).unwrap_or_default(), album.to_lowercase()))
+}
+
+/// 整张专辑的跳过开头秒数
+pub fn load_albums() -> HashMap<AlbumKey, f32> {
+    let records = storage::read_records(ALBUM_FILE).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 跳过开头秒数 \t 专辑艺术家 \t 专辑名
+            let [intro_skip, artist, album] = <[String; 3]>::try_from(record).ok()?;
+            Some(((artist, album), intro_skip.parse().ok()?))
+        })
+        .collect()
+}
+
+pub fn save_albums(albums: &HashMap<AlbumKey, f32>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = albums
+        .iter()
+        .filter(|(_, intro_skip)| **intro_skip > 0.0)
+        .map(|((artist, album), intro_skip)| vec![format!("{:.1}", intro_skip), artist.clone(), album.clone()])
+        .collect();
+    records.sort_by(|a, b| (&a[1], &a[2]).cmp(&(&b[1], &b[2])));
+    storage::write_records(ALBUM_FILE, &records)
+}
+
+/// 应用前置增益、开头淡入和结尾淡出的 Source 适配器
+pub struct TrackGain<S> {
+    input: S,
+    gain: f32,
+    /// 当前在歌曲中的位置（秒）
+    position: f64,
+    fade_start: f64,
+    fade_length: f64,
+    /// 淡入在这个位置结束，手动切歌时从开始位置淡入
+    fade_in_end: f64,
+    fade_in_length: f64,
+}
+
+impl<S> TrackGain<S>
+where
+    S: Source<Item = f32>,
+{
+    /// start 是解码开始的位置，total 是歌曲总时长（未知时为 0，不做淡出）
+    pub fn new(input: S, settings: TrackSettings, start: Duration, total: Duration) -> Self {
+        let fade_length = if total.is_zero() { 0.0 } else { settings.outro_fade.max(0.0) as f64 };
+        TrackGain {
+            input,
+            gain: 10f32.powf(settings.preamp_db / 20.0),
+            position: start.as_secs_f64(),
+            fade_start: total.as_secs_f64() - fade_length,
+            fade_length,
+            fade_in_end: 0.0,
+            fade_in_length: 0.0,
+        }
+    }
+
+    /// 从开始位置起用 length 淡入
+    pub fn with_fade_in(mut self, length: Duration) -> Self {
+        self.fade_in_length = length.as_secs_f64();
+        self.fade_in_end = self.position + self.fade_in_length;
+        self
+    }
+}
+
+impl<S> Iterator for TrackGain<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let samples_per_second = self.input.sample_rate().max(1) as f64 * self.input.channels().max(1) as f64;
+        self.position += 1.0 / samples_per_second;
+
+        let mut gain = self.gain;
+        if self.fade_length > 0.0 && self.position > self.fade_start {
+            gain *= (1.0 - (self.position - self.fade_start) / self.fade_length).clamp(0.0, 1.0) as f32;
+        }
+        if self.fade_in_length > 0.0 && self.position < self.fade_in_end {
+            gain *= (1.0 - (self.fade_in_end - self.position) / self.fade_in_length).clamp(0.0, 1.0) as f32;
+        }
+        Some((sample * gain).clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for TrackGain<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
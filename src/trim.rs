@@ -0,0 +1,142 @@
+use anyhow::{anyhow, bail, Result};
+use rodio::Source;
+use std::fs;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::streaming::StreamingDecoder;
+
+/// 写 WAV 时每处理这么多个样本检查一次是否取消
+const CANCEL_CHECK: usize = 1 << 16;
+
+/// 片段是怎样导出的
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    /// ffmpeg 直接复制音频数据，不重新编码
+    Copy,
+    /// 格式不同或无法直接复制时，ffmpeg 按目标文件的扩展名重新编码
+    Encode,
+    /// 没有安装 ffmpeg，用内置的解码器解码后写成 16 位 WAV
+    Wav,
+}
+
+impl Method {
+    pub fn label(self) -> &'static str {
+        match self {
+            Method::Copy => "无损复制",
+            Method::Encode => "重新编码",
+            Method::Wav => "WAV",
+        }
+    }
+}
+
+/// 默认的输出文件：放在原文件旁边，文件名加上片段的起止时间
+pub fn default_output(source: &Path, start: Duration, end: Duration) -> PathBuf {
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = source.extension().map(|extension| extension.to_string_lossy()).unwrap_or("wav".into());
+    let time = |position: Duration| format!("{}m{:02}s", position.as_secs() / 60, position.as_secs() % 60);
+    source.with_file_name(format!("{} ({}-{}).{}", stem, time(start), time(end), extension))
+}
+
+/// 把 start..end 导出到 dest。扩展名和原文件相同时先尝试不重新编码的复制，失败再重新编码；
+/// 没有 ffmpeg 时写成 WAV（扩展名改为 .wav）。返回实际写入的文件和导出方式
+pub fn export(source: &Path, start: Duration, end: Duration, dest: &Path, cancel: &AtomicBool) -> Result<(PathBuf, Method)> {
+    if end <= start {
+        bail!("片段的结束点要在开始点之后");
+    }
+    if dest.exists() {
+        bail!("{} 已存在", dest.display());
+    }
+    let extension = |path: &Path| path.extension().map(|extension| extension.to_ascii_lowercase());
+    let method = match extension(source) == extension(dest) {
+        true => Method::Copy,
+        false => Method::Encode,
+    };
+    let result = match ffmpeg(source, start, end, dest, method) {
+        Err(err) if method == Method::Copy && err.kind() != io::ErrorKind::NotFound => {
+            let _ = fs::remove_file(dest);
+            ffmpeg(source, start, end, dest, Method::Encode).map(|()| Method::Encode)
+        }
+        result => result.map(|()| method),
+    };
+    match result {
+        Ok(method) => Ok((dest.to_path_buf(), method)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let dest = dest.with_extension("wav");
+            if dest.exists() {
+                bail!("{} 已存在", dest.display());
+            }
+            if let Err(err) = write_wav(source, start, end, &dest, cancel) {
+                let _ = fs::remove_file(&dest);
+                return Err(err);
+            }
+            Ok((dest, Method::Wav))
+        }
+        Err(err) => {
+            let _ = fs::remove_file(dest);
+            Err(anyhow!("ffmpeg 导出失败: {}", err))
+        }
+    }
+}
+
+/// 只导出音频流（不带封面），Copy 时不重新编码
+fn ffmpeg(source: &Path, start: Duration, end: Duration, dest: &Path, method: Method) -> io::Result<()> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-n", "-ss", &format!("{:.3}", start.as_secs_f64()), "-i"])
+        .arg(source)
+        .args(["-t", &format!("{:.3}", (end - start).as_secs_f64()), "-map", "0:a"]);
+    if method == Method::Copy {
+        command.args(["-c", "copy"]);
+    }
+    let output = command.arg(dest).stdin(Stdio::null()).stdout(Stdio::null()).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().last().unwrap_or_default().trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(())
+}
+
+fn write_wav(source: &Path, start: Duration, end: Duration, dest: &Path, cancel: &AtomicBool) -> Result<()> {
+    let decoder = StreamingDecoder::new(Box::new(fs::File::open(source)?), source, start)?;
+    let channels = decoder.channels().max(1);
+    let sample_rate = decoder.sample_rate();
+    let samples = ((end - start).as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+
+    let mut writer = BufWriter::new(fs::File::create(dest)?);
+    write_wav_header(&mut writer, channels, sample_rate, 0)?;
+    let mut written = 0;
+    for sample in decoder.take(samples) {
+        if written % CANCEL_CHECK == 0 && cancel.load(Ordering::Relaxed) {
+            bail!("已取消");
+        }
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        writer.write_all(&value.to_le_bytes())?;
+        written += 1;
+    }
+    // 文件结束得比 B 点早时按实际写入的长度修正文件头
+    writer.seek(SeekFrom::Start(0))?;
+    write_wav_header(&mut writer, channels, sample_rate, written as u32 * 2)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_wav_header(writer: &mut impl Write, channels: u16, sample_rate: u32, data_size: u32) -> io::Result<()> {
+    let block_align = channels * 2;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
@@ -1,25 +1,75 @@
 use anyhow::Result;
+use chrono::{Local, TimeZone};
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Line},
-    widgets::{Block, Borders, Gauge, List, ListItem, Paragraph},
+    widgets::{block::Title, Block, BorderType, Borders, Clear, Gauge, LineGauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
+use std::time::Duration;
 
-use crate::app::{App, PlaybackState};
+use crate::app::{self, App, EqMode, Focus, PlaybackState, PromptKind, Song, View};
+use crate::equalizer;
+use crate::genre;
+use crate::http_stream;
+use crate::jobs::JobState;
+use crate::keymap::{self, Action};
+use crate::library;
+use crate::metadata::Badge;
+use crate::now_playing;
+use crate::qr::QrCode;
+use crate::ratings;
+use crate::search::SearchHit;
+use crate::settings;
+use crate::status_line;
+use crate::track_settings;
+use crate::visualizer::VisualizerMode;
+use crate::waveform;
+
+/// 比这更小的终端放不下各个面板的边框，只显示一行提示
+const MIN_WIDTH: u16 = 32;
+const MIN_HEIGHT: u16 = 12;
 
 pub fn render(frame: &mut Frame, app: &App) -> Result<()> {
+    let size = frame.size();
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        render_too_small(frame, size);
+        return Ok(());
+    }
+    // 切歌提醒：在界面四周的留白处画一圈醒目的外框
+    if let Some(text) = app.active_flash() {
+        render_flash(frame, text);
+    }
+    if app.karaoke.is_enabled() {
+        return render_karaoke_screen(frame, app);
+    }
+    let visualizer_height = match app.visualizer.mode {
+        VisualizerMode::Off => 0,
+        _ => app.config.visualizer_height,
+    };
+    // 预览模式只保留播放状态、进度和播放列表
+    let controls_height = match app.preview {
+        Some(_) => 0,
+        None => app.config.controls_height,
+    };
+    // 音频错误的说明至少要两行
+    let now_playing_height = match app.audio_error {
+        Some(_) => app.config.now_playing_height.max(4),
+        None => app.config.now_playing_height,
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
         .constraints(
             [
                 Constraint::Length(3),  // Title
-                Constraint::Length(3),  // Now playing
+                Constraint::Length(now_playing_height),  // Now playing
                 Constraint::Length(3),  // Progress bar
-                Constraint::Length(3),  // Controls
+                Constraint::Length(controls_height),  // Controls
+                Constraint::Length(visualizer_height),  // Visualizer
                 Constraint::Min(0),     // Playlist
+                Constraint::Length(u16::from(!app.config.status_format.is_empty())),  // Status bar
             ]
             .as_ref(),
         )
@@ -28,15 +78,268 @@ pub fn render(frame: &mut Frame, app: &App) -> Result<()> {
     render_title(frame, app, chunks[0])?;
     render_now_playing(frame, app, chunks[1])?;
     render_progress(frame, app, chunks[2])?;
-    render_controls(frame, app, chunks[3])?;
-    render_playlist(frame, app, chunks[4])?;
+    if controls_height > 0 {
+        render_controls(frame, app, chunks[3])?;
+    }
+    if visualizer_height > 0 {
+        render_visualizer(frame, app, chunks[4]);
+    }
+    if !app.config.status_format.is_empty() {
+        render_status_bar(frame, app, chunks[6]);
+    }
+    // 任务面板显示在最下方，每个任务一行
+    let content_area = if app.jobs_visible {
+        let height = app.jobs.len().clamp(1, 6) as u16 + 2;
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(height)].as_ref())
+            .split(chunks[5]);
+        render_jobs(frame, app, rows[1])?;
+        rows[0]
+    } else {
+        chunks[5]
+    };
+    
+    // 编辑界面：曲库和队列各占一半
+    if app.view == View::Editor {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(content_area);
+        render_playlist(frame, app, columns[0])?;
+        render_queue(frame, app, columns[1])?;
+        return render_overlays(frame, app);
+    }
+    
+    // 队列、信息或歌词面板需要显示时与列表左右分屏，右侧一列上下排列
+    let show_queue = !app.queue.is_empty() || app.focus == Focus::Queue;
+    let side_panes = usize::from(show_queue) + usize::from(app.info_visible) + usize::from(app.lyrics_visible);
+    let main_area = if side_panes > 0 {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(100 - app.config.info_width),
+                    Constraint::Percentage(app.config.info_width),
+                ]
+                .as_ref(),
+            )
+            .split(content_area);
+        let side = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Ratio(1, side_panes as u32); side_panes])
+            .split(columns[1]);
+        let mut side = side.iter();
+        if show_queue {
+            render_queue(frame, app, *side.next().unwrap())?;
+        }
+        if app.info_visible {
+            render_info(frame, app, *side.next().unwrap())?;
+        }
+        if app.lyrics_visible {
+            render_lyrics(frame, app, *side.next().unwrap());
+        }
+        columns[0]
+    } else {
+        content_area
+    };
+
+    match app.view {
+        View::Bookmarks => render_bookmarks(frame, app, main_area)?,
+        View::Artists => render_artists(frame, app, main_area),
+        View::Collections => render_collections(frame, app, main_area),
+        View::Snapshots => render_snapshots(frame, app, main_area),
+        View::Genres => render_genres(frame, app, main_area),
+        View::Settings => render_settings(frame, app, main_area),
+        View::Playlist | View::Editor => render_playlist(frame, app, main_area)?,
+    }
+
+    render_overlays(frame, app)
+}
+
+fn render_flash(frame: &mut Frame, text: &str) {
+    let style = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Thick)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(format!(" {} ", text), style))
+        .title_alignment(Alignment::Center);
+    frame.render_widget(block, frame.size());
+}
+
+fn render_too_small(frame: &mut Frame, area: Rect) {
+    let text = format!("终端窗口太小，至少需要 {}×{}", MIN_WIDTH, MIN_HEIGHT);
+    let middle = Rect::new(area.x, area.y + area.height / 2, area.width, 1.min(area.height));
+    let paragraph = Paragraph::new(Span::styled(text, Style::default().fg(Color::Yellow)))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    frame.render_widget(paragraph, middle);
+}
+
+/// 卡拉 OK 模式只保留标题栏和进度条，中间全部用来显示歌词
+fn render_karaoke_screen(frame: &mut Frame, app: &App) -> Result<()> {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(frame.size());
+    render_title(frame, app, chunks[0])?;
+    render_karaoke(frame, app, chunks[1]);
+    render_progress(frame, app, chunks[2])?;
+    render_overlays(frame, app)
+}
+
+/// 当前行居中并按时间从左到右变色，上面是刚唱过的一行，下面预告接下来的几行
+fn render_karaoke(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("卡拉 OK（Z: 退出）");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    
+    let dim = Style::default().fg(Color::DarkGray);
+    let lyrics = app.lyrics.as_ref().and_then(|pane| pane.lyrics.as_ref());
+    let Some(lyrics) = lyrics.filter(|lyrics| lyrics.synced) else {
+        let text = match (&app.lyrics, lyrics) {
+            (Some(pane), None) if pane.loading => "正在在线查找歌词…".to_string(),
+            (Some(pane), None) => pane.status.clone(),
+            (_, Some(_)) => "这首歌的歌词没有时间轴".to_string(),
+            (None, None) => "没有歌词".to_string(),
+        };
+        let top = Rect::new(inner.x, inner.y + inner.height / 2, inner.width, 1.min(inner.height));
+        frame.render_widget(Paragraph::new(Span::styled(text, dim)).alignment(Alignment::Center), top);
+        return;
+    };
+    
+    let position = app.get_current_time();
+    let current = lyrics.current_line(position);
+    let next = current.map_or(0, |current| current + 1);
+    let mut lines = Vec::new();
+    
+    // 刚唱过的一行
+    match current.and_then(|current| current.checked_sub(1)) {
+        Some(previous) => lines.push(Line::from(Span::styled(lyrics.lines[previous].1.clone(), dim))),
+        None => lines.push(Line::from("")),
+    }
+    lines.push(Line::from(""));
+    
+    match current {
+        Some(current) => {
+            let (start, text) = &lyrics.lines[current];
+            let end = lyrics.lines.get(current + 1).map_or(*start + Duration::from_secs(5), |(time, _)| *time);
+            let length = end.saturating_sub(*start).min(Duration::from_secs(10)).max(Duration::from_millis(1));
+            let ratio = (position.saturating_sub(*start).as_secs_f32() / length.as_secs_f32()).min(1.0);
+            let chars: Vec<char> = text.chars().collect();
+            let sung = (chars.len() as f32 * ratio).round() as usize;
+            let bold = Style::default().add_modifier(Modifier::BOLD);
+            lines.push(Line::from(vec![
+                Span::styled(chars[..sung].iter().collect::<String>(), bold.fg(Color::Yellow)),
+                Span::styled(chars[sung..].iter().collect::<String>(), bold.fg(Color::White)),
+            ]));
+        }
+        // 前奏：最后三秒倒数
+        None => {
+            let first = lyrics.lines.first().map_or(Duration::ZERO, |(time, _)| *time);
+            let left = first.saturating_sub(position).as_secs();
+            let glyphs = app.config.glyphs.glyphs();
+            let text = if left < 3 { format!("{} ", glyphs.countdown).repeat(left as usize + 1) } else { glyphs.note.to_string() };
+            lines.push(Line::from(Span::styled(text.trim_end().to_string(), Style::default().fg(Color::Yellow))));
+        }
+    }
+    lines.push(Line::from(""));
+    
+    // 接下来的几行，越往后颜色越淡
+    for (offset, (_, text)) in lyrics.lines.iter().skip(next).take(3).enumerate() {
+        let color = if offset == 0 { Color::Cyan } else { Color::DarkGray };
+        lines.push(Line::from(Span::styled(text.clone(), Style::default().fg(color))));
+    }
+    
+    // 当前行固定在面板中间
+    let top = (inner.height / 2).saturating_sub(2);
+    let text_area = Rect::new(inner.x, inner.y + top, inner.width, inner.height - top);
+    frame.render_widget(Paragraph::new(lines).alignment(Alignment::Center), text_area);
+}
+
+/// 弹出窗口和确认框，显示在所有面板之上
+fn render_overlays(frame: &mut Frame, app: &App) -> Result<()> {
+    if app.track_editor.is_some() {
+        render_track_editor(frame, app, frame.size())?;
+    }
+
+    if app.details.is_some() {
+        render_details(frame, app, frame.size())?;
+    }
+
+    if app.search.is_some() {
+        render_search(frame, app, frame.size());
+    }
+
+    if app.prompt.is_some() {
+        render_prompt(frame, app, frame.size())?;
+    }
+
+    if app.confirm_quit {
+        render_quit_confirm(frame, frame.size())?;
+    }
+
+    if app.restore_prompt.is_some() {
+        render_restore_prompt(frame, app, frame.size())?;
+    }
+
+    if app.playlist_reload.is_some() {
+        render_playlist_reload(frame, app, frame.size());
+    }
+
+    if let Some(url) = &app.pairing {
+        render_pairing(frame, url, frame.size());
+    }
 
     Ok(())
 }
 
-fn render_title(frame: &mut Frame, _app: &App, area: Rect) -> Result<()> {
-    let title = Paragraph::new("🎵 Cuper Music TUI  🎵")
-        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+fn render_title(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let glyphs = app.config.glyphs.glyphs();
+    let mut spans = vec![Span::styled(
+        format!("{} Cuper Music TUI  {}", glyphs.note, glyphs.note),
+        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+    )];
+    if app.radio {
+        spans.push(Span::styled(format!("  {} 推荐电台", glyphs.radio), Style::default().fg(Color::Cyan)));
+    }
+    if app.preview.is_some() {
+        spans.push(Span::styled("  预览", Style::default().fg(Color::Cyan)));
+    }
+    let eq_name = equalizer::PRESETS[app.eq_preset].name;
+    let eq_status = match app.eq_mode {
+        EqMode::Auto => format!("  EQ: {} (自动)", eq_name),
+        EqMode::Manual(_) => format!("  EQ: {}", eq_name),
+    };
+    spans.push(Span::styled(eq_status, Style::default().fg(Color::Green)));
+    if app.config.leveler {
+        spans.push(Span::styled("  响度均衡", Style::default().fg(Color::Green)));
+    }
+    if app.config.automix {
+        spans.push(Span::styled("  自动混音", Style::default().fg(Color::Green)));
+    }
+    if app.config.power_saver {
+        spans.push(Span::styled("  省电", Style::default().fg(Color::Green)));
+    }
+    if let Some(alarm) = app.scheduler.next_alarm {
+        spans.push(Span::styled(
+            format!("  {} {}", glyphs.alarm, alarm.format("%H:%M")),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if let Some(message) = app.active_message() {
+        spans.push(Span::styled(format!("  {}", message), Style::default().fg(Color::Yellow)));
+    }
+    if let Some(keys) = app.pending_keys() {
+        spans.push(Span::styled(format!("  {}…", keys), Style::default().fg(Color::Cyan)));
+    }
+    if let Some(register) = app.macros.recording() {
+        spans.push(Span::styled(format!("  录制宏 @{}", register), Style::default().fg(Color::Red)));
+    }
+
+    let title = Paragraph::new(Line::from(spans))
         .block(Block::default().borders(Borders::ALL).title("Cuper Music Player"));
     
     frame.render_widget(title, area);
@@ -44,36 +347,69 @@ fn render_title(frame: &mut Frame, _app: &App, area: Rect) -> Result<()> {
 }
 
 fn render_now_playing(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
-    let current_song = app.get_current_song();
-    let song_name = current_song
-        .map(|song| song.name.clone())
-        .unwrap_or_else(|| "没有歌曲".to_string());
-
-    let status = match app.playback_state {
-        PlaybackState::Playing => "▶️ 播放中",
-        PlaybackState::Paused => "⏸️ 暂停",
-        PlaybackState::Stopped => "⏹️ 停止",
-    };
+    if let Some(err) = &app.audio_error {
+        render_audio_error(frame, app, err, area);
+        return Ok(());
+    }
+    // 内容由 now_playing_format 模板决定
+    let text = now_playing::lines(app.config.now_playing_format(), app);
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("播放状态"));
+    
+    frame.render_widget(paragraph, area);
+    Ok(())
+}
 
+/// 音频设备打不开时播放状态面板换成错误说明，其余界面照常浏览
+fn render_audio_error(frame: &mut Frame, app: &App, err: &str, area: Rect) {
     let text = vec![
         Line::from(vec![
-            Span::styled("当前播放: ", Style::default().fg(Color::Yellow)),
-            Span::styled(song_name, Style::default().fg(Color::White)),
-        ]),
-        Line::from(vec![
-            Span::styled("状态: ", Style::default().fg(Color::Yellow)),
-            Span::styled(status, Style::default().fg(Color::Green)),
+            Span::styled("错误: ", Style::default().fg(Color::Red)),
+            Span::styled(err.to_string(), Style::default().fg(Color::White)),
         ]),
+        Line::from(Span::styled(
+            format!(
+                "检查声音服务器或其他占用设备的程序，然后按 {} 重新打开",
+                app.keymap.action_label(Action::RetryAudio)
+            ),
+            Style::default().fg(Color::Yellow),
+        )),
     ];
-
-    let paragraph = Paragraph::new(text)
-        .block(Block::default().borders(Borders::ALL).title("播放状态"));
-    
+    let paragraph = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title("音频设备不可用（只能浏览）"),
+    );
     frame.render_widget(paragraph, area);
-    Ok(())
 }
 
-fn format_duration(duration: std::time::Duration) -> String {
+/// 最下方一行的状态栏，内容由配置中的 status_format 模板决定
+fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let text = status_line::format(&app.config.status_format, app);
+    let bar = Paragraph::new(text).style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    frame.render_widget(bar, area);
+}
+
+/// 列表标题中的总时长，如 `9 小时 37 分`；有歌曲时长未知时前面加 `≥`
+fn list_summary<'a>(songs: impl IntoIterator<Item = &'a Song>) -> String {
+    let (total, unknown) = app::total_duration(songs);
+    let prefix = if unknown { "≥ " } else { "" };
+    format!("{}{}", prefix, format_long_duration(total))
+}
+
+fn format_long_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    let hours = total_secs / 3600;
+    let minutes = total_secs % 3600 / 60;
+    match (hours, minutes) {
+        (0, 0) => format!("{} 秒", total_secs),
+        (0, minutes) => format!("{} 分", minutes),
+        (hours, minutes) => format!("{} 小时 {} 分", hours, minutes),
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
     let total_secs = duration.as_secs();
     let minutes = total_secs / 60;
     let seconds = total_secs % 60;
@@ -93,15 +429,49 @@ fn render_progress(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
         PlaybackState::Stopped => Color::Gray,
     };
     
-    let time_label = format!("{} / {}", 
-        format_duration(current_time), 
-        format_duration(total_duration)
-    );
+    let glyphs = app.config.glyphs.glyphs();
+    // 时长未知时只显示已播放的时间
+    let total_label = match total_duration {
+        Some(total) => format!(" / {}", format_duration(total)),
+        None => " (时长未知)".to_string(),
+    };
+    let (title, time_label) = match app.scrub {
+        Some(position) => (
+            format!(
+                "播放进度 [拖动: {} 5秒  {} 30秒  Enter 跳转  Esc 取消]",
+                glyphs.arrows_horizontal, glyphs.arrows_vertical
+            ),
+            format!("{} {} {}{}",
+                format_duration(current_time),
+                glyphs.arrow_right,
+                format_duration(position),
+                total_label
+            ),
+        ),
+        None => {
+            let mut title = "播放进度".to_string();
+            if let Some((_, name)) = app.current_chapter() {
+                title.push_str(&format!(" · {}", name));
+            }
+            if let Some(ab_loop) = app.current_loop() {
+                match ab_loop.end {
+                    Some(end) => title.push_str(&format!(
+                        " [A-B 循环 {}-{}]",
+                        format_duration(ab_loop.start),
+                        format_duration(end)
+                    )),
+                    None => title.push_str(&format!(" [A 点 {}]", format_duration(ab_loop.start))),
+                }
+            }
+            (title, format!("{}{}", format_duration(current_time), total_label))
+        }
+    };
     
+    let label_width = time_label.chars().count() as u16;
     let progress_gauge = Gauge::default()
-        .block(Block::default().borders(Borders::ALL).title("播放进度"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .gauge_style(Style::default().fg(progress_color))
-        .ratio(progress as f64)
+        .ratio(progress.unwrap_or(0.0) as f64)
         .label(time_label);
 
     let volume_gauge = Gauge::default()
@@ -118,11 +488,81 @@ fn render_progress(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
     frame.render_widget(progress_gauge, chunks[0]);
     frame.render_widget(volume_gauge, chunks[1]);
     
+    // 时长未知：不确定进度的样式，一小段色块随播放时间来回移动，暂停时停住
+    let inner = Block::default().borders(Borders::ALL).inner(chunks[0]);
+    if progress.is_none() && app.playback_state != PlaybackState::Stopped && inner.width > 0 {
+        let segment = (inner.width / 5).max(1);
+        let travel = (inner.width - segment) as f64;
+        let phase = current_time.as_secs_f64() % 4.0 / 2.0;
+        let offset = (if phase > 1.0 { 2.0 - phase } else { phase } * travel).round() as u16;
+        for x in inner.x + offset..inner.x + offset + segment {
+            for y in inner.y..inner.y + inner.height {
+                frame.buffer_mut().get_mut(x, y).set_bg(progress_color);
+            }
+        }
+    }
+    
+    // 章节开头、书签和 A-B 循环的位置，盖住中间时间文字的不画
+    if let Some(total) = total_duration.filter(|total| !total.is_zero()) {
+        if inner.width > 0 && inner.height > 0 {
+            let mut markers: Vec<(Duration, String, Color)> = app
+                .current_chapters()
+                .iter()
+                .filter(|(start, _)| !start.is_zero())
+                .map(|(start, _)| (*start, glyphs.chapter.to_string(), Color::White))
+                .collect();
+            // DJ 提示点用软件中设置的颜色，热提示点显示编号
+            markers.extend(app.current_cues().iter().map(|cue| {
+                let glyph = cue.number().map_or_else(|| glyphs.cue.to_string(), String::from);
+                let color = cue.color.map_or(Color::Green, |(red, green, blue)| Color::Rgb(red, green, blue));
+                (cue.position, glyph, color)
+            }));
+            if let Some(song) = app.get_current_song() {
+                markers.extend(
+                    app.bookmarks
+                        .iter()
+                        .filter(|bookmark| bookmark.path == song.path)
+                        .map(|bookmark| (bookmark.position, glyphs.bookmark.to_string(), Color::Cyan)),
+                );
+            }
+            if let Some(ab_loop) = app.current_loop() {
+                markers.push((ab_loop.start, "A".to_string(), Color::Magenta));
+                markers.extend(ab_loop.end.map(|end| (end, "B".to_string(), Color::Magenta)));
+            }
+            let label_start = inner.x + inner.width.saturating_sub(label_width) / 2;
+            let label = label_start..label_start + label_width;
+            let y = inner.y + inner.height / 2;
+            for (position, glyph, color) in markers {
+                let ratio = (position.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0);
+                let x = inner.x + ((inner.width - 1) as f64 * ratio).round() as u16;
+                if !label.contains(&x) {
+                    frame.buffer_mut().get_mut(x, y).set_symbol(&glyph).set_fg(color);
+                }
+            }
+        }
+    }
+    
+    // 拖动模式下在进度条上画出虚拟光标
+    if let (Some(position), Some(total_duration)) = (app.scrub, total_duration) {
+        if inner.width > 0 && inner.height > 0 && !total_duration.is_zero() {
+            let ratio = (position.as_secs_f64() / total_duration.as_secs_f64()).clamp(0.0, 1.0);
+            let x = inner.x + ((inner.width - 1) as f64 * ratio).round() as u16;
+            for y in inner.y..inner.y + inner.height {
+                frame
+                    .buffer_mut()
+                    .get_mut(x, y)
+                    .set_symbol(glyphs.scrub_cursor)
+                    .set_fg(Color::Magenta);
+            }
+        }
+    }
+    
     Ok(())
 }
 
 fn render_controls(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
-    let shuffle_status = if app.is_shuffle { "🔀 随机播放开启" } else { "🔀 随机播放关闭" };
+    let shuffle = app.config.glyphs.glyphs().shuffle;
+    let shuffle_status = format!("{} {}", shuffle, app.shuffle.label());
     
     let controls_text = vec![
         Line::from(vec![
@@ -135,9 +575,11 @@ fn render_controls(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
         ]),
         Line::from(vec![
             Span::styled("S: ", Style::default().fg(Color::Yellow)),
-            Span::styled("切换随机播放", Style::default().fg(Color::White)),
+            Span::styled("切换随机模式", Style::default().fg(Color::White)),
             Span::styled("  +/-: ", Style::default().fg(Color::Yellow)),
             Span::styled("调节音量", Style::default().fg(Color::White)),
+            Span::styled("  b/B: ", Style::default().fg(Color::Yellow)),
+            Span::styled("添加/查看书签", Style::default().fg(Color::White)),
             Span::styled("  Q: ", Style::default().fg(Color::Yellow)),
             Span::styled("退出", Style::default().fg(Color::White)),
         ]),
@@ -156,30 +598,1027 @@ fn render_controls(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
     Ok(())
 }
 
+/// 示波器或频谱图，每个字符是 2x4 的盲文点阵
+fn render_visualizer(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("{}（v: 切换）", app.visualizer.mode.label()));
+    let inner = block.inner(area);
+    let lines = app.visualizer.lines(inner.width as usize, inner.height as usize);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
 fn render_playlist(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
-    let items: Vec<ListItem> = app
-        .songs
+    let visible: Vec<usize> = (0..app.songs.len()).filter(|&i| app.is_visible(i)).collect();
+    // 只为显示得下的几行生成 ListItem，曲库有几万首时每帧的开销也不变
+    let selected = visible.iter().position(|&index| index == app.playlist_selected);
+    let height = area.height.saturating_sub(2).max(1) as usize;
+    let offset = window_offset(app.playlist_offset.get(), selected.unwrap_or(0), height, visible.len());
+    app.playlist_offset.set(offset);
+    let items: Vec<ListItem> = visible[offset..visible.len().min(offset + height)]
         .iter()
-        .enumerate()
-        .map(|(index, song)| {
+        .map(|&index| {
+            let song = &app.songs[index];
+            let unavailable = app.unavailable_entry(&song.path);
             let style = if index == app.current_index {
                 Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else if song.missing {
+                Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+            } else if unavailable.is_some() || app.decode_errors.contains_key(&song.path) {
+                Style::default().fg(Color::DarkGray)
             } else {
                 Style::default().fg(Color::White)
             };
             
-            ListItem::new(vec![Line::from(vec![
-                Span::styled(format!("{:2}. ", index + 1), style),
-                Span::styled(song.name.clone(), style),
-            ])])
+            let mut spans = vec![Span::styled(format!("{:2}. ", index + 1), style)];
+            // 波形缩略图单独占一列，还没生成的留空对齐
+            if app.show_waveforms() {
+                let sparkline = app.waveforms.get(&song.path).map(|waveform| waveform.sparkline());
+                let column = format!("{:<width$} ", sparkline.unwrap_or_default(), width = waveform::WIDTH);
+                spans.push(Span::styled(column, Style::default().fg(Color::DarkGray)));
+            }
+            if song.missing {
+                spans.push(Span::styled("[缺失] ", Style::default().fg(Color::Red)));
+            } else if app.decode_errors.contains_key(&song.path) {
+                let label = format!("[{} 无法解码] ", app.config.glyphs.glyphs().error);
+                spans.push(Span::styled(label, Style::default().fg(Color::Red)));
+            } else if let Some(entry) = unavailable {
+                let label = if entry.checking { "[重试中] " } else { "[暂不可用] " };
+                spans.push(Span::styled(label, Style::default().fg(Color::Red)));
+            }
+            if let Some(position) = app.queue.iter().position(|&queued| queued == index) {
+                spans.push(Span::styled(format!("[队列 {}] ", position + 1), Style::default().fg(Color::Cyan)));
+            }
+            if http_stream::is_url(&song.path) {
+                let label = format!("[{} 网络] ", app.config.glyphs.glyphs().remote);
+                spans.push(Span::styled(label, Style::default().fg(Color::Magenta)));
+            }
+            spans.push(Span::styled(song.name.clone(), style));
+            if let Some(badge) = &song.badge {
+                spans.push(Span::styled(format!("  {}", badge.label), badge_style(badge)));
+            }
+            if let Some(over) = app.clipping_db(song) {
+                spans.push(Span::styled(format!("  [削波 {:+.1} dB]", over), Style::default().fg(Color::Red)));
+            }
+            if let Some(bpm) = app.bpms.get(&song.path) {
+                spans.push(Span::styled(format!("  {:.0} BPM", bpm), Style::default().fg(Color::DarkGray)));
+            }
+            if let Some(stars) = app.ratings.get(&song.path) {
+                let glyphs = app.config.glyphs.glyphs();
+                let label = ratings::stars_label(stars, glyphs.star, glyphs.star_empty);
+                spans.push(Span::styled(format!("  {}", label), Style::default().fg(Color::Yellow)));
+            }
+            
+            ListItem::new(vec![Line::from(spans)])
         })
         .collect();
 
+    let summary = list_summary(visible.iter().map(|&index| &app.songs[index]));
+    let mut title = match app.bpm_filter {
+        Some((min, max)) => format!(
+            "播放列表 (BPM {}-{}) · {}/{} 首匹配 · {}",
+            min,
+            max,
+            visible.len(),
+            app.songs.len(),
+            summary
+        ),
+        None if app.artist_filter.is_some() || app.collection_filter.is_some() || app.instrumental_filter => {
+            format!("播放列表 · {}/{} 首 · {}", visible.len(), app.songs.len(), summary)
+        }
+        None => format!("播放列表 · {} 首 · {}", app.songs.len(), summary),
+    };
+    if let Some((name, _)) = &app.artist_filter {
+        title.push_str(&format!(" (艺术家: {})", name));
+    }
+    if let Some(name) = &app.collection_filter {
+        title.push_str(&format!(" (收藏集: {})", name));
+    }
+    if app.instrumental_filter {
+        title.push_str(" (纯音乐)");
+    }
+    if app.view == View::Editor {
+        let arrow = app.config.glyphs.glyphs().arrow_right;
+        title.push_str(&format!(" [Enter/{}: 加入队列  p: 播放  Tab: 切换到队列  Esc: 返回]", arrow));
+    }
+
+    let focused = app.focus == Focus::Playlist;
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("播放列表"))
+        .block(pane_block(title, focused))
         .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(selected.map(|position| position - offset));
 
-    frame.render_widget(list, area);
+    frame.render_stateful_widget(list, area, &mut state);
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// 列表窗口的新起点：尽量保持上一帧的位置，光标移出窗口时只滚动到刚好露出光标，
+/// 列表变短时不在末尾留空行
+fn window_offset(previous: usize, selected: usize, height: usize, len: usize) -> usize {
+    let offset = if selected < previous {
+        selected
+    } else if selected >= previous + height {
+        selected + 1 - height
+    } else {
+        previous
+    };
+    offset.min(len.saturating_sub(height))
+}
+
+/// 无损格式青色，高码率绿色，低码率红色，方便发现低质量的重复文件
+fn badge_style(badge: &Badge) -> Style {
+    let color = match badge.bitrate_kbps {
+        _ if badge.lossless => Color::Cyan,
+        Some(kbps) if kbps >= 256 => Color::Green,
+        Some(kbps) if kbps < 160 => Color::Red,
+        _ => Color::Gray,
+    };
+    Style::default().fg(color)
+}
+
+fn render_bookmarks(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let items: Vec<ListItem> = app
+        .bookmarks
+        .iter()
+        .map(|bookmark| {
+            let song_name = bookmark
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("[{}] ", format_duration(bookmark.position)), Style::default().fg(Color::Cyan)),
+                Span::styled(bookmark.name.clone(), Style::default().fg(Color::White)),
+                Span::styled(format!("  {}", song_name), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let focused = app.focus == Focus::Playlist;
+    let list = List::new(items)
+        .block(pane_block("书签 (Enter: 跳转  D: 删除  Esc: 返回)", focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.bookmarks.is_empty() {
+        state.select(Some(app.bookmark_selected));
+    }
+
+    frame.render_stateful_widget(list, area, &mut state);
+    Ok(())
+}
+
+fn render_artists(frame: &mut Frame, app: &App, area: Rect) {
+    let all = ListItem::new(Line::from(vec![
+        Span::styled("全部艺术家", Style::default().fg(Color::White)),
+        Span::styled(format!("  {} 首", app.songs.len()), Style::default().fg(Color::DarkGray)),
+    ]));
+    let items: Vec<ListItem> = std::iter::once(all)
+        .chain(app.artists.iter().map(|entry| {
+            let style = match entry.name == library::VARIOUS_ARTISTS {
+                true => Style::default().fg(Color::Cyan),
+                false => Style::default().fg(Color::White),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(entry.name.clone(), style),
+                Span::styled(
+                    format!("  {} 张专辑 · {} 首", entry.albums, entry.songs.len()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        }))
+        .collect();
+
+    let focused = app.focus == Focus::Playlist;
+    let title = format!("专辑艺术家 · {} 位 (Enter: 只显示该艺术家  Esc: 返回)", app.artists.len());
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(app.artist_selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_collections(frame: &mut Frame, app: &App, area: Rect) {
+    let all = ListItem::new(Line::from(vec![
+        Span::styled("全部曲库", Style::default().fg(Color::White)),
+        Span::styled(format!("  {} 首", app.songs.len()), Style::default().fg(Color::DarkGray)),
+    ]));
+    let items: Vec<ListItem> = std::iter::once(all)
+        .chain(app.collections.iter().map(|collection| {
+            let count = app.songs.iter().filter(|song| collection.contains(song)).count();
+            let pins: Vec<String> = collection.pins.iter().take(3).map(|pin| pin.describe()).collect();
+            let mut detail = format!("  {} 首 · {}", count, pins.join("、"));
+            if collection.pins.len() > pins.len() {
+                detail.push_str(&format!(" 等 {} 项", collection.pins.len()));
+            }
+            ListItem::new(Line::from(vec![
+                Span::styled(collection.name.clone(), Style::default().fg(Color::White)),
+                Span::styled(detail, Style::default().fg(Color::DarkGray)),
+            ]))
+        }))
+        .collect();
+
+    let focused = app.focus == Focus::Playlist;
+    let title = format!(
+        "收藏集 · {} 个 (Enter: 只显示该收藏集  d: 删除  Esc: 返回；播放列表中按 c 加入)",
+        app.collections.len()
+    );
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(app.collection_selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_snapshots(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .queue_snapshots
+        .iter()
+        .map(|snapshot| {
+            let saved_at = Local
+                .timestamp_opt(snapshot.saved_at, 0)
+                .single()
+                .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let names: Vec<String> = snapshot
+                .paths
+                .iter()
+                .take(3)
+                .map(|path| path.file_stem().unwrap_or_default().to_string_lossy().to_string())
+                .collect();
+            let mut detail = format!("  {} · {} 首 · {}", saved_at, snapshot.paths.len(), names.join("、"));
+            if snapshot.paths.len() > names.len() {
+                detail.push_str(" …");
+            }
+            ListItem::new(Line::from(vec![
+                Span::styled(snapshot.name.clone(), Style::default().fg(Color::White)),
+                Span::styled(detail, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let focused = app.focus == Focus::Playlist;
+    let title = format!(
+        "队列快照 · {} 个 (Enter: 恢复为播放队列  d: 删除  Esc: 返回)",
+        app.queue_snapshots.len()
+    );
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(app.snapshot_selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_genres(frame: &mut Frame, app: &App, area: Rect) {
+    let pending = app.pending_genres();
+    let items: Vec<ListItem> = pending
+        .iter()
+        .map(|(index, suggestion)| {
+            let origin = match suggestion.origin {
+                genre::Origin::Model(confidence) => format!("  内置模型 · 置信度 {:.0}%", confidence * 100.0),
+                genre::Origin::Command => format!("  {}", app.config.genre_command),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<12}", suggestion.genre), Style::default().fg(Color::Yellow)),
+                Span::styled(app.songs[*index].name.clone(), Style::default().fg(Color::White)),
+                Span::styled(origin, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let focused = app.focus == Focus::Playlist;
+    let title = format!(
+        "流派建议 · {} 首 (Enter: 确认并写入标签  e: 修改后确认  d: 拒绝  Esc: 返回)",
+        pending.len()
+    );
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(app.genre_selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_settings(frame: &mut Frame, app: &App, area: Rect) {
+    let entries = app.config.entries();
+    let mut items: Vec<ListItem> = entries
+        .iter()
+        .map(|(key, value)| {
+            let mut description = settings::description(key).to_string();
+            if settings::needs_restart(key) {
+                description.push_str("（重启后生效）");
+            }
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<24}", key), Style::default().fg(Color::Yellow)),
+                Span::styled(settings::display_value(key, value), Style::default().fg(Color::White)),
+                Span::styled(format!("  {}", description), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    // 按键绑定：冲突的标红，正在等待按键的显示提示
+    items.extend(keymap::ACTIONS.iter().enumerate().map(|(index, (_, name, label, _))| {
+        let capturing = app.key_capture.as_ref().filter(|capture| capture.index == index);
+        let keys = match capturing {
+            Some(capture) => match capture.pending {
+                Some(key) => format!("再按一次 {} 确认改绑", keymap::key_name(key)),
+                None => "请按下新的按键…".to_string(),
+            },
+            None if app.keymap.label(index).is_empty() => "（未绑定）".to_string(),
+            None => app.keymap.label(index),
+        };
+        let style = match (capturing.is_some(), app.keymap.has_conflict(index)) {
+            (true, _) => Style::default().fg(Color::Black).bg(Color::Yellow),
+            (false, true) => Style::default().fg(Color::Red),
+            (false, false) => Style::default().fg(Color::White),
+        };
+        let mut description = format!("  {}", label);
+        if app.keymap.has_conflict(index) {
+            description.push_str("（按键冲突）");
+        }
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<24}", format!("key.{}", name)), Style::default().fg(Color::Cyan)),
+            Span::styled(keys, style),
+            Span::styled(description, Style::default().fg(Color::DarkGray)),
+        ]))
+    }));
+
+    let focused = app.focus == Focus::Playlist;
+    let title = format!(
+        "设置 · {} 项 · {} 个按键绑定 (Enter: 修改/切换开关/绑定新键  Backspace: 按键恢复默认  Esc: 返回)",
+        entries.len(),
+        keymap::ACTIONS.len()
+    );
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let list_area = match app.shows_loudness_meter() {
+        true => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(7)])
+                .split(area);
+            render_loudness_meter(frame, app, chunks[1]);
+            chunks[0]
+        }
+        false => area,
+    };
+    let mut state = ListState::default();
+    state.select(Some(app.setting_selected));
+    frame.render_stateful_widget(list, list_area, &mut state);
+}
+
+/// 响度表的刻度范围 (LUFS)
+const METER_FLOOR_LUFS: f64 = -40.0;
+
+/// 调整响度均衡时的实时响度表：均衡前后的短期响度和目标位置，以及当前歌曲的分析结果
+fn render_loudness_meter(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(" 响度预览 ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let reading = app.leveler.reading();
+    let target = app.config.leveler_target_lufs as f64;
+    let width = inner.width.saturating_sub(24) as usize;
+    let position = |lufs: f64| {
+        let share = (lufs - METER_FLOOR_LUFS) / -METER_FLOOR_LUFS;
+        (share.clamp(0.0, 1.0) * width.saturating_sub(1) as f64).round() as usize
+    };
+    let bar = |label: &str, value: Option<f64>, color: Color| {
+        let filled = value.map(|lufs| position(lufs) + 1).unwrap_or(0);
+        let marker = position(target);
+        let mut spans = vec![Span::styled(format!(" {:<8}", label), Style::default().fg(Color::Yellow))];
+        spans.extend((0..width).map(|cell| match (cell == marker, cell < filled) {
+            (true, _) => Span::styled("│", Style::default().fg(Color::Yellow)),
+            (false, true) => Span::styled("█", Style::default().fg(color)),
+            (false, false) => Span::styled("·", Style::default().fg(Color::DarkGray)),
+        }));
+        let value = value.map(|lufs| format!(" {:6.1} LUFS", lufs)).unwrap_or_else(|| "      -- LUFS".to_string());
+        spans.push(Span::raw(value));
+        Line::from(spans)
+    };
+
+    let mut lines = vec![
+        bar("原始", reading.before, Color::Blue),
+        bar("均衡后", reading.after, Color::Green),
+    ];
+    let status = match (app.playback_state, app.leveler.is_enabled()) {
+        (PlaybackState::Stopped, _) => "没有在播放，开始播放后显示实时响度".to_string(),
+        (_, false) => format!("目标 {} LUFS · 响度均衡已关闭", app.config.leveler_target_lufs),
+        (_, true) => format!("目标 {} LUFS · 当前增益 {:+.1} dB", app.config.leveler_target_lufs, reading.gain_db),
+    };
+    lines.push(Line::from(Span::styled(format!(" {}", status), Style::default().fg(Color::Gray))));
+
+    // 分析过响度或者有 ReplayGain 标签的歌曲使用固定增益，可以直接算出均衡后的响度
+    let song = app.songs.get(app.current_index).filter(|_| app.playback_state != PlaybackState::Stopped);
+    if let Some(song) = song {
+        let analysis = match (song.tags.replaygain_db, app.loudness.get(&song.path)) {
+            (Some(gain), Some(loudness)) => format!(
+                "积分响度 {:.1} LUFS · 标签增益 {:+.1} dB · 均衡后 {:.1} LUFS",
+                loudness.lufs,
+                gain,
+                loudness.lufs + app.playback_gain_db(song, 0.0)
+            ),
+            (Some(gain), None) => format!("标签增益 {:+.1} dB · 没有分析过响度", gain),
+            (None, _) => "没有 ReplayGain 标签，使用动态增益".to_string(),
+        };
+        lines.push(Line::from(Span::styled(format!(" {}", analysis), Style::default().fg(Color::DarkGray))));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_queue(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let items: Vec<ListItem> = app
+        .queue
+        .iter()
+        .enumerate()
+        .map(|(position, &index)| {
+            let name = app.songs.get(index).map(|song| song.name.clone()).unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:2}. ", position + 1), Style::default().fg(Color::Cyan)),
+                Span::styled(name, Style::default().fg(Color::White)),
+            ]))
+        })
+        .collect();
+
+    let summary = list_summary(app.queue.iter().filter_map(|&index| app.songs.get(index)));
+    let mut title = format!("播放队列 · {} 首 · {}", app.queue.len(), summary);
+    if app.view == View::Editor {
+        let arrow = app.config.glyphs.glyphs().arrow_left;
+        title.push_str(&format!(" [Enter/{}: 移出  K/J: 调整顺序  c: 清空  u: 撤销]", arrow));
+    }
+    let focused = app.focus == Focus::Queue;
+    let list = List::new(items)
+        .block(pane_block(title, focused))
+        .highlight_style(cursor_style(focused))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.queue.is_empty() {
+        state.select(Some(app.queue_selected));
+    }
+
+    frame.render_stateful_widget(list, area, &mut state);
+    Ok(())
+}
+
+fn render_jobs(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let focused = app.focus == Focus::Jobs;
+    let block = pane_block("后台任务 (x: 取消  c: 清除已结束  J: 隐藏)", focused);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    
+    if app.jobs.is_empty() {
+        let hint = Paragraph::new("没有后台任务。L: 重新扫描曲库  G: 计算 ReplayGain")
+            .style(Style::default().fg(Color::Gray));
+        frame.render_widget(hint, inner);
+        return Ok(());
+    }
+    
+    // 任务较多时让选中的任务保持可见
+    let visible = inner.height as usize;
+    let first = app.job_selected.saturating_sub(visible.saturating_sub(1));
+    for (row, (index, job)) in app.jobs.iter().enumerate().skip(first).take(visible).enumerate() {
+        let color = match job.state {
+            JobState::Running => Color::Blue,
+            JobState::Finished(_) => Color::Green,
+            JobState::Failed(_) => Color::Red,
+            JobState::Cancelled => Color::Gray,
+        };
+        let (done, total) = job.counts();
+        let counts = if total > 0 { format!(" {}/{}", done, total) } else { String::new() };
+        let marker = if focused && index == app.job_selected { "> " } else { "  " };
+        
+        let gauge = LineGauge::default()
+            .label(format!("{}{}{}  {}", marker, job.name, counts, job.status()))
+            .gauge_style(Style::default().fg(color))
+            .ratio(job.ratio());
+        let line_area = Rect::new(inner.x, inner.y + row as u16, inner.width, 1);
+        frame.render_widget(gauge, line_area);
+    }
+    Ok(())
+}
+
+/// 同步歌词：当前行高亮并尽量显示在面板中间，纯文本歌词从头显示
+fn render_lyrics(frame: &mut Frame, app: &App, area: Rect) {
+    let focused = app.focus == Focus::Lyrics;
+    let title = match focused {
+        true => "歌词 (Enter: 跳到选中的行  Esc: 跟随播放)",
+        false => "歌词",
+    };
+    let block = pane_block(title, focused);
+    let inner = block.inner(area);
+    let (width, height) = (inner.width.max(1) as usize, inner.height as usize);
+    let Some(pane) = &app.lyrics else {
+        frame.render_widget(block, area);
+        return;
+    };
+    let dim = Style::default().fg(Color::DarkGray);
+    let Some(lyrics) = &pane.lyrics else {
+        let text = if pane.loading { "正在在线查找歌词…" } else { pane.status.as_str() };
+        let paragraph = Paragraph::new(Span::styled(text.to_string(), dim)).block(block).wrap(Wrap { trim: true });
+        frame.render_widget(paragraph, area);
+        return;
+    };
+    
+    let current = lyrics.current_line(app.get_current_time());
+    let lines: Vec<Line> = lyrics
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(index, (_, text))| {
+            let line = match current {
+                Some(current) if current == index => {
+                    Line::from(Span::styled(text.clone(), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)))
+                }
+                Some(_) => Line::from(Span::styled(text.clone(), dim)),
+                None => Line::from(text.clone()),
+            };
+            // 方向键选中的行反色显示
+            match pane.cursor == Some(index) {
+                true => line.patch_style(Style::default().add_modifier(Modifier::REVERSED)),
+                false => line,
+            }
+        })
+        .collect();
+    // 选中了某一行时围绕它滚动，否则围绕正在唱的行。过长的行按面板宽度折行，滚动时按折行后的行数计算
+    let scroll = pane.cursor.or(current).map_or(0, |line| {
+        let above: usize = lines.iter().take(line).map(|line| line.width().max(1).div_ceil(width)).sum();
+        above.saturating_sub(height / 2)
+    });
+    let block = match pane.status.is_empty() {
+        true => block,
+        false => block.title_bottom(Line::from(Span::styled(pane.status.clone(), dim))),
+    };
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .scroll((scroll.min(u16::MAX as usize) as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+fn render_info(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let lines: Vec<Line> = match &app.info {
+        Some(pane) if pane.loading => vec![Line::from(Span::styled(
+            "正在从 MusicBrainz 获取信息…",
+            Style::default().fg(Color::DarkGray),
+        ))],
+        Some(pane) => pane
+            .lines
+            .iter()
+            .map(|line| match line.split_once(": ") {
+                Some((label, value)) => Line::from(vec![
+                    Span::styled(format!("{}: ", label), Style::default().fg(Color::Yellow)),
+                    Span::styled(value.to_string(), Style::default().fg(Color::White)),
+                ]),
+                None => Line::from(line.clone()),
+            })
+            .collect(),
+        None => vec![Line::from("没有歌曲")],
+    };
+
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .scroll((app.info_scroll, 0))
+        .block(pane_block("艺术家/专辑信息 (I: 关闭)", app.focus == Focus::Info));
+
+    frame.render_widget(paragraph, area);
+    Ok(())
+}
+
+fn render_prompt(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let Some(prompt) = &app.prompt else {
+        return Ok(());
+    };
+
+    let title = match &prompt.kind {
+        PromptKind::BookmarkName { position, .. } => {
+            format!("书签名称 @ {} (Enter: 确认  Esc: 取消)", format_duration(*position))
+        }
+        PromptKind::BpmFilter => "BPM 范围，如 120-130，留空清除 (Enter: 确认  Esc: 取消)".to_string(),
+        PromptKind::Collection { scope, .. } => {
+            format!("收藏集名称 · {} (Tab: 切换范围  Enter: 加入/移出  Esc: 取消)", scope.label())
+        }
+        PromptKind::RelocateDir => "在哪个目录中查找移动过的文件 (Enter: 开始  Esc: 取消)".to_string(),
+        PromptKind::Setting { key } => format!("{} (Enter: 保存  Esc: 取消)", key),
+        PromptKind::SnapshotName => {
+            format!("队列快照名称 · {} 首，同名时覆盖 (Enter: 保存  Esc: 取消)", app.queue.len())
+        }
+        PromptKind::Genre { .. } => "流派 (Enter: 确认并写入标签  Esc: 取消)".to_string(),
+        PromptKind::ExportSegment { start, end, .. } => {
+            format!("导出片段 {} - {} 到 (Enter: 导出  Esc: 取消)", format_duration(*start), format_duration(*end))
+        }
+    };
+
+    let popup_area = centered_rect(60, 3, area);
+    let input = Paragraph::new(format!("{}_", prompt.input))
+        .style(Style::default().fg(Color::White))
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(input, popup_area);
+    Ok(())
+}
+
+fn render_search(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(pane) = &app.search else {
+        return;
+    };
+
+    // 分组标题不能选中，光标位置要跳过它们
+    let mut items = Vec::new();
+    let mut selected = None;
+    let mut position = 0;
+    for group in &pane.groups {
+        items.push(ListItem::new(Line::from(Span::styled(
+            format!("{} ({})", group.source, group.hits.len()),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        ))));
+        for hit in &group.hits {
+            if position == pane.selected {
+                selected = Some(items.len());
+            }
+            position += 1;
+            let spans = match hit {
+                SearchHit::Song { name, album, .. } => {
+                    let mut spans = vec![Span::styled(name.clone(), Style::default().fg(Color::White))];
+                    if let Some(album) = album {
+                        spans.push(Span::styled(format!("  {}", album), Style::default().fg(Color::DarkGray)));
+                    }
+                    spans
+                }
+                SearchHit::Playlist { name, songs, matched } => vec![
+                    Span::styled(name.clone(), Style::default().fg(Color::White)),
+                    Span::styled(
+                        format!("  {} 首，{} 首匹配", songs.len(), matched),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ],
+            };
+            items.push(ListItem::new(Line::from(spans)));
+        }
+    }
+    if items.is_empty() {
+        let hint = match (pane.searching, pane.query.trim().is_empty()) {
+            (true, _) => "查找中…",
+            (false, true) => "输入歌名、艺术家、专辑或播放列表名，字母按顺序出现即可匹配",
+            (false, false) => "没有找到",
+        };
+        items.push(ListItem::new(Span::styled(hint, Style::default().fg(Color::DarkGray))));
+    }
+
+    let popup_area = centered_rect(70, area.height.saturating_sub(6), area);
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("搜索 (Enter: 播放  Ctrl+A: 加入队列  Esc: 关闭)");
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(inner);
+    let status = if pane.searching { "  查找中…" } else { "" };
+    let input = Line::from(vec![
+        Span::styled(format!("> {}_", pane.query), Style::default().fg(Color::Yellow)),
+        Span::styled(status, Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(Paragraph::new(input), rows[0]);
+
+    let list = List::new(items).highlight_style(cursor_style(true)).highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(selected);
+    frame.render_stateful_widget(list, rows[1], &mut state);
+}
+
+fn render_track_editor(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let Some(editor) = &app.track_editor else {
+        return Ok(());
+    };
+
+    let mut lines: Vec<Line> = track_settings::FIELDS
+        .iter()
+        .enumerate()
+        .map(|(field, label)| {
+            let style = if field == editor.field {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let value = match (field, &editor.album) {
+                (track_settings::ALBUM_FIELD, Some((_, skip))) if *skip > 0.0 => format!("{:.1} 秒", skip),
+                (track_settings::ALBUM_FIELD, Some(_)) => "关闭".to_string(),
+                (track_settings::ALBUM_FIELD, None) => "没有专辑标签".to_string(),
+                _ => editor.settings.label(field),
+            };
+            Line::from(vec![
+                Span::styled(format!(" {:<8}", label), Style::default().fg(Color::Yellow)),
+                Span::styled(format!(" {} ", value), style),
+            ])
+        })
+        .collect();
+
+    lines.push(Line::from(""));
+    match app.loudness.get(&editor.path) {
+        Some(loudness) => {
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {:<10}", "响度"), Style::default().fg(Color::Yellow)),
+                Span::raw(format!(" {:.1} LUFS", loudness.lufs)),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {:<9}", "真峰值"), Style::default().fg(Color::Yellow)),
+                Span::raw(format!(" {:+.1} dBTP", loudness.true_peak_db())),
+            ]));
+            // 按窗口中正在调整的前置增益计算，调整时就能看到是否还会削波
+            let gain = app
+                .songs
+                .iter()
+                .find(|song| song.path == editor.path)
+                .map(|song| app.playback_gain_db(song, editor.settings.preamp_db))
+                .unwrap_or(editor.settings.preamp_db as f64);
+            let (text, color) = match loudness.clip_db(gain) {
+                Some(over) => (format!(" 按当前增益 {:+.1} dB 播放会削波 {:+.1} dB", gain, over), Color::Red),
+                None => (format!(" 按当前增益 {:+.1} dB 播放不会削波", gain), Color::Green),
+            };
+            lines.push(Line::from(Span::styled(text, Style::default().fg(color))));
+        }
+        None => lines.push(Line::from(Span::styled(
+            " 还没有分析响度，按 G 计算",
+            Style::default().fg(Color::DarkGray),
+        ))),
+    }
+    if let Some(song) = app.songs.iter().find(|song| song.path == editor.path) {
+        let source = match song.badge.as_ref().and_then(|badge| badge.bits_per_sample) {
+            Some(bits) => format!("{} 位", bits),
+            None => "浮点".to_string(),
+        };
+        let output = match app.output.bits {
+            Some(bits) => format!("{} 位整数", bits),
+            None => "浮点".to_string(),
+        };
+        let dither = match app.dither_bits(song) {
+            Some(_) => "，TPDF 抖动",
+            None => "",
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {:<10}", "输出"), Style::default().fg(Color::Yellow)),
+            Span::raw(format!(" {} {} {}{}", source, app.config.glyphs.glyphs().arrow_right, output, dither)),
+        ]));
+    }
+    lines.push(Line::from(Span::styled(
+        format!(
+            " {}: 调整  PgUp/PgDn: 大步调整  0: 重置  Enter: 保存  Esc: 取消",
+            app.config.glyphs.glyphs().arrows_horizontal
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let popup_area = centered_rect(60, lines.len() as u16 + 2, area);
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!("歌曲属性: {}", editor.name)));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+    Ok(())
+}
+
+fn render_details(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let Some(details) = &app.details else {
+        return Ok(());
+    };
+
+    let mut lines = Vec::new();
+    for (title, entries) in &details.sections {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(
+            title.to_string(),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        )));
+        if entries.is_empty() {
+            lines.push(Line::from(Span::styled("  （无）", Style::default().fg(Color::DarkGray))));
+        }
+        for (key, value) in entries {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {}: ", key), Style::default().fg(Color::Yellow)),
+                Span::styled(value.clone(), Style::default().fg(Color::White)),
+            ]));
+        }
+    }
+
+    let height = (lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_area = centered_rect(70, height, area);
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((details.scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "歌曲信息: {} ({}: 滚动  Esc: 关闭)",
+                    details.name,
+                    app.config.glyphs.glyphs().arrows_vertical
+                )),
+        );
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+    Ok(())
+}
+
+fn render_restore_prompt(frame: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let Some(session) = &app.restore_prompt else {
+        return Ok(());
+    };
+
+    let mut lines = Vec::new();
+    if let Some((path, position)) = &session.current {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        lines.push(Line::from(vec![
+            Span::styled("歌曲: ", Style::default().fg(Color::Yellow)),
+            Span::styled(format!("{} @ {}", name, format_duration(*position)), Style::default().fg(Color::White)),
+        ]));
+    }
+    lines.push(Line::from(vec![
+        Span::styled("队列: ", Style::default().fg(Color::Yellow)),
+        Span::styled(format!("{} 首", session.queue.len()), Style::default().fg(Color::White)),
+    ]));
+    lines.push(Line::from(vec![
+        Span::styled("Y/Enter: ", Style::default().fg(Color::Yellow)),
+        Span::styled("恢复", Style::default().fg(Color::White)),
+        Span::styled("  N/Esc: ", Style::default().fg(Color::Yellow)),
+        Span::styled("不恢复", Style::default().fg(Color::White)),
+    ]));
+
+    let title = if session.clean_exit {
+        "恢复上次的播放会话？"
+    } else {
+        "上次没有正常退出，恢复播放会话？"
+    };
+    let popup_area = centered_rect(60, lines.len() as u16 + 2, area);
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+    Ok(())
+}
+
+fn render_playlist_reload(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(change) = &app.playlist_reload else {
+        return;
+    };
+    let label = Style::default().fg(Color::Yellow);
+    let value = Style::default().fg(Color::White);
+    let mut lines = vec![Line::from(vec![
+        Span::styled("文件中: ", label),
+        Span::styled(format!("新增 {} 首，移除 {} 首", change.added, change.removed), value),
+    ])];
+    if change.unsaved > 0 {
+        lines.push(Line::from(vec![
+            Span::styled("本地: ", label),
+            Span::styled(format!("{} 首还没有保存到文件，重新加载后保留", change.unsaved), value),
+        ]));
+    }
+    lines.push(Line::from(vec![
+        Span::styled("Y/Enter: ", label),
+        Span::styled("重新加载", value),
+        Span::styled("  N/Esc: ", label),
+        Span::styled("保留当前的播放列表", value),
+    ]));
+
+    let name = app.playlist_file_name().unwrap_or_default();
+    let popup_area = centered_rect(60, lines.len() as u16 + 2, area);
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!("播放列表 {} 已被其他程序修改", name)));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// 用半格字符画二维码，一个字符显示上下两个模块。白底黑块，四周留 2 个模块的空白
+fn render_pairing(frame: &mut Frame, url: &str, area: Rect) {
+    const QUIET: i32 = 2;
+    let code = QrCode::encode(url.as_bytes()).ok();
+    let mut lines = Vec::new();
+    if let Some(code) = &code {
+        let size = code.size as i32 + QUIET * 2;
+        let dark = |x: i32, y: i32| {
+            let (x, y) = (x - QUIET, y - QUIET);
+            x >= 0 && y >= 0 && code.get(x as usize, y as usize)
+        };
+        for y in (0..size).step_by(2) {
+            let row: String = (0..size)
+                .map(|x| match (dark(x, y), dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect();
+            lines.push(Line::from(Span::styled(row, Style::default().fg(Color::Black).bg(Color::White))));
+        }
+    }
+    let qr_width = lines.first().map(|line| line.width()).unwrap_or(0) as u16;
+    // 放不下时只显示地址
+    if qr_width + 2 > area.width || lines.len() as u16 + 4 > area.height {
+        lines.clear();
+        lines.push(Line::from(Span::styled("终端窗口太小，放不下二维码", Style::default().fg(Color::Yellow))));
+    }
+    lines.push(Line::from(Span::styled(url.to_string(), Style::default().fg(Color::White))));
+    lines.push(Line::from(Span::styled("按任意键关闭", Style::default().fg(Color::DarkGray))));
+
+    let width = (lines.iter().map(|line| line.width()).max().unwrap_or(0) as u16 + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let paragraph = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("用手机扫码打开网页遥控"));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+}
+
+fn render_quit_confirm(frame: &mut Frame, area: Rect) -> Result<()> {
+    let text = Line::from(vec![
+        Span::styled("Y: ", Style::default().fg(Color::Yellow)),
+        Span::styled("退出", Style::default().fg(Color::White)),
+        Span::styled("  D: ", Style::default().fg(Color::Yellow)),
+        Span::styled("脱离终端", Style::default().fg(Color::White)),
+        Span::styled("  M: ", Style::default().fg(Color::Yellow)),
+        Span::styled("收起界面", Style::default().fg(Color::White)),
+        Span::styled("  N/Esc: ", Style::default().fg(Color::Yellow)),
+        Span::styled("取消", Style::default().fg(Color::White)),
+    ]);
+
+    let popup_area = centered_rect(60, 3, area);
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("正在播放，确定退出？"));
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+    Ok(())
+}
+
+/// 面板边框，获得焦点时高亮
+fn pane_block<'a>(title: impl Into<Title<'a>>, focused: bool) -> Block<'a> {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if focused {
+        block
+            .border_style(Style::default().fg(Color::Cyan))
+            .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+    } else {
+        block
+    }
+}
+
+/// 列表光标样式，所在面板没有焦点时不反色
+fn cursor_style(focused: bool) -> Style {
+    if focused {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default()
+    }
+}
+
+/// 在区域中央取一块宽度为百分比、高度为固定行数的矩形
+fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
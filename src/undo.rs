@@ -0,0 +1,41 @@
+/// 最多保留的撤销步数
+const LIMIT: usize = 50;
+
+/// 撤销/重做栈。每一项是编辑的名称和编辑前的状态快照
+pub struct History<T> {
+    undo: Vec<(String, T)>,
+    redo: Vec<(String, T)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// 记录一次编辑，新的编辑会清空重做栈
+    pub fn record(&mut self, label: impl Into<String>, before: T) {
+        self.undo.push((label.into(), before));
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// 取出最近一次编辑前的状态；capture 根据该快照保存当前状态，用于重做
+    pub fn undo(&mut self, capture: impl FnOnce(&T) -> T) -> Option<(String, T)> {
+        let (label, before) = self.undo.pop()?;
+        self.redo.push((label.clone(), capture(&before)));
+        Some((label, before))
+    }
+
+    pub fn redo(&mut self, capture: impl FnOnce(&T) -> T) -> Option<(String, T)> {
+        let (label, after) = self.redo.pop()?;
+        self.undo.push((label.clone(), capture(&after)));
+        Some((label, after))
+    }
+}
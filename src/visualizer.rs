@@ -0,0 +1,423 @@
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use rodio::Source;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 保留的最近样本数（单声道），足够一次频谱分析和一屏示波器
+const TAP_CAPACITY: usize = 4096;
+/// 播放线程每攒够这么多帧才加锁写入一次
+const PUSH_FRAMES: usize = 256;
+/// 频谱分析的窗口长度，44.1 kHz 下约 46ms，频率分辨率约 21 Hz
+const FFT_SIZE: usize = 2048;
+/// 频谱图每列的间隔，也就是滚动速度
+const COLUMN_INTERVAL: Duration = Duration::from_millis(40);
+/// 保存的频谱图列数，超过终端宽度的两倍即可
+const MAX_COLUMNS: usize = 600;
+/// 每列按对数频率分成的频段数，显示时再按终端高度取样
+const BANDS: usize = 128;
+const MIN_FREQ: f32 = 30.0;
+const MAX_FREQ: f32 = 16000.0;
+/// 频谱图显示的动态范围 (dB)
+const RANGE_DB: f32 = 70.0;
+
+/// 2x4 盲文点阵中每个点对应的位，按 [行][列]
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+/// 频谱图抖动用的阈值（2x4 Bayer 矩阵），让强度不同的区域点的疏密不同
+const DITHER: [[f32; 2]; 4] = [[0.0625, 0.5625], [0.8125, 0.3125], [0.1875, 0.6875], [0.9375, 0.4375]];
+
+/// 可视化模式，按 `v` 依次切换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisualizerMode {
+    #[default]
+    Off,
+    /// 示波器：最近一段时域波形
+    Oscilloscope,
+    /// 滚动的频谱图：横轴是时间，纵轴是对数频率
+    Spectrogram,
+}
+
+impl VisualizerMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "off" | "" => Some(VisualizerMode::Off),
+            "oscilloscope" | "scope" => Some(VisualizerMode::Oscilloscope),
+            "spectrogram" => Some(VisualizerMode::Spectrogram),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VisualizerMode::Off => "off",
+            VisualizerMode::Oscilloscope => "oscilloscope",
+            VisualizerMode::Spectrogram => "spectrogram",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VisualizerMode::Off => "关闭",
+            VisualizerMode::Oscilloscope => "示波器",
+            VisualizerMode::Spectrogram => "频谱图",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            VisualizerMode::Off => VisualizerMode::Oscilloscope,
+            VisualizerMode::Oscilloscope => VisualizerMode::Spectrogram,
+            VisualizerMode::Spectrogram => VisualizerMode::Off,
+        }
+    }
+}
+
+/// 最近播放的样本（各声道混合成单声道），播放线程写入、界面线程读取
+#[derive(Debug, Default)]
+pub struct SampleTap {
+    /// 可视化关闭时播放线程不复制样本
+    active: AtomicBool,
+    sample_rate: AtomicU32,
+    samples: Mutex<VecDeque<f32>>,
+}
+
+impl SampleTap {
+    pub fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+        if !active {
+            self.clear();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
+        }
+    }
+
+    /// 最近的 count 个样本，不够时返回全部
+    pub fn latest(&self, count: usize) -> Vec<f32> {
+        let Ok(samples) = self.samples.lock() else {
+            return Vec::new();
+        };
+        let skip = samples.len().saturating_sub(count);
+        samples.iter().skip(skip).copied().collect()
+    }
+
+    fn push(&self, frames: &[f32]) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.extend(frames);
+            let excess = samples.len().saturating_sub(TAP_CAPACITY);
+            samples.drain(..excess);
+        }
+    }
+}
+
+/// 把经过的样本复制一份给可视化的 Source 适配器，样本本身原样输出
+pub struct Tap<S> {
+    input: S,
+    tap: Arc<SampleTap>,
+    channel: u16,
+    frame_sum: f32,
+    pending: Vec<f32>,
+}
+
+impl<S> Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, tap: Arc<SampleTap>) -> Self {
+        tap.sample_rate.store(input.sample_rate(), Ordering::Relaxed);
+        Tap {
+            input,
+            tap,
+            channel: 0,
+            frame_sum: 0.0,
+            pending: Vec::with_capacity(PUSH_FRAMES),
+        }
+    }
+}
+
+impl<S> Iterator for Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if !self.tap.is_active() {
+            return Some(sample);
+        }
+
+        self.frame_sum += sample;
+        self.channel += 1;
+        let channels = self.input.channels().max(1);
+        if self.channel >= channels {
+            self.pending.push(self.frame_sum / channels as f32);
+            self.frame_sum = 0.0;
+            self.channel = 0;
+            if self.pending.len() >= PUSH_FRAMES {
+                self.tap.push(&self.pending);
+                self.pending.clear();
+            }
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// 界面一侧的可视化状态：当前模式和频谱图的历史
+#[derive(Debug, Default)]
+pub struct Visualizer {
+    pub mode: VisualizerMode,
+    pub tap: Arc<SampleTap>,
+    /// 每列是一次分析的结果，BANDS 个 0..1 的强度，从低频到高频
+    columns: VecDeque<Vec<f32>>,
+    last_column: Option<Instant>,
+}
+
+impl Visualizer {
+    pub fn set_mode(&mut self, mode: VisualizerMode) {
+        self.mode = mode;
+        self.tap.set_active(mode != VisualizerMode::Off);
+        self.columns.clear();
+        self.last_column = None;
+    }
+
+    /// 播放时每个循环调用一次，频谱图按固定间隔追加新的一列
+    pub fn update(&mut self) {
+        if self.mode != VisualizerMode::Spectrogram {
+            return;
+        }
+        if self.last_column.is_some_and(|last| last.elapsed() < COLUMN_INTERVAL) {
+            return;
+        }
+        self.last_column = Some(Instant::now());
+        let samples = self.tap.latest(FFT_SIZE);
+        if samples.len() < FFT_SIZE {
+            return;
+        }
+        self.columns.push_back(analyze(&samples, self.tap.sample_rate()));
+        if self.columns.len() > MAX_COLUMNS {
+            self.columns.pop_front();
+        }
+    }
+
+    /// 按终端区域的大小（字符数）生成可视化的各行
+    pub fn lines(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        match self.mode {
+            VisualizerMode::Off => Vec::new(),
+            VisualizerMode::Oscilloscope => self.oscilloscope(width, height),
+            VisualizerMode::Spectrogram => self.spectrogram(width, height),
+        }
+    }
+
+    /// 每个点列取一个样本，相邻样本之间画竖线连起来，波形是连续的
+    fn oscilloscope(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        let mut grid = BrailleGrid::new(width, height);
+        let (dot_width, dot_height) = (width * 2, height * 4);
+        if dot_width == 0 || dot_height == 0 {
+            return Vec::new();
+        }
+        // 显示约 20ms，和示波器的一屏差不多；采样率不同时时间跨度保持一致
+        let span = (self.tap.sample_rate().max(1) as usize / 50).clamp(dot_width, TAP_CAPACITY);
+        let samples = self.tap.latest(span);
+        let to_row = |sample: f32| {
+            let y = (1.0 - sample.clamp(-1.0, 1.0)) / 2.0 * (dot_height - 1) as f32;
+            y.round() as usize
+        };
+        let mut previous = None;
+        for x in 0..dot_width {
+            let y = match samples.len() {
+                0 => to_row(0.0),
+                len => to_row(samples[x * len / dot_width]),
+            };
+            let (from, to) = match previous {
+                Some(previous) if previous < y => (previous + 1, y),
+                Some(previous) if previous > y => (y, previous - 1),
+                _ => (y, y),
+            };
+            for row in from..=to {
+                grid.set(x, row);
+            }
+            previous = Some(y);
+        }
+        grid.lines(|_| Style::default().fg(Color::Green))
+    }
+
+    /// 最新的一列在最右边；点是否点亮由强度和抖动阈值决定，字符的颜色取格内最强的点
+    fn spectrogram(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        let (dot_width, dot_height) = (width * 2, height * 4);
+        let mut grid = BrailleGrid::new(width, height);
+        let mut levels = vec![0.0f32; width * height];
+        let skip = self.columns.len().saturating_sub(dot_width);
+        let offset = dot_width - (self.columns.len() - skip);
+        for (i, column) in self.columns.iter().skip(skip).enumerate() {
+            let x = offset + i;
+            for y in 0..dot_height {
+                // 最下面是低频
+                let band = (dot_height - 1 - y) * BANDS / dot_height;
+                let level = column[band];
+                if level > DITHER[y % 4][x % 2] {
+                    grid.set(x, y);
+                }
+                let cell = &mut levels[(y / 4) * width + x / 2];
+                *cell = cell.max(level);
+            }
+        }
+        grid.lines(|cell| Style::default().fg(heat_color(levels[cell])))
+    }
+}
+
+fn heat_color(level: f32) -> Color {
+    match level {
+        level if level < 0.3 => Color::Blue,
+        level if level < 0.5 => Color::Cyan,
+        level if level < 0.7 => Color::Green,
+        level if level < 0.85 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+/// 按字符格组织的盲文点阵，每个字符 2 列 4 行点
+struct BrailleGrid {
+    width: usize,
+    cells: Vec<u8>,
+}
+
+impl BrailleGrid {
+    fn new(width: usize, height: usize) -> Self {
+        BrailleGrid { width, cells: vec![0; width * height] }
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        if let Some(cell) = self.cells.get_mut((y / 4) * self.width + x / 2) {
+            *cell |= BRAILLE_DOTS[y % 4][x % 2];
+        }
+    }
+
+    /// style 按字符格的下标给出颜色
+    fn lines(&self, style: impl Fn(usize) -> Style) -> Vec<Line<'static>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        self.cells
+            .chunks(self.width)
+            .enumerate()
+            .map(|(row, cells)| {
+                let spans: Vec<Span> = cells
+                    .iter()
+                    .enumerate()
+                    .map(|(col, &bits)| {
+                        let glyph = char::from_u32(0x2800 + bits as u32).unwrap_or(' ');
+                        Span::styled(glyph.to_string(), style(row * self.width + col))
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
+
+/// 加 Hann 窗做 FFT，按对数频率分成 BANDS 段，每段取最大值并换算成 0..1 的强度
+fn analyze(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let n = samples.len();
+    let mut re: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * (0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    // Hann 窗下满幅正弦波的峰值约为 n/4
+    let scale = 4.0 / n as f32;
+    let bin_hz = sample_rate.max(1) as f32 / n as f32;
+    let max_freq = MAX_FREQ.min(sample_rate as f32 / 2.0);
+    let ratio = max_freq / MIN_FREQ;
+    (0..BANDS)
+        .map(|band| {
+            let low = MIN_FREQ * ratio.powf(band as f32 / BANDS as f32);
+            let high = MIN_FREQ * ratio.powf((band + 1) as f32 / BANDS as f32);
+            let first = ((low / bin_hz) as usize).clamp(1, n / 2 - 1);
+            let last = ((high / bin_hz) as usize).clamp(first, n / 2 - 1);
+            let magnitude = (first..=last)
+                .map(|bin| (re[bin] * re[bin] + im[bin] * im[bin]).sqrt() * scale)
+                .fold(0.0, f32::max);
+            let db = 20.0 * magnitude.max(1e-9).log10();
+            ((db + RANGE_DB) / RANGE_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// 原地的基 2 FFT，长度必须是 2 的幂
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use rodio::Source;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::storage;
+use crate::streaming::StreamingDecoder;
+
+const WAVEFORMS_FILE: &str = "waveforms.tsv";
+
+/// 缩略图的宽度（字符数），每个字符代表歌曲的 1/12
+pub const WIDTH: usize = 12;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// 最响的一段显示满格，比它低这么多分贝及以上的显示最矮的一格
+const RANGE_DB: f64 = 12.0;
+
+/// 歌曲的波形缩略图：把整首歌分成 WIDTH 段，每段相对最响一段的响度 0-7。
+/// 文件大小变化时需要重新生成
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    pub size: u64,
+    pub levels: Vec<u8>,
+}
+
+impl Waveform {
+    pub fn is_current(&self, path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|meta| meta.len() == self.size)
+    }
+
+    /// 用方块字符画成的迷你波形
+    pub fn sparkline(&self) -> String {
+        self.levels.iter().map(|level| BARS[(*level as usize).min(BARS.len() - 1)]).collect()
+    }
+}
+
+fn cache_path() -> PathBuf {
+    storage::cache_dir().join(WAVEFORMS_FILE)
+}
+
+pub fn load() -> HashMap<PathBuf, Waveform> {
+    let records = storage::read_records_at(&cache_path()).unwrap_or_default();
+    records
+        .into_iter()
+        .filter_map(|record| {
+            // 格式: 文件大小 \t 每段响度（0-7 的数字） \t 文件路径
+            let [size, levels, path] = <[String; 3]>::try_from(record).ok()?;
+            let levels = levels.chars().map(|c| c.to_digit(10).map(|level| level as u8)).collect::<Option<_>>()?;
+            Some((storage::path_from_field(&path), Waveform { size: size.parse().ok()?, levels }))
+        })
+        .collect()
+}
+
+pub fn save(waveforms: &HashMap<PathBuf, Waveform>) -> Result<()> {
+    let mut records: Vec<Vec<String>> = waveforms
+        .iter()
+        .map(|(path, waveform)| {
+            vec![
+                waveform.size.to_string(),
+                waveform.levels.iter().map(|level| level.to_string()).collect(),
+                storage::path_to_field(path),
+            ]
+        })
+        .collect();
+    records.sort_by(|a, b| a[2].cmp(&b[2]));
+    storage::write_records_at(&cache_path(), &records)
+}
+
+/// 解码整首歌，按时长分成 WIDTH 段计算每段的 RMS。没有时长的文件无法分段，返回 None
+pub fn compute(path: &Path, duration: Option<Duration>, cancel: &AtomicBool) -> Option<Waveform> {
+    let duration = duration.filter(|duration| !duration.is_zero())?;
+    let size = fs::metadata(path).ok()?.len();
+    let file = fs::File::open(path).ok()?;
+    let decoder = StreamingDecoder::new(Box::new(file), path, Duration::ZERO).ok()?;
+    let samples_per_second = decoder.sample_rate().max(1) as f64 * decoder.channels().max(1) as f64;
+    let segment = ((duration.as_secs_f64() * samples_per_second) / WIDTH as f64).ceil().max(1.0) as usize;
+
+    let mut energies = [(0.0f64, 0usize); WIDTH];
+    for (index, sample) in decoder.enumerate() {
+        if index % 65536 == 0 && cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        // 实际长度比标签中的时长长时，多出的部分算进最后一段
+        let (energy, count) = &mut energies[(index / segment).min(WIDTH - 1)];
+        *energy += sample as f64 * sample as f64;
+        *count += 1;
+    }
+    let db: Vec<f64> = energies
+        .iter()
+        .map(|(energy, count)| 10.0 * (energy / (*count).max(1) as f64).max(1e-12).log10())
+        .collect();
+    let loudest = db.iter().copied().fold(f64::MIN, f64::max);
+    let levels = db
+        .iter()
+        .map(|db| {
+            let share = 1.0 - (loudest - db) / RANGE_DB;
+            (share.clamp(0.0, 1.0) * (BARS.len() - 1) as f64).round() as u8
+        })
+        .collect();
+    Some(Waveform { size, levels })
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, bail, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::http;
+use crate::remote::Command;
+
+/// 请求体的上限，命令只有一行
+const MAX_BODY: usize = 1024;
+
+const PAGE: &str = include_str!("web.html");
+
+/// 手机浏览器上的遥控页面：`/` 是页面本身，`/api/state` 返回播放状态和队列，
+/// `/api/command` 接收和 `ctl` 相同的命令。接口需要配置的令牌
+pub struct WebRemote {
+    pub commands: Receiver<Command>,
+    state: Arc<Mutex<String>>,
+    address: SocketAddr,
+    token: String,
+}
+
+impl WebRemote {
+    pub fn start(address: &str, token: &str) -> Result<Self> {
+        if token.is_empty() {
+            bail!("网页遥控需要先设置 web_token");
+        }
+        let listener = TcpListener::bind(address).map_err(|err| anyhow!("无法监听网页遥控地址 {}: {}", address, err))?;
+        let address = listener.local_addr()?;
+        let (tx, commands) = mpsc::channel();
+        let state = Arc::new(Mutex::new("{}".to_string()));
+        let shared = state.clone();
+        let token = token.to_string();
+        let listener_token = token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream, &listener_token, &shared, &tx);
+            }
+        });
+        Ok(WebRemote { commands, state, address, token })
+    }
+
+    /// 手机扫码后打开的地址，带上令牌。监听所有网卡时使用本机在局域网中的地址
+    pub fn pairing_url(&self) -> String {
+        let ip = match self.address.ip() {
+            ip if ip.is_unspecified() => lan_address().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ip => ip,
+        };
+        format!("http://{}/?token={}", SocketAddr::new(ip, self.address.port()), http::encode(&self.token))
+    }
+
+    /// 更新 `/api/state` 返回的 JSON
+    pub fn set_state(&self, json: String) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = json;
+        }
+    }
+}
+
+/// 本机访问外网时使用的地址，一般就是局域网地址。UDP 的 connect 只选择路由，不会发出数据
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:80").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: String,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(http::decode);
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_lowercase().as_str() {
+            "content-length" => length = value.trim().parse::<usize>().unwrap_or(0).min(MAX_BODY),
+            "authorization" => {
+                if let Some(bearer) = value.trim().strip_prefix("Bearer ") {
+                    token = Some(bearer.trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        token,
+        body: String::from_utf8_lossy(&body).trim().to_string(),
+    })
+}
+
+fn respond(stream: TcpStream, token: &str, state: &Mutex<String>, tx: &Sender<Command>) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let request = read_request(&stream)?;
+    let authorized = request.token.as_deref().is_some_and(|given| same(given, token));
+    let (status, content_type, body) = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+        (_, "/api/state" | "/api/command") if !authorized => {
+            ("401 Unauthorized", "text/plain; charset=utf-8", "令牌错误".to_string())
+        }
+        ("GET", "/api/state") => {
+            let state = state.lock().map(|state| state.clone()).unwrap_or_default();
+            ("200 OK", "application/json", state)
+        }
+        ("POST", "/api/command") => match Command::parse(&request.body) {
+            Some(command) => {
+                let _ = tx.send(command);
+                ("200 OK", "text/plain; charset=utf-8", "ok".to_string())
+            }
+            None => ("400 Bad Request", "text/plain; charset=utf-8", format!("未知命令: {}", request.body)),
+        },
+        _ => ("404 Not Found", "text/plain; charset=utf-8", "not found".to_string()),
+    };
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+/// 比较令牌，耗时不随第一个不同字符的位置变化
+fn same(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::App;
+use crate::http;
+use crate::json;
+use crate::status_line;
+
+/// 第一次重试前的等待时间，之后每次翻倍
+const FIRST_RETRY: Duration = Duration::from_secs(2);
+
+/// 发给 webhook 的播放事件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// 开始播放一首歌（包括切歌和自动播放下一首）
+    Start,
+    Pause,
+    Resume,
+    Stop,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Start => "start",
+            Event::Pause => "pause",
+            Event::Resume => "resume",
+            Event::Stop => "stop",
+        }
+    }
+}
+
+/// 后台发送 webhook 的线程：事件按顺序逐个发给每个地址，失败时按退避时间重试
+pub struct Webhooks {
+    tx: Sender<String>,
+    /// 重试后仍然失败的地址和错误
+    pub errors: Receiver<String>,
+}
+
+impl Webhooks {
+    pub fn start(urls: Vec<String>, retries: u32) -> Self {
+        let (tx, rx) = mpsc::channel::<String>();
+        let (error_tx, errors) = mpsc::channel();
+        thread::spawn(move || {
+            for payload in rx {
+                for url in &urls {
+                    if let Err(err) = deliver(url, &payload, retries) {
+                        let _ = error_tx.send(format!("{}: {}", url, err));
+                    }
+                }
+            }
+        });
+        Webhooks { tx, errors }
+    }
+
+    pub fn send(&self, payload: String) {
+        let _ = self.tx.send(payload);
+    }
+}
+
+fn deliver(url: &str, payload: &str, retries: u32) -> Result<()> {
+    let mut wait = FIRST_RETRY;
+    let mut attempt = 0;
+    loop {
+        match http::post_json(url, payload) {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= retries => return Err(err),
+            Err(_) => {
+                thread::sleep(wait);
+                wait *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// 事件的 JSON 内容。没有模板时是播放状态（和 MQTT 发布的相同）加上 `event` 字段；
+/// 模板中的 `%event%` 和状态栏变量替换为转义后的值，用来拼出 Discord、ntfy 等服务要求的格式
+pub fn payload(template: &str, event: Event, state_json: &str, app: &App) -> String {
+    if template.is_empty() {
+        return format!("{{\"event\":{},{}", json::string(event.name()), &state_json[1..]);
+    }
+    status_line::expand(template, |name| {
+        let value = match name {
+            "event" => event.name().to_string(),
+            name if status_line::VARIABLES.contains(&name) => status_line::value(name, app),
+            _ => return None,
+        };
+        let quoted = json::string(&value);
+        Some(quoted[1..quoted.len() - 1].to_string())
+    })
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::Song;
+use crate::jobs::JobProgress;
+use crate::json::{self, Value};
+use crate::metadata::{ProbeResult, Tags};
+
+/// 优先选择 symphonia 能解码的 AAC (m4a) 和 MP3 音轨，Opus/WebM 无法播放
+const FORMAT: &str = "bestaudio[ext=m4a]/bestaudio[acodec=mp3]/bestaudio[acodec^=mp4a]/best[ext=mp4]";
+
+/// 通过 yt-dlp 把视频或播放列表页面解析成可以直接播放的音频地址，每个视频一首歌。
+/// 得到的地址通常几个小时后失效，所以只加入队列，不写入曲库文件
+pub fn resolve(program: &str, url: &str, progress: &JobProgress) -> Result<Vec<Song>> {
+    progress.set_total(1);
+    progress.set_status(url);
+    let output = Command::new(program)
+        .args(["-j", "--no-warnings", "--ignore-errors", "-f", FORMAT])
+        .arg(url)
+        .output()
+        .map_err(|err| anyhow!("无法运行 {}: {}", program, err))?;
+    progress.check_cancelled()?;
+
+    // 播放列表中个别视频失败时 yt-dlp 仍会输出其余视频的信息
+    let songs: Vec<Song> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| json::parse(line).ok())
+        .filter_map(|info| to_song(&info))
+        .collect();
+    if songs.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rfind(|line| !line.trim().is_empty()).unwrap_or("没有找到可以播放的音频");
+        bail!("{}", reason.trim());
+    }
+    progress.advance();
+    Ok(songs)
+}
+
+fn to_song(info: &Value) -> Option<Song> {
+    let text = |key: &str| info.get(key).and_then(Value::as_str).map(str::to_string);
+    let stream_url = text("url")?;
+    let title = text("track").or_else(|| text("title"))?;
+    let tags = Tags {
+        title: Some(title.clone()),
+        artist: text("artist").or_else(|| text("uploader")).or_else(|| text("channel")),
+        album: text("album"),
+        explicit: info.get("age_limit").and_then(Value::as_f64).is_some_and(|age| age >= 18.0),
+        ..Default::default()
+    };
+    let mut song = Song::from_probe(PathBuf::from(stream_url), ProbeResult::default());
+    song.name = title;
+    song.tags = tags;
+    song.duration = info
+        .get("duration")
+        .and_then(Value::as_f64)
+        .filter(|secs| *secs > 0.0)
+        .map(Duration::from_secs_f64);
+    Some(song)
+}
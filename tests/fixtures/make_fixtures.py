@@ -0,0 +1,165 @@
+#!/usr/bin/env python3
+"""生成解码测试用的小音频文件。没有可用的编码器，按格式规范直接拼出字节：
+AAC-LC 用不含频谱数据的静音帧，ALAC 用不压缩的（escape）帧，内容是已知的正弦波。
+
+    python3 tests/fixtures/make_fixtures.py
+"""
+import math
+import os
+import struct
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+RATE = 44100
+
+
+class Bits:
+    def __init__(self):
+        self.bits = []
+
+    def push(self, value, count):
+        for i in reversed(range(count)):
+            self.bits.append((value >> i) & 1)
+
+    def bytes(self):
+        bits = self.bits + [0] * (-len(self.bits) % 8)
+        return bytes(int("".join(map(str, bits[i:i + 8])), 2) for i in range(0, len(bits), 8))
+
+
+def box(kind, *payload):
+    data = b"".join(payload)
+    return struct.pack(">I", 8 + len(data)) + kind + data
+
+
+def full_box(kind, version, flags, *payload):
+    return box(kind, struct.pack(">I", (version << 24) | flags), *payload)
+
+
+def aac_silent_frame():
+    """单声道 AAC-LC raw_data_block：一个 max_sfb = 0 的 SCE（全部频谱为零），然后是 END"""
+    bits = Bits()
+    bits.push(0, 3)    # SCE
+    bits.push(0, 4)    # element_instance_tag
+    bits.push(100, 8)  # global_gain
+    bits.push(0, 1)    # ics_reserved_bit
+    bits.push(0, 2)    # ONLY_LONG_SEQUENCE
+    bits.push(0, 1)    # window_shape
+    bits.push(0, 6)    # max_sfb
+    bits.push(0, 1)    # predictor_data_present
+    bits.push(0, 1)    # pulse_data_present
+    bits.push(0, 1)    # tns_data_present
+    bits.push(0, 1)    # gain_control_data_present
+    bits.push(7, 3)    # END
+    return bits.bytes()
+
+
+def sine(index, channel):
+    return int(8000 * math.sin(2 * math.pi * (440 + 220 * channel) * index / RATE))
+
+
+def alac_frame(start, count, frame_length):
+    """立体声 16 位 ALAC 的不压缩帧，样本是 sine() 的值"""
+    bits = Bits()
+    bits.push(1, 3)   # CPE
+    bits.push(0, 4)   # element instance tag
+    bits.push(0, 12)  # unused
+    partial = count != frame_length
+    bits.push(int(partial), 1)
+    bits.push(0, 2)   # bytes shifted
+    bits.push(1, 1)   # is not compressed
+    if partial:
+        bits.push(count, 32)
+    for index in range(start, start + count):
+        for channel in range(2):
+            bits.push(sine(index, channel) & 0xFFFF, 16)
+    bits.push(7, 3)   # END
+    return bits.bytes()
+
+
+def adts(frame):
+    header = Bits()
+    header.push(0xFFF, 12)
+    header.push(0, 1)               # MPEG-4
+    header.push(0, 2)               # layer
+    header.push(1, 1)               # 没有 CRC
+    header.push(1, 2)               # AAC-LC
+    header.push(4, 4)               # 44100 Hz
+    header.push(0, 1)
+    header.push(1, 3)               # 单声道
+    header.push(0, 4)
+    header.push(7 + len(frame), 13)
+    header.push(0x7FF, 11)
+    header.push(0, 2)
+    return header.bytes() + frame
+
+
+def descriptor(tag, *payload):
+    data = b"".join(payload)
+    return bytes([tag, len(data)]) + data
+
+
+def mp4(sample_entry, samples, durations, timescale):
+    total = sum(durations)
+    matrix = struct.pack(">9I", 0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000)
+    mvhd = full_box(b"mvhd", 0, 0, struct.pack(">IIII", 0, 0, timescale, total),
+                    struct.pack(">IH", 0x10000, 0x100), bytes(10), matrix, bytes(24), struct.pack(">I", 2))
+    tkhd = full_box(b"tkhd", 0, 7, struct.pack(">IIIII", 0, 0, 1, 0, total), bytes(8),
+                    struct.pack(">HHHH", 0, 0, 0x100, 0), matrix, struct.pack(">II", 0, 0))
+    mdhd = full_box(b"mdhd", 0, 0, struct.pack(">IIIIHH", 0, 0, timescale, total, 0x55C4, 0))
+    hdlr = full_box(b"hdlr", 0, 0, struct.pack(">I", 0), b"soun", bytes(12), b"SoundHandler\0")
+    smhd = full_box(b"smhd", 0, 0, struct.pack(">HH", 0, 0))
+    dinf = box(b"dinf", full_box(b"dref", 0, 0, struct.pack(">I", 1), full_box(b"url ", 0, 1)))
+    runs = []
+    for duration in durations:
+        if runs and runs[-1][1] == duration:
+            runs[-1][0] += 1
+        else:
+            runs.append([1, duration])
+    stts = full_box(b"stts", 0, 0, struct.pack(">I", len(runs)), *(struct.pack(">II", *run) for run in runs))
+    stsc = full_box(b"stsc", 0, 0, struct.pack(">IIII", 1, 1, len(samples), 1))
+    stsz = full_box(b"stsz", 0, 0, struct.pack(">II", 0, len(samples)), *(struct.pack(">I", len(s)) for s in samples))
+
+    def build(offset):
+        stco = full_box(b"stco", 0, 0, struct.pack(">II", 1, offset))
+        stsd = full_box(b"stsd", 0, 0, struct.pack(">I", 1), sample_entry)
+        stbl = box(b"stbl", stsd, stts, stsc, stsz, stco)
+        trak = box(b"trak", tkhd, box(b"mdia", mdhd, hdlr, box(b"minf", smhd, dinf, stbl)))
+        return box(b"moov", mvhd, trak)
+
+    ftyp = box(b"ftyp", b"M4A ", struct.pack(">I", 0), b"M4A mp42isom")
+    moov = build(0)
+    moov = build(len(ftyp) + len(moov) + 8)
+    return ftyp + moov + box(b"mdat", *samples)
+
+
+def audio_entry(kind, channels, rate, child):
+    return box(kind, bytes(6), struct.pack(">HHHI", 1, 0, 0, 0),
+               struct.pack(">HHHHI", channels, 16, 0, 0, rate << 16), child)
+
+
+def aac_m4a(frames):
+    config = bytes([0x12, 0x08])  # AAC-LC，44100 Hz，单声道
+    decoder_config = descriptor(0x04, bytes([0x40, 0x15]), bytes(3), struct.pack(">II", 0, 0), descriptor(0x05, config))
+    esds = full_box(b"esds", 0, 0, descriptor(0x03, struct.pack(">HB", 1, 0), decoder_config, descriptor(0x06, b"\x02")))
+    return mp4(audio_entry(b"mp4a", 1, RATE, esds), frames, [1024] * len(frames), RATE)
+
+
+def alac_m4a(lengths, frame_length):
+    cookie = struct.pack(">IBBBBBBHIII", frame_length, 0, 16, 40, 10, 14, 2, 255, 0, 0, RATE)
+    entry = audio_entry(b"alac", 2, RATE, full_box(b"alac", 0, 0, cookie))
+    frames, start = [], 0
+    for length in lengths:
+        frames.append(alac_frame(start, length, frame_length))
+        start += length
+    return mp4(entry, frames, lengths, RATE)
+
+
+def write(name, data):
+    with open(os.path.join(HERE, name), "wb") as file:
+        file.write(data)
+
+
+if __name__ == "__main__":
+    silent = [aac_silent_frame()] * 10
+    write("aac-lc.m4a", aac_m4a(silent))
+    write("adts.aac", b"".join(adts(frame) for frame in silent))
+    write("alac.m4a", alac_m4a([4096, 1000], 4096))